@@ -1,7 +1,15 @@
 use anyhow::Context;
 use clap::{Parser, Subcommand, ValueEnum};
-use quil_rs::{expression::Expression, quil::Quil, Program};
-use std::str::FromStr;
+use quil_rs::{
+    expression::Expression,
+    program::{
+        diff::{EntryDiff, InstructionChange, ProgramDiff, ValueChange},
+        CalibrationSource,
+    },
+    quil::Quil,
+    Program,
+};
+use std::{fmt::Write, path::PathBuf, str::FromStr};
 
 #[derive(Parser, Debug)]
 struct Cli {
@@ -17,6 +25,8 @@ pub enum Command {
         input_type: InputType,
         input: String,
     },
+    /// Compare the calibrations, frames, waveforms, and gate definitions of two Quil programs
+    Diff { old: PathBuf, new: PathBuf },
 }
 
 #[derive(ValueEnum, Clone, Debug, Default)]
@@ -33,6 +43,7 @@ fn main() -> anyhow::Result<()> {
 
     match cli.command {
         Command::Parse { input_type, input } => handle_parse(input_type, input)?,
+        Command::Diff { old, new } => handle_diff(old, new)?,
     };
 
     Ok(())
@@ -58,3 +69,221 @@ fn handle_parse(input_type: InputType, input: String) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn handle_diff(old: PathBuf, new: PathBuf) -> anyhow::Result<()> {
+    let read_program = |path: &PathBuf| -> anyhow::Result<Program> {
+        let input = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}.", path.display()))?;
+        Program::from_str(&input)
+            .with_context(|| format!("Failed to parse program from {}.", path.display()))
+    };
+    let diff = read_program(&old)?.diff(&read_program(&new)?);
+
+    print!("{}", render_diff(&diff)?);
+
+    Ok(())
+}
+
+/// Render a [`ProgramDiff`] as text, with one line per added, removed, or modified entry, each
+/// followed by the changes within it.
+fn render_diff(diff: &ProgramDiff) -> Result<String, std::fmt::Error> {
+    let mut output = String::new();
+    let render_calibration = |key: &CalibrationSource| match key {
+        CalibrationSource::Calibration(identifier) => identifier.to_quil_or_debug(),
+        CalibrationSource::MeasureCalibration(identifier) => identifier.to_quil_or_debug(),
+    };
+    for entry in &diff.calibrations {
+        write_entry(&mut output, entry, render_calibration, |output, changes| {
+            for change in changes {
+                match change {
+                    InstructionChange::Inserted { index, instruction } => {
+                        writeln!(output, "    + [{index}] {}", instruction.to_quil_or_debug())
+                    }
+                    InstructionChange::Removed { index, instruction } => {
+                        writeln!(output, "    - [{index}] {}", instruction.to_quil_or_debug())
+                    }
+                }?;
+            }
+            Ok(())
+        })?;
+    }
+    for entry in &diff.frames {
+        write_entry(
+            &mut output,
+            entry,
+            |key| format!("DEFFRAME {}", key.to_quil_or_debug()),
+            |output, changes| {
+                write_value_changes(
+                    output,
+                    changes,
+                    |key| key.clone(),
+                    |value| value.to_quil_or_debug(),
+                )
+            },
+        )?;
+    }
+    for entry in &diff.waveforms {
+        write_entry(
+            &mut output,
+            entry,
+            |key| format!("DEFWAVEFORM {key}"),
+            |output, changes| {
+                if let Some((old, new)) = &changes.parameters {
+                    writeln!(
+                        output,
+                        "    ~ parameters: ({}) -> ({})",
+                        old.join(", "),
+                        new.join(", ")
+                    )?;
+                }
+                write_value_changes(
+                    output,
+                    &changes.samples,
+                    |key| format!("[{key}]"),
+                    |value| value.to_quil_or_debug(),
+                )
+            },
+        )?;
+    }
+    for entry in &diff.gate_definitions {
+        write_entry(
+            &mut output,
+            entry,
+            |key| format!("DEFGATE {key}"),
+            |output, changes| {
+                if let Some((old, new)) = &changes.parameters {
+                    writeln!(
+                        output,
+                        "    ~ parameters: ({}) -> ({})",
+                        old.join(", "),
+                        new.join(", ")
+                    )?;
+                }
+                if let Some((old, new)) = &changes.specification {
+                    for (prefix, specification) in [("-", old), ("+", new)] {
+                        for line in specification.to_quil_or_debug().lines() {
+                            writeln!(output, "    {prefix} {}", line.trim())?;
+                        }
+                    }
+                }
+                Ok(())
+            },
+        )?;
+    }
+
+    Ok(output)
+}
+
+/// Write a single entry of a [`quil_rs::program::diff::ProgramDiff`], prefixed by `+`, `-`, or `~`
+/// if it was added, removed, or modified, respectively.
+fn write_entry<K, C>(
+    output: &mut String,
+    entry: &EntryDiff<K, C>,
+    render_key: impl Fn(&K) -> String,
+    write_changes: impl Fn(&mut String, &C) -> std::fmt::Result,
+) -> std::fmt::Result {
+    match entry {
+        EntryDiff::Added(key) => writeln!(output, "+ {}", render_key(key)),
+        EntryDiff::Removed(key) => writeln!(output, "- {}", render_key(key)),
+        EntryDiff::Modified { key, changes } => {
+            writeln!(output, "~ {}", render_key(key))?;
+            write_changes(output, changes)
+        }
+    }
+}
+
+fn write_value_changes<K, V>(
+    output: &mut String,
+    changes: &[ValueChange<K, V>],
+    render_key: impl Fn(&K) -> String,
+    render_value: impl Fn(&V) -> String,
+) -> std::fmt::Result {
+    for change in changes {
+        match change {
+            ValueChange::Added { key, value } => {
+                writeln!(output, "    + {}: {}", render_key(key), render_value(value))
+            }
+            ValueChange::Removed { key, value } => {
+                writeln!(output, "    - {}: {}", render_key(key), render_value(value))
+            }
+            ValueChange::Changed { key, old, new } => writeln!(
+                output,
+                "    ~ {}: {} -> {}",
+                render_key(key),
+                render_value(old),
+                render_value(new)
+            ),
+        }?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use quil_rs::Program;
+
+    use super::render_diff;
+
+    #[test]
+    fn renders_program_diff() {
+        let old = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+    INITIAL-FREQUENCY: 5e9
+DEFWAVEFORM custom:
+    1, 2
+DEFWAVEFORM removed:
+    1
+DEFGATE G AS MATRIX:
+    1, 0
+    0, 1
+DEFCAL X 0:
+    PULSE 0 "rf" custom
+    NOP
+"#,
+        )
+        .unwrap();
+        let new = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 2e9
+    INITIAL-FREQUENCY: 5e9
+    DIRECTION: "tx"
+DEFWAVEFORM custom(%scale):
+    1, 2*%scale
+DEFGATE G AS MATRIX:
+    0, 1
+    1, 0
+DEFCAL X 0:
+    PULSE 0 "rf" custom(scale: 1)
+    NOP
+DEFCAL MEASURE 0 addr:
+    CAPTURE 0 "rf" custom(scale: 1) addr
+"#,
+        )
+        .unwrap();
+
+        let output = render_diff(&old.diff(&new)).unwrap();
+        assert_eq!(
+            output,
+            r#"~ DEFCAL X 0
+    - [0] PULSE 0 "rf" custom
+    + [0] PULSE 0 "rf" custom(scale: 1)
++ DEFCAL MEASURE 0 addr
+~ DEFFRAME 0 "rf"
+    ~ SAMPLE-RATE: 1000000000 -> 2000000000
+    + DIRECTION: "tx"
+~ DEFWAVEFORM custom
+    ~ parameters: () -> (scale)
+    ~ [1]: 2 -> 2*%scale
+- DEFWAVEFORM removed
+~ DEFGATE G
+    - 1, 0
+    - 0, 1
+    + 0, 1
+    + 1, 0
+"#
+        );
+    }
+}
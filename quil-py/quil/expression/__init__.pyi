@@ -4,12 +4,15 @@ from enum import Enum
 from typing import Dict, Optional, Sequence, Union, final
 
 from quil.instructions import MemoryReference
+from quil.program import MemoryState
 
 __all__ = [
     'Expression',
+    'BinaryFunctionCallExpression',
     'FunctionCallExpression',
     'InfixExpression',
     'PrefixExpression',
+    'BinaryExpressionFunction',
     'ExpressionFunction',
     'PrefixOperator',
     'InfixOperator',
@@ -29,6 +32,7 @@ class Expression:
 
     # Variants:
     - ``address``: An address defined by a `quil.instructions.MemoryReference`.
+    - ``binary_function_call``: A `BinaryFunctionCallExpression`.
     - ``function_call``: A `FunctionCallExpression`.
     - ``infix``: An `InfixExpression`.
     - ``number``: A number defined as a `complex`.
@@ -57,6 +61,7 @@ class Expression:
         self,
     ) -> Union[
         MemoryReference,
+        BinaryFunctionCallExpression,
         FunctionCallExpression,
         InfixExpression,
         int,
@@ -72,6 +77,7 @@ class Expression:
         Raises a ``ParseExpressionError`` if the string isn't a valid Quil expression.
         """
     def is_address(self) -> bool: ...
+    def is_binary_function_call(self) -> bool: ...
     def is_function_call(self) -> bool: ...
     def is_infix(self) -> bool: ...
     def is_number(self) -> bool: ...
@@ -83,6 +89,8 @@ class Expression:
     @staticmethod
     def from_address(inner: MemoryReference) -> "Expression": ...
     @staticmethod
+    def from_binary_function_call(inner: BinaryFunctionCallExpression) -> "Expression": ...
+    @staticmethod
     def from_function_call(inner: FunctionCallExpression) -> "Expression": ...
     @staticmethod
     def from_infix(inner: InfixExpression) -> "Expression": ...
@@ -94,6 +102,8 @@ class Expression:
     def from_variable(inner: str) -> "Expression": ...
     def as_address(self) -> Optional[MemoryReference]: ...
     def to_address(self) -> MemoryReference: ...
+    def as_binary_function_call(self) -> Optional[BinaryFunctionCallExpression]: ...
+    def to_binary_function_call(self) -> BinaryFunctionCallExpression: ...
     def as_function_call(self) -> Optional[FunctionCallExpression]: ...
     def to_function_call(self) -> FunctionCallExpression: ...
     def as_infix(self) -> Optional[InfixExpression]: ...
@@ -116,6 +126,17 @@ class Expression:
     ) -> complex:
         """Evaluate an expression, expecting that it may be fully reduced to a single complex number.
 
+        If it cannot be reduced to a complex number, raises an ``EvaluationError``.
+        """
+        ...
+    def evaluate_with_memory(
+        self,
+        variables: Dict[str, complex],
+        memory: MemoryState,
+    ) -> complex:
+        """Evaluate an expression as by ``evaluate``, reading memory references from a ``MemoryState``,
+        in which values of every type are read as real numbers.
+
         If it cannot be reduced to a complex number, raises an ``EvaluationError``.
         """
         ...
@@ -137,6 +158,14 @@ class Expression:
         If any part of the instruction can't be converted to valid Quil, it will be printed in a human-readable debug
         format that isn't valid Quil.
         """
+    def to_quil_with_non_standard_functions(self) -> str:
+        """Convert the expression to a Quil string, in which functions beyond the Quil specification, such as
+        ``atan2``, are written as calls like any other.
+
+        The result can be parsed by this library, but not necessarily by other Quil implementations. Raises an
+        exception if the expression otherwise can't be converted to valid Quil.
+        """
+        ...
 
 class FunctionCallExpression:
     """A Quil function call."""
@@ -151,6 +180,25 @@ class FunctionCallExpression:
     @expression.setter
     def expression(self, expression: Expression): ...
 
+class BinaryFunctionCallExpression:
+    """A call to a function of two arguments, which is not defined by the Quil specification."""
+    @staticmethod
+    def __new__(
+        cls, function: BinaryExpressionFunction, left: Expression, right: Expression
+    ) -> "BinaryFunctionCallExpression": ...
+    @property
+    def function(self) -> BinaryExpressionFunction: ...
+    @function.setter
+    def function(self, function: BinaryExpressionFunction): ...
+    @property
+    def left(self) -> Expression: ...
+    @left.setter
+    def left(self, expression: Expression): ...
+    @property
+    def right(self) -> Expression: ...
+    @right.setter
+    def right(self, expression: Expression): ...
+
 class InfixExpression:
     """A Quil infix expression."""
     @staticmethod
@@ -183,13 +231,41 @@ class PrefixExpression:
 
 @final
 class ExpressionFunction(Enum):
-    """An enum representing a Quil function that can be applied to an expression."""
+    """An enum representing a Quil function that can be applied to an expression.
 
+    Only ``Cis``, ``Cosine``, ``Exponent``, ``Sine``, and ``SquareRoot`` are defined by the Quil
+    specification. The others are parsed and evaluated, but ``to_quil`` raises an exception for
+    an expression which uses them; use ``to_quil_or_debug`` to write them.
+    """
+
+    AbsoluteValue = "ABSOLUTEVALUE"
+    ArcCosine = "ARCCOSINE"
+    ArcSine = "ARCSINE"
+    ArcTangent = "ARCTANGENT"
+    Ceiling = "CEILING"
     Cis = "CIS"
+    Conjugate = "CONJUGATE"
     Cosine = "COSINE"
     Exponent = "EXPONENT"
+    Floor = "FLOOR"
+    ImaginaryPart = "IMAGINARYPART"
+    Logarithm = "LOGARITHM"
+    RealPart = "REALPART"
     Sine = "SINE"
     SquareRoot = "SQUAREROOT"
+    Tangent = "TANGENT"
+
+    def is_standard(self) -> bool:
+        """Whether the function is defined by the Quil specification."""
+        ...
+
+@final
+class BinaryExpressionFunction(Enum):
+    """An enum representing a function of two real arguments, none of which are defined by the Quil specification."""
+
+    ArcTangent2 = "ARCTANGENT2"
+    Maximum = "MAXIMUM"
+    Minimum = "MINIMUM"
 
 @final
 class PrefixOperator(Enum):
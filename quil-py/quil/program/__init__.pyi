@@ -68,7 +68,7 @@ assert sources == [1]
 ```
 """
 
-from typing import Callable, Dict, FrozenSet, List, Optional, Sequence, Set, Union, final
+from typing import Callable, Dict, FrozenSet, List, Optional, Sequence, Set, Tuple, Union, final
 
 import numpy as np
 from numpy.typing import NDArray
@@ -89,6 +89,7 @@ from quil.instructions import (
     MemoryReference,
     Qubit,
     QubitPlaceholder,
+    ScalarType,
     Sharing,
     Target,
     TargetPlaceholder,
@@ -109,6 +110,12 @@ __all__ = [
     'ProgramCalibrationExpansionSourceMapEntry',
     'CalibrationSet',
     'MemoryRegion',
+    'MemoryLayout',
+    'MemoryLayoutError',
+    'RegionLayout',
+    'MemoryState',
+    'MemoryValue',
+    'MemoryStateError',
     'BasicBlock',
     'ControlFlowGraph',
     'ScheduleSeconds',
@@ -208,6 +215,14 @@ class Program:
 
         If any part of the instruction can't be converted to valid Quil, it will be printed in a human-readable debug format.
         """
+    def to_quil_with_non_standard_functions(self) -> str:
+        """Convert the program to a Quil string, in which functions beyond the Quil specification, such as ``atan2``,
+        are written as calls like any other.
+
+        The result can be parsed by this library, but not necessarily by other Quil implementations. Raises an
+        exception if the program otherwise can't be converted to valid Quil.
+        """
+        ...
     def filter_instructions(self, predicate: Callable[[Instruction], bool]) -> "Program":
         """Return a new ``Program`` containing only the instructions for which ``predicate`` returns ``True``."""
         ...
@@ -624,6 +639,172 @@ class MemoryRegion:
     @sharing.setter
     def sharing(self, sharing: Optional[Sharing]): ...
 
+class MemoryLayoutError(ValueError):
+    """Error that may occur while computing a ``MemoryLayout``."""
+
+@final
+class RegionLayout:
+    """The position of a memory region within the buffer described by a ``MemoryLayout``."""
+
+    def __new__(cls, data_type: ScalarType, length: int, bit_offset: int) -> Self: ...
+    @property
+    def data_type(self) -> ScalarType: ...
+    @data_type.setter
+    def data_type(self, data_type: ScalarType): ...
+    @property
+    def length(self) -> int: ...
+    @length.setter
+    def length(self, length: int): ...
+    @property
+    def bit_offset(self) -> int:
+        """The position of the first bit of the region within the buffer."""
+        ...
+    @bit_offset.setter
+    def bit_offset(self, bit_offset: int): ...
+    def bit_size(self) -> int:
+        """The number of bits occupied by the region."""
+        ...
+    def bit_range(self) -> Tuple[int, int]:
+        """The start and (exclusive) end of the bits of the buffer occupied by the region."""
+        ...
+    def byte_range(self) -> Tuple[int, int]:
+        """The start and (exclusive) end of the bytes of the buffer which hold at least one bit of the region."""
+        ...
+    def is_aligned(self) -> bool:
+        """Whether the region starts at a multiple of the width of its type."""
+        ...
+
+@final
+class MemoryLayout:
+    """The layout of every memory region declared by a program within a single buffer, with the widths of
+    each type given by the Quil specification.
+
+    Each region which does not share another is allocated its own memory, in the order of declaration and
+    aligned to 64 bits. Each region declared with ``SHARING`` starts at the sum of its ``OFFSET``s from the
+    start of the region it shares, and must fit within that region.
+    """
+
+    def __new__(cls, memory_regions: Dict[str, MemoryRegion]) -> "MemoryLayout":
+        """Compute the layout of the memory declared by a program, such as ``Program.memory_regions``.
+
+        Raises a ``MemoryLayoutError`` if a region shares one which is not declared, if regions share one
+        another cyclically, if a region extends beyond the end of the one it shares, or if the memory is too
+        large to be addressed.
+        """
+        ...
+    def bit_size(self) -> int:
+        """The number of bits in the buffer, holding every region."""
+        ...
+    def byte_size(self) -> int:
+        """The number of bytes in the buffer, holding every region."""
+        ...
+    def get(self, name: str) -> Optional[RegionLayout]:
+        """The layout of a memory region, if it is declared."""
+        ...
+    def regions(self) -> Dict[str, RegionLayout]:
+        """The layout of every memory region, in the order of declaration."""
+        ...
+    def parent(self, name: str) -> Optional[str]:
+        """The name of the region shared by a memory region, if it is declared with ``SHARING``."""
+        ...
+    def misaligned_regions(self) -> List[str]:
+        """The names of the regions which do not start at a multiple of the width of their type."""
+        ...
+    def overlapping_regions(self) -> List[Tuple[str, str]]:
+        """Each pair of regions which occupy some of the same memory, though neither shares the other,
+        directly or through other regions.
+        """
+        ...
+
+class MemoryStateError(ValueError):
+    """Error that may occur while allocating, reading, or writing a ``MemoryState``."""
+
+@final
+class MemoryValue:
+    """A single value stored in classical memory.
+
+    # Variants:
+    - ``bit``: The value of a ``BIT``.
+    - ``octet``: The value of an ``OCTET``.
+    - ``integer``: The value of an ``INTEGER``.
+    - ``real``: The value of a ``REAL``.
+
+    Methods (for each variant):
+    - ``is_*``: Returns ``True`` if the value is that variant, ``False`` otherwise.
+    - ``as_*``: Returns the inner data if it is the given variant, ``None`` otherwise.
+    - ``to_*``: Returns the inner data if it is the given variant, raises ``ValueError`` otherwise.
+    - ``from_*``: Creates a new ``MemoryValue`` of the given variant from an instance of the inner type.
+    """
+
+    def inner(self) -> Union[bool, int, float]:
+        """Returns the inner value of the variant."""
+        ...
+    def is_bit(self) -> bool: ...
+    def is_octet(self) -> bool: ...
+    def is_integer(self) -> bool: ...
+    def is_real(self) -> bool: ...
+    def as_bit(self) -> Optional[bool]: ...
+    def as_octet(self) -> Optional[int]: ...
+    def as_integer(self) -> Optional[int]: ...
+    def as_real(self) -> Optional[float]: ...
+    def to_bit(self) -> bool: ...
+    def to_octet(self) -> int: ...
+    def to_integer(self) -> int: ...
+    def to_real(self) -> float: ...
+    @staticmethod
+    def from_bit(inner: bool) -> "MemoryValue": ...
+    @staticmethod
+    def from_octet(inner: int) -> "MemoryValue": ...
+    @staticmethod
+    def from_integer(inner: int) -> "MemoryValue": ...
+    @staticmethod
+    def from_real(inner: float) -> "MemoryValue": ...
+
+@final
+class MemoryState:
+    """The classical memory of a Quil program: a single buffer of bytes, holding every declared memory
+    region with the width of its type.
+
+    Memory is laid out as by ``MemoryLayout`` and initialized to zero. Regions declared with ``SHARING``
+    alias the memory of the region they share. Values are stored as little-endian, with ``BIT``s packed from the least significant bit of
+    each byte.
+    """
+
+    def __new__(cls, memory_regions: Dict[str, MemoryRegion]) -> "MemoryState":
+        """Allocate the memory declared by a program, such as ``Program.memory_regions``.
+
+        Raises a ``MemoryStateError`` if the layout of the memory cannot be computed, as by ``MemoryLayout``.
+        """
+        ...
+    def as_bytes(self) -> bytes:
+        """The bytes of memory, holding every region."""
+        ...
+    def layout(self) -> MemoryLayout:
+        """The position of each memory region within ``as_bytes``."""
+        ...
+    def region_size(self, name: str) -> Optional[Tuple[ScalarType, int]]:
+        """The type and length of a memory region, if it is declared."""
+        ...
+    def read(self, reference: MemoryReference) -> MemoryValue:
+        """Read a single value from memory.
+
+        Raises a ``MemoryStateError`` if the reference is not to a declared region, or is out of bounds.
+        """
+        ...
+    def read_region(self, name: str) -> List[MemoryValue]:
+        """Read every value of a memory region.
+
+        Raises a ``MemoryStateError`` if the region is not declared.
+        """
+        ...
+    def write(self, reference: MemoryReference, value: MemoryValue):
+        """Write a single value to memory, which must have the type of its region.
+
+        Raises a ``MemoryStateError`` if the reference is not to a declared region, is out of bounds, or
+        if the value has a different type.
+        """
+        ...
+
 @final
 class ProgramCalibrationExpansion:
     def program(self) -> Program: ...
@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
 use quil_rs::expression::{
-    Expression, ExpressionFunction, FunctionCallExpression, InfixExpression, InfixOperator,
-    PrefixExpression, PrefixOperator,
+    BinaryExpressionFunction, BinaryFunctionCallExpression, Expression, ExpressionFunction,
+    FunctionCallExpression, InfixExpression, InfixOperator, PrefixExpression, PrefixOperator,
 };
 
 use rigetti_pyo3::{
@@ -18,7 +18,7 @@ use rigetti_pyo3::{
     wrap_error, PyTryFrom, PyWrapper, PyWrapperMut, ToPython, ToPythonError,
 };
 
-use crate::{impl_eq, impl_to_quil, instruction::PyMemoryReference};
+use crate::{impl_eq, impl_to_quil, instruction::PyMemoryReference, program::PyMemoryState};
 
 wrap_error!(RustEvaluationError(quil_rs::expression::EvaluationError));
 py_wrap_error!(quil, RustEvaluationError, EvaluationError, PyValueError);
@@ -35,6 +35,7 @@ py_wrap_union_enum! {
     #[pyo3(module="quil.expression")]
     PyExpression(Expression) as "Expression" {
         address: Address => PyMemoryReference,
+        binary_function_call: BinaryFunctionCall => PyBinaryFunctionCallExpression,
         function_call: FunctionCall => PyFunctionCallExpression,
         infix: Infix => PyInfixExpression,
         number: Number => Py<PyComplex>,
@@ -52,6 +53,11 @@ impl_eq!(PyExpression);
 
 #[pymethods]
 impl PyExpression {
+    pub fn to_quil_with_non_standard_functions(&self) -> PyResult<String> {
+        quil_rs::quil::Quil::to_quil_with_non_standard_functions(self.as_inner())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
     pub fn simplify(&mut self) {
         self.as_inner_mut().simplify()
     }
@@ -71,6 +77,17 @@ impl PyExpression {
             .map_err(RustEvaluationError::to_py_err)
     }
 
+    pub fn evaluate_with_memory(
+        &self,
+        variables: HashMap<String, Complex64>,
+        memory: &PyMemoryState,
+    ) -> PyResult<Complex64> {
+        self.as_inner()
+            .evaluate_with_memory(&variables, memory.as_inner())
+            .map_err(RustEvaluationError::from)
+            .map_err(RustEvaluationError::to_py_err)
+    }
+
     pub fn substitute_variables(
         &self,
         py: Python<'_>,
@@ -130,6 +147,36 @@ impl PyFunctionCallExpression {
     }
 }
 
+py_wrap_data_struct! {
+    #[pyo3(subclass)]
+    #[derive(Debug)]
+    PyBinaryFunctionCallExpression(BinaryFunctionCallExpression) as "BinaryFunctionCallExpression" {
+        function: BinaryExpressionFunction => PyBinaryExpressionFunction,
+        left: Box<Expression> => PyExpression,
+        right: Box<Expression> => PyExpression
+    }
+}
+impl_repr!(PyBinaryFunctionCallExpression);
+
+#[pymethods]
+impl PyBinaryFunctionCallExpression {
+    #[new]
+    pub fn new(
+        py: Python<'_>,
+        function: PyBinaryExpressionFunction,
+        left: PyExpression,
+        right: PyExpression,
+    ) -> PyResult<Self> {
+        Ok(PyBinaryFunctionCallExpression(
+            BinaryFunctionCallExpression::new(
+                BinaryExpressionFunction::py_try_from(py, &function)?,
+                Box::<Expression>::py_try_from(py, &left)?,
+                Box::<Expression>::py_try_from(py, &right)?,
+            ),
+        ))
+    }
+}
+
 py_wrap_data_struct! {
     #[derive(Debug)]
     #[pyo3(subclass)]
@@ -185,11 +232,22 @@ impl PyPrefixExpression {
 py_wrap_simple_enum! {
     #[derive(Debug, PartialEq, Eq, Hash)]
     PyExpressionFunction(ExpressionFunction) as "ExpressionFunction" {
+        AbsoluteValue,
+        ArcCosine,
+        ArcSine,
+        ArcTangent,
+        Ceiling,
         Cis,
+        Conjugate,
         Cosine,
         Exponent,
+        Floor,
+        ImaginaryPart,
+        Logarithm,
+        RealPart,
         Sine,
-        SquareRoot
+        SquareRoot,
+        Tangent
     }
 }
 impl_repr!(PyExpressionFunction);
@@ -197,6 +255,26 @@ impl_str!(PyExpressionFunction);
 impl_hash!(PyExpressionFunction);
 impl_eq!(PyExpressionFunction);
 
+#[pymethods]
+impl PyExpressionFunction {
+    pub fn is_standard(&self) -> bool {
+        self.as_inner().is_standard()
+    }
+}
+
+py_wrap_simple_enum! {
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    PyBinaryExpressionFunction(BinaryExpressionFunction) as "BinaryExpressionFunction" {
+        ArcTangent2,
+        Maximum,
+        Minimum
+    }
+}
+impl_repr!(PyBinaryExpressionFunction);
+impl_str!(PyBinaryExpressionFunction);
+impl_hash!(PyBinaryExpressionFunction);
+impl_eq!(PyBinaryExpressionFunction);
+
 py_wrap_simple_enum! {
     #[derive(Debug, PartialEq, Eq, Hash)]
     PyPrefixOperator(PrefixOperator) as "PrefixOperator" {
@@ -225,6 +303,6 @@ impl_hash!(PyInfixOperator);
 impl_eq!(PyInfixOperator);
 
 create_init_submodule! {
-    classes: [PyExpression, PyFunctionCallExpression, PyInfixExpression, PyPrefixExpression, PyExpressionFunction, PyPrefixOperator, PyInfixOperator],
+    classes: [PyExpression, PyBinaryFunctionCallExpression, PyFunctionCallExpression, PyInfixExpression, PyPrefixExpression, PyBinaryExpressionFunction, PyExpressionFunction, PyPrefixOperator, PyInfixOperator],
     errors: [EvaluationError, ParseExpressionError],
 }
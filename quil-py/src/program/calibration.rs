@@ -43,6 +43,7 @@ impl PyCalibrationSet {
                 .map(|c| c.into_inner())
                 .collect::<Vec<MeasureCalibrationDefinition>>()
                 .into(),
+            ..Default::default()
         }))
     }
 
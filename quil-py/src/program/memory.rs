@@ -1,16 +1,26 @@
+use indexmap::IndexMap;
 use quil_rs::{
-    instruction::{Sharing, Vector},
-    program::MemoryRegion,
+    instruction::{MemoryReference, ScalarType, Sharing, Vector},
+    program::{
+        MemoryLayout, MemoryLayoutError, MemoryRegion, MemoryState, MemoryStateError, MemoryValue,
+        RegionLayout,
+    },
 };
 use rigetti_pyo3::{
-    impl_hash, impl_repr, py_wrap_data_struct,
-    pyo3::{pymethods, PyResult, Python},
-    PyTryFrom,
+    impl_as_mut_for_wrapper, impl_hash, impl_repr, py_wrap_data_struct, py_wrap_error,
+    py_wrap_type, py_wrap_union_enum,
+    pyo3::{
+        exceptions::PyValueError,
+        pymethods,
+        types::{PyBool, PyBytes, PyFloat, PyInt},
+        Py, PyResult, Python,
+    },
+    wrap_error, PyTryFrom, PyWrapper, PyWrapperMut, ToPython, ToPythonError,
 };
 
 use crate::{
     impl_eq,
-    instruction::{PySharing, PyVector},
+    instruction::{PyMemoryReference, PyScalarType, PySharing, PyVector},
 };
 
 py_wrap_data_struct! {
@@ -35,3 +45,194 @@ impl PyMemoryRegion {
         )))
     }
 }
+
+wrap_error!(RustMemoryLayoutError(MemoryLayoutError));
+py_wrap_error!(
+    quil,
+    RustMemoryLayoutError,
+    PyMemoryLayoutError,
+    PyValueError
+);
+
+py_wrap_data_struct! {
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    #[pyo3(subclass)]
+    PyRegionLayout(RegionLayout) as "RegionLayout" {
+        data_type: ScalarType => PyScalarType,
+        length: u64 => Py<PyInt>,
+        bit_offset: u64 => Py<PyInt>
+    }
+}
+impl_repr!(PyRegionLayout);
+impl_hash!(PyRegionLayout);
+impl_eq!(PyRegionLayout);
+
+#[pymethods]
+impl PyRegionLayout {
+    #[new]
+    pub fn new(
+        py: Python<'_>,
+        data_type: PyScalarType,
+        length: u64,
+        bit_offset: u64,
+    ) -> PyResult<Self> {
+        Ok(Self(RegionLayout::new(
+            ScalarType::py_try_from(py, &data_type)?,
+            length,
+            bit_offset,
+        )))
+    }
+
+    pub fn bit_size(&self) -> u64 {
+        self.as_inner().bit_size()
+    }
+
+    pub fn bit_range(&self) -> (u64, u64) {
+        let range = self.as_inner().bit_range();
+        (range.start, range.end)
+    }
+
+    pub fn byte_range(&self) -> (u64, u64) {
+        let range = self.as_inner().byte_range();
+        (range.start, range.end)
+    }
+
+    pub fn is_aligned(&self) -> bool {
+        self.as_inner().is_aligned()
+    }
+}
+
+py_wrap_type! {
+    #[derive(Debug, PartialEq)]
+    PyMemoryLayout(MemoryLayout) as "MemoryLayout"
+}
+impl_repr!(PyMemoryLayout);
+impl_eq!(PyMemoryLayout);
+
+#[pymethods]
+impl PyMemoryLayout {
+    #[new]
+    pub fn new(py: Python<'_>, memory_regions: IndexMap<String, PyMemoryRegion>) -> PyResult<Self> {
+        MemoryLayout::new(&IndexMap::<String, MemoryRegion>::py_try_from(
+            py,
+            &memory_regions,
+        )?)
+        .map(Self)
+        .map_err(RustMemoryLayoutError::from)
+        .map_err(RustMemoryLayoutError::to_py_err)
+    }
+
+    pub fn bit_size(&self) -> u64 {
+        self.as_inner().bit_size()
+    }
+
+    pub fn byte_size(&self) -> usize {
+        self.as_inner().byte_size()
+    }
+
+    pub fn get(&self, name: &str) -> Option<PyRegionLayout> {
+        self.as_inner().get(name).cloned().map(PyRegionLayout::from)
+    }
+
+    pub fn regions(&self) -> IndexMap<String, PyRegionLayout> {
+        self.as_inner()
+            .iter()
+            .map(|(name, layout)| (name.clone(), PyRegionLayout::from(layout.clone())))
+            .collect()
+    }
+
+    pub fn parent(&self, name: &str) -> Option<&str> {
+        self.as_inner().parent(name)
+    }
+
+    pub fn misaligned_regions(&self) -> Vec<&str> {
+        self.as_inner().misaligned_regions()
+    }
+
+    pub fn overlapping_regions(&self) -> Vec<(&str, &str)> {
+        self.as_inner().overlapping_regions()
+    }
+}
+
+wrap_error!(RustMemoryStateError(MemoryStateError));
+py_wrap_error!(quil, RustMemoryStateError, PyMemoryStateError, PyValueError);
+
+py_wrap_union_enum! {
+    #[derive(Debug, PartialEq)]
+    PyMemoryValue(MemoryValue) as "MemoryValue" {
+        bit: Bit => Py<PyBool>,
+        octet: Octet => Py<PyInt>,
+        integer: Integer => Py<PyInt>,
+        real: Real => Py<PyFloat>
+    }
+}
+impl_repr!(PyMemoryValue);
+impl_eq!(PyMemoryValue);
+
+py_wrap_type! {
+    #[derive(Debug, PartialEq)]
+    PyMemoryState(MemoryState) as "MemoryState"
+}
+impl_as_mut_for_wrapper!(PyMemoryState);
+impl_repr!(PyMemoryState);
+impl_eq!(PyMemoryState);
+
+#[pymethods]
+impl PyMemoryState {
+    #[new]
+    pub fn new(py: Python<'_>, memory_regions: IndexMap<String, PyMemoryRegion>) -> PyResult<Self> {
+        MemoryState::new(&IndexMap::<String, MemoryRegion>::py_try_from(
+            py,
+            &memory_regions,
+        )?)
+        .map(Self)
+        .map_err(RustMemoryStateError::from)
+        .map_err(RustMemoryStateError::to_py_err)
+    }
+
+    pub fn as_bytes<'a>(&self, py: Python<'a>) -> &'a PyBytes {
+        PyBytes::new(py, self.as_inner().as_bytes())
+    }
+
+    pub fn layout(&self) -> PyMemoryLayout {
+        PyMemoryLayout::from(self.as_inner().layout().clone())
+    }
+
+    pub fn region_size(&self, py: Python<'_>, name: &str) -> PyResult<Option<(PyScalarType, u64)>> {
+        self.as_inner()
+            .region_size(name)
+            .map(|(data_type, length)| Ok((data_type.to_python(py)?, length)))
+            .transpose()
+    }
+
+    pub fn read(&self, py: Python<'_>, reference: PyMemoryReference) -> PyResult<PyMemoryValue> {
+        self.as_inner()
+            .read(&MemoryReference::py_try_from(py, &reference)?)
+            .map(PyMemoryValue::from)
+            .map_err(RustMemoryStateError::from)
+            .map_err(RustMemoryStateError::to_py_err)
+    }
+
+    pub fn read_region(&self, name: &str) -> PyResult<Vec<PyMemoryValue>> {
+        self.as_inner()
+            .read_region(name)
+            .map(|values| values.into_iter().map(PyMemoryValue::from).collect())
+            .map_err(RustMemoryStateError::from)
+            .map_err(RustMemoryStateError::to_py_err)
+    }
+
+    pub fn write(
+        &mut self,
+        py: Python<'_>,
+        reference: PyMemoryReference,
+        value: PyMemoryValue,
+    ) -> PyResult<()> {
+        self.as_inner_mut()
+            .write(
+                &MemoryReference::py_try_from(py, &reference)?,
+                *value.as_inner(),
+            )
+            .map_err(RustMemoryStateError::from)
+            .map_err(RustMemoryStateError::to_py_err)
+    }
+}
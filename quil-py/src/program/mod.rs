@@ -1,6 +1,7 @@
 use std::{
     collections::{HashMap, HashSet},
     str::FromStr,
+    sync::Arc,
 };
 
 use indexmap::IndexMap;
@@ -44,7 +45,14 @@ use self::{
         PyProgramCalibrationExpansionSourceMapEntry,
     },
 };
-pub use self::{calibration::PyCalibrationSet, frame::PyFrameSet, memory::PyMemoryRegion};
+pub use self::{
+    calibration::PyCalibrationSet,
+    frame::PyFrameSet,
+    memory::{
+        PyMemoryLayout, PyMemoryLayoutError, PyMemoryRegion, PyMemoryState, PyMemoryStateError,
+        PyMemoryValue, PyRegionLayout,
+    },
+};
 
 mod analysis;
 mod calibration;
@@ -86,6 +94,11 @@ impl PyProgram {
         Self(self.as_inner().clone_without_body_instructions())
     }
 
+    pub fn to_quil_with_non_standard_functions(&self) -> PyResult<String> {
+        quil_rs::quil::Quil::to_quil_with_non_standard_functions(self.as_inner())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
     pub fn copy(&self) -> Self {
         Self(self.as_inner().clone())
     }
@@ -141,7 +154,8 @@ impl PyProgram {
         py: Python<'_>,
         waveforms: IndexMap<String, PyWaveform>,
     ) -> PyResult<()> {
-        self.as_inner_mut().waveforms = IndexMap::<String, Waveform>::py_try_from(py, &waveforms)?;
+        self.as_inner_mut().waveforms =
+            Arc::new(IndexMap::<String, Waveform>::py_try_from(py, &waveforms)?);
         Ok(())
     }
 
@@ -202,10 +216,12 @@ impl PyProgram {
 
     #[setter]
     pub fn set_gate_definitions(&mut self, definitions: IndexMap<String, PyGateDefinition>) {
-        self.as_inner_mut().gate_definitions = definitions
-            .into_iter()
-            .map(|(name, gate_def)| (name, gate_def.into_inner()))
-            .collect();
+        self.as_inner_mut().gate_definitions = Arc::new(
+            definitions
+                .into_iter()
+                .map(|(name, gate_def)| (name, gate_def.into_inner()))
+                .collect(),
+        );
     }
 
     pub fn dagger(&self) -> PyResult<Self> {
@@ -403,10 +419,15 @@ create_init_submodule! {
         PyProgramCalibrationExpansionSourceMapEntry,
         PyCalibrationSet,
         PyMemoryRegion,
+        PyMemoryLayout,
+        PyRegionLayout,
+        PyMemoryState,
+        PyMemoryValue,
         PyBasicBlock,
         PyControlFlowGraph,
         PyScheduleSeconds,
         PyScheduleSecondsItem,
         PyTimeSpanSeconds
     ],
+    errors: [PyMemoryLayoutError, PyMemoryStateError],
 }
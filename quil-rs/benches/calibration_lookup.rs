@@ -0,0 +1,93 @@
+use std::str::FromStr;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use quil_rs::{instruction::Instruction, Program};
+
+/// The qubit counts of the synthetic devices for which calibrations are generated.
+const QUBIT_COUNTS: [u64; 3] = [8, 80, 800];
+
+/// Build a program with a full set of calibrations for a device with `qubit_count` qubits in a line,
+/// and a body which uses each of them once.
+fn build_program(qubit_count: u64) -> Program {
+    let mut quil = String::from("DECLARE ro BIT[1]\n");
+
+    for qubit in 0..qubit_count {
+        for angle in ["pi/2", "-pi/2", "pi", "-pi"] {
+            quil.push_str(&format!(
+                "DEFCAL RX({angle}) {qubit}:\n    NONBLOCKING PULSE {qubit} \"rf\" drag_gaussian(duration: 4e-8, fwhm: 1e-8, t0: 2e-8, anh: -2e8, alpha: 0.5)\n"
+            ));
+        }
+        quil.push_str(&format!(
+            "DEFCAL RZ(%theta) {qubit}:\n    SHIFT-PHASE {qubit} \"rf\" -%theta\n"
+        ));
+        quil.push_str(&format!(
+            "DEFCAL MEASURE {qubit} addr:\n    CAPTURE {qubit} \"ro_rx\" boxcar_kernel(duration: 2e-6) addr\n"
+        ));
+        if qubit + 1 < qubit_count {
+            let next = qubit + 1;
+            quil.push_str(&format!(
+                "DEFCAL CZ {qubit} {next}:\n    PULSE {qubit} {next} \"cz\" erf_square(duration: 2e-7, risetime: 2e-8, pad_left: 0, pad_right: 0)\n"
+            ));
+        }
+    }
+
+    for qubit in 0..qubit_count {
+        quil.push_str(&format!("RX(pi/2) {qubit}\nRZ(0.5) {qubit}\n"));
+        if qubit + 1 < qubit_count {
+            quil.push_str(&format!("CZ {qubit} {}\n", qubit + 1));
+        }
+        quil.push_str(&format!("MEASURE {qubit} ro[0]\n"));
+    }
+
+    Program::from_str(&quil).expect("program should parse successfully")
+}
+
+fn benchmark_get_match_for_gate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Calibrations::get_match_for_gate");
+    for qubit_count in QUBIT_COUNTS {
+        let program = build_program(qubit_count);
+        let gates = program
+            .body_instructions()
+            .filter_map(|instruction| match instruction {
+                Instruction::Gate(gate) => Some(gate),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        group.bench_function(format!("{qubit_count} qubits"), |b| {
+            b.iter(|| {
+                for gate in &gates {
+                    black_box(program.calibrations.get_match_for_gate(gate));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn benchmark_expand_calibrations(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Program::expand_calibrations");
+    for qubit_count in QUBIT_COUNTS {
+        group.bench_function(format!("{qubit_count} qubits"), |b| {
+            b.iter_batched(
+                || build_program(qubit_count),
+                |program| {
+                    black_box(
+                        program
+                            .expand_calibrations()
+                            .expect("calibration expansion should succeed"),
+                    );
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    benchmark_get_match_for_gate,
+    benchmark_expand_calibrations
+);
+criterion_main!(benches);
@@ -7,8 +7,8 @@
 use clap::Parser;
 use quil_rs::{
     expression::{
-        Expression, ExpressionFunction, FunctionCallExpression, InfixExpression, InfixOperator,
-        PrefixExpression, PrefixOperator,
+        BinaryFunctionCallExpression, Expression, ExpressionFunction, FunctionCallExpression,
+        InfixExpression, InfixOperator, PrefixExpression, PrefixOperator,
     },
     instruction::MemoryReference,
     quil::Quil,
@@ -53,6 +53,15 @@ fn parenthesized(expression: &Expression) -> String {
     use Expression::*;
     match expression {
         Address(memory_reference) => format!("({memory_reference})"),
+        BinaryFunctionCall(BinaryFunctionCallExpression {
+            function,
+            left,
+            right,
+        }) => format!(
+            "({function}({}, {}))",
+            parenthesized(left),
+            parenthesized(right)
+        ),
         FunctionCall(FunctionCallExpression {
             function,
             expression,
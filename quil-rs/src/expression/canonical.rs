@@ -0,0 +1,1243 @@
+//! Canonical forms of [`Expression`]s, for comparing expressions by value
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    f64::consts::PI,
+    hash::{Hash, Hasher},
+};
+
+use num_complex::Complex64;
+
+use crate::{imag, instruction::MemoryReference, real};
+
+use super::{
+    calculate_binary_function, calculate_function, BinaryExpressionFunction,
+    BinaryFunctionCallExpression, Expression, ExpressionFunction, FunctionCallExpression,
+    InfixExpression, InfixOperator, PrefixExpression, PrefixOperator,
+};
+
+/// The greatest number of terms produced by multiplying out a product of sums; larger products
+/// are kept factored.
+const MAX_EXPANDED_TERMS: usize = 256;
+
+/// The greatest integer power to which a sum is expanded; larger powers are kept factored.
+const MAX_EXPANDED_POWER: i64 = 8;
+
+/// The greatest magnitude of an integer exponent which is applied exactly.
+const MAX_INTEGER_POWER: u128 = 64;
+
+/// An [`Expression`] in a canonical form, such that expressions which are equal by the rules of
+/// algebra generally have equal canonical forms, and so compare and hash equal.
+///
+/// The canonical form is a polynomial over variables, memory references, `pi`, and irreducible
+/// function calls and powers, with exact rational coefficients: each floating point number is
+/// taken as the exact rational value it represents. Beyond polynomial arithmetic, `cis` is
+/// rewritten in terms of `cos` and `sin`, multiples of `pi` are reduced within the arguments of
+/// `sin` and `cos`, `sin(-x)` and `cos(-x)` are rewritten as `-sin(x)` and `cos(x)`, and
+/// `sin(x)^2 + cos(x)^2` is rewritten as `1`.
+///
+/// Two expressions with different canonical forms may still be equal, since no canonical form can
+/// capture all identities. Functions of constants are evaluated in floating point, and so may
+/// differ by rounding from the same value computed in another way.
+///
+/// # Example
+///
+/// ```rust
+/// use quil_rs::expression::{CanonicalExpression, Expression};
+/// use std::str::FromStr;
+///
+/// let left = Expression::from_str("2*%theta + %theta").unwrap();
+/// let right = Expression::from_str("3*%theta").unwrap();
+///
+/// assert_ne!(left, right);
+/// assert_eq!(CanonicalExpression::from(&left), CanonicalExpression::from(&right));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CanonicalExpression(Polynomial);
+
+impl CanonicalExpression {
+    /// Build an [`Expression`] from the canonical form.
+    ///
+    /// The result has the same canonical form, and is generally, but not always, simpler than the
+    /// expression from which the canonical form was taken.
+    pub fn to_expression(&self) -> Expression {
+        self.0.to_expression()
+    }
+}
+
+impl From<&Expression> for CanonicalExpression {
+    fn from(expression: &Expression) -> Self {
+        Self(Polynomial::from_expression(expression))
+    }
+}
+
+impl From<Expression> for CanonicalExpression {
+    fn from(expression: Expression) -> Self {
+        Self::from(&expression)
+    }
+}
+
+impl Expression {
+    /// Rewrite the expression in canonical form, in-place. See [`CanonicalExpression`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use std::str::FromStr;
+    ///
+    /// let mut expression = Expression::from_str("sin(%x)^2 + cos(%x + 2*pi)^2").unwrap();
+    /// expression.canonicalize();
+    ///
+    /// assert_eq!(expression, Expression::from_str("1").unwrap());
+    /// ```
+    pub fn canonicalize(&mut self) {
+        *self = CanonicalExpression::from(&*self).to_expression();
+    }
+
+    /// Consume the expression, returning it in canonical form. See [`CanonicalExpression`].
+    pub fn into_canonical(mut self) -> Self {
+        self.canonicalize();
+        self
+    }
+
+    /// Whether the two expressions have the same canonical form, and so are equal by the rules of
+    /// algebra. See [`CanonicalExpression`].
+    pub fn is_equivalent(&self, other: &Self) -> bool {
+        CanonicalExpression::from(self) == CanonicalExpression::from(other)
+    }
+}
+
+/// An exact rational number, in lowest terms with a positive denominator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl Rational {
+    const ZERO: Self = Self::integer(0);
+    const ONE: Self = Self::integer(1);
+
+    const fn integer(value: i128) -> Self {
+        Self {
+            numerator: value,
+            denominator: 1,
+        }
+    }
+
+    fn new(numerator: i128, denominator: i128) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor =
+            i128::try_from(gcd(numerator.unsigned_abs(), denominator.unsigned_abs())).ok()?;
+        let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+        if denominator < 0 {
+            Some(Self {
+                numerator: numerator.checked_neg()?,
+                denominator: denominator.checked_neg()?,
+            })
+        } else {
+            Some(Self {
+                numerator,
+                denominator,
+            })
+        }
+    }
+
+    /// The exact value of a finite floating point number, if it is small enough to represent.
+    fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+        if value == 0.0 {
+            return Some(Self::ZERO);
+        }
+
+        let bits = value.to_bits();
+        let biased_exponent = ((bits >> 52) & 0x7ff) as i32;
+        let fraction = bits & ((1 << 52) - 1);
+        let (mantissa, exponent) = if biased_exponent == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | (1 << 52), biased_exponent - 1075)
+        };
+        let shift = (mantissa.trailing_zeros() as i32).min(-exponent).max(0);
+        let (mantissa, exponent) = (i128::from(mantissa >> shift), exponent + shift);
+        let numerator = if bits >> 63 == 1 { -mantissa } else { mantissa };
+
+        if exponent >= 0 {
+            (exponent <= 73).then(|| Self::integer(numerator << exponent))
+        } else {
+            (-exponent <= 126).then(|| Self {
+                numerator,
+                denominator: 1 << -exponent,
+            })
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    fn is_zero(self) -> bool {
+        self.numerator == 0
+    }
+
+    fn is_negative(self) -> bool {
+        self.numerator < 0
+    }
+
+    fn floor(self) -> i128 {
+        self.numerator.div_euclid(self.denominator)
+    }
+
+    fn checked_neg(self) -> Option<Self> {
+        Some(Self {
+            numerator: self.numerator.checked_neg()?,
+            denominator: self.denominator,
+        })
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        let numerator = self
+            .numerator
+            .checked_mul(other.denominator)?
+            .checked_add(other.numerator.checked_mul(self.denominator)?)?;
+        Self::new(numerator, self.denominator.checked_mul(other.denominator)?)
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.numerator.checked_mul(other.numerator)?,
+            self.denominator.checked_mul(other.denominator)?,
+        )
+    }
+
+    fn checked_div(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.numerator.checked_mul(other.denominator)?,
+            self.denominator.checked_mul(other.numerator)?,
+        )
+    }
+
+    /// The exact square root, if it is rational.
+    fn sqrt(self) -> Option<Self> {
+        if self.is_negative() {
+            return None;
+        }
+        Some(Self {
+            numerator: integer_sqrt(self.numerator)?,
+            denominator: integer_sqrt(self.denominator)?,
+        })
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The exact square root of a non-negative integer, if it is an integer.
+fn integer_sqrt(value: i128) -> Option<i128> {
+    let mut root = (value as f64).sqrt() as i128;
+    while root.checked_mul(root)? > value {
+        root -= 1;
+    }
+    while (root + 1).checked_mul(root + 1)? <= value {
+        root += 1;
+    }
+    (root * root == value).then_some(root)
+}
+
+/// The coefficient of a term of a [`Polynomial`]: an exact complex rational where possible, and
+/// otherwise a complex floating point number, such as for infinite values.
+#[derive(Clone, Copy, Debug)]
+enum Coefficient {
+    Exact { re: Rational, im: Rational },
+    Inexact(Complex64),
+}
+
+impl Coefficient {
+    const ZERO: Self = Self::real(Rational::ZERO);
+    const ONE: Self = Self::real(Rational::ONE);
+    const I: Self = Self::Exact {
+        re: Rational::ZERO,
+        im: Rational::ONE,
+    };
+
+    const fn real(value: Rational) -> Self {
+        Self::Exact {
+            re: value,
+            im: Rational::ZERO,
+        }
+    }
+
+    fn from_complex(value: Complex64) -> Self {
+        match (Rational::from_f64(value.re), Rational::from_f64(value.im)) {
+            (Some(re), Some(im)) => Self::Exact { re, im },
+            _ => Self::Inexact(value),
+        }
+    }
+
+    fn to_complex(self) -> Complex64 {
+        match self {
+            Self::Exact { re, im } => Complex64::new(re.to_f64(), im.to_f64()),
+            Self::Inexact(value) => value,
+        }
+    }
+
+    /// The value by which coefficients are compared and hashed, where inexact values are compared
+    /// by their bits, treating all zeroes and all NaNs alike.
+    fn key(&self) -> Result<(Rational, Rational), (u64, u64)> {
+        let bits = |value: f64| {
+            if value.is_nan() {
+                f64::NAN.to_bits()
+            } else {
+                (value + 0.0).to_bits()
+            }
+        };
+        match self {
+            Self::Exact { re, im } => Ok((*re, *im)),
+            Self::Inexact(value) => Err((bits(value.re), bits(value.im))),
+        }
+    }
+
+    fn as_real(&self) -> Option<Rational> {
+        match self {
+            Self::Exact { re, im } if im.is_zero() => Some(*re),
+            _ => None,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Self::Exact { re, im } => re.is_zero() && im.is_zero(),
+            Self::Inexact(value) => value.re == 0.0 && value.im == 0.0,
+        }
+    }
+
+    fn is_one(&self) -> bool {
+        *self == Self::ONE
+    }
+
+    /// Whether the coefficient is "negative": its real part is negative, or its real part is zero
+    /// and its imaginary part is negative.
+    fn is_negative(&self) -> bool {
+        match self {
+            Self::Exact { re, im } => re.is_negative() || (re.is_zero() && im.is_negative()),
+            Self::Inexact(value) => value.re < 0.0 || (value.re == 0.0 && value.im < 0.0),
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        if let (Self::Exact { re: a, im: b }, Self::Exact { re: c, im: d }) = (self, other) {
+            if let (Some(re), Some(im)) = (a.checked_add(c), b.checked_add(d)) {
+                return Self::Exact { re, im };
+            }
+        }
+        Self::from_complex(self.to_complex() + other.to_complex())
+    }
+
+    fn neg(self) -> Self {
+        if let Self::Exact { re, im } = self {
+            if let (Some(re), Some(im)) = (re.checked_neg(), im.checked_neg()) {
+                return Self::Exact { re, im };
+            }
+        }
+        Self::from_complex(-self.to_complex())
+    }
+
+    fn mul(self, other: Self) -> Self {
+        if let (Self::Exact { re: a, im: b }, Self::Exact { re: c, im: d }) = (self, other) {
+            // (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+            let re = a.checked_mul(c).zip(b.checked_mul(d));
+            let im = a.checked_mul(d).zip(b.checked_mul(c));
+            if let (Some(re), Some(im)) = (
+                re.and_then(|(ac, bd)| ac.checked_sub(bd)),
+                im.and_then(|(ad, bc)| ad.checked_add(bc)),
+            ) {
+                return Self::Exact { re, im };
+            }
+        }
+        Self::from_complex(self.to_complex() * other.to_complex())
+    }
+
+    fn div(self, other: Self) -> Self {
+        if let (Self::Exact { re: a, im: b }, Self::Exact { re: c, im: d }) = (self, other) {
+            // (a + bi)/(c + di) = ((ac + bd) + (bc - ad)i) / (c^2 + d^2)
+            let exact = || {
+                let norm = c.checked_mul(c)?.checked_add(d.checked_mul(d)?)?;
+                let re = a.checked_mul(c)?.checked_add(b.checked_mul(d)?)?;
+                let im = b.checked_mul(c)?.checked_sub(a.checked_mul(d)?)?;
+                Some(Self::Exact {
+                    re: re.checked_div(norm)?,
+                    im: im.checked_div(norm)?,
+                })
+            };
+            if let Some(quotient) = exact() {
+                return quotient;
+            }
+        }
+        Self::from_complex(self.to_complex() / other.to_complex())
+    }
+
+    fn powi(self, exponent: i64) -> Self {
+        let mut result = Self::ONE;
+        let mut base = self;
+        let mut remaining = exponent.unsigned_abs();
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = result.mul(base);
+            }
+            base = base.mul(base);
+            remaining >>= 1;
+        }
+        if exponent < 0 {
+            Self::ONE.div(result)
+        } else {
+            result
+        }
+    }
+
+    fn sqrt(self) -> Self {
+        match self.as_real().and_then(Rational::sqrt) {
+            Some(root) => Self::real(root),
+            None => Self::from_complex(self.to_complex().sqrt()),
+        }
+    }
+}
+
+impl PartialEq for Coefficient {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Coefficient {}
+
+impl PartialOrd for Coefficient {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Coefficient {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for Coefficient {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+/// An irreducible factor of a term of a [`Polynomial`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Atom {
+    Pi,
+    Variable(String),
+    Address(MemoryReference),
+    Function(ExpressionFunction, Box<Polynomial>),
+    BinaryFunction(BinaryExpressionFunction, Box<Polynomial>, Box<Polynomial>),
+    /// A power which cannot be expanded, such as one with a non-integer exponent.
+    Power(Box<Polynomial>, Box<Polynomial>),
+    /// A sum which is kept factored, such as a divisor, with a leading coefficient of one.
+    Sum(Box<Polynomial>),
+}
+
+/// A product of [`Atom`]s, each raised to a non-zero integer power.
+type Monomial = BTreeMap<Atom, i64>;
+
+/// A sum of [`Monomial`]s, each with a non-zero [`Coefficient`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Polynomial {
+    terms: BTreeMap<Monomial, Coefficient>,
+}
+
+impl Polynomial {
+    fn from_expression(expression: &Expression) -> Self {
+        match expression {
+            Expression::Address(reference) => Self::atom(Atom::Address(reference.clone())),
+            Expression::Variable(name) => Self::atom(Atom::Variable(name.clone())),
+            Expression::Number(number) => Self::constant(Coefficient::from_complex(*number)),
+            Expression::PiConstant => Self::atom(Atom::Pi),
+            Expression::Prefix(PrefixExpression {
+                operator,
+                expression,
+            }) => {
+                let inner = Self::from_expression(expression);
+                match operator {
+                    PrefixOperator::Plus => inner,
+                    PrefixOperator::Minus => inner.neg(),
+                }
+            }
+            Expression::FunctionCall(FunctionCallExpression {
+                function,
+                expression,
+            }) => Self::function(*function, Self::from_expression(expression)),
+            Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+                function,
+                left,
+                right,
+            }) => Self::binary_function(
+                *function,
+                Self::from_expression(left),
+                Self::from_expression(right),
+            ),
+            Expression::Infix(InfixExpression {
+                left,
+                operator,
+                right,
+            }) => {
+                let left = Self::from_expression(left);
+                let right = Self::from_expression(right);
+                match operator {
+                    InfixOperator::Plus => left.add(right),
+                    InfixOperator::Minus => left.add(right.neg()),
+                    InfixOperator::Star => left.mul(&right),
+                    InfixOperator::Slash => left.div(right),
+                    InfixOperator::Caret => left.pow(right),
+                }
+                .flattened()
+                .apply_pythagorean_identity()
+            }
+        }
+    }
+
+    fn constant(coefficient: Coefficient) -> Self {
+        let mut polynomial = Self::default();
+        polynomial.add_term(Monomial::new(), coefficient);
+        polynomial
+    }
+
+    fn atom(atom: Atom) -> Self {
+        Self::atom_power(atom, 1)
+    }
+
+    fn atom_power(atom: Atom, exponent: i64) -> Self {
+        Self {
+            terms: BTreeMap::from([(Monomial::from([(atom, exponent)]), Coefficient::ONE)]),
+        }
+    }
+
+    fn add_term(&mut self, monomial: Monomial, coefficient: Coefficient) {
+        let sum = match self.terms.get(&monomial) {
+            Some(existing) => existing.add(coefficient),
+            None => coefficient,
+        };
+        if sum.is_zero() {
+            self.terms.remove(&monomial);
+        } else {
+            self.terms.insert(monomial, sum);
+        }
+    }
+
+    fn add(mut self, other: Self) -> Self {
+        for (monomial, coefficient) in other.terms {
+            self.add_term(monomial, coefficient);
+        }
+        self
+    }
+
+    fn neg(self) -> Self {
+        self.scale(Coefficient::ONE.neg())
+    }
+
+    fn scale(self, factor: Coefficient) -> Self {
+        let mut scaled = Self::default();
+        for (monomial, coefficient) in self.terms {
+            scaled.add_term(monomial, coefficient.mul(factor));
+        }
+        scaled
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        if self.terms.len() * other.terms.len() > MAX_EXPANDED_TERMS {
+            return self.clone().factored().mul(&other.clone().factored());
+        }
+
+        let mut product = Self::default();
+        for (left_monomial, left_coefficient) in &self.terms {
+            for (right_monomial, right_coefficient) in &other.terms {
+                let mut monomial = left_monomial.clone();
+                for (atom, exponent) in right_monomial {
+                    let total = monomial.get(atom).copied().unwrap_or(0) + exponent;
+                    if total == 0 {
+                        monomial.remove(atom);
+                    } else {
+                        monomial.insert(atom.clone(), total);
+                    }
+                }
+                product.add_term(monomial, left_coefficient.mul(*right_coefficient));
+            }
+        }
+        product
+    }
+
+    fn div(self, divisor: Self) -> Self {
+        if let Some((monomial, coefficient)) = divisor.single_term() {
+            let inverse = Self {
+                terms: BTreeMap::from([(
+                    monomial
+                        .iter()
+                        .map(|(atom, exponent)| (atom.clone(), -exponent))
+                        .collect(),
+                    Coefficient::ONE.div(coefficient),
+                )]),
+            };
+            return self.mul(&inverse);
+        }
+
+        // A multiple of a sum divided by that sum
+        if let (Some(numerator), Some(denominator)) =
+            (self.leading_coefficient(), divisor.leading_coefficient())
+        {
+            let ratio = numerator.div(denominator);
+            if divisor.clone().scale(ratio) == self {
+                return Self::constant(ratio);
+            }
+        }
+
+        self.mul(&divisor.pow_integer(-1))
+    }
+
+    fn pow(self, exponent: Self) -> Self {
+        let integer_exponent = exponent
+            .as_constant()
+            .and_then(|coefficient| coefficient.as_real())
+            .filter(|value| {
+                value.denominator == 1 && value.numerator.unsigned_abs() <= MAX_INTEGER_POWER
+            })
+            .map(|value| value.numerator as i64);
+        if let Some(exponent) = integer_exponent {
+            return self.pow_integer(exponent);
+        }
+
+        if let (Some(base), Some(exponent)) = (self.numeric_value(), exponent.numeric_value()) {
+            return Self::constant(Coefficient::from_complex(base.powc(exponent)));
+        }
+
+        Self::atom(Atom::Power(Box::new(self), Box::new(exponent)))
+    }
+
+    fn pow_integer(self, exponent: i64) -> Self {
+        if exponent == 0 {
+            return Self::constant(Coefficient::ONE);
+        }
+        if exponent > 0 && self.terms.is_empty() {
+            return self;
+        }
+
+        if let Some((monomial, coefficient)) = self.single_term() {
+            return Self {
+                terms: BTreeMap::from([(
+                    monomial
+                        .iter()
+                        .map(|(atom, power)| (atom.clone(), power * exponent))
+                        .collect(),
+                    coefficient.powi(exponent),
+                )]),
+            };
+        }
+
+        if (1..=MAX_EXPANDED_POWER).contains(&exponent) {
+            let mut power = self.clone();
+            for _ in 1..exponent {
+                power = power.mul(&self);
+            }
+            return power;
+        }
+
+        let (coefficient, sum) = self.normalized();
+        Self::atom_power(Atom::Sum(Box::new(sum)), exponent).scale(coefficient.powi(exponent))
+    }
+
+    fn function(function: ExpressionFunction, argument: Self) -> Self {
+        match function {
+            ExpressionFunction::Cis => {
+                // cis(x) = cos(x) + i sin(x)
+                let sine = Self::function(ExpressionFunction::Sine, argument.clone());
+                Self::function(ExpressionFunction::Cosine, argument).add(sine.scale(Coefficient::I))
+            }
+            ExpressionFunction::Cosine | ExpressionFunction::Sine => {
+                Self::trigonometric(function, argument)
+            }
+            ExpressionFunction::Exponent => match argument.numeric_value() {
+                Some(value) => Self::constant(Coefficient::from_complex(value.exp())),
+                None => Self::atom(Atom::Function(function, Box::new(argument))),
+            },
+            ExpressionFunction::SquareRoot => match argument.as_constant() {
+                Some(value) => Self::constant(value.sqrt()),
+                None => match argument.numeric_value() {
+                    Some(value) => Self::constant(Coefficient::from_complex(value.sqrt())),
+                    None => Self::atom(Atom::Function(function, Box::new(argument))),
+                },
+            },
+            _ => match argument.numeric_value() {
+                Some(value) => Self::constant(Coefficient::from_complex(calculate_function(
+                    &function, &value,
+                ))),
+                None => Self::atom(Atom::Function(function, Box::new(argument))),
+            },
+        }
+    }
+
+    fn binary_function(function: BinaryExpressionFunction, left: Self, right: Self) -> Self {
+        if let (Some(left), Some(right)) = (left.numeric_value(), right.numeric_value()) {
+            if let Ok(value) = calculate_binary_function(&function, &left, &right) {
+                return Self::constant(Coefficient::from_complex(value));
+            }
+        }
+        // min(a, b) = min(b, a) and max(a, b) = max(b, a)
+        let (left, right) = match function {
+            BinaryExpressionFunction::Maximum | BinaryExpressionFunction::Minimum
+                if right < left =>
+            {
+                (right, left)
+            }
+            _ => (left, right),
+        };
+        Self::atom(Atom::BinaryFunction(
+            function,
+            Box::new(left),
+            Box::new(right),
+        ))
+    }
+
+    /// Canonicalize `sin` or `cos` of the argument, using their symmetry and periodicity.
+    fn trigonometric(function: ExpressionFunction, argument: Self) -> Self {
+        let (mut rest, mut turns) = argument.split_pi();
+        let mut sign = Coefficient::ONE;
+
+        // sin(-x) = -sin(x) and cos(-x) = cos(x)
+        if rest
+            .leading_coefficient()
+            .is_some_and(|coefficient| coefficient.is_negative())
+        {
+            if let Some(negated) = turns.checked_neg() {
+                rest = rest.neg();
+                turns = negated;
+                if function == ExpressionFunction::Sine {
+                    sign = sign.neg();
+                }
+            }
+        }
+
+        // sin(x + pi) = -sin(x) and cos(x + pi) = -cos(x)
+        let whole = turns.floor();
+        if let Some(fraction) = turns.checked_sub(Rational::integer(whole)) {
+            turns = fraction;
+            if whole % 2 != 0 {
+                sign = sign.neg();
+            }
+        }
+
+        let exact = match (function, rest.terms.is_empty(), turns) {
+            (_, false, _) => None,
+            (ExpressionFunction::Sine, true, Rational::ZERO)
+            | (
+                ExpressionFunction::Cosine,
+                true,
+                Rational {
+                    numerator: 1,
+                    denominator: 2,
+                },
+            ) => Some(Coefficient::ZERO),
+            (
+                ExpressionFunction::Sine,
+                true,
+                Rational {
+                    numerator: 1,
+                    denominator: 2,
+                },
+            )
+            | (ExpressionFunction::Cosine, true, Rational::ZERO) => Some(Coefficient::ONE),
+            _ => None,
+        };
+        if let Some(value) = exact {
+            return Self::constant(value.mul(sign));
+        }
+
+        let argument = rest.add(Self::atom(Atom::Pi).scale(Coefficient::real(turns)));
+        match argument.numeric_value() {
+            Some(value) => {
+                let value = if function == ExpressionFunction::Sine {
+                    value.sin()
+                } else {
+                    value.cos()
+                };
+                Self::constant(Coefficient::from_complex(value).mul(sign))
+            }
+            None => Self::atom(Atom::Function(function, Box::new(argument))).scale(sign),
+        }
+    }
+
+    /// Multiply out each factored sum which is raised to a positive power, such as one left over
+    /// after dividing by its reciprocal, so long as the expansion of its term is small enough.
+    fn flattened(self) -> Self {
+        let is_factored = |(atom, exponent): (&Atom, &i64)| {
+            matches!(atom, Atom::Sum(_)) && (1..=MAX_EXPANDED_POWER).contains(exponent)
+        };
+        if !self
+            .terms
+            .keys()
+            .any(|monomial| monomial.iter().any(is_factored))
+        {
+            return self;
+        }
+
+        let mut flattened = Self::default();
+        for (monomial, coefficient) in self.terms {
+            let expanded_terms = monomial
+                .iter()
+                .filter(|&factor| is_factored(factor))
+                .try_fold(1usize, |size, (atom, exponent)| match atom {
+                    Atom::Sum(sum) => sum
+                        .terms
+                        .len()
+                        .checked_pow(u32::try_from(*exponent).ok()?)
+                        .and_then(|terms| size.checked_mul(terms)),
+                    _ => Some(size),
+                })
+                .filter(|size| *size <= MAX_EXPANDED_TERMS);
+            if expanded_terms.is_none() {
+                flattened.add_term(monomial, coefficient);
+                continue;
+            }
+
+            let (sums, rest): (Monomial, Monomial) = monomial
+                .into_iter()
+                .partition(|(atom, exponent)| is_factored((atom, exponent)));
+            let mut term = Self {
+                terms: BTreeMap::from([(rest, coefficient)]),
+            };
+            for (atom, exponent) in sums {
+                if let Atom::Sum(sum) = atom {
+                    term = term.mul(&sum.pow_integer(exponent));
+                }
+            }
+            flattened = flattened.add(term);
+        }
+        flattened
+    }
+
+    /// Rewrite each pair of terms `c*m*sin(x)^2` and `c*m*cos(x)^2` as `c*m`.
+    fn apply_pythagorean_identity(mut self) -> Self {
+        loop {
+            let pair = self.terms.iter().find_map(|(monomial, coefficient)| {
+                monomial.iter().find_map(|(atom, exponent)| {
+                    let Atom::Function(ExpressionFunction::Sine, argument) = atom else {
+                        return None;
+                    };
+                    if *exponent < 2 {
+                        return None;
+                    }
+
+                    let mut remainder = monomial.clone();
+                    if *exponent == 2 {
+                        remainder.remove(atom);
+                    } else {
+                        remainder.insert(atom.clone(), exponent - 2);
+                    }
+                    let mut cosine_monomial = remainder.clone();
+                    let cosine = Atom::Function(ExpressionFunction::Cosine, argument.clone());
+                    let cosine_exponent = cosine_monomial.get(&cosine).copied().unwrap_or(0) + 2;
+                    if cosine_exponent == 0 {
+                        cosine_monomial.remove(&cosine);
+                    } else {
+                        cosine_monomial.insert(cosine, cosine_exponent);
+                    }
+
+                    (self.terms.get(&cosine_monomial) == Some(coefficient))
+                        .then(|| (monomial.clone(), cosine_monomial, remainder, *coefficient))
+                })
+            });
+
+            let Some((sine_monomial, cosine_monomial, remainder, coefficient)) = pair else {
+                return self;
+            };
+            self.terms.remove(&sine_monomial);
+            self.terms.remove(&cosine_monomial);
+            self.add_term(remainder, coefficient);
+        }
+    }
+
+    /// Split the polynomial into the rest and the rational multiple of `pi` which it contains.
+    fn split_pi(mut self) -> (Self, Rational) {
+        let pi = Monomial::from([(Atom::Pi, 1)]);
+        match self.terms.get(&pi).and_then(Coefficient::as_real) {
+            Some(turns) => {
+                self.terms.remove(&pi);
+                (self, turns)
+            }
+            None => (self, Rational::ZERO),
+        }
+    }
+
+    /// Keep a sum as a single factor, rather than multiplying it out.
+    fn factored(self) -> Self {
+        if self.terms.len() <= 1 {
+            return self;
+        }
+        let (coefficient, sum) = self.normalized();
+        Self::atom(Atom::Sum(Box::new(sum))).scale(coefficient)
+    }
+
+    /// Split the polynomial into its leading coefficient and the remainder, which has a leading
+    /// coefficient of one.
+    fn normalized(self) -> (Coefficient, Self) {
+        match self.leading_coefficient() {
+            Some(coefficient) => (coefficient, self.scale(Coefficient::ONE.div(coefficient))),
+            None => (Coefficient::ONE, self),
+        }
+    }
+
+    fn leading_coefficient(&self) -> Option<Coefficient> {
+        self.terms.values().next().copied()
+    }
+
+    fn single_term(&self) -> Option<(&Monomial, Coefficient)> {
+        match self.terms.len() {
+            1 => self
+                .terms
+                .iter()
+                .next()
+                .map(|(monomial, coefficient)| (monomial, *coefficient)),
+            _ => None,
+        }
+    }
+
+    fn as_constant(&self) -> Option<Coefficient> {
+        match self.terms.len() {
+            0 => Some(Coefficient::ZERO),
+            1 => self.terms.get(&Monomial::new()).copied(),
+            _ => None,
+        }
+    }
+
+    /// The value of the polynomial, if it contains no atoms other than `pi`.
+    fn numeric_value(&self) -> Option<Complex64> {
+        self.terms
+            .iter()
+            .try_fold(real!(0.0), |sum, (monomial, coefficient)| {
+                let mut value = coefficient.to_complex();
+                for (atom, exponent) in monomial {
+                    match atom {
+                        Atom::Pi => value *= PI.powi(i32::try_from(*exponent).ok()?),
+                        _ => return None,
+                    }
+                }
+                Some(sum + value)
+            })
+    }
+
+    fn to_expression(&self) -> Expression {
+        let mut terms = self.terms.iter();
+        let Some((monomial, coefficient)) = terms.next() else {
+            return Expression::Number(real!(0.0));
+        };
+        let mut expression = term_expression(monomial, *coefficient);
+        for (monomial, coefficient) in terms {
+            expression = if coefficient.as_real().is_some() && coefficient.is_negative() {
+                expression - term_expression(monomial, coefficient.neg())
+            } else {
+                expression + term_expression(monomial, *coefficient)
+            };
+        }
+        expression
+    }
+}
+
+impl Atom {
+    fn to_expression(&self) -> Expression {
+        match self {
+            Self::Pi => Expression::PiConstant,
+            Self::Variable(name) => Expression::Variable(name.clone()),
+            Self::Address(reference) => Expression::Address(reference.clone()),
+            Self::Function(function, argument) => Expression::FunctionCall(
+                FunctionCallExpression::new(*function, Box::new(argument.to_expression())),
+            ),
+            Self::BinaryFunction(function, left, right) => {
+                Expression::BinaryFunctionCall(BinaryFunctionCallExpression::new(
+                    *function,
+                    Box::new(left.to_expression()),
+                    Box::new(right.to_expression()),
+                ))
+            }
+            Self::Power(base, exponent) => base.to_expression() ^ exponent.to_expression(),
+            Self::Sum(sum) => sum.to_expression(),
+        }
+    }
+}
+
+fn term_expression(monomial: &Monomial, coefficient: Coefficient) -> Expression {
+    let mut numerator: Option<Expression> = None;
+    let mut divisors = Vec::new();
+    for (atom, exponent) in monomial {
+        let factor = atom.to_expression();
+        let factor = match *exponent {
+            1 => factor,
+            -1 => {
+                divisors.push(factor);
+                continue;
+            }
+            exponent => factor ^ Expression::Number(real!(exponent as f64)),
+        };
+        numerator = Some(match numerator {
+            Some(product) => product * factor,
+            None => factor,
+        });
+    }
+
+    let mut expression = match numerator {
+        Some(product) if coefficient.is_one() => product,
+        Some(product) => coefficient_expression(coefficient) * product,
+        None => coefficient_expression(coefficient),
+    };
+    // Divide by each factor separately, so that each remains a separate factor when the
+    // expression is canonicalized again.
+    for divisor in divisors {
+        expression /= divisor;
+    }
+    expression
+}
+
+fn coefficient_expression(coefficient: Coefficient) -> Expression {
+    match coefficient {
+        Coefficient::Inexact(value) => Expression::Number(value),
+        Coefficient::Exact { re, im } => {
+            let value = coefficient.to_complex();
+            if Coefficient::from_complex(value) == coefficient {
+                Expression::Number(value)
+            } else if im.is_zero() {
+                rational_expression(re)
+            } else {
+                rational_expression(re) + rational_expression(im) * Expression::Number(imag!(1.0))
+            }
+        }
+    }
+}
+
+/// An expression for a rational number, which is a quotient if the number cannot be represented
+/// exactly in floating point.
+fn rational_expression(value: Rational) -> Expression {
+    let approximation = value.to_f64();
+    if Rational::from_f64(approximation) == Some(value) {
+        Expression::Number(real!(approximation))
+    } else {
+        integer_expression(value.numerator) / integer_expression(value.denominator)
+    }
+}
+
+/// An expression for an integer, which is split into parts if the integer cannot be represented
+/// exactly in floating point.
+fn integer_expression(value: i128) -> Expression {
+    const SPLIT: i128 = 1 << 52;
+    if value.unsigned_abs() <= SPLIT as u128 {
+        return Expression::Number(real!(value as f64));
+    }
+    let (high, low) = (value.div_euclid(SPLIT), value.rem_euclid(SPLIT));
+    integer_expression(high) * Expression::Number(real!(SPLIT as f64))
+        + Expression::Number(real!(low as f64))
+}
+
+#[cfg(test)]
+// This lint should be re-enabled once this proptest issue is resolved
+// https://github.com/proptest-rs/proptest/issues/364
+#[allow(clippy::arc_with_non_send_sync)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        str::FromStr,
+    };
+
+    use proptest::prelude::*;
+    use rstest::rstest;
+
+    use crate::{
+        expression::{
+            Expression, ExpressionFunction, FunctionCallExpression, InfixExpression, InfixOperator,
+            PrefixExpression, PrefixOperator,
+        },
+        instruction::MemoryReference,
+        real,
+    };
+
+    use super::CanonicalExpression;
+
+    fn canonical(input: &str) -> CanonicalExpression {
+        CanonicalExpression::from(Expression::from_str(input).unwrap())
+    }
+
+    #[rstest]
+    #[case("2*%theta + %theta", "3*%theta")]
+    #[case("%x - %x", "0")]
+    #[case("(%a + %b)^2", "%a^2 + 2*%a*%b + %b^2")]
+    #[case("(%a + %b) * (%a - %b)", "%a^2 - %b^2")]
+    #[case("%x/2 + %x/2", "%x")]
+    #[case("1/3 + 1/3 + 1/3", "1")]
+    #[case("theta[0] * theta[1]", "theta[1] * theta[0]")]
+    #[case("(%a + %b) / (2*%a + 2*%b)", "0.5")]
+    #[case("%x / (%a + %b) + %y / (%a + %b)", "(%x + %y) / (%b + %a)")]
+    #[case("%x^-1 * %x^2", "%x")]
+    #[case("pi/2 + pi/2", "pi")]
+    #[case("sin(pi/2)", "1")]
+    #[case("cos(pi)", "-1")]
+    #[case("%x/(%x/(%y + %x))", "%x + %y")]
+    #[case("1/(1/(%a + %b))", "%a + %b")]
+    #[case("sin(-%x)", "-sin(%x)")]
+    #[case("cos(-%x)", "cos(%x)")]
+    #[case("cos(%x + 2*pi)", "cos(%x)")]
+    #[case("sin(%x + pi)", "-sin(%x)")]
+    #[case("cis(%x)", "cos(%x) + 1.0i*sin(%x)")]
+    #[case("sin(%x)^2 + cos(%x)^2", "1")]
+    #[case("%y*sin(2*%x)^3 + %y*sin(2*%x)*cos(2*%x)^2", "%y*sin(2*%x)")]
+    #[case("sqrt(9/4)", "1.5")]
+    #[case("exp(0)", "1")]
+    #[case("abs(-2) + floor(%x - %x + 0.5)", "2")]
+    #[case("max(%x, %y)", "max(%y, %x)")]
+    #[case("min(%x + 1, %y)", "min(%y, 1 + %x)")]
+    #[case("max(1, 2)", "2")]
+    fn equivalent(#[case] left: &str, #[case] right: &str) {
+        assert_eq!(canonical(left), canonical(right));
+    }
+
+    #[rstest]
+    #[case("%x/(%x/(%y + %x))")]
+    #[case("(%a + %b) * %x / %x")]
+    #[case("(%a + %b)^-1 * (%a + %b)^3")]
+    fn canonical_form_of_canonical_form(#[case] input: &str) {
+        let canonical = canonical(input);
+        assert_eq!(
+            CanonicalExpression::from(canonical.to_expression()),
+            canonical
+        );
+    }
+
+    #[rstest]
+    #[case("%x", "%y")]
+    #[case("sin(%x)", "cos(%x)")]
+    #[case("%x^2", "%x^3")]
+    #[case("theta[0]", "theta[1]")]
+    #[case("atan2(%x, %y)", "atan2(%y, %x)")]
+    fn not_equivalent(#[case] left: &str, #[case] right: &str) {
+        assert_ne!(canonical(left), canonical(right));
+    }
+
+    #[test]
+    fn hashes_by_value() {
+        let set: HashSet<_> = ["%a*2 + 1", "1 + 2*%a", "(2*%a*%b + %b)/%b"]
+            .into_iter()
+            .map(canonical)
+            .collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    fn arb_expr() -> impl Strategy<Value = Expression> {
+        let leaf = prop_oneof![
+            Just(Expression::Variable("x".to_string())),
+            Just(Expression::Variable("y".to_string())),
+            Just(Expression::Address(MemoryReference {
+                name: "theta".to_string(),
+                index: 0,
+            })),
+            (-4i32..4).prop_map(|value| Expression::Number(real!(f64::from(value) / 2.0))),
+            Just(Expression::PiConstant),
+        ];
+        leaf.prop_recursive(4, 24, 2, |expr| {
+            prop_oneof![
+                (
+                    prop_oneof![
+                        Just(ExpressionFunction::Cis),
+                        Just(ExpressionFunction::Cosine),
+                        Just(ExpressionFunction::Exponent),
+                        Just(ExpressionFunction::Sine),
+                    ],
+                    expr.clone()
+                )
+                    .prop_map(|(function, e)| {
+                        Expression::FunctionCall(FunctionCallExpression::new(function, Box::new(e)))
+                    }),
+                (
+                    expr.clone(),
+                    prop_oneof![
+                        Just(InfixOperator::Plus),
+                        Just(InfixOperator::Minus),
+                        Just(InfixOperator::Star),
+                        Just(InfixOperator::Slash),
+                    ],
+                    expr.clone()
+                )
+                    .prop_map(|(left, operator, right)| {
+                        Expression::Infix(InfixExpression::new(
+                            Box::new(left),
+                            operator,
+                            Box::new(right),
+                        ))
+                    }),
+                (expr.clone(), 0..4u8).prop_map(|(base, exponent)| {
+                    base ^ Expression::Number(real!(f64::from(exponent)))
+                }),
+                expr.prop_map(|e| {
+                    Expression::Prefix(PrefixExpression::new(PrefixOperator::Minus, Box::new(e)))
+                }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn canonical_form_is_idempotent(expression in arb_expr()) {
+            let canonical = CanonicalExpression::from(&expression);
+            prop_assert_eq!(CanonicalExpression::from(canonical.to_expression()), canonical);
+        }
+
+        #[test]
+        fn canonical_form_preserves_value(
+            expression in arb_expr(),
+            x in -1.0..1.0f64,
+            y in -1.0..1.0f64,
+            theta in -1.0..1.0f64,
+        ) {
+            let variables = HashMap::from([("x".to_string(), real!(x)), ("y".to_string(), real!(y))]);
+            let memory = HashMap::from([("theta", vec![theta])]);
+
+            let expected = expression.evaluate(&variables, &memory).unwrap();
+            prop_assume!(expected.is_finite() && expected.norm() < 1e6);
+            let actual = expression
+                .clone()
+                .into_canonical()
+                .evaluate(&variables, &memory)
+                .unwrap();
+            prop_assert!(
+                (actual - expected).norm() <= 1e-6 * (1.0 + expected.norm()),
+                "canonical form evaluates to {actual} rather than {expected}"
+            );
+        }
+    }
+}
@@ -0,0 +1,496 @@
+//! Compilation of [`Expression`]s into a flat form for fast repeated evaluation
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::f64::consts::PI;
+
+use indexmap::IndexSet;
+use num_complex::Complex64;
+
+use crate::{instruction::MemoryReference, real};
+
+use super::{
+    calculate_binary_function, calculate_function, calculate_infix, BinaryExpressionFunction,
+    BinaryFunctionCallExpression, EvaluationError, Expression, ExpressionFunction,
+    FunctionCallExpression, InfixExpression, InfixOperator, PrefixExpression, PrefixOperator,
+};
+
+/// An assignment of slots to the variables and memory references used by compiled expressions.
+///
+/// Each distinct variable and memory reference is given a slot the first time it is compiled.
+/// Compiled expressions are evaluated against slices of values ordered by these slots, so that no
+/// lookups by name happen during evaluation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParameterSlots {
+    variables: IndexSet<String>,
+    memory_references: IndexSet<MemoryReference>,
+}
+
+impl ParameterSlots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The variables with assigned slots, in slot order.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.variables.iter().map(String::as_str)
+    }
+
+    /// The memory references with assigned slots, in slot order.
+    pub fn memory_references(&self) -> impl Iterator<Item = &MemoryReference> {
+        self.memory_references.iter()
+    }
+
+    /// The slot of the given variable, if it has one.
+    pub fn variable_slot(&self, name: &str) -> Option<usize> {
+        self.variables.get_index_of(name)
+    }
+
+    /// The slot of the given memory reference, if it has one.
+    pub fn memory_reference_slot(&self, reference: &MemoryReference) -> Option<usize> {
+        self.memory_references.get_index_of(reference)
+    }
+}
+
+/// A single step of a [`CompiledExpression`], which operates on a stack of values.
+#[derive(Clone, Debug, PartialEq)]
+enum Operation {
+    Number(Complex64),
+    Variable(usize),
+    MemoryReference(usize),
+    Function(ExpressionFunction),
+    BinaryFunction(BinaryExpressionFunction),
+    Infix(InfixOperator),
+    Negate,
+}
+
+/// An [`Expression`] compiled against [`ParameterSlots`] for fast repeated evaluation.
+///
+/// Evaluation produces the same result as [`Expression::evaluate`] given the same values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledExpression {
+    operations: Vec<Operation>,
+    /// The greatest number of values on the stack at any point during evaluation.
+    stack_size: usize,
+    /// One more than the greatest variable slot used, or zero if none are.
+    variable_count: usize,
+    /// One more than the greatest memory reference slot used, or zero if none are.
+    memory_reference_count: usize,
+}
+
+impl Expression {
+    /// Compile the expression for fast repeated evaluation, assigning slots to any of its variables
+    /// and memory references which do not have them already.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::{Expression, ParameterSlots};
+    /// use std::str::FromStr;
+    /// use num_complex::Complex64;
+    ///
+    /// let mut slots = ParameterSlots::new();
+    /// let compiled = Expression::from_str("%beta + theta[0]").unwrap().compile(&mut slots);
+    ///
+    /// assert_eq!(slots.variable_slot("beta"), Some(0));
+    /// assert_eq!(compiled.evaluate(&[Complex64::from(1.0)], &[2.0]), Ok(Complex64::from(3.0)));
+    /// ```
+    pub fn compile(&self, slots: &mut ParameterSlots) -> CompiledExpression {
+        let mut compiled = CompiledExpression {
+            operations: Vec::new(),
+            stack_size: 0,
+            variable_count: 0,
+            memory_reference_count: 0,
+        };
+        compiled.stack_size = compiled.emit(self, slots);
+        compiled
+    }
+}
+
+impl CompiledExpression {
+    /// Append the operations for `expression`, returning the stack space they need.
+    fn emit(&mut self, expression: &Expression, slots: &mut ParameterSlots) -> usize {
+        match expression {
+            Expression::Address(reference) => {
+                let slot = match slots.memory_references.get_index_of(reference) {
+                    Some(slot) => slot,
+                    None => slots.memory_references.insert_full(reference.clone()).0,
+                };
+                self.memory_reference_count = self.memory_reference_count.max(slot + 1);
+                self.operations.push(Operation::MemoryReference(slot));
+                1
+            }
+            Expression::Variable(name) => {
+                let slot = match slots.variables.get_index_of(name) {
+                    Some(slot) => slot,
+                    None => slots.variables.insert_full(name.clone()).0,
+                };
+                self.variable_count = self.variable_count.max(slot + 1);
+                self.operations.push(Operation::Variable(slot));
+                1
+            }
+            Expression::Number(number) => {
+                self.operations.push(Operation::Number(*number));
+                1
+            }
+            Expression::PiConstant => {
+                self.operations.push(Operation::Number(real!(PI)));
+                1
+            }
+            Expression::FunctionCall(FunctionCallExpression {
+                function,
+                expression,
+            }) => {
+                let size = self.emit(expression, slots);
+                self.operations.push(Operation::Function(*function));
+                size
+            }
+            Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+                function,
+                left,
+                right,
+            }) => {
+                let left_size = self.emit(left, slots);
+                let right_size = self.emit(right, slots);
+                self.operations.push(Operation::BinaryFunction(*function));
+                left_size.max(right_size + 1)
+            }
+            Expression::Prefix(PrefixExpression {
+                operator,
+                expression,
+            }) => {
+                let size = self.emit(expression, slots);
+                if matches!(operator, PrefixOperator::Minus) {
+                    self.operations.push(Operation::Negate);
+                }
+                size
+            }
+            Expression::Infix(InfixExpression {
+                left,
+                operator,
+                right,
+            }) => {
+                let left_size = self.emit(left, slots);
+                let right_size = self.emit(right, slots);
+                self.operations.push(Operation::Infix(*operator));
+                left_size.max(right_size + 1)
+            }
+        }
+    }
+
+    /// Evaluate the expression, given values for variables and memory references ordered by the
+    /// [`ParameterSlots`] it was compiled against.
+    ///
+    /// Returns [`EvaluationError::Incomplete`] if a slot used by the expression has no value.
+    pub fn evaluate(
+        &self,
+        variables: &[Complex64],
+        memory_references: &[f64],
+    ) -> Result<Complex64, EvaluationError> {
+        let mut stack = Vec::with_capacity(self.stack_size);
+        self.evaluate_with_stack(variables, memory_references, &mut stack)
+    }
+
+    /// Evaluate the expression at each of many points, each given as values for variables and
+    /// memory references as for [`CompiledExpression::evaluate`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::{Expression, ParameterSlots};
+    /// use std::str::FromStr;
+    /// use num_complex::Complex64;
+    ///
+    /// let mut slots = ParameterSlots::new();
+    /// let compiled = Expression::from_str("2 * theta[0]").unwrap().compile(&mut slots);
+    ///
+    /// let sweep = [0.0, 1.0, 2.0];
+    /// let values = compiled.evaluate_batch(sweep.chunks(1).map(|point| (&[][..], point))).unwrap();
+    ///
+    /// assert_eq!(values, [0.0, 2.0, 4.0].map(Complex64::from));
+    /// ```
+    pub fn evaluate_batch<'a>(
+        &self,
+        points: impl IntoIterator<Item = (&'a [Complex64], &'a [f64])>,
+    ) -> Result<Vec<Complex64>, EvaluationError> {
+        let mut stack = Vec::with_capacity(self.stack_size);
+        points
+            .into_iter()
+            .map(|(variables, memory_references)| {
+                self.evaluate_with_stack(variables, memory_references, &mut stack)
+            })
+            .collect()
+    }
+
+    fn evaluate_with_stack(
+        &self,
+        variables: &[Complex64],
+        memory_references: &[f64],
+        stack: &mut Vec<Complex64>,
+    ) -> Result<Complex64, EvaluationError> {
+        if variables.len() < self.variable_count
+            || memory_references.len() < self.memory_reference_count
+        {
+            return Err(EvaluationError::Incomplete);
+        }
+
+        stack.clear();
+        for operation in &self.operations {
+            match operation {
+                Operation::Number(number) => stack.push(*number),
+                Operation::Variable(slot) => stack.push(variables[*slot]),
+                Operation::MemoryReference(slot) => stack.push(real!(memory_references[*slot])),
+                Operation::Function(function) => {
+                    let argument = stack.last_mut().expect("compiled stack is never empty");
+                    *argument = calculate_function(function, argument);
+                }
+                Operation::BinaryFunction(function) => {
+                    let right = stack.pop().expect("compiled stack is never empty");
+                    let left = stack.last_mut().expect("compiled stack is never empty");
+                    *left = calculate_binary_function(function, left, &right)?;
+                }
+                Operation::Infix(operator) => {
+                    let right = stack.pop().expect("compiled stack is never empty");
+                    let left = stack.last_mut().expect("compiled stack is never empty");
+                    *left = calculate_infix(left, operator, &right);
+                }
+                Operation::Negate => {
+                    let value = stack.last_mut().expect("compiled stack is never empty");
+                    *value = -*value;
+                }
+            }
+        }
+        Ok(stack.pop().expect("compiled stack is never empty"))
+    }
+}
+
+/// A collection of [`CompiledExpression`]s sharing one set of [`ParameterSlots`], so that they may
+/// all be evaluated against the same values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompiledExpressionSet {
+    slots: ParameterSlots,
+    expressions: Vec<CompiledExpression>,
+}
+
+impl CompiledExpressionSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile an expression into the set, returning its position.
+    pub fn insert(&mut self, expression: &Expression) -> usize {
+        let compiled = expression.compile(&mut self.slots);
+        self.expressions.push(compiled);
+        self.expressions.len() - 1
+    }
+
+    pub fn slots(&self) -> &ParameterSlots {
+        &self.slots
+    }
+
+    pub fn expressions(&self) -> &[CompiledExpression] {
+        &self.expressions
+    }
+
+    /// Evaluate every expression in the set, in order of insertion.
+    pub fn evaluate(
+        &self,
+        variables: &[Complex64],
+        memory_references: &[f64],
+    ) -> Result<Vec<Complex64>, EvaluationError> {
+        let mut stack = Vec::new();
+        self.expressions
+            .iter()
+            .map(|expression| {
+                expression.evaluate_with_stack(variables, memory_references, &mut stack)
+            })
+            .collect()
+    }
+
+    /// Evaluate every expression in the set at each of many points, returning one row of values
+    /// per point.
+    pub fn evaluate_batch<'a>(
+        &self,
+        points: impl IntoIterator<Item = (&'a [Complex64], &'a [f64])>,
+    ) -> Result<Vec<Vec<Complex64>>, EvaluationError> {
+        points
+            .into_iter()
+            .map(|(variables, memory_references)| self.evaluate(variables, memory_references))
+            .collect()
+    }
+}
+
+impl FromIterator<Expression> for CompiledExpressionSet {
+    fn from_iter<T: IntoIterator<Item = Expression>>(iter: T) -> Self {
+        let mut set = Self::new();
+        for expression in iter {
+            set.insert(&expression);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+// This lint should be re-enabled once this proptest issue is resolved
+// https://github.com/proptest-rs/proptest/issues/364
+#[allow(clippy::arc_with_non_send_sync)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use num_complex::Complex64;
+    use proptest::prelude::*;
+    use rstest::rstest;
+
+    use crate::{
+        expression::{
+            BinaryExpressionFunction, BinaryFunctionCallExpression, EvaluationError, Expression,
+            ExpressionFunction, FunctionCallExpression, InfixExpression, InfixOperator,
+            PrefixExpression, PrefixOperator,
+        },
+        instruction::MemoryReference,
+        real,
+    };
+
+    use super::{CompiledExpressionSet, ParameterSlots};
+
+    #[rstest]
+    #[case("1 + 2i", vec![], vec![], Ok(Complex64::new(1.0, 2.0)))]
+    #[case("%a - %b * %a", vec![real!(2.0), real!(3.0)], vec![], Ok(real!(-4.0)))]
+    #[case("-sin(pi/2) * theta[1]", vec![], vec![0.0, 2.0], Ok(real!(-2.0)))]
+    #[case("%a + theta[1]", vec![real!(1.0)], vec![0.0], Err(EvaluationError::Incomplete))]
+    fn evaluate(
+        #[case] input: &str,
+        #[case] variables: Vec<Complex64>,
+        #[case] memory_references: Vec<f64>,
+        #[case] expected: Result<Complex64, EvaluationError>,
+    ) {
+        let mut slots = ParameterSlots::new();
+        slots.memory_references.insert(MemoryReference {
+            name: "theta".to_string(),
+            index: 0,
+        });
+        let compiled = Expression::from_str(input).unwrap().compile(&mut slots);
+        let result = compiled.evaluate(&variables, &memory_references);
+        match (result, expected) {
+            (Ok(result), Ok(expected)) => assert!((result - expected).norm() < 1e-12),
+            (result, expected) => assert_eq!(result, expected),
+        }
+    }
+
+    #[test]
+    fn set_shares_slots() {
+        let set: CompiledExpressionSet = ["%a * theta[0]", "theta[0] + %b", "%a"]
+            .into_iter()
+            .map(|input| Expression::from_str(input).unwrap())
+            .collect();
+
+        assert_eq!(set.slots().variables().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(set.slots().memory_references().count(), 1);
+
+        let points = [
+            (vec![real!(1.0), real!(2.0)], vec![3.0]),
+            (vec![real!(4.0), real!(5.0)], vec![6.0]),
+        ];
+        let values = set
+            .evaluate_batch(
+                points
+                    .iter()
+                    .map(|(variables, memory)| (variables.as_slice(), memory.as_slice())),
+            )
+            .unwrap();
+        assert_eq!(
+            values,
+            [
+                vec![real!(3.0), real!(5.0), real!(1.0)],
+                vec![real!(24.0), real!(11.0), real!(4.0)],
+            ]
+        );
+    }
+
+    fn arb_expr() -> impl Strategy<Value = Expression> {
+        let leaf = prop_oneof![
+            prop_oneof![Just("a"), Just("b")].prop_map(|name| Expression::Variable(name.into())),
+            (0..3u64).prop_map(|index| Expression::Address(MemoryReference {
+                name: "theta".to_string(),
+                index,
+            })),
+            ((-2.0..2.0f64), (-2.0..2.0f64))
+                .prop_map(|(re, im)| Expression::Number(Complex64::new(re, im))),
+            Just(Expression::PiConstant),
+        ];
+        leaf.prop_recursive(4, 32, 2, |expr| {
+            prop_oneof![
+                (any::<ExpressionFunction>(), expr.clone()).prop_map(|(function, e)| {
+                    Expression::FunctionCall(FunctionCallExpression::new(function, Box::new(e)))
+                }),
+                (
+                    any::<BinaryExpressionFunction>(),
+                    expr.clone(),
+                    expr.clone()
+                )
+                    .prop_map(|(function, left, right)| {
+                        Expression::BinaryFunctionCall(BinaryFunctionCallExpression::new(
+                            function,
+                            Box::new(left),
+                            Box::new(right),
+                        ))
+                    }),
+                (expr.clone(), any::<InfixOperator>(), expr.clone()).prop_map(
+                    |(left, operator, right)| {
+                        Expression::Infix(InfixExpression::new(
+                            Box::new(left),
+                            operator,
+                            Box::new(right),
+                        ))
+                    }
+                ),
+                (any::<PrefixOperator>(), expr).prop_map(|(operator, e)| {
+                    Expression::Prefix(PrefixExpression::new(operator, Box::new(e)))
+                }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn compiled_matches_tree_evaluation(
+            expression in arb_expr(),
+            a in -2.0..2.0f64,
+            b in -2.0..2.0f64,
+            theta in proptest::collection::vec(-2.0..2.0f64, 3),
+        ) {
+            let variables = HashMap::from([("a".to_string(), real!(a)), ("b".to_string(), real!(b))]);
+            let memory = HashMap::from([("theta", theta.clone())]);
+            let expected = expression.evaluate(&variables, &memory);
+
+            let mut slots = ParameterSlots::new();
+            let compiled = expression.compile(&mut slots);
+            let variable_values: Vec<_> = slots.variables().map(|name| variables[name]).collect();
+            let memory_values: Vec<_> = slots
+                .memory_references()
+                .map(|reference| theta[reference.index as usize])
+                .collect();
+            let result = compiled.evaluate(&variable_values, &memory_values);
+
+            match (result, expected) {
+                (Ok(result), Ok(expected)) => prop_assert!(
+                    result == expected || (result.is_nan() && expected.is_nan()),
+                    "compiled {result} differs from tree {expected}"
+                ),
+                (result, expected) => prop_assert_eq!(result, expected),
+            }
+        }
+    }
+}
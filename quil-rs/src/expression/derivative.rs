@@ -0,0 +1,453 @@
+//! Symbolic differentiation of [`Expression`]s
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{imag, instruction::MemoryReference, quil::Quil, real};
+
+use super::{
+    BinaryExpressionFunction, BinaryFunctionCallExpression, Expression, ExpressionFunction,
+    FunctionCallExpression, InfixExpression, InfixOperator, PrefixExpression, PrefixOperator,
+};
+
+/// The quantity with respect to which an [`Expression`] is differentiated.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DerivativeTarget {
+    /// A variable, such as `%theta`.
+    Variable(String),
+    /// A single element of a memory region, such as `theta[0]`.
+    Address(MemoryReference),
+}
+
+impl DerivativeTarget {
+    fn matches(&self, expression: &Expression) -> bool {
+        match (self, expression) {
+            (Self::Variable(target), Expression::Variable(name)) => target == name,
+            (Self::Address(target), Expression::Address(reference)) => target == reference,
+            _ => false,
+        }
+    }
+}
+
+impl From<MemoryReference> for DerivativeTarget {
+    fn from(reference: MemoryReference) -> Self {
+        Self::Address(reference)
+    }
+}
+
+/// The different possible types of errors that could occur during differentiation.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum DerivativeError {
+    /// The derivative of `base ^ exponent` with a varying exponent requires the logarithm of the
+    /// base, which can only be represented when the base is a constant number.
+    #[error(
+        "cannot differentiate {}: the exponent varies but the base is not a constant number",
+        .0.to_quil_or_debug()
+    )]
+    NonConstantBase(Expression),
+}
+
+impl Expression {
+    /// Differentiate the expression with respect to a variable or memory address, returning the
+    /// simplified derivative.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::{DerivativeTarget, Expression};
+    /// use std::str::FromStr;
+    ///
+    /// let expression = Expression::from_str("2 * sin(%theta)").unwrap();
+    /// let derivative = expression
+    ///     .derivative(&DerivativeTarget::Variable("theta".to_string()))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(derivative, Expression::from_str("2 * cos(%theta)").unwrap());
+    /// ```
+    pub fn derivative(&self, wrt: &DerivativeTarget) -> Result<Self, DerivativeError> {
+        Ok(self.derivative_inner(wrt)?.into_simplified())
+    }
+
+    /// Whether the expression contains the target, and so may have a non-zero derivative.
+    fn depends_on(&self, wrt: &DerivativeTarget) -> bool {
+        match self {
+            Expression::Address(_) | Expression::Variable(_) => wrt.matches(self),
+            Expression::FunctionCall(FunctionCallExpression { expression, .. })
+            | Expression::Prefix(PrefixExpression { expression, .. }) => expression.depends_on(wrt),
+            Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+                left, right, ..
+            })
+            | Expression::Infix(InfixExpression { left, right, .. }) => {
+                left.depends_on(wrt) || right.depends_on(wrt)
+            }
+            Expression::Number(_) | Expression::PiConstant => false,
+        }
+    }
+
+    /// Differentiate without simplifying, so that simplification runs once over the result.
+    fn derivative_inner(&self, wrt: &DerivativeTarget) -> Result<Self, DerivativeError> {
+        if !self.depends_on(wrt) {
+            return Ok(Expression::Number(real!(0.0)));
+        }
+
+        Ok(match self {
+            Expression::Address(_) | Expression::Variable(_) => Expression::Number(real!(1.0)),
+            Expression::Number(_) | Expression::PiConstant => Expression::Number(real!(0.0)),
+            Expression::Prefix(PrefixExpression {
+                operator,
+                expression,
+            }) => Expression::Prefix(PrefixExpression::new(
+                *operator,
+                Box::new(expression.derivative_inner(wrt)?),
+            )),
+            Expression::FunctionCall(FunctionCallExpression {
+                function,
+                expression,
+            }) => {
+                let inner = expression.as_ref().clone();
+                let d_inner = expression.derivative_inner(wrt)?;
+                match function {
+                    ExpressionFunction::Cis => {
+                        Expression::Number(imag!(1.0))
+                            * call(ExpressionFunction::Cis, inner)
+                            * d_inner
+                    }
+                    ExpressionFunction::Cosine => {
+                        negate(call(ExpressionFunction::Sine, inner)) * d_inner
+                    }
+                    ExpressionFunction::Exponent => {
+                        call(ExpressionFunction::Exponent, inner) * d_inner
+                    }
+                    ExpressionFunction::Sine => call(ExpressionFunction::Cosine, inner) * d_inner,
+                    ExpressionFunction::SquareRoot => {
+                        Expression::Number(real!(0.5)) / call(ExpressionFunction::SquareRoot, inner)
+                            * d_inner
+                    }
+                    ExpressionFunction::Tangent => {
+                        d_inner / (call(ExpressionFunction::Cosine, inner) ^ number(2.0))
+                    }
+                    ExpressionFunction::ArcSine => {
+                        d_inner
+                            / call(
+                                ExpressionFunction::SquareRoot,
+                                number(1.0) - (inner ^ number(2.0)),
+                            )
+                    }
+                    ExpressionFunction::ArcCosine => negate(
+                        d_inner
+                            / call(
+                                ExpressionFunction::SquareRoot,
+                                number(1.0) - (inner ^ number(2.0)),
+                            ),
+                    ),
+                    ExpressionFunction::ArcTangent => {
+                        d_inner / (number(1.0) + (inner ^ number(2.0)))
+                    }
+                    ExpressionFunction::Logarithm => d_inner / inner,
+                    // The remaining functions are not holomorphic, and so are differentiated with
+                    // respect to the real target alone.
+                    ExpressionFunction::AbsoluteValue => {
+                        call(
+                            ExpressionFunction::RealPart,
+                            call(ExpressionFunction::Conjugate, inner.clone()) * d_inner,
+                        ) / call(ExpressionFunction::AbsoluteValue, inner)
+                    }
+                    ExpressionFunction::Conjugate
+                    | ExpressionFunction::ImaginaryPart
+                    | ExpressionFunction::RealPart => call(*function, d_inner),
+                    // Zero except at the discontinuities, where there is no derivative.
+                    ExpressionFunction::Ceiling | ExpressionFunction::Floor => number(0.0),
+                }
+            }
+            Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+                function,
+                left,
+                right,
+            }) => {
+                let left = left.as_ref().clone();
+                let right = right.as_ref().clone();
+                let d_left = left.derivative_inner(wrt)?;
+                let d_right = right.derivative_inner(wrt)?;
+                match function {
+                    BinaryExpressionFunction::ArcTangent2 => {
+                        // d(atan2(y, x)) = (x * dy - y * dx) / (x^2 + y^2)
+                        (right.clone() * d_left - left.clone() * d_right)
+                            / ((right ^ number(2.0)) + (left ^ number(2.0)))
+                    }
+                    BinaryExpressionFunction::Maximum | BinaryExpressionFunction::Minimum => {
+                        // max(a, b) = (a + b + |a - b|) / 2, and min(a, b) = (a + b - |a - b|) / 2
+                        let difference = left - right;
+                        let d_distance = difference.clone() * (d_left.clone() - d_right.clone())
+                            / call(ExpressionFunction::AbsoluteValue, difference);
+                        let sum = if *function == BinaryExpressionFunction::Maximum {
+                            d_left + d_right + d_distance
+                        } else {
+                            d_left + d_right - d_distance
+                        };
+                        sum / number(2.0)
+                    }
+                }
+            }
+            Expression::Infix(InfixExpression {
+                left,
+                operator,
+                right,
+            }) => {
+                let left = left.as_ref().clone();
+                let right = right.as_ref().clone();
+                match operator {
+                    InfixOperator::Plus => {
+                        left.derivative_inner(wrt)? + right.derivative_inner(wrt)?
+                    }
+                    InfixOperator::Minus => {
+                        left.derivative_inner(wrt)? - right.derivative_inner(wrt)?
+                    }
+                    InfixOperator::Star => {
+                        let d_left = left.derivative_inner(wrt)?;
+                        let d_right = right.derivative_inner(wrt)?;
+                        d_left * right + left * d_right
+                    }
+                    InfixOperator::Slash => {
+                        let d_left = left.derivative_inner(wrt)?;
+                        let d_right = right.derivative_inner(wrt)?;
+                        (d_left * right.clone() - left * d_right)
+                            / (right ^ Expression::Number(real!(2.0)))
+                    }
+                    InfixOperator::Caret if !right.depends_on(wrt) => {
+                        // d(u^c) = c * u^(c - 1) * du
+                        let d_left = left.derivative_inner(wrt)?;
+                        right.clone() * (left ^ (right - Expression::Number(real!(1.0)))) * d_left
+                    }
+                    InfixOperator::Caret => {
+                        // d(b^v) = b^v * ln(b) * dv, for a constant number b
+                        let Expression::Number(base) = left.clone().into_simplified() else {
+                            return Err(DerivativeError::NonConstantBase(self.clone()));
+                        };
+                        let d_right = right.derivative_inner(wrt)?;
+                        (left ^ right) * Expression::Number(base.ln()) * d_right
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn call(function: ExpressionFunction, expression: Expression) -> Expression {
+    Expression::FunctionCall(FunctionCallExpression::new(function, Box::new(expression)))
+}
+
+fn number(value: f64) -> Expression {
+    Expression::Number(real!(value))
+}
+
+fn negate(expression: Expression) -> Expression {
+    Expression::Prefix(PrefixExpression::new(
+        PrefixOperator::Minus,
+        Box::new(expression),
+    ))
+}
+
+#[cfg(test)]
+// This lint should be re-enabled once this proptest issue is resolved
+// https://github.com/proptest-rs/proptest/issues/364
+#[allow(clippy::arc_with_non_send_sync)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr};
+
+    use num_complex::Complex64;
+    use proptest::prelude::*;
+    use rstest::rstest;
+
+    use crate::{expression::Expression, instruction::MemoryReference, real};
+
+    use super::{DerivativeError, DerivativeTarget};
+
+    fn variable(name: &str) -> DerivativeTarget {
+        DerivativeTarget::Variable(name.to_string())
+    }
+
+    #[rstest]
+    #[case("%x", "1")]
+    #[case("%y", "0")]
+    #[case("pi * %y", "0")]
+    #[case("-%x", "-1")]
+    #[case("%x * %x", "2 * %x")]
+    #[case("sin(%x)", "cos(%x)")]
+    #[case("cos(%x)", "-sin(%x)")]
+    #[case("exp(2 * %x)", "2 * exp(2 * %x)")]
+    #[case("1 / %x", "-1 / %x^2")]
+    #[case("%x^3", "3 * %x^2")]
+    #[case("tan(%x)", "1 / cos(%x)^2")]
+    #[case("arcsin(%x)", "1 / sqrt(1 - %x^2)")]
+    #[case("arccos(%x)", "-1 / sqrt(1 - %x^2)")]
+    #[case("arctan(%x)", "1 / (1 + %x^2)")]
+    #[case("log(%x)", "1 / %x")]
+    #[case("abs(-%x)", "1")]
+    #[case("imag(%x * i)", "1")]
+    #[case("floor(%x)", "0")]
+    #[case("atan2(%y, %x)", "-%y / (%x^2 + %y^2)")]
+    #[case("max(%x, %y)", "0")]
+    #[case("min(%x, %y)", "1")]
+    fn derivative_of_variable(#[case] input: &str, #[case] expected: &str) {
+        let derivative = Expression::from_str(input)
+            .unwrap()
+            .derivative(&variable("x"))
+            .unwrap();
+        let expected = Expression::from_str(expected).unwrap();
+
+        let variables =
+            HashMap::from([("x".to_string(), real!(0.7)), ("y".to_string(), real!(1.3))]);
+        let memory = HashMap::new();
+        let difference = derivative.evaluate(&variables, &memory).unwrap()
+            - expected.evaluate(&variables, &memory).unwrap();
+        assert!(difference.norm() < 1e-12, "{input}: got {derivative:?}");
+    }
+
+    #[test]
+    fn derivative_of_address() {
+        let theta = MemoryReference {
+            name: "theta".to_string(),
+            index: 1,
+        };
+        let expression = Expression::from_str("theta[0] * theta[1]").unwrap();
+        let derivative = expression.derivative(&theta.into()).unwrap();
+        assert_eq!(derivative, Expression::from_str("theta[0]").unwrap());
+    }
+
+    #[test]
+    fn derivative_of_non_constant_base() {
+        let expression = Expression::from_str("%y^%x").unwrap();
+        assert_eq!(
+            expression.derivative(&variable("x")),
+            Err(DerivativeError::NonConstantBase(expression))
+        );
+    }
+
+    /// Generate an expression in `%x`, `%y`, and `theta[0]` which is analytic on the real line,
+    /// so that its derivative may be checked against finite differences.
+    fn arb_analytic_expr() -> impl Strategy<Value = Expression> {
+        use crate::expression::{
+            ExpressionFunction, FunctionCallExpression, InfixExpression, InfixOperator,
+            PrefixExpression, PrefixOperator,
+        };
+
+        let leaf = prop_oneof![
+            Just(Expression::Variable("x".to_string())),
+            Just(Expression::Variable("y".to_string())),
+            Just(Expression::Address(MemoryReference {
+                name: "theta".to_string(),
+                index: 0,
+            })),
+            (-2.0..2.0f64).prop_map(|value| Expression::Number(real!(value))),
+            Just(Expression::PiConstant),
+        ];
+        leaf.prop_recursive(3, 24, 2, |expr| {
+            prop_oneof![
+                (
+                    prop_oneof![
+                        Just(ExpressionFunction::Cis),
+                        Just(ExpressionFunction::Cosine),
+                        Just(ExpressionFunction::Exponent),
+                        Just(ExpressionFunction::Sine),
+                    ],
+                    expr.clone()
+                )
+                    .prop_map(|(function, e)| {
+                        Expression::FunctionCall(FunctionCallExpression::new(function, Box::new(e)))
+                    }),
+                (
+                    expr.clone(),
+                    prop_oneof![
+                        Just(InfixOperator::Plus),
+                        Just(InfixOperator::Minus),
+                        Just(InfixOperator::Star),
+                    ],
+                    expr.clone()
+                )
+                    .prop_map(|(left, operator, right)| {
+                        Expression::Infix(InfixExpression::new(
+                            Box::new(left),
+                            operator,
+                            Box::new(right),
+                        ))
+                    }),
+                (expr.clone(), 1..4u8).prop_map(|(base, exponent)| {
+                    base ^ Expression::Number(real!(f64::from(exponent)))
+                }),
+                expr.clone()
+                    .prop_map(|exponent| Expression::Number(real!(2.0)) ^ exponent),
+                expr.prop_map(|e| {
+                    Expression::Prefix(PrefixExpression::new(PrefixOperator::Minus, Box::new(e)))
+                }),
+            ]
+        })
+    }
+
+    /// Compare a derivative to its estimate by central differences, allowing for the rounding
+    /// error of the estimate, which grows with the magnitude of the `value` being differentiated.
+    fn assert_close(
+        derivative: Complex64,
+        estimate: Complex64,
+        value: Complex64,
+    ) -> Result<(), TestCaseError> {
+        prop_assume!(derivative.is_finite() && estimate.is_finite());
+        prop_assume!(derivative.norm() < 1e6);
+        let tolerance = 1e-4 * (1.0 + derivative.norm()) + 1e-8 * value.norm();
+        prop_assert!(
+            (derivative - estimate).norm() < tolerance,
+            "derivative {derivative} differs from finite difference {estimate}"
+        );
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn derivative_matches_finite_differences(
+            expression in arb_analytic_expr(),
+            x in -1.0..1.0f64,
+            y in -1.0..1.0f64,
+            theta in -1.0..1.0f64,
+        ) {
+            const STEP: f64 = 1e-6;
+
+            let variables_at = |x: f64| {
+                HashMap::from([("x".to_string(), real!(x)), ("y".to_string(), real!(y))])
+            };
+            let memory_at = |theta: f64| HashMap::from([("theta", vec![theta])]);
+
+            let value = expression.evaluate(&variables_at(x), &memory_at(theta)).unwrap();
+
+            let derivative = expression
+                .derivative(&variable("x"))
+                .unwrap()
+                .evaluate(&variables_at(x), &memory_at(theta))
+                .unwrap();
+            let estimate = (expression.evaluate(&variables_at(x + STEP), &memory_at(theta)).unwrap()
+                - expression.evaluate(&variables_at(x - STEP), &memory_at(theta)).unwrap())
+                / (2.0 * STEP);
+            assert_close(derivative, estimate, value)?;
+
+            let theta_reference = MemoryReference { name: "theta".to_string(), index: 0 };
+            let derivative = expression
+                .derivative(&theta_reference.into())
+                .unwrap()
+                .evaluate(&variables_at(x), &memory_at(theta))
+                .unwrap();
+            let estimate = (expression.evaluate(&variables_at(x), &memory_at(theta + STEP)).unwrap()
+                - expression.evaluate(&variables_at(x), &memory_at(theta - STEP)).unwrap())
+                / (2.0 * STEP);
+            assert_close(derivative, estimate, value)?;
+        }
+    }
+}
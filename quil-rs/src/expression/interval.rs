@@ -0,0 +1,611 @@
+//! Interval analysis of [`Expression`]s: bounding their values given ranges for their parameters
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    f64::consts::{FRAC_PI_2, PI, TAU},
+    fmt,
+};
+
+use crate::quil::Quil;
+
+use super::{
+    is_small, BinaryExpressionFunction, BinaryFunctionCallExpression, Expression,
+    ExpressionFunction, FunctionCallExpression, InfixExpression, InfixOperator, PrefixExpression,
+    PrefixOperator,
+};
+
+/// A closed interval of real numbers, which may be unbounded in either direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    lower: f64,
+    upper: f64,
+}
+
+/// The different possible types of errors that could occur during interval analysis.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum IntervalError {
+    #[error("[{lower}, {upper}] is not an interval")]
+    InvalidBounds { lower: f64, upper: f64 },
+
+    #[error("no range was given for variable %{0}")]
+    UnknownVariable(String),
+
+    #[error("no range was given for memory region {0}")]
+    UnknownMemoryRegion(String),
+
+    /// Intervals bound only real values, so no range can be given for an expression which may be
+    /// complex, such as `sqrt(%x)` where `%x` may be negative.
+    #[error("{} may not be real over the given ranges", .0.to_quil_or_debug())]
+    MayBeComplex(Expression),
+}
+
+impl Interval {
+    /// Every real number.
+    pub const UNBOUNDED: Self = Self {
+        lower: f64::NEG_INFINITY,
+        upper: f64::INFINITY,
+    };
+
+    /// Create the interval `[lower, upper]`, either bound of which may be infinite.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either bound is NaN or if `lower` is greater than `upper`.
+    pub fn new(lower: f64, upper: f64) -> Result<Self, IntervalError> {
+        // Written so as to reject NaN
+        if lower <= upper {
+            Ok(Self { lower, upper })
+        } else {
+            Err(IntervalError::InvalidBounds { lower, upper })
+        }
+    }
+
+    /// Create the interval containing only `value`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `value` is NaN.
+    pub fn point(value: f64) -> Result<Self, IntervalError> {
+        Self::new(value, value)
+    }
+
+    pub fn lower(&self) -> f64 {
+        self.lower
+    }
+
+    pub fn upper(&self) -> f64 {
+        self.upper
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        self.lower <= value && value <= self.upper
+    }
+
+    /// Whether every value within this interval is also within `other`.
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        other.lower <= self.lower && self.upper <= other.upper
+    }
+
+    /// Create an interval from bounds computed by interval arithmetic, widening it to be unbounded
+    /// in the direction of either bound which is NaN, such as from `-inf + inf`.
+    fn from_bounds(lower: f64, upper: f64) -> Self {
+        Self {
+            lower: if lower.is_nan() {
+                f64::NEG_INFINITY
+            } else {
+                lower
+            },
+            upper: if upper.is_nan() { f64::INFINITY } else { upper },
+        }
+    }
+
+    /// The smallest interval containing each of the given values, or every real number if any of
+    /// them is NaN.
+    fn hull(values: &[f64]) -> Self {
+        if values.iter().any(|value| value.is_nan()) {
+            return Self::UNBOUNDED;
+        }
+        values.iter().fold(
+            Self {
+                lower: f64::INFINITY,
+                upper: f64::NEG_INFINITY,
+            },
+            |hull, &value| Self {
+                lower: hull.lower.min(value),
+                upper: hull.upper.max(value),
+            },
+        )
+    }
+
+    /// The interval of the values of a non-decreasing function over this interval.
+    fn map_increasing(self, function: impl Fn(f64) -> f64) -> Self {
+        Self::from_bounds(function(self.lower), function(self.upper))
+    }
+
+    /// The interval of the values of a non-increasing function over this interval.
+    fn map_decreasing(self, function: impl Fn(f64) -> f64) -> Self {
+        Self::from_bounds(function(self.upper), function(self.lower))
+    }
+
+    /// The interval of the values of a function with period `2π` and range `[-1, 1]` over this
+    /// interval, given one point at which it attains its maximum and one at which it attains its
+    /// minimum.
+    fn map_periodic(self, function: impl Fn(f64) -> f64, maximum_at: f64, minimum_at: f64) -> Self {
+        let width = self.upper - self.lower;
+        if width >= TAU || width.is_nan() {
+            return Self::from_bounds(-1.0, 1.0);
+        }
+        let attains = |at: f64| at + ((self.lower - at) / TAU).ceil() * TAU <= self.upper;
+        let endpoints = Self::hull(&[function(self.lower), function(self.upper)]);
+        Self {
+            lower: if attains(minimum_at) {
+                -1.0
+            } else {
+                endpoints.lower
+            },
+            upper: if attains(maximum_at) {
+                1.0
+            } else {
+                endpoints.upper
+            },
+        }
+    }
+
+    fn negate(self) -> Self {
+        Self::from_bounds(-self.upper, -self.lower)
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self::from_bounds(self.lower + other.lower, self.upper + other.upper)
+    }
+
+    fn subtract(self, other: Self) -> Self {
+        self.add(other.negate())
+    }
+
+    fn multiply(self, other: Self) -> Self {
+        // Zero times an infinite bound is the limit of zero times a finite one, rather than NaN.
+        let product = |a: f64, b: f64| if a == 0.0 || b == 0.0 { 0.0 } else { a * b };
+        Self::hull(&[
+            product(self.lower, other.lower),
+            product(self.lower, other.upper),
+            product(self.upper, other.lower),
+            product(self.upper, other.upper),
+        ])
+    }
+
+    fn divide(self, other: Self) -> Self {
+        if other.contains(0.0) {
+            Self::UNBOUNDED
+        } else {
+            self.multiply(Self::from_bounds(1.0 / other.upper, 1.0 / other.lower))
+        }
+    }
+
+    /// The interval of `self ^ exponent`, or `None` if it may not be real.
+    fn power(self, exponent: Self) -> Option<Self> {
+        if exponent.lower == exponent.upper && exponent.lower.fract() == 0.0 {
+            // An integer power is real for any base, and monotonic wherever the base keeps its sign.
+            let n = exponent.lower;
+            if n == 0.0 {
+                Some(Self::from_bounds(1.0, 1.0))
+            } else if !self.contains(0.0) || (n > 0.0 && n % 2.0 != 0.0) {
+                Some(Self::hull(&[self.lower.powf(n), self.upper.powf(n)]))
+            } else if n > 0.0 {
+                Some(Self::from_bounds(
+                    0.0,
+                    self.lower.powf(n).max(self.upper.powf(n)),
+                ))
+            } else {
+                Some(Self::UNBOUNDED)
+            }
+        } else if self.lower >= 0.0 {
+            // For a non-negative base, `x ^ y` is monotonic in each of `x` and `y` when the other
+            // is fixed, so its extremes are at the corners.
+            Some(Self::hull(&[
+                self.lower.powf(exponent.lower),
+                self.lower.powf(exponent.upper),
+                self.upper.powf(exponent.lower),
+                self.upper.powf(exponent.upper),
+            ]))
+        } else {
+            None
+        }
+    }
+
+    fn absolute_value(self) -> Self {
+        if self.contains(0.0) {
+            Self::from_bounds(0.0, self.upper.max(-self.lower))
+        } else {
+            Self::hull(&[self.lower.abs(), self.upper.abs()])
+        }
+    }
+
+    fn tangent(self) -> Self {
+        let width = self.upper - self.lower;
+        let crosses_pole = width >= PI
+            || width.is_nan()
+            || FRAC_PI_2 + ((self.lower - FRAC_PI_2) / PI).ceil() * PI <= self.upper;
+        if crosses_pole {
+            Self::UNBOUNDED
+        } else {
+            self.map_increasing(f64::tan)
+        }
+    }
+
+    /// The interval of `atan2(self, x)`, where `self` is the `y` coordinate.
+    fn arc_tangent_2(self, x: Self) -> Self {
+        // A box touching the non-positive `x` axis contains either the origin or the branch cut;
+        // any other is a convex region on which the angle is continuous, with extremes at corners.
+        if x.lower <= 0.0 && self.contains(0.0) {
+            Self::from_bounds(-PI, PI)
+        } else {
+            Self::hull(&[
+                self.lower.atan2(x.lower),
+                self.lower.atan2(x.upper),
+                self.upper.atan2(x.lower),
+                self.upper.atan2(x.upper),
+            ])
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {}]", self.lower, self.upper)
+    }
+}
+
+impl Expression {
+    /// Compute an interval containing every value the expression may take, given intervals for the
+    /// values of its variables and of the memory regions it references. Each interval applies to
+    /// every element of its memory region.
+    ///
+    /// The interval is not necessarily the smallest possible, and its bounds are subject to
+    /// floating-point rounding.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::{Expression, Interval};
+    /// use std::collections::HashMap;
+    /// use std::f64::consts::PI;
+    /// use std::str::FromStr;
+    ///
+    /// let expression = Expression::from_str("cos(%theta) / 2").unwrap();
+    /// let variables = HashMap::from([("theta".to_string(), Interval::new(0.0, PI).unwrap())]);
+    ///
+    /// let range = expression.range(&variables, &HashMap::new()).unwrap();
+    ///
+    /// assert_eq!(range, Interval::new(-0.5, 0.5).unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a variable or memory region has no given interval, or if the expression
+    /// may take a complex value.
+    pub fn range(
+        &self,
+        variables: &HashMap<String, Interval>,
+        memory_regions: &HashMap<String, Interval>,
+    ) -> Result<Interval, IntervalError> {
+        use Expression::*;
+
+        let may_be_complex = || IntervalError::MayBeComplex(self.clone());
+
+        match self {
+            Address(reference) => memory_regions
+                .get(&reference.name)
+                .copied()
+                .ok_or_else(|| IntervalError::UnknownMemoryRegion(reference.name.clone())),
+            BinaryFunctionCall(BinaryFunctionCallExpression {
+                function,
+                left,
+                right,
+            }) => {
+                let left = left.range(variables, memory_regions)?;
+                let right = right.range(variables, memory_regions)?;
+                Ok(match function {
+                    BinaryExpressionFunction::ArcTangent2 => left.arc_tangent_2(right),
+                    BinaryExpressionFunction::Maximum => Interval::from_bounds(
+                        left.lower.max(right.lower),
+                        left.upper.max(right.upper),
+                    ),
+                    BinaryExpressionFunction::Minimum => Interval::from_bounds(
+                        left.lower.min(right.lower),
+                        left.upper.min(right.upper),
+                    ),
+                })
+            }
+            FunctionCall(FunctionCallExpression {
+                function,
+                expression,
+            }) => {
+                use ExpressionFunction::*;
+
+                let argument = expression.range(variables, memory_regions)?;
+                let within_unit = argument.is_subset_of(&Interval::from_bounds(-1.0, 1.0));
+                match function {
+                    AbsoluteValue => Ok(argument.absolute_value()),
+                    ArcCosine if within_unit => Ok(argument.map_decreasing(f64::acos)),
+                    ArcSine if within_unit => Ok(argument.map_increasing(f64::asin)),
+                    ArcCosine | ArcSine | Cis => Err(may_be_complex()),
+                    ArcTangent => Ok(argument.map_increasing(f64::atan)),
+                    Ceiling => Ok(argument.map_increasing(f64::ceil)),
+                    Conjugate | RealPart => Ok(argument),
+                    Cosine => Ok(argument.map_periodic(f64::cos, 0.0, PI)),
+                    Exponent => Ok(argument.map_increasing(f64::exp)),
+                    Floor => Ok(argument.map_increasing(f64::floor)),
+                    ImaginaryPart => Ok(Interval::from_bounds(0.0, 0.0)),
+                    Logarithm | SquareRoot if argument.lower < 0.0 => Err(may_be_complex()),
+                    Logarithm => Ok(argument.map_increasing(f64::ln)),
+                    Sine => Ok(argument.map_periodic(f64::sin, FRAC_PI_2, -FRAC_PI_2)),
+                    SquareRoot => Ok(argument.map_increasing(f64::sqrt)),
+                    Tangent => Ok(argument.tangent()),
+                }
+            }
+            Infix(InfixExpression {
+                left,
+                operator,
+                right,
+            }) => {
+                let left = left.range(variables, memory_regions)?;
+                let right = right.range(variables, memory_regions)?;
+                match operator {
+                    InfixOperator::Caret => left.power(right).ok_or_else(may_be_complex),
+                    InfixOperator::Plus => Ok(left.add(right)),
+                    InfixOperator::Minus => Ok(left.subtract(right)),
+                    InfixOperator::Slash => Ok(left.divide(right)),
+                    InfixOperator::Star => Ok(left.multiply(right)),
+                }
+            }
+            Number(number) if is_small(number.im) => Interval::point(number.re),
+            Number(_) => Err(may_be_complex()),
+            PiConstant => Ok(Interval::from_bounds(PI, PI)),
+            Prefix(PrefixExpression {
+                operator,
+                expression,
+            }) => {
+                let range = expression.range(variables, memory_regions)?;
+                Ok(match operator {
+                    PrefixOperator::Minus => range.negate(),
+                    PrefixOperator::Plus => range,
+                })
+            }
+            Variable(name) => variables
+                .get(name)
+                .copied()
+                .ok_or_else(|| IntervalError::UnknownVariable(name.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, f64::consts::PI, str::FromStr};
+
+    use proptest::prelude::*;
+    use rstest::rstest;
+
+    use crate::{
+        expression::{
+            BinaryExpressionFunction, BinaryFunctionCallExpression, Expression, ExpressionFunction,
+            FunctionCallExpression, InfixExpression, InfixOperator, PrefixExpression,
+            PrefixOperator,
+        },
+        instruction::MemoryReference,
+        quil::Quil,
+        real,
+    };
+
+    use super::{Interval, IntervalError};
+
+    const A: (f64, f64) = (-1.0, 2.0);
+    const B: (f64, f64) = (0.5, 3.0);
+    const THETA: (f64, f64) = (0.0, 2.0 * PI);
+
+    fn ranges() -> (HashMap<String, Interval>, HashMap<String, Interval>) {
+        (
+            HashMap::from([
+                ("a".to_string(), Interval::new(A.0, A.1).unwrap()),
+                ("b".to_string(), Interval::new(B.0, B.1).unwrap()),
+            ]),
+            HashMap::from([(
+                "theta".to_string(),
+                Interval::new(THETA.0, THETA.1).unwrap(),
+            )]),
+        )
+    }
+
+    #[rstest]
+    #[case("1.5", 1.5, 1.5)]
+    #[case("%a + %b", -0.5, 5.0)]
+    #[case("%a - %b", -4.0, 1.5)]
+    #[case("%a * %b", -3.0, 6.0)]
+    #[case("-%a", -2.0, 1.0)]
+    #[case("%a ^ 2", 0.0, 4.0)]
+    #[case("%a ^ 3", -1.0, 8.0)]
+    #[case("%b ^ -1", 1.0 / 3.0, 2.0)]
+    #[case("%b ^ %b", 0.125, 27.0)]
+    #[case("1 / %b", 1.0 / 3.0, 2.0)]
+    #[case("1 / %a", f64::NEG_INFINITY, f64::INFINITY)]
+    #[case("sin(theta[0])", -1.0, 1.0)]
+    #[case("cos(theta[0] / 2)", -1.0, 1.0)]
+    #[case("sin(%b)", 3.0_f64.sin(), 1.0)]
+    #[case("cos(%b)", 3.0_f64.cos(), 0.5_f64.cos())]
+    #[case("tan(%a)", f64::NEG_INFINITY, f64::INFINITY)]
+    #[case("tan(%a / 2)", (-0.5_f64).tan(), 1.0_f64.tan())]
+    #[case("abs(%a)", 0.0, 2.0)]
+    #[case("floor(%b)", 0.0, 3.0)]
+    #[case("ceil(%a)", -1.0, 2.0)]
+    #[case("exp(%a)", (-1.0_f64).exp(), 2.0_f64.exp())]
+    #[case("log(%b)", 0.5_f64.ln(), 3.0_f64.ln())]
+    #[case("sqrt(%b)", 0.5_f64.sqrt(), 3.0_f64.sqrt())]
+    #[case("arccos(%a / 2)", 0.0, (-0.5_f64).acos())]
+    #[case("arcsin(%a / 2)", (-0.5_f64).asin(), PI / 2.0)]
+    #[case("arctan(%a)", (-1.0_f64).atan(), 2.0_f64.atan())]
+    #[case("imag(%a)", 0.0, 0.0)]
+    #[case("atan2(%b, %b)", 0.5_f64.atan2(3.0), 3.0_f64.atan2(0.5))]
+    #[case("atan2(%a, %a)", -PI, PI)]
+    #[case("max(%a, %b)", 0.5, 3.0)]
+    #[case("min(%a, %b)", -1.0, 2.0)]
+    fn range(#[case] input: &str, #[case] lower: f64, #[case] upper: f64) {
+        let (variables, memory_regions) = ranges();
+        let range = Expression::from_str(input)
+            .unwrap()
+            .range(&variables, &memory_regions)
+            .unwrap();
+        assert!(
+            (range.lower() - lower).abs() < 1e-12 || range.lower() == lower,
+            "{input}: expected lower bound {lower}, got {range}"
+        );
+        assert!(
+            (range.upper() - upper).abs() < 1e-12 || range.upper() == upper,
+            "{input}: expected upper bound {upper}, got {range}"
+        );
+    }
+
+    #[rstest]
+    #[case("%c", IntervalError::UnknownVariable("c".to_string()))]
+    #[case("phi[0]", IntervalError::UnknownMemoryRegion("phi".to_string()))]
+    #[case("sqrt(%a)", IntervalError::MayBeComplex(Expression::from_str("sqrt(%a)").unwrap()))]
+    #[case("log(%a)", IntervalError::MayBeComplex(Expression::from_str("log(%a)").unwrap()))]
+    #[case("arcsin(%b)", IntervalError::MayBeComplex(Expression::from_str("arcsin(%b)").unwrap()))]
+    #[case("%a ^ 0.5", IntervalError::MayBeComplex(Expression::from_str("%a ^ 0.5").unwrap()))]
+    #[case("cis(%a)", IntervalError::MayBeComplex(Expression::from_str("cis(%a)").unwrap()))]
+    #[case("1 + 2i", IntervalError::MayBeComplex(Expression::from_str("1 + 2i").unwrap().into_simplified()))]
+    fn range_error(#[case] input: &str, #[case] expected: IntervalError) {
+        let (variables, memory_regions) = ranges();
+        let error = Expression::from_str(input)
+            .unwrap()
+            .into_simplified()
+            .range(&variables, &memory_regions)
+            .unwrap_err();
+        assert_eq!(error, expected);
+    }
+
+    #[rstest]
+    #[case(0.0, -1.0)]
+    #[case(f64::NAN, 1.0)]
+    fn invalid_bounds(#[case] lower: f64, #[case] upper: f64) {
+        assert!(Interval::new(lower, upper).is_err());
+    }
+
+    /// Functions which are continuous and finite wherever their ranges are computed, so that
+    /// rounding cannot move a sampled value far outside of its computed range.
+    fn arb_continuous_function() -> impl Strategy<Value = ExpressionFunction> {
+        use ExpressionFunction::*;
+        prop_oneof![
+            Just(AbsoluteValue),
+            Just(ArcCosine),
+            Just(ArcSine),
+            Just(ArcTangent),
+            Just(Conjugate),
+            Just(Cosine),
+            Just(Exponent),
+            Just(ImaginaryPart),
+            Just(RealPart),
+            Just(Sine),
+            Just(SquareRoot),
+        ]
+    }
+
+    fn arb_expr() -> impl Strategy<Value = Expression> {
+        let leaf = prop_oneof![
+            prop_oneof![Just("a"), Just("b")].prop_map(|name| Expression::Variable(name.into())),
+            (0..2u64).prop_map(|index| Expression::Address(MemoryReference {
+                name: "theta".to_string(),
+                index,
+            })),
+            (-2.0..2.0f64).prop_map(|value| Expression::Number(real!(value))),
+            Just(Expression::PiConstant),
+        ];
+        leaf.prop_recursive(4, 32, 2, |expr| {
+            prop_oneof![
+                (arb_continuous_function(), expr.clone()).prop_map(|(function, e)| {
+                    Expression::FunctionCall(FunctionCallExpression::new(function, Box::new(e)))
+                }),
+                (
+                    any::<BinaryExpressionFunction>(),
+                    expr.clone(),
+                    expr.clone()
+                )
+                    .prop_map(|(function, left, right)| {
+                        Expression::BinaryFunctionCall(BinaryFunctionCallExpression::new(
+                            function,
+                            Box::new(left),
+                            Box::new(right),
+                        ))
+                    }),
+                (
+                    expr.clone(),
+                    prop_oneof![
+                        Just(InfixOperator::Minus),
+                        Just(InfixOperator::Plus),
+                        Just(InfixOperator::Star),
+                    ],
+                    expr.clone()
+                )
+                    .prop_map(|(left, operator, right)| {
+                        Expression::Infix(InfixExpression::new(
+                            Box::new(left),
+                            operator,
+                            Box::new(right),
+                        ))
+                    }),
+                (any::<PrefixOperator>(), expr).prop_map(|(operator, e)| {
+                    Expression::Prefix(PrefixExpression::new(operator, Box::new(e)))
+                }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn range_contains_evaluation(
+            expression in arb_expr(),
+            a in A.0..=A.1,
+            b in B.0..=B.1,
+            theta in proptest::collection::vec(THETA.0..=THETA.1, 2),
+        ) {
+            let (variable_ranges, memory_ranges) = ranges();
+            let Ok(range) = expression.range(&variable_ranges, &memory_ranges) else {
+                return Ok(());
+            };
+
+            let variables = HashMap::from([("a".to_string(), real!(a)), ("b".to_string(), real!(b))]);
+            let memory = HashMap::from([("theta", theta)]);
+            // Rounding may leave a tiny imaginary part on an argument of a binary function, which
+            // evaluation then rejects, and overflow may leave NaN in complex arithmetic.
+            let Ok(value) = expression.evaluate(&variables, &memory) else {
+                return Ok(());
+            };
+            if !value.is_finite() {
+                return Ok(());
+            }
+
+            let tolerance = 1e-9 * (1.0 + value.re.abs());
+            prop_assert!(
+                value.im.abs() < tolerance
+                    && range.lower() - tolerance <= value.re
+                    && value.re <= range.upper() + tolerance,
+                "{} = {value} is outside of {range}",
+                expression.to_quil_or_debug()
+            );
+        }
+    }
+}
@@ -1,7 +1,8 @@
 /// Complex machinery for simplifying [`Expression`]s.
 use crate::expression::{
-    imag, real, Expression, ExpressionFunction, FunctionCallExpression, InfixExpression,
-    InfixOperator, PrefixExpression, PrefixOperator,
+    calculate_binary_function, calculate_function, imag, real, BinaryExpressionFunction,
+    BinaryFunctionCallExpression, Expression, ExpressionFunction, FunctionCallExpression,
+    InfixExpression, InfixOperator, PrefixExpression, PrefixOperator,
 };
 use std::cmp::min_by_key;
 
@@ -28,6 +29,11 @@ fn simplify(e: &Expression, limit: u64) -> Expression {
     } else {
         match e {
             Expression::Address(_) | Expression::Number(_) | Expression::Variable(_) => e.clone(),
+            Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+                function,
+                left,
+                right,
+            }) => simplify_binary_function_call(*function, left, right, limit - 1),
             Expression::FunctionCall(FunctionCallExpression {
                 function,
                 expression,
@@ -78,6 +84,9 @@ fn simplify_function_call(func: ExpressionFunction, expr: &Expression, limit: u6
             (ExpressionFunction::SquareRoot, Expression::PiConstant) => {
                 Expression::Number(PI.sqrt())
             }
+            (function, Expression::Number(x)) => {
+                Expression::Number(calculate_function(&function, &x))
+            }
             (function, expression) => Expression::FunctionCall(FunctionCallExpression {
                 function,
                 expression: expression.into(),
@@ -86,6 +95,33 @@ fn simplify_function_call(func: ExpressionFunction, expr: &Expression, limit: u6
     }
 }
 
+/// Simplify a call to a two-argument function inside an `Expression`, terminating the recursion
+/// if `limit` has reached zero.
+fn simplify_binary_function_call(
+    function: BinaryExpressionFunction,
+    left: &Expression,
+    right: &Expression,
+    limit: u64,
+) -> Expression {
+    let (left, right) = if limit == 0 {
+        // bail
+        (left.clone(), right.clone())
+    } else {
+        (simplify(left, limit - 1), simplify(right, limit - 1))
+    };
+    if let (Expression::Number(x), Expression::Number(y)) = (&left, &right) {
+        // Calls with complex arguments have no value, and so are left as they are
+        if let Ok(value) = calculate_binary_function(&function, x, y) {
+            return Expression::Number(value);
+        }
+    }
+    Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+        function,
+        left: left.into(),
+        right: right.into(),
+    })
+}
+
 #[inline]
 fn is_zero(x: num_complex::Complex64) -> bool {
     x.norm() < 1e-10
@@ -103,6 +139,11 @@ fn size(expr: &Expression) -> usize {
         | Expression::Number(_)
         | Expression::PiConstant
         | Expression::Variable(_) => 1,
+        Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+            function: _,
+            left,
+            right,
+        }) => 1 + size(left) + size(right),
         Expression::FunctionCall(FunctionCallExpression {
             function: _,
             expression,
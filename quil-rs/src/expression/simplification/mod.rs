@@ -60,6 +60,42 @@ mod tests {
         "3"
     }
 
+    test_simplify! {
+        function_abs,
+        "abs(-3)",
+        "3"
+    }
+
+    test_simplify! {
+        function_floor,
+        "floor(2.5)",
+        "2"
+    }
+
+    test_simplify! {
+        function_extended_of_variable,
+        "arctan(%x + 0)",
+        "arctan(%x)"
+    }
+
+    test_simplify! {
+        binary_function_max,
+        "max(1, 2)",
+        "2"
+    }
+
+    test_simplify! {
+        binary_function_atan2,
+        "atan2(1, 1)",
+        "0.7853981633974483"
+    }
+
+    test_simplify! {
+        binary_function_of_complex,
+        "min(2*i, 1)",
+        "min(2.0i, 1)"
+    }
+
     test_simplify! {
         infix_add_0_r,
         "x + 0",
@@ -1,9 +1,11 @@
+use std::f64::consts::TAU;
+
 use crate::{
     instruction::{
         write_expression_parameter_string, write_instruction_block, Expression, GateModifier,
         Instruction, Qubit,
     },
-    quil::{Quil, INDENT},
+    quil::{Quil, WriteOptions, INDENT},
     validation::identifier::{validate_identifier, IdentifierValidationError},
 };
 
@@ -18,6 +20,56 @@ pub trait CalibrationSignature {
     fn has_signature(&self, signature: &Self::Signature<'_>) -> bool;
 }
 
+/// A coarse key under which calibrations are indexed within a [`CalibrationSet`], so that lookups
+/// need only consider the calibrations which share a key rather than scanning the entire set.
+///
+/// All calibrations with the same [`CalibrationSignature`] must have the same key.
+///
+/// [`CalibrationSet`]: crate::program::CalibrationSet
+pub trait CalibrationIndexKey: CalibrationSignature {
+    type Key: Clone + Eq + std::hash::Hash;
+
+    /// Return the key under which calibrations with the given signature are indexed.
+    fn key_for_signature(signature: &Self::Signature<'_>) -> Self::Key;
+
+    /// Return the key under which this calibration is indexed.
+    fn key(&self) -> Self::Key {
+        Self::key_for_signature(&self.signature())
+    }
+}
+
+/// The key under which a gate [`Calibration`] is indexed: its name, its modifiers, its parameter
+/// count, and its qubits, of which only fixed qubits are distinguished.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GateCalibrationKey {
+    pub(crate) name: String,
+    pub(crate) modifiers: Vec<GateModifier>,
+    pub(crate) parameter_count: usize,
+    pub(crate) fixed_qubits: Vec<Option<u64>>,
+}
+
+impl GateCalibrationKey {
+    pub(crate) fn new(
+        name: &str,
+        modifiers: &[GateModifier],
+        parameter_count: usize,
+        qubits: &[Qubit],
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            modifiers: modifiers.to_vec(),
+            parameter_count,
+            fixed_qubits: qubits
+                .iter()
+                .map(|qubit| match qubit {
+                    Qubit::Fixed(index) => Some(*index),
+                    Qubit::Placeholder(_) | Qubit::Variable(_) => None,
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Calibration {
     pub identifier: CalibrationIdentifier,
@@ -38,7 +90,7 @@ impl Calibration {
 }
 
 impl CalibrationSignature for Calibration {
-    type Signature<'a> = (&'a str, &'a [Expression], &'a [Qubit]);
+    type Signature<'a> = (&'a str, &'a [GateModifier], &'a [Expression], &'a [Qubit]);
 
     fn signature(&self) -> Self::Signature<'_> {
         self.identifier.signature()
@@ -49,22 +101,72 @@ impl CalibrationSignature for Calibration {
     }
 }
 
+impl CalibrationIndexKey for Calibration {
+    type Key = GateCalibrationKey;
+
+    fn key_for_signature(signature: &Self::Signature<'_>) -> Self::Key {
+        CalibrationIdentifier::key_for_signature(signature)
+    }
+}
+
 impl Quil for Calibration {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
-        self.identifier.write(f, fall_back_to_debug)?;
+        self.identifier.write(f, options)?;
         write!(f, ":")?;
         for instruction in &self.instructions {
             write!(f, "\n{INDENT}")?;
-            instruction.write(f, fall_back_to_debug)?;
+            instruction.write(f, options)?;
         }
         Ok(())
     }
 }
 
+/// How the fixed parameters of a calibration are compared to the parameters of a gate when
+/// determining whether the calibration matches the gate.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CalibrationParameterMatching {
+    /// Parameters match only if their simplified forms are equal, per the Quil-T specification.
+    #[default]
+    Exact,
+
+    /// Parameters which simplify to real numbers match if they differ by a multiple of 2π, to
+    /// within `tolerance`, such that `RX(pi/2)` matches both `DEFCAL RX(1.5707963267948966)` and
+    /// `DEFCAL RX(-3*pi/2)`. Other parameters match only if their simplified forms are equal.
+    AngleModulo2Pi { tolerance: f64 },
+
+    /// Parameters match if their canonical forms are equal, such that `RX(%theta*2)` matches
+    /// `DEFCAL RX(2*%theta)`. See [`crate::expression::CanonicalExpression`].
+    Canonical,
+}
+
+impl CalibrationParameterMatching {
+    /// Return whether the given fixed calibration parameter matches the given gate parameter, both
+    /// of which have already been simplified.
+    pub(crate) fn matches(
+        &self,
+        calibration_parameter: &Expression,
+        gate_parameter: &Expression,
+    ) -> bool {
+        match self {
+            Self::Exact => calibration_parameter == gate_parameter,
+            Self::Canonical => calibration_parameter.is_equivalent(gate_parameter),
+            Self::AngleModulo2Pi { tolerance } => {
+                match (calibration_parameter.to_real(), gate_parameter.to_real()) {
+                    (Ok(calibration_angle), Ok(gate_angle)) => {
+                        let difference = (calibration_angle - gate_angle).rem_euclid(TAU);
+                        difference <= *tolerance || TAU - difference <= *tolerance
+                    }
+                    _ => calibration_parameter == gate_parameter,
+                }
+            }
+        }
+    }
+}
+
 /// Unique identifier for a calibration definition within a program
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct CalibrationIdentifier {
@@ -103,6 +205,16 @@ impl CalibrationIdentifier {
     }
 
     pub fn matches(&self, gate: &Gate) -> bool {
+        self.matches_with(gate, CalibrationParameterMatching::Exact)
+    }
+
+    /// Returns `true` if the calibration matches the given gate, comparing fixed parameters using
+    /// the given [`CalibrationParameterMatching`]. See [`CalibrationIdentifier::matches`].
+    pub fn matches_with(
+        &self,
+        gate: &Gate,
+        parameter_matching: CalibrationParameterMatching,
+    ) -> bool {
         // Filter out non-matching calibrations: check rules 1-4
         if self.name != gate.name
             || self.modifiers != gate.modifiers
@@ -150,7 +262,7 @@ impl CalibrationIdentifier {
                         // If the calibration is variable, it matches any fixed qubit
                         (Expression::Variable(_), _) => true,
                         // If the calibration is fixed, but the gate's qubit is variable, it's not a match
-                        (calib, gate) => calib == gate,
+                        (calib, gate) => parameter_matching.matches(&calib, &gate),
                     }
                 });
         fixed_parameters_match
@@ -158,19 +270,32 @@ impl CalibrationIdentifier {
 }
 
 impl CalibrationSignature for CalibrationIdentifier {
-    type Signature<'a> = (&'a str, &'a [Expression], &'a [Qubit]);
+    type Signature<'a> = (&'a str, &'a [GateModifier], &'a [Expression], &'a [Qubit]);
 
     fn signature(&self) -> Self::Signature<'_> {
         (
             self.name.as_str(),
+            self.modifiers.as_slice(),
             self.parameters.as_slice(),
             self.qubits.as_slice(),
         )
     }
 
     fn has_signature(&self, signature: &Self::Signature<'_>) -> bool {
-        let (name, parameters, qubits) = signature;
-        self.name == *name && self.parameters == *parameters && self.qubits == *qubits
+        let (name, modifiers, parameters, qubits) = signature;
+        self.name == *name
+            && self.modifiers == *modifiers
+            && self.parameters == *parameters
+            && self.qubits == *qubits
+    }
+}
+
+impl CalibrationIndexKey for CalibrationIdentifier {
+    type Key = GateCalibrationKey;
+
+    fn key_for_signature(signature: &Self::Signature<'_>) -> Self::Key {
+        let (name, modifiers, parameters, qubits) = signature;
+        GateCalibrationKey::new(name, modifiers, parameters.len(), qubits)
     }
 }
 
@@ -178,11 +303,11 @@ impl Quil for CalibrationIdentifier {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "DEFCAL {}", self.name)?;
-        write_expression_parameter_string(f, fall_back_to_debug, &self.parameters)?;
-        write_qubit_parameters(f, fall_back_to_debug, &self.qubits)?;
+        write_expression_parameter_string(f, options, &self.parameters)?;
+        write_qubit_parameters(f, options, &self.qubits)?;
         Ok(())
     }
 }
@@ -214,16 +339,23 @@ impl CalibrationSignature for MeasureCalibrationDefinition {
     }
 }
 
+/// Measurement calibrations are few enough, typically one per qubit, that they are not indexed.
+impl CalibrationIndexKey for MeasureCalibrationDefinition {
+    type Key = ();
+
+    fn key_for_signature(_signature: &Self::Signature<'_>) -> Self::Key {}
+}
+
 impl Quil for MeasureCalibrationDefinition {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
-        self.identifier.write(f, fall_back_to_debug)?;
+        self.identifier.write(f, options)?;
         writeln!(f, ":")?;
 
-        write_instruction_block(f, fall_back_to_debug, &self.instructions)?;
+        write_instruction_block(f, options, &self.instructions)?;
         writeln!(f)?;
         Ok(())
     }
@@ -258,16 +390,22 @@ impl CalibrationSignature for MeasureCalibrationIdentifier {
     }
 }
 
+impl CalibrationIndexKey for MeasureCalibrationIdentifier {
+    type Key = ();
+
+    fn key_for_signature(_signature: &Self::Signature<'_>) -> Self::Key {}
+}
+
 impl Quil for MeasureCalibrationIdentifier {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "DEFCAL MEASURE")?;
         if let Some(qubit) = &self.qubit {
             write!(f, " ")?;
-            qubit.write(f, fall_back_to_debug)?;
+            qubit.write(f, options)?;
         }
         write!(f, " {}", self.parameter,)?;
         Ok(())
@@ -1,4 +1,4 @@
-use crate::quil::{Quil, INDENT};
+use crate::quil::{Quil, WriteOptions, INDENT};
 
 use super::Instruction;
 
@@ -31,7 +31,7 @@ impl Quil for CircuitDefinition {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         write!(writer, "DEFCIRCUIT {}", self.name)?;
         if !self.parameters.is_empty() {
@@ -50,10 +50,8 @@ impl Quil for CircuitDefinition {
         }
         writeln!(writer, ":")?;
         for instruction in &self.instructions {
-            let lines = match fall_back_to_debug {
-                true => instruction.to_quil_or_debug(),
-                false => instruction.to_quil()?,
-            };
+            let mut lines = String::new();
+            instruction.write(&mut lines, options)?;
             for line in lines.split('\n') {
                 writeln!(writer, "{INDENT}{line}")?;
             }
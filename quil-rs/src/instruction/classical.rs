@@ -1,4 +1,7 @@
-use crate::{hash::hash_f64, quil::Quil};
+use crate::{
+    hash::hash_f64,
+    quil::{Quil, WriteOptions},
+};
 
 use super::MemoryReference;
 
@@ -27,13 +30,13 @@ impl Quil for Arithmetic {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
-        self.operator.write(f, fall_back_to_debug)?;
+        self.operator.write(f, options)?;
         write!(f, " ")?;
-        self.destination.write(f, fall_back_to_debug)?;
+        self.destination.write(f, options)?;
         write!(f, " ")?;
-        self.source.write(f, fall_back_to_debug)
+        self.source.write(f, options)
     }
 }
 
@@ -58,12 +61,12 @@ impl Quil for ArithmeticOperand {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self {
             ArithmeticOperand::LiteralInteger(value) => write!(f, "{value}").map_err(Into::into),
             ArithmeticOperand::LiteralReal(value) => write!(f, "{value}").map_err(Into::into),
-            ArithmeticOperand::MemoryReference(value) => value.write(f, fall_back_to_debug),
+            ArithmeticOperand::MemoryReference(value) => value.write(f, options),
         }
     }
 }
@@ -86,7 +89,7 @@ impl Quil for ArithmeticOperator {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self {
             ArithmeticOperator::Add => write!(f, "ADD"),
@@ -108,11 +111,11 @@ impl Quil for BinaryOperand {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self {
             BinaryOperand::LiteralInteger(value) => write!(f, "{value}").map_err(Into::into),
-            BinaryOperand::MemoryReference(value) => value.write(f, fall_back_to_debug),
+            BinaryOperand::MemoryReference(value) => value.write(f, options),
         }
     }
 }
@@ -128,7 +131,7 @@ impl Quil for BinaryOperator {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self {
             BinaryOperator::And => write!(f, "AND"),
@@ -150,13 +153,13 @@ impl Quil for BinaryLogic {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
-        self.operator.write(f, fall_back_to_debug)?;
+        self.operator.write(f, options)?;
         write!(f, " ")?;
-        self.destination.write(f, fall_back_to_debug)?;
+        self.destination.write(f, options)?;
         write!(f, " ")?;
-        self.source.write(f, fall_back_to_debug)?;
+        self.source.write(f, options)?;
         Ok(())
     }
 }
@@ -194,12 +197,12 @@ impl Quil for Convert {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "CONVERT ")?;
-        self.destination.write(f, fall_back_to_debug)?;
+        self.destination.write(f, options)?;
         write!(f, " ")?;
-        self.source.write(f, fall_back_to_debug)?;
+        self.source.write(f, options)?;
         Ok(())
     }
 }
@@ -223,12 +226,12 @@ impl Quil for Move {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "MOVE ")?;
-        self.destination.write(f, fall_back_to_debug)?;
+        self.destination.write(f, options)?;
         write!(f, " ")?;
-        self.source.write(f, fall_back_to_debug)?;
+        self.source.write(f, options)?;
         Ok(())
     }
 }
@@ -243,12 +246,12 @@ impl Quil for Exchange {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "EXCHANGE ")?;
-        self.left.write(f, fall_back_to_debug)?;
+        self.left.write(f, options)?;
         write!(f, " ")?;
-        self.right.write(f, fall_back_to_debug)?;
+        self.right.write(f, options)?;
         Ok(())
     }
 }
@@ -287,15 +290,15 @@ impl Quil for Comparison {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
-        self.operator.write(f, fall_back_to_debug)?;
+        self.operator.write(f, options)?;
         write!(f, " ")?;
-        self.destination.write(f, fall_back_to_debug)?;
+        self.destination.write(f, options)?;
         write!(f, " ")?;
-        self.lhs.write(f, fall_back_to_debug)?;
+        self.lhs.write(f, options)?;
         write!(f, " ")?;
-        self.rhs.write(f, fall_back_to_debug)?;
+        self.rhs.write(f, options)?;
         Ok(())
     }
 }
@@ -311,12 +314,12 @@ impl Quil for ComparisonOperand {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self {
             ComparisonOperand::LiteralInteger(value) => write!(f, "{value}").map_err(Into::into),
             ComparisonOperand::LiteralReal(value) => write!(f, "{value}").map_err(Into::into),
-            ComparisonOperand::MemoryReference(value) => value.write(f, fall_back_to_debug),
+            ComparisonOperand::MemoryReference(value) => value.write(f, options),
         }
     }
 }
@@ -344,7 +347,7 @@ impl Quil for ComparisonOperator {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self {
             ComparisonOperator::Equal => write!(f, "EQ"),
@@ -373,11 +376,11 @@ impl Quil for UnaryLogic {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
-        self.operator.write(f, fall_back_to_debug)?;
+        self.operator.write(f, options)?;
         write!(f, " ")?;
-        self.operand.write(f, fall_back_to_debug)?;
+        self.operand.write(f, options)?;
         Ok(())
     }
 }
@@ -392,7 +395,7 @@ impl Quil for UnaryOperator {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self {
             UnaryOperator::Neg => write!(f, "NEG"),
@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use super::MemoryReference;
-use crate::quil::{Quil, ToQuilError};
+use crate::quil::{Quil, ToQuilError, WriteOptions};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Label {
@@ -18,10 +18,10 @@ impl Quil for Label {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "LABEL ")?;
-        self.target.write(writer, fall_back_to_debug)
+        self.target.write(writer, options)
     }
 }
 
@@ -48,12 +48,12 @@ impl Quil for Target {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match self {
             Target::Fixed(label) => write!(writer, "@{}", label).map_err(Into::into),
             Target::Placeholder(_) => {
-                if fall_back_to_debug {
+                if options.fall_back_to_debug {
                     write!(writer, "@{:?}", self).map_err(Into::into)
                 } else {
                     Err(ToQuilError::UnresolvedLabelPlaceholder)
@@ -117,10 +117,10 @@ impl Quil for Jump {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         write!(writer, "JUMP ")?;
-        self.target.write(writer, fall_back_to_debug)?;
+        self.target.write(writer, options)?;
         Ok(())
     }
 }
@@ -147,10 +147,10 @@ impl Quil for JumpWhen {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         write!(writer, "JUMP-WHEN ")?;
-        self.target.write(writer, fall_back_to_debug)?;
+        self.target.write(writer, options)?;
         write!(writer, " {}", self.condition)?;
         Ok(())
     }
@@ -172,10 +172,10 @@ impl Quil for JumpUnless {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         write!(writer, "JUMP-UNLESS ")?;
-        self.target.write(writer, fall_back_to_debug)?;
+        self.target.write(writer, options)?;
         write!(writer, " {}", self.condition)?;
         Ok(())
     }
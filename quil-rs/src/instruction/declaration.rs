@@ -5,7 +5,7 @@ use nom_locate::LocatedSpan;
 use crate::{
     parser::{common::parse_memory_reference, lex, ParseError},
     program::{disallow_leftover, SyntaxError},
-    quil::Quil,
+    quil::{Quil, WriteOptions},
 };
 
 use super::ArithmeticOperand;
@@ -18,11 +18,23 @@ pub enum ScalarType {
     Real,
 }
 
+impl ScalarType {
+    /// The number of bits occupied by a single value of this type, as given by the Quil
+    /// specification.
+    pub fn bit_width(&self) -> u64 {
+        match self {
+            Self::Bit => 1,
+            Self::Octet => 8,
+            Self::Integer | Self::Real => 64,
+        }
+    }
+}
+
 impl Quil for ScalarType {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         use ScalarType::*;
         write!(
@@ -55,9 +67,9 @@ impl Quil for Vector {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
-        self.data_type.write(f, fall_back_to_debug)?;
+        self.data_type.write(f, options)?;
         write!(f, "[{}]", self.length).map_err(Into::into)
     }
 }
@@ -90,10 +102,10 @@ impl Quil for Offset {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "{} ", self.offset)?;
-        self.data_type.write(f, fall_back_to_debug)
+        self.data_type.write(f, options)
     }
 }
 
@@ -118,17 +130,17 @@ impl Quil for Declaration {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "DECLARE {} ", self.name)?;
-        self.size.write(f, fall_back_to_debug)?;
+        self.size.write(f, options)?;
         if let Some(shared) = &self.sharing {
             write!(f, " SHARING {}", shared.name)?;
             if !shared.offsets.is_empty() {
                 write!(f, " OFFSET")?;
                 for offset in shared.offsets.iter() {
                     write!(f, " ")?;
-                    offset.write(f, fall_back_to_debug)?;
+                    offset.write(f, options)?;
                 }
             }
         }
@@ -199,7 +211,7 @@ impl Quil for MemoryReference {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "{}[{}]", self.name, self.index).map_err(Into::into)
     }
@@ -244,12 +256,12 @@ impl Quil for Load {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "LOAD ")?;
-        self.destination.write(f, fall_back_to_debug)?;
+        self.destination.write(f, options)?;
         write!(f, " {} ", self.source)?;
-        self.offset.write(f, fall_back_to_debug)?;
+        self.offset.write(f, options)?;
         Ok(())
     }
 }
@@ -275,12 +287,12 @@ impl Quil for Store {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "STORE {} ", self.destination)?;
-        self.offset.write(f, fall_back_to_debug)?;
+        self.offset.write(f, options)?;
         write!(f, " ")?;
-        self.source.write(f, fall_back_to_debug)?;
+        self.source.write(f, options)?;
         Ok(())
     }
 }
@@ -16,7 +16,7 @@ use crate::{
     hash::hash_f64,
     parser::lex,
     program::{disallow_leftover, MemoryAccesses, MemoryRegion, SyntaxError},
-    quil::Quil,
+    quil::{Quil, WriteOptions},
     validation::identifier::{validate_user_identifier, IdentifierValidationError},
 };
 
@@ -48,13 +48,13 @@ impl Quil for ExternParameterType {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match self {
-            ExternParameterType::Scalar(value) => value.write(f, fall_back_to_debug),
-            ExternParameterType::FixedLengthVector(value) => value.write(f, fall_back_to_debug),
+            ExternParameterType::Scalar(value) => value.write(f, options),
+            ExternParameterType::FixedLengthVector(value) => value.write(f, options),
             ExternParameterType::VariableLengthVector(value) => {
-                value.write(f, fall_back_to_debug)?;
+                value.write(f, options)?;
                 Ok(write!(f, "[]")?)
             }
         }
@@ -105,13 +105,13 @@ impl Quil for ExternParameter {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         write!(writer, "{} : ", self.name)?;
         if self.mutable {
             write!(writer, "mut ")?;
         }
-        self.data_type.write(writer, fall_back_to_debug)
+        self.data_type.write(writer, options)
     }
 }
 
@@ -214,10 +214,10 @@ impl Quil for ExternSignature {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         if let Some(return_type) = &self.return_type {
-            return_type.write(writer, fall_back_to_debug)?;
+            return_type.write(writer, options)?;
             if !self.parameters.is_empty() {
                 write!(writer, " ")?;
             }
@@ -230,7 +230,7 @@ impl Quil for ExternSignature {
             if i > 0 {
                 write!(writer, ", ")?;
             }
-            parameter.write(writer, fall_back_to_debug)?;
+            parameter.write(writer, options)?;
         }
         write!(writer, ")").map_err(Into::into)
     }
@@ -559,11 +559,11 @@ impl Quil for UnresolvedCallArgument {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self {
             UnresolvedCallArgument::Identifier(value) => write!(f, "{value}",).map_err(Into::into),
-            UnresolvedCallArgument::MemoryReference(value) => value.write(f, fall_back_to_debug),
+            UnresolvedCallArgument::MemoryReference(value) => value.write(f, options),
             UnresolvedCallArgument::Immediate(value) => {
                 write!(f, "{}", format_complex(value)).map_err(Into::into)
             }
@@ -891,12 +891,12 @@ impl Quil for Call {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "CALL {}", self.name)?;
         for argument in self.arguments.as_slice() {
             write!(f, " ")?;
-            argument.write(f, fall_back_to_debug)?;
+            argument.write(f, options)?;
         }
         Ok(())
     }
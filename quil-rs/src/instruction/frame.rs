@@ -8,7 +8,7 @@ use crate::{
     expression::Expression,
     parser::{common::parse_frame_identifier, lex, ParseError},
     program::{disallow_leftover, SyntaxError},
-    quil::{Quil, INDENT},
+    quil::{Quil, WriteOptions, INDENT},
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, strum::EnumTryAs)]
@@ -21,12 +21,12 @@ impl Quil for AttributeValue {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         use AttributeValue::*;
         match self {
             String(value) => write!(f, "{}", QuotedString(value)).map_err(Into::into),
-            Expression(value) => value.write(f, fall_back_to_debug),
+            Expression(value) => value.write(f, options),
         }
     }
 }
@@ -52,20 +52,196 @@ impl Quil for FrameDefinition {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "DEFFRAME ")?;
-        self.identifier.write(writer, fall_back_to_debug)?;
+        self.identifier.write(writer, options)?;
         write!(writer, ":")?;
         for (key, value) in &self.attributes {
             write!(writer, "\n{INDENT}{key}: ")?;
-            value.write(writer, fall_back_to_debug)?;
+            value.write(writer, options)?;
         }
 
         Ok(())
     }
 }
 
+/// The direction of a frame, as given by its `DIRECTION` attribute.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum FrameDirection {
+    /// The frame is used to transmit, such as by `PULSE`
+    Tx,
+
+    /// The frame is used to receive, such as by `CAPTURE`
+    Rx,
+}
+
+/// An error in the value of a frame attribute known to Quil-T.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum FrameAttributeError {
+    #[error("frame attribute {attribute} must be a real number, but is {}", .value.to_quil_or_debug())]
+    ExpectedNumber {
+        attribute: String,
+        value: AttributeValue,
+    },
+
+    #[error("frame attribute {attribute} must be a string, but is {}", .value.to_quil_or_debug())]
+    ExpectedString {
+        attribute: String,
+        value: AttributeValue,
+    },
+
+    #[error(
+        "frame attribute {} must be a positive, finite rate in Hz, but is {}",
+        TypedFrameAttributes::SAMPLE_RATE,
+        .0
+    )]
+    InvalidSampleRate(f64),
+
+    #[error("frame attribute {attribute} must be a finite, non-negative frequency in Hz, but is {value}")]
+    InvalidFrequency { attribute: String, value: f64 },
+
+    #[error(
+        "frame attribute {} must be \"tx\" or \"rx\", but is {:?}",
+        TypedFrameAttributes::DIRECTION,
+        .0
+    )]
+    UnknownDirection(String),
+}
+
+/// A non-fatal problem with the attributes of a frame.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum FrameAttributeWarning {
+    #[error("frame attribute {0} is not known to Quil-T")]
+    UnknownAttribute(String),
+}
+
+/// A typed view of the [`FrameAttributes`] known to Quil-T.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TypedFrameAttributes {
+    /// `SAMPLE-RATE`, in Hz
+    pub sample_rate: Option<f64>,
+
+    /// `INITIAL-FREQUENCY`, in Hz
+    pub initial_frequency: Option<f64>,
+
+    /// `CENTER-FREQUENCY`, in Hz
+    pub center_frequency: Option<f64>,
+
+    /// `HARDWARE-OBJECT`
+    pub hardware_object: Option<String>,
+
+    /// `DIRECTION`
+    pub direction: Option<FrameDirection>,
+
+    /// Problems with the attributes which do not prevent their use, such as unknown attributes
+    pub warnings: Vec<FrameAttributeWarning>,
+}
+
+impl TypedFrameAttributes {
+    pub const SAMPLE_RATE: &'static str = "SAMPLE-RATE";
+    pub const INITIAL_FREQUENCY: &'static str = "INITIAL-FREQUENCY";
+    pub const CENTER_FREQUENCY: &'static str = "CENTER-FREQUENCY";
+    pub const HARDWARE_OBJECT: &'static str = "HARDWARE-OBJECT";
+    pub const DIRECTION: &'static str = "DIRECTION";
+
+    /// Read and validate the known attributes of a frame, recording a warning for each unknown
+    /// attribute.
+    ///
+    /// Numeric attributes may be given by any expression which simplifies to a real number.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for the first known attribute, in order, with an invalid value.
+    pub fn try_from_attributes(attributes: &FrameAttributes) -> Result<Self, FrameAttributeError> {
+        let mut typed = Self::default();
+        for (attribute, value) in attributes {
+            match attribute.as_str() {
+                Self::SAMPLE_RATE => typed.sample_rate = Some(parse_sample_rate(value)?),
+                Self::INITIAL_FREQUENCY => {
+                    typed.initial_frequency = Some(parse_frequency(attribute, value)?)
+                }
+                Self::CENTER_FREQUENCY => {
+                    typed.center_frequency = Some(parse_frequency(attribute, value)?)
+                }
+                Self::HARDWARE_OBJECT => {
+                    typed.hardware_object = Some(parse_string(attribute, value)?.to_string())
+                }
+                Self::DIRECTION => {
+                    let direction = parse_string(attribute, value)?;
+                    typed.direction = Some(direction.parse().map_err(|_| {
+                        FrameAttributeError::UnknownDirection(direction.to_string())
+                    })?)
+                }
+                _ => typed
+                    .warnings
+                    .push(FrameAttributeWarning::UnknownAttribute(attribute.clone())),
+            }
+        }
+        Ok(typed)
+    }
+
+    /// Read and validate only the `SAMPLE-RATE` of a frame, if it has one, ignoring its other
+    /// attributes.
+    pub fn sample_rate_of(
+        attributes: &FrameAttributes,
+    ) -> Result<Option<f64>, FrameAttributeError> {
+        attributes
+            .get(Self::SAMPLE_RATE)
+            .map(parse_sample_rate)
+            .transpose()
+    }
+}
+
+fn parse_number(attribute: &str, value: &AttributeValue) -> Result<f64, FrameAttributeError> {
+    match value {
+        AttributeValue::Expression(expression) => {
+            expression.clone().into_simplified().to_real().ok()
+        }
+        AttributeValue::String(_) => None,
+    }
+    .ok_or_else(|| FrameAttributeError::ExpectedNumber {
+        attribute: attribute.to_string(),
+        value: value.clone(),
+    })
+}
+
+fn parse_sample_rate(value: &AttributeValue) -> Result<f64, FrameAttributeError> {
+    let sample_rate = parse_number(TypedFrameAttributes::SAMPLE_RATE, value)?;
+    // Written so as to reject NaN
+    if sample_rate > 0.0 && sample_rate.is_finite() {
+        Ok(sample_rate)
+    } else {
+        Err(FrameAttributeError::InvalidSampleRate(sample_rate))
+    }
+}
+
+fn parse_frequency(attribute: &str, value: &AttributeValue) -> Result<f64, FrameAttributeError> {
+    let frequency = parse_number(attribute, value)?;
+    if frequency >= 0.0 && frequency.is_finite() {
+        Ok(frequency)
+    } else {
+        Err(FrameAttributeError::InvalidFrequency {
+            attribute: attribute.to_string(),
+            value: frequency,
+        })
+    }
+}
+
+fn parse_string<'a>(
+    attribute: &str,
+    value: &'a AttributeValue,
+) -> Result<&'a str, FrameAttributeError> {
+    match value {
+        AttributeValue::String(string) => Ok(string),
+        AttributeValue::Expression(_) => Err(FrameAttributeError::ExpectedString {
+            attribute: attribute.to_string(),
+            value: value.clone(),
+        }),
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct FrameIdentifier {
     pub name: String,
@@ -82,10 +258,10 @@ impl Quil for FrameIdentifier {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> std::result::Result<(), crate::quil::ToQuilError> {
         for qubit in &self.qubits {
-            qubit.write(writer, fall_back_to_debug)?;
+            qubit.write(writer, options)?;
             write!(writer, " ")?;
         }
         write!(writer, "{}", QuotedString(&self.name)).map_err(Into::into)
@@ -132,7 +308,7 @@ impl Quil for Capture {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         {
             if self.blocking {
@@ -141,11 +317,11 @@ impl Quil for Capture {
                 write!(writer, "NONBLOCKING CAPTURE ")?;
             }
 
-            self.frame.write(writer, fall_back_to_debug)?;
+            self.frame.write(writer, options)?;
             write!(writer, " ")?;
-            self.waveform.write(writer, fall_back_to_debug)?;
+            self.waveform.write(writer, options)?;
             write!(writer, " ")?;
-            self.memory_reference.write(writer, fall_back_to_debug)?;
+            self.memory_reference.write(writer, options)?;
             Ok(())
         }
     }
@@ -172,7 +348,7 @@ impl Quil for Pulse {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         {
             if self.blocking {
@@ -180,9 +356,9 @@ impl Quil for Pulse {
             } else {
                 write!(writer, "NONBLOCKING PULSE ")?;
             }
-            self.frame.write(writer, fall_back_to_debug)?;
+            self.frame.write(writer, options)?;
             write!(writer, " ")?;
-            self.waveform.write(writer, fall_back_to_debug)?;
+            self.waveform.write(writer, options)?;
             Ok(())
         }
     }
@@ -216,7 +392,7 @@ impl Quil for RawCapture {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         {
             if self.blocking {
@@ -224,11 +400,11 @@ impl Quil for RawCapture {
             } else {
                 write!(writer, "NONBLOCKING RAW-CAPTURE ")?;
             }
-            self.frame.write(writer, fall_back_to_debug)?;
+            self.frame.write(writer, options)?;
             write!(writer, " ")?;
-            self.duration.write(writer, fall_back_to_debug)?;
+            self.duration.write(writer, options)?;
             write!(writer, " ")?;
-            self.memory_reference.write(writer, fall_back_to_debug)?;
+            self.memory_reference.write(writer, options)?;
             Ok(())
         }
     }
@@ -250,12 +426,12 @@ impl Quil for SetFrequency {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "SET-FREQUENCY ")?;
-        self.frame.write(writer, fall_back_to_debug)?;
+        self.frame.write(writer, options)?;
         write!(writer, " ")?;
-        self.frequency.write(writer, fall_back_to_debug)?;
+        self.frequency.write(writer, options)?;
         Ok(())
     }
 }
@@ -276,12 +452,12 @@ impl Quil for SetPhase {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "SET-PHASE ")?;
-        self.frame.write(writer, fall_back_to_debug)?;
+        self.frame.write(writer, options)?;
         write!(writer, " ")?;
-        self.phase.write(writer, fall_back_to_debug)?;
+        self.phase.write(writer, options)?;
         Ok(())
     }
 }
@@ -302,12 +478,12 @@ impl Quil for SetScale {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "SET-SCALE ")?;
-        self.frame.write(writer, fall_back_to_debug)?;
+        self.frame.write(writer, options)?;
         write!(writer, " ")?;
-        self.scale.write(writer, fall_back_to_debug)?;
+        self.scale.write(writer, options)?;
         Ok(())
     }
 }
@@ -328,12 +504,12 @@ impl Quil for ShiftFrequency {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "SHIFT-FREQUENCY ")?;
-        self.frame.write(writer, fall_back_to_debug)?;
+        self.frame.write(writer, options)?;
         write!(writer, " ")?;
-        self.frequency.write(writer, fall_back_to_debug)?;
+        self.frequency.write(writer, options)?;
         Ok(())
     }
 }
@@ -354,12 +530,12 @@ impl Quil for ShiftPhase {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "SHIFT-PHASE ")?;
-        self.frame.write(writer, fall_back_to_debug)?;
+        self.frame.write(writer, options)?;
         write!(writer, " ")?;
-        self.phase.write(writer, fall_back_to_debug)?;
+        self.phase.write(writer, options)?;
         Ok(())
     }
 }
@@ -380,12 +556,12 @@ impl Quil for SwapPhases {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "SWAP-PHASES ")?;
-        self.frame_1.write(writer, fall_back_to_debug)?;
+        self.frame_1.write(writer, options)?;
         write!(writer, " ")?;
-        self.frame_2.write(writer, fall_back_to_debug)?;
+        self.frame_2.write(writer, options)?;
         Ok(())
     }
 }
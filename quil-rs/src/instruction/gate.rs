@@ -2,7 +2,7 @@ use crate::{
     expression::Expression,
     imag,
     instruction::{write_expression_parameter_string, write_parameter_string, write_qubits, Qubit},
-    quil::{write_join_quil, Quil, INDENT},
+    quil::{write_join_quil, Quil, WriteOptions, INDENT},
     real,
     validation::identifier::{
         validate_identifier, validate_user_identifier, IdentifierValidationError,
@@ -580,16 +580,16 @@ impl Quil for Gate {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         for modifier in &self.modifiers {
-            modifier.write(f, fall_back_to_debug)?;
+            modifier.write(f, options)?;
             write!(f, " ")?;
         }
 
         write!(f, "{}", self.name)?;
-        write_expression_parameter_string(f, fall_back_to_debug, &self.parameters)?;
-        write_qubits(f, fall_back_to_debug, &self.qubits)
+        write_expression_parameter_string(f, options, &self.parameters)?;
+        write_qubits(f, options, &self.qubits)
     }
 }
 
@@ -597,7 +597,7 @@ impl Quil for GateModifier {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match self {
             Self::Controlled => write!(f, "CONTROLLED"),
@@ -865,13 +865,13 @@ impl Quil for GateSpecification {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match self {
             GateSpecification::Matrix(matrix) => {
                 for row in matrix {
                     write!(f, "{INDENT}")?;
-                    write_join_quil(f, fall_back_to_debug, row.iter(), ", ", "")?;
+                    write_join_quil(f, options, row.iter(), ", ", "")?;
                     writeln!(f)?;
                 }
             }
@@ -892,7 +892,7 @@ impl Quil for GateSpecification {
                         write!(f, "{word}")?;
                     }
                     write!(f, "(")?;
-                    term.expression.write(f, fall_back_to_debug)?;
+                    term.expression.write(f, options)?;
                     write!(f, ")")?;
                     for argument in term.arguments() {
                         write!(f, " {argument}")?;
@@ -932,7 +932,7 @@ impl Quil for GateDefinition {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "DEFGATE {}", self.name,)?;
         write_parameter_string(f, &self.parameters)?;
@@ -946,7 +946,7 @@ impl Quil for GateDefinition {
                 writeln!(f, " AS PAULI-SUM:")?
             }
         }
-        self.specification.write(f, fall_back_to_debug)?;
+        self.specification.write(f, options)?;
         Ok(())
     }
 }
@@ -1090,7 +1090,7 @@ impl Quil for GateType {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match self {
             Self::Matrix => write!(f, "MATRIX"),
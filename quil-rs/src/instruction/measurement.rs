@@ -1,4 +1,4 @@
-use crate::quil::Quil;
+use crate::quil::{Quil, WriteOptions};
 
 use super::{MemoryReference, Qubit};
 
@@ -18,13 +18,13 @@ impl Quil for Measurement {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         write!(writer, "MEASURE ")?;
-        self.qubit.write(writer, fall_back_to_debug)?;
+        self.qubit.write(writer, options)?;
         if let Some(target) = &self.target {
             write!(writer, " ")?;
-            target.write(writer, fall_back_to_debug)?;
+            target.write(writer, options)?;
         }
 
         Ok(())
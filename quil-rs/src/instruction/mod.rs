@@ -23,7 +23,7 @@ use crate::parser::parse_instructions;
 use crate::program::frame::{FrameMatchCondition, FrameMatchConditions};
 use crate::program::ProgramError;
 use crate::program::{MatchedFrames, MemoryAccesses};
-use crate::quil::{write_join_quil, Quil, ToQuilResult};
+use crate::quil::{write_join_quil, Quil, ToQuilResult, WriteOptions};
 use crate::Program;
 
 mod calibration;
@@ -42,7 +42,8 @@ mod timing;
 mod waveform;
 
 pub use self::calibration::{
-    Calibration, CalibrationIdentifier, CalibrationSignature, MeasureCalibrationDefinition,
+    Calibration, CalibrationIdentifier, CalibrationIndexKey, CalibrationParameterMatching,
+    CalibrationSignature, GateCalibrationKey, MeasureCalibrationDefinition,
     MeasureCalibrationIdentifier,
 };
 pub use self::circuit::CircuitDefinition;
@@ -57,8 +58,9 @@ pub use self::declaration::{
 };
 pub use self::extern_call::*;
 pub use self::frame::{
-    AttributeValue, Capture, FrameAttributes, FrameDefinition, FrameIdentifier, Pulse, RawCapture,
-    SetFrequency, SetPhase, SetScale, ShiftFrequency, ShiftPhase, SwapPhases,
+    AttributeValue, Capture, FrameAttributeError, FrameAttributeWarning, FrameAttributes,
+    FrameDefinition, FrameDirection, FrameIdentifier, Pulse, RawCapture, SetFrequency, SetPhase,
+    SetScale, ShiftFrequency, ShiftPhase, SwapPhases, TypedFrameAttributes,
 };
 pub use self::gate::{
     Gate, GateDefinition, GateError, GateModifier, GateSpecification, GateType, Matrix, PauliGate,
@@ -178,14 +180,14 @@ impl From<&Instruction> for InstructionRole {
 
 pub fn write_instruction_block<'i, I, Q>(
     f: &mut impl std::fmt::Write,
-    fall_back_to_debug: bool,
+    options: WriteOptions,
     values: I,
 ) -> crate::quil::ToQuilResult<()>
 where
     I: IntoIterator<Item = &'i Q>,
     Q: Quil + 'i,
 {
-    write_join_quil(f, fall_back_to_debug, values, "\n", "\t")
+    write_join_quil(f, options, values, "\n", "\t")
 }
 
 pub(crate) fn write_join(
@@ -216,12 +218,12 @@ pub fn format_integer_vector(values: &[u64]) -> String {
 /// Write a list of qubits, with each prefixed by a space (including the first)
 fn write_qubits(
     f: &mut impl std::fmt::Write,
-    fall_back_to_debug: bool,
+    options: WriteOptions,
     qubits: &[Qubit],
 ) -> crate::quil::ToQuilResult<()> {
     for qubit in qubits {
         write!(f, " ")?;
-        qubit.write(f, fall_back_to_debug)?;
+        qubit.write(f, options)?;
     }
     Ok(())
 }
@@ -229,19 +231,19 @@ fn write_qubits(
 /// Write qubits as a Quil parameter list, where all are prefixed with ` `.
 fn write_qubit_parameters(
     f: &mut impl std::fmt::Write,
-    fall_back_to_debug: bool,
+    options: WriteOptions,
     qubits: &[Qubit],
 ) -> ToQuilResult<()> {
     for qubit in qubits.iter() {
         write!(f, " ")?;
-        qubit.write(f, fall_back_to_debug)?;
+        qubit.write(f, options)?;
     }
     Ok(())
 }
 
 fn write_expression_parameter_string(
     f: &mut impl std::fmt::Write,
-    fall_back_to_debug: bool,
+    options: WriteOptions,
     parameters: &[Expression],
 ) -> crate::quil::ToQuilResult<()> {
     if parameters.is_empty() {
@@ -249,7 +251,7 @@ fn write_expression_parameter_string(
     }
 
     write!(f, "(")?;
-    write_join_quil(f, fall_back_to_debug, parameters, ", ", "")?;
+    write_join_quil(f, options, parameters, ", ", "")?;
     write!(f, ")")?;
     Ok(())
 }
@@ -268,61 +270,53 @@ impl Quil for Instruction {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         match self {
-            Instruction::Arithmetic(arithmetic) => arithmetic.write(f, fall_back_to_debug),
-            Instruction::CalibrationDefinition(calibration) => {
-                calibration.write(f, fall_back_to_debug)
-            }
-            Instruction::Call(call) => call.write(f, fall_back_to_debug),
-            Instruction::Capture(capture) => capture.write(f, fall_back_to_debug),
-            Instruction::CircuitDefinition(circuit) => circuit.write(f, fall_back_to_debug),
-            Instruction::Convert(convert) => convert.write(f, fall_back_to_debug),
-            Instruction::Declaration(declaration) => declaration.write(f, fall_back_to_debug),
-            Instruction::Delay(delay) => delay.write(f, fall_back_to_debug),
-            Instruction::Fence(fence) => fence.write(f, fall_back_to_debug),
-            Instruction::FrameDefinition(frame_definition) => {
-                frame_definition.write(f, fall_back_to_debug)
-            }
-            Instruction::Gate(gate) => gate.write(f, fall_back_to_debug),
-            Instruction::GateDefinition(gate_definition) => {
-                gate_definition.write(f, fall_back_to_debug)
-            }
-            Instruction::Include(include) => include.write(f, fall_back_to_debug),
+            Instruction::Arithmetic(arithmetic) => arithmetic.write(f, options),
+            Instruction::CalibrationDefinition(calibration) => calibration.write(f, options),
+            Instruction::Call(call) => call.write(f, options),
+            Instruction::Capture(capture) => capture.write(f, options),
+            Instruction::CircuitDefinition(circuit) => circuit.write(f, options),
+            Instruction::Convert(convert) => convert.write(f, options),
+            Instruction::Declaration(declaration) => declaration.write(f, options),
+            Instruction::Delay(delay) => delay.write(f, options),
+            Instruction::Fence(fence) => fence.write(f, options),
+            Instruction::FrameDefinition(frame_definition) => frame_definition.write(f, options),
+            Instruction::Gate(gate) => gate.write(f, options),
+            Instruction::GateDefinition(gate_definition) => gate_definition.write(f, options),
+            Instruction::Include(include) => include.write(f, options),
             Instruction::MeasureCalibrationDefinition(measure_calibration) => {
-                measure_calibration.write(f, fall_back_to_debug)
-            }
-            Instruction::Measurement(measurement) => measurement.write(f, fall_back_to_debug),
-            Instruction::Move(r#move) => r#move.write(f, fall_back_to_debug),
-            Instruction::Exchange(exchange) => exchange.write(f, fall_back_to_debug),
-            Instruction::Load(load) => load.write(f, fall_back_to_debug),
-            Instruction::Store(store) => store.write(f, fall_back_to_debug),
-            Instruction::Pulse(pulse) => pulse.write(f, fall_back_to_debug),
-            Instruction::Pragma(pragma) => pragma.write(f, fall_back_to_debug),
-            Instruction::RawCapture(raw_capture) => raw_capture.write(f, fall_back_to_debug),
-            Instruction::Reset(reset) => reset.write(f, fall_back_to_debug),
-            Instruction::SetFrequency(set_frequency) => set_frequency.write(f, fall_back_to_debug),
-            Instruction::SetPhase(set_phase) => set_phase.write(f, fall_back_to_debug),
-            Instruction::SetScale(set_scale) => set_scale.write(f, fall_back_to_debug),
-            Instruction::ShiftFrequency(shift_frequency) => {
-                shift_frequency.write(f, fall_back_to_debug)
+                measure_calibration.write(f, options)
             }
-            Instruction::ShiftPhase(shift_phase) => shift_phase.write(f, fall_back_to_debug),
-            Instruction::SwapPhases(swap_phases) => swap_phases.write(f, fall_back_to_debug),
+            Instruction::Measurement(measurement) => measurement.write(f, options),
+            Instruction::Move(r#move) => r#move.write(f, options),
+            Instruction::Exchange(exchange) => exchange.write(f, options),
+            Instruction::Load(load) => load.write(f, options),
+            Instruction::Store(store) => store.write(f, options),
+            Instruction::Pulse(pulse) => pulse.write(f, options),
+            Instruction::Pragma(pragma) => pragma.write(f, options),
+            Instruction::RawCapture(raw_capture) => raw_capture.write(f, options),
+            Instruction::Reset(reset) => reset.write(f, options),
+            Instruction::SetFrequency(set_frequency) => set_frequency.write(f, options),
+            Instruction::SetPhase(set_phase) => set_phase.write(f, options),
+            Instruction::SetScale(set_scale) => set_scale.write(f, options),
+            Instruction::ShiftFrequency(shift_frequency) => shift_frequency.write(f, options),
+            Instruction::ShiftPhase(shift_phase) => shift_phase.write(f, options),
+            Instruction::SwapPhases(swap_phases) => swap_phases.write(f, options),
             Instruction::WaveformDefinition(waveform_definition) => {
-                waveform_definition.write(f, fall_back_to_debug)
+                waveform_definition.write(f, options)
             }
             Instruction::Halt => write!(f, "HALT").map_err(Into::into),
             Instruction::Nop => write!(f, "NOP").map_err(Into::into),
             Instruction::Wait => write!(f, "WAIT").map_err(Into::into),
-            Instruction::Jump(jump) => jump.write(f, fall_back_to_debug),
-            Instruction::JumpUnless(jump) => jump.write(f, fall_back_to_debug),
-            Instruction::JumpWhen(jump) => jump.write(f, fall_back_to_debug),
-            Instruction::Label(label) => label.write(f, fall_back_to_debug),
-            Instruction::Comparison(comparison) => comparison.write(f, fall_back_to_debug),
-            Instruction::BinaryLogic(binary_logic) => binary_logic.write(f, fall_back_to_debug),
-            Instruction::UnaryLogic(unary_logic) => unary_logic.write(f, fall_back_to_debug),
+            Instruction::Jump(jump) => jump.write(f, options),
+            Instruction::JumpUnless(jump) => jump.write(f, options),
+            Instruction::JumpWhen(jump) => jump.write(f, options),
+            Instruction::Label(label) => label.write(f, options),
+            Instruction::Comparison(comparison) => comparison.write(f, options),
+            Instruction::BinaryLogic(binary_logic) => binary_logic.write(f, options),
+            Instruction::UnaryLogic(unary_logic) => unary_logic.write(f, options),
         }
     }
 }
@@ -468,6 +462,68 @@ impl Instruction {
         }
     }
 
+    /// Like [`Instruction::apply_to_expressions`], but visits each expression by reference in the
+    /// same order, without modifying the instruction.
+    pub(crate) fn for_each_expression(&self, mut closure: impl FnMut(&Expression)) {
+        match self {
+            Instruction::CalibrationDefinition(Calibration {
+                identifier: CalibrationIdentifier { parameters, .. },
+                ..
+            })
+            | Instruction::Gate(Gate { parameters, .. }) => {
+                parameters.iter().for_each(closure);
+            }
+            Instruction::Capture(Capture { waveform, .. })
+            | Instruction::Pulse(Pulse { waveform, .. }) => {
+                waveform.parameters.values().for_each(closure);
+            }
+            Instruction::Delay(Delay { duration, .. })
+            | Instruction::RawCapture(RawCapture { duration, .. }) => {
+                closure(duration);
+            }
+            Instruction::FrameDefinition(FrameDefinition { attributes, .. }) => {
+                for value in attributes.values() {
+                    if let AttributeValue::Expression(expression) = value {
+                        closure(expression);
+                    }
+                }
+            }
+            Instruction::SetFrequency(SetFrequency {
+                frequency: expression,
+                ..
+            })
+            | Instruction::SetPhase(SetPhase {
+                phase: expression, ..
+            })
+            | Instruction::SetScale(SetScale {
+                scale: expression, ..
+            })
+            | Instruction::ShiftFrequency(ShiftFrequency {
+                frequency: expression,
+                ..
+            })
+            | Instruction::ShiftPhase(ShiftPhase {
+                phase: expression, ..
+            }) => {
+                closure(expression);
+            }
+            Instruction::WaveformDefinition(WaveformDefinition { definition, .. }) => {
+                definition.matrix.iter().for_each(closure);
+            }
+            Instruction::GateDefinition(GateDefinition {
+                specification: GateSpecification::Matrix(matrix),
+                ..
+            }) => {
+                for row in matrix {
+                    for cell in row {
+                        closure(cell);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
     pub(crate) fn get_frame_match_condition<'a>(
         &'a self,
         qubits_available: &'a HashSet<Qubit>,
@@ -1,4 +1,4 @@
-use crate::quil::Quil;
+use crate::quil::{Quil, WriteOptions};
 
 use super::QuotedString;
 
@@ -23,12 +23,12 @@ impl Quil for Pragma {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "PRAGMA {}", self.name)?;
         for arg in &self.arguments {
             write!(f, " ")?;
-            arg.write(f, fall_back_to_debug)?;
+            arg.write(f, options)?;
         }
         if let Some(data) = &self.data {
             write!(f, " {}", QuotedString(data))?;
@@ -47,7 +47,7 @@ impl Quil for PragmaArgument {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match self {
             PragmaArgument::Identifier(i) => write!(f, "{i}"),
@@ -66,7 +66,7 @@ impl Quil for Include {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        _options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, r#"INCLUDE {}"#, QuotedString(&self.filename)).map_err(Into::into)
     }
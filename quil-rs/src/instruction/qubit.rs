@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::quil::{Quil, ToQuilError};
+use crate::quil::{Quil, ToQuilError, WriteOptions};
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, strum::EnumTryAs)]
 pub enum Qubit {
@@ -26,13 +26,13 @@ impl Quil for Qubit {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> std::result::Result<(), crate::quil::ToQuilError> {
         use Qubit::*;
         match self {
             Fixed(value) => write!(writer, "{value}").map_err(Into::into),
             Placeholder(_) => {
-                if fall_back_to_debug {
+                if options.fall_back_to_debug {
                     write!(writer, "{:?}", self).map_err(Into::into)
                 } else {
                     Err(ToQuilError::UnresolvedQubitPlaceholder)
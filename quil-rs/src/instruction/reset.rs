@@ -1,4 +1,4 @@
-use crate::quil::Quil;
+use crate::quil::{Quil, WriteOptions};
 
 use super::Qubit;
 
@@ -17,12 +17,12 @@ impl Quil for Reset {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         match &self.qubit {
             Some(qubit) => {
                 write!(writer, "RESET ")?;
-                qubit.write(writer, fall_back_to_debug)
+                qubit.write(writer, options)
             }
             None => write!(writer, "RESET").map_err(Into::into),
         }
@@ -1,5 +1,8 @@
 use super::Qubit;
-use crate::{expression::Expression, quil::Quil};
+use crate::{
+    expression::Expression,
+    quil::{Quil, WriteOptions},
+};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Delay {
@@ -22,18 +25,18 @@ impl Quil for Delay {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(writer, "DELAY")?;
         for qubit in &self.qubits {
             write!(writer, " ")?;
-            qubit.write(writer, fall_back_to_debug)?;
+            qubit.write(writer, options)?;
         }
         for frame_name in &self.frame_names {
             write!(writer, " \"{}\"", frame_name)?;
         }
         write!(writer, " ",)?;
-        self.duration.write(writer, fall_back_to_debug)
+        self.duration.write(writer, options)
     }
 }
 
@@ -46,12 +49,12 @@ impl Quil for Fence {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), crate::quil::ToQuilError> {
         write!(writer, "FENCE")?;
         for qubit in &self.qubits {
             write!(writer, " ")?;
-            qubit.write(writer, fall_back_to_debug)?;
+            qubit.write(writer, options)?;
         }
         Ok(())
     }
@@ -2,7 +2,7 @@ use indexmap::IndexMap;
 
 use crate::{
     expression::Expression,
-    quil::{write_join_quil, Quil, INDENT},
+    quil::{write_join_quil, Quil, WriteOptions, INDENT},
 };
 
 use super::write_parameter_string;
@@ -35,13 +35,12 @@ impl Quil for WaveformDefinition {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         write!(f, "DEFWAVEFORM {}", self.name)?;
         write_parameter_string(f, &self.definition.parameters)?;
         write!(f, ":\n{INDENT}")?;
-        write_join_quil(f, fall_back_to_debug, &self.definition.matrix, ", ", "")
-            .map_err(Into::into)
+        write_join_quil(f, options, &self.definition.matrix, ", ", "").map_err(Into::into)
     }
 }
 
@@ -97,7 +96,7 @@ impl Quil for WaveformInvocation {
     fn write(
         &self,
         f: &mut impl std::fmt::Write,
-        _fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> crate::quil::ToQuilResult<()> {
         let mut key_value_pairs = self
             .parameters
@@ -106,19 +105,17 @@ impl Quil for WaveformInvocation {
 
         key_value_pairs.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
 
-        if key_value_pairs.is_empty() {
-            write!(f, "{}", self.name,)?;
-        } else {
-            write!(
-                f,
-                "{}({})",
-                self.name,
-                key_value_pairs
-                    .iter()
-                    .map(|(k, v)| format!("{k}: {}", v.to_quil_or_debug()))
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            )?;
+        write!(f, "{}", self.name)?;
+        if !key_value_pairs.is_empty() {
+            write!(f, "(")?;
+            for (index, (k, v)) in key_value_pairs.into_iter().enumerate() {
+                if index > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{k}: ")?;
+                v.write(f, options)?;
+            }
+            write!(f, ")")?;
         }
         Ok(())
     }
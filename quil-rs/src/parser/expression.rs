@@ -15,7 +15,10 @@
 use nom::combinator::opt;
 use num_complex::Complex64;
 
-use crate::expression::{FunctionCallExpression, InfixExpression, PrefixExpression};
+use crate::expression::{
+    BinaryExpressionFunction, BinaryFunctionCallExpression, FunctionCallExpression,
+    InfixExpression, PrefixExpression,
+};
 use crate::parser::InternalParserResult;
 use crate::{
     expected_token,
@@ -150,13 +153,66 @@ fn parse_function_call<'a>(
     ))
 }
 
+/// Given a two-argument expression function, parse the comma-separated expressions within its
+/// parentheses.
+fn parse_binary_function_call<'a>(
+    input: ParserInput<'a>,
+    function: BinaryExpressionFunction,
+) -> InternalParserResult<'a, Expression> {
+    let (input, _) = token!(LParenthesis)(input)?;
+    let (input, left) = parse(input, Precedence::Lowest)?;
+    let (input, _) = token!(Comma)(input)?;
+    let (input, right) = parse(input, Precedence::Lowest)?;
+    let (input, _) = token!(RParenthesis)(input)?;
+    Ok((
+        input,
+        Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+            function,
+            left: Box::new(left),
+            right: Box::new(right),
+        }),
+    ))
+}
+
+/// A function which is not defined by the Quil specification, taking one or two arguments.
+enum ExtendedFunction {
+    Unary(ExpressionFunction),
+    Binary(BinaryExpressionFunction),
+}
+
+/// The function named by an identifier which is not defined by the Quil specification, and so
+/// which is only a function when it is called, rather than a memory reference.
+fn extended_function(name: &str) -> Option<ExtendedFunction> {
+    use BinaryExpressionFunction::*;
+    use ExpressionFunction::*;
+    use ExtendedFunction::{Binary, Unary};
+    Some(match name {
+        "abs" => Unary(AbsoluteValue),
+        "arccos" => Unary(ArcCosine),
+        "arcsin" => Unary(ArcSine),
+        "arctan" => Unary(ArcTangent),
+        "atan2" => Binary(ArcTangent2),
+        "ceil" => Unary(Ceiling),
+        "conj" => Unary(Conjugate),
+        "floor" => Unary(Floor),
+        "imag" => Unary(ImaginaryPart),
+        "log" => Unary(Logarithm),
+        "max" => Binary(Maximum),
+        "min" => Binary(Minimum),
+        "real" => Unary(RealPart),
+        "tan" => Unary(Tangent),
+        _ => return None,
+    })
+}
+
 /// Identifiers have to be handled specially because some have special meaning.
 ///
 /// By order of precedence:
 ///
 /// 1. Memory references with brackets
 /// 2. Special function and constant identifiers
-/// 3. Anything else is considered to be a memory reference without index brackets
+/// 3. Extended function identifiers, when followed by a parenthesis
+/// 4. Anything else is considered to be a memory reference without index brackets
 fn parse_expression_identifier(input: ParserInput) -> InternalParserResult<Expression> {
     let (input, memory_reference) = opt(parse_memory_reference_with_brackets)(input)?;
     if let Some(memory_reference) = memory_reference {
@@ -173,13 +229,27 @@ fn parse_expression_identifier(input: ParserInput) -> InternalParserResult<Expre
             "pi" => Ok((remainder, Expression::PiConstant)),
             "sin" => parse_function_call(remainder, ExpressionFunction::Sine),
             "sqrt" => parse_function_call(remainder, ExpressionFunction::SquareRoot),
-            name => Ok((
-                remainder,
-                Expression::Address(MemoryReference {
-                    name: name.to_owned(),
-                    index: 0,
-                }),
-            )),
+            name => match extended_function(name) {
+                Some(function)
+                    if matches!(super::first_token(remainder), Some(Token::LParenthesis)) =>
+                {
+                    match function {
+                        ExtendedFunction::Unary(function) => {
+                            parse_function_call(remainder, function)
+                        }
+                        ExtendedFunction::Binary(function) => {
+                            parse_binary_function_call(remainder, function)
+                        }
+                    }
+                }
+                _ => Ok((
+                    remainder,
+                    Expression::Address(MemoryReference {
+                        name: name.to_owned(),
+                        index: 0,
+                    }),
+                )),
+            },
         },
         Some((other_token, _)) => expected_token!(input, other_token, "identifier".to_owned()),
     }
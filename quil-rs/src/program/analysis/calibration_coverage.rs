@@ -0,0 +1,408 @@
+//! Reporting which gates and measurements in a program are calibrated
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    expression::Expression,
+    instruction::{
+        CalibrationSignature, CircuitDefinition, Gate, Include, Instruction, Measurement, Qubit,
+    },
+    program::{CalibrationExpansion, CalibrationSource, ProgramError},
+    Program,
+};
+
+/// Whether a single gate or measurement within a program is calibrated, and if so, by which
+/// calibration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalibrationCoverageItem {
+    /// The index of the body instruction from which this instruction originates. This differs from
+    /// the instruction itself if it is the product of expanding a `DEFCIRCUIT`.
+    pub instruction_index: usize,
+
+    /// The gate or measurement
+    pub instruction: Instruction,
+
+    /// The calibration which matches the instruction, if any
+    pub calibration: Option<CalibrationSource>,
+}
+
+/// A report of the calibrations which match the gates and measurements of a program.
+///
+/// See [`Program::calibration_coverage`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CalibrationCoverage {
+    items: Vec<CalibrationCoverageItem>,
+    unused_calibrations: Vec<CalibrationSource>,
+    unresolved_includes: Vec<Include>,
+}
+
+impl CalibrationCoverage {
+    /// Every gate and measurement within the program, in order, along with its calibration.
+    pub fn items(&self) -> &[CalibrationCoverageItem] {
+        &self.items
+    }
+
+    /// The gates and measurements which have a matching calibration.
+    pub fn calibrated(&self) -> impl Iterator<Item = &CalibrationCoverageItem> {
+        self.items.iter().filter(|item| item.calibration.is_some())
+    }
+
+    /// The gates and measurements which have no matching calibration.
+    pub fn uncalibrated(&self) -> impl Iterator<Item = &CalibrationCoverageItem> {
+        self.items.iter().filter(|item| item.calibration.is_none())
+    }
+
+    /// The calibrations which match no instruction, either within the program body or within the
+    /// expansion of another calibration, in the order in which they are defined.
+    pub fn unused_calibrations(&self) -> &[CalibrationSource] {
+        &self.unused_calibrations
+    }
+
+    /// The `INCLUDE` instructions within the program, whose contents could not be inspected.
+    pub fn unresolved_includes(&self) -> &[Include] {
+        &self.unresolved_includes
+    }
+
+    /// Whether every gate and measurement is calibrated, and there are no unresolved includes
+    /// which may contain more of them.
+    pub fn is_complete(&self) -> bool {
+        self.uncalibrated().next().is_none() && self.unresolved_includes.is_empty()
+    }
+}
+
+impl Program {
+    /// Report which calibration will be used to expand each gate and measurement in the program
+    /// body, as chosen by [`Calibrations::get_match_for_gate`] and
+    /// [`Calibrations::get_match_for_measurement`], along with the instructions which have no
+    /// calibration and the calibrations which are never used.
+    ///
+    /// Gates which have no calibration but which invoke a `DEFCIRCUIT` within this program are
+    /// replaced by the contents of that circuit. `INCLUDE`d files are not read, and are instead
+    /// reported by [`CalibrationCoverage::unresolved_includes`].
+    ///
+    /// Return an error if any calibration directly or indirectly expands into itself.
+    ///
+    /// [`Calibrations::get_match_for_gate`]: crate::program::Calibrations::get_match_for_gate
+    /// [`Calibrations::get_match_for_measurement`]: crate::program::Calibrations::get_match_for_measurement
+    pub fn calibration_coverage(&self) -> Result<CalibrationCoverage, ProgramError> {
+        let circuits = self
+            .body_instructions()
+            .filter_map(|instruction| match instruction {
+                Instruction::CircuitDefinition(circuit) => Some((circuit.name.as_str(), circuit)),
+                _ => None,
+            })
+            .collect();
+        let mut builder = CoverageBuilder {
+            program: self,
+            circuits,
+            coverage: CalibrationCoverage::default(),
+            used_calibrations: HashSet::new(),
+            used_measure_calibrations: HashSet::new(),
+        };
+
+        for (index, instruction) in self.body_instructions().enumerate() {
+            match instruction {
+                Instruction::Gate(_) | Instruction::Measurement(_) => {
+                    builder.visit(index, instruction.clone(), &mut Vec::new())?;
+                }
+                Instruction::Include(include) => {
+                    builder.coverage.unresolved_includes.push(include.clone());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(builder.finish())
+    }
+}
+
+struct CoverageBuilder<'p> {
+    program: &'p Program,
+    circuits: HashMap<&'p str, &'p CircuitDefinition>,
+    coverage: CalibrationCoverage,
+
+    /// The positions of the used calibrations within their [`crate::program::CalibrationSet`]s
+    used_calibrations: HashSet<usize>,
+    used_measure_calibrations: HashSet<usize>,
+}
+
+impl<'p> CoverageBuilder<'p> {
+    /// Record the calibration of a gate or measurement, expanding it first if it invokes a circuit.
+    ///
+    /// `circuit_path` holds the names of the circuits being expanded, to avoid infinite recursion.
+    fn visit(
+        &mut self,
+        instruction_index: usize,
+        instruction: Instruction,
+        circuit_path: &mut Vec<String>,
+    ) -> Result<(), ProgramError> {
+        if let Some(output) = self
+            .program
+            .calibrations
+            .expand_with_detail(&instruction, &[])?
+        {
+            self.mark_used(&output.detail);
+            self.coverage.items.push(CalibrationCoverageItem {
+                instruction_index,
+                instruction,
+                calibration: Some(output.detail.calibration_used().clone()),
+            });
+            return Ok(());
+        }
+
+        if let Instruction::Gate(gate) = &instruction {
+            if let Some(circuit) = self.get_circuit_for_gate(gate) {
+                if !circuit_path.contains(&circuit.name) {
+                    circuit_path.push(circuit.name.clone());
+                    for expanded in expand_circuit(circuit, gate) {
+                        self.visit(instruction_index, expanded, circuit_path)?;
+                    }
+                    circuit_path.pop();
+                    return Ok(());
+                }
+            }
+        }
+
+        self.coverage.items.push(CalibrationCoverageItem {
+            instruction_index,
+            instruction,
+            calibration: None,
+        });
+        Ok(())
+    }
+
+    /// Return the circuit invoked by the given gate, if any.
+    fn get_circuit_for_gate(&self, gate: &Gate) -> Option<&'p CircuitDefinition> {
+        self.circuits
+            .get(gate.name.as_str())
+            .copied()
+            .filter(|circuit| {
+                gate.modifiers.is_empty()
+                    && circuit.parameters.len() == gate.parameters.len()
+                    && circuit.qubit_variables.len() == gate.qubits.len()
+            })
+    }
+
+    /// Mark the calibration used in the given expansion, and in any nested expansions, as used.
+    fn mark_used(&mut self, expansion: &CalibrationExpansion) {
+        let calibrations = &self.program.calibrations;
+        match expansion.calibration_used() {
+            CalibrationSource::Calibration(identifier) => {
+                if let Some(position) = calibrations
+                    .calibrations
+                    .signature_position(&identifier.signature())
+                {
+                    self.used_calibrations.insert(position);
+                }
+            }
+            CalibrationSource::MeasureCalibration(identifier) => {
+                if let Some(position) = calibrations
+                    .measure_calibrations
+                    .signature_position(&identifier.signature())
+                {
+                    self.used_measure_calibrations.insert(position);
+                }
+            }
+        }
+
+        for entry in expansion.expansions().entries() {
+            self.mark_used(entry.target_location());
+        }
+    }
+
+    fn finish(mut self) -> CalibrationCoverage {
+        let calibrations = &self.program.calibrations;
+        let unused_calibrations = calibrations
+            .iter_calibrations()
+            .enumerate()
+            .filter(|(position, _)| !self.used_calibrations.contains(position))
+            .map(|(_, calibration)| CalibrationSource::from(calibration.identifier.clone()));
+        let unused_measure_calibrations = calibrations
+            .iter_measure_calibrations()
+            .enumerate()
+            .filter(|(position, _)| !self.used_measure_calibrations.contains(position))
+            .map(|(_, calibration)| CalibrationSource::from(calibration.identifier.clone()));
+        self.coverage.unused_calibrations = unused_calibrations
+            .chain(unused_measure_calibrations)
+            .collect();
+
+        self.coverage
+    }
+}
+
+/// Return the gates and measurements within the given circuit, with its qubit variables and
+/// parameters replaced by the qubits and parameters of the gate which invokes it.
+fn expand_circuit(circuit: &CircuitDefinition, gate: &Gate) -> Vec<Instruction> {
+    let qubits: HashMap<&String, &Qubit> = circuit
+        .qubit_variables
+        .iter()
+        .zip(gate.qubits.iter())
+        .collect();
+    let substitute_qubit = |qubit: &mut Qubit| {
+        if let Qubit::Variable(name) = qubit {
+            if let Some(replacement) = qubits.get(&*name) {
+                *qubit = (*replacement).clone();
+            }
+        }
+    };
+    let parameters: HashMap<String, Expression> = circuit
+        .parameters
+        .iter()
+        .cloned()
+        .zip(gate.parameters.iter().cloned())
+        .collect();
+
+    circuit
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Gate(inner) => {
+                let mut inner = inner.clone();
+                inner.qubits.iter_mut().for_each(substitute_qubit);
+                inner.parameters = inner
+                    .parameters
+                    .into_iter()
+                    .map(|parameter| parameter.substitute_variables(&parameters))
+                    .collect();
+                Some(Instruction::Gate(inner))
+            }
+            Instruction::Measurement(Measurement { qubit, target }) => {
+                let mut qubit = qubit.clone();
+                substitute_qubit(&mut qubit);
+                Some(Instruction::Measurement(Measurement {
+                    qubit,
+                    target: target.clone(),
+                }))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        instruction::{Instruction, Qubit},
+        program::CalibrationSource,
+        quil::Quil,
+        Program,
+    };
+
+    const PROGRAM: &str = r#"DECLARE ro BIT[2]
+DEFCAL X 0:
+    Y 0
+DEFCAL Y 0:
+    NOP
+DEFCAL Y 1:
+    NOP
+DEFCAL CZ 0 1:
+    NOP
+DEFCAL CZ 3 4:
+    NOP
+DEFCAL MEASURE 0 addr:
+    NOP
+DEFCAL MEASURE q addr:
+    NOP
+DEFCIRCUIT BELL a b:
+    X a
+    CZ a b
+BELL 0 1
+BELL 1 2
+MEASURE 0 ro[0]
+MEASURE 1 ro[1]
+"#;
+
+    #[test]
+    fn reports_calibrated_and_uncalibrated_instructions() {
+        let program = Program::from_str(PROGRAM).unwrap();
+        let coverage = program.calibration_coverage().unwrap();
+
+        let items = coverage
+            .items()
+            .iter()
+            .map(|item| {
+                (
+                    item.instruction_index,
+                    item.instruction.to_quil_or_debug(),
+                    item.calibration.is_some(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            vec![
+                (1, "X 0".to_string(), true),
+                (1, "CZ 0 1".to_string(), true),
+                (2, "X 1".to_string(), false),
+                (2, "CZ 1 2".to_string(), false),
+                (3, "MEASURE 0 ro[0]".to_string(), true),
+                (4, "MEASURE 1 ro[1]".to_string(), true),
+            ]
+        );
+        assert!(!coverage.is_complete());
+    }
+
+    #[test]
+    fn reports_unused_calibrations() {
+        let program = Program::from_str(PROGRAM).unwrap();
+        let coverage = program.calibration_coverage().unwrap();
+
+        // `DEFCAL Y 0` is used within the expansion of `DEFCAL X 0`
+        let unused = coverage
+            .unused_calibrations()
+            .iter()
+            .map(|source| match source {
+                CalibrationSource::Calibration(identifier) => {
+                    format!("{} {:?}", identifier.name, identifier.qubits)
+                }
+                CalibrationSource::MeasureCalibration(identifier) => {
+                    format!("MEASURE {:?}", identifier.qubit)
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            unused,
+            vec![
+                format!("Y {:?}", vec![Qubit::Fixed(1)]),
+                format!("CZ {:?}", vec![Qubit::Fixed(3), Qubit::Fixed(4)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unresolved_includes() {
+        let program = Program::from_str(
+            r#"DEFCAL X 0:
+    NOP
+INCLUDE "calibrations.quil"
+X 0
+"#,
+        )
+        .unwrap();
+        let coverage = program.calibration_coverage().unwrap();
+
+        assert_eq!(coverage.calibrated().count(), 1);
+        assert_eq!(coverage.unresolved_includes().len(), 1);
+        assert!(!coverage.is_complete());
+        assert!(matches!(
+            coverage.items()[0].instruction,
+            Instruction::Gate(_)
+        ));
+    }
+}
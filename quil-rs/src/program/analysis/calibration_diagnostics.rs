@@ -0,0 +1,533 @@
+//! Diagnostics for calibrations which overlap, shadow, or replace one another
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::{
+    expression::Expression,
+    instruction::{
+        Calibration, CalibrationIdentifier, CalibrationParameterMatching, CalibrationSignature,
+        Instruction, MeasureCalibrationDefinition, MeasureCalibrationIdentifier, Qubit,
+    },
+    program::{CalibrationSource, Calibrations},
+};
+
+/// A pair of calibrations which both match some instructions, such that the precedence rules of
+/// [`Calibrations::get_match_for_gate`] or [`Calibrations::get_match_for_measurement`] decide which
+/// of them is used.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalibrationOverlap {
+    /// The calibration which is used for the instructions matched by both
+    pub winner: CalibrationSource,
+
+    /// The calibration which is not used for the instructions matched by both
+    pub loser: CalibrationSource,
+
+    /// A calibration identifier which matches exactly the instructions matched by both
+    /// calibrations
+    pub overlap: CalibrationSource,
+
+    /// Whether every instruction matched by the loser is also matched by the winner, in which case
+    /// the loser is never used.
+    pub loser_shadowed: bool,
+}
+
+/// A calibration which was replaced by a later calibration with the same [`CalibrationSignature`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalibrationReplacement {
+    /// The identifier shared by both calibrations
+    pub calibration: CalibrationSource,
+
+    /// The index of the replaced calibration within the analyzed instructions
+    pub replaced_index: usize,
+
+    /// The index of the replacing calibration within the analyzed instructions
+    pub replacement_index: usize,
+}
+
+/// Diagnostics for a set of calibrations; see [`Calibrations::get_diagnostics`] and
+/// [`CalibrationDiagnostics::from_instructions`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CalibrationDiagnostics {
+    overlaps: Vec<CalibrationOverlap>,
+    replacements: Vec<CalibrationReplacement>,
+}
+
+impl CalibrationDiagnostics {
+    /// Return the diagnostics for the calibrations defined by the given instructions, in order,
+    /// including those which are replaced by a later calibration with the same signature.
+    ///
+    /// A [`crate::Program`] retains only the last calibration with each signature, so replaced
+    /// calibrations can only be reported when analyzing instructions before they are added to a
+    /// program.
+    pub fn from_instructions<'a>(instructions: impl IntoIterator<Item = &'a Instruction>) -> Self {
+        let mut calibrations = Calibrations::default();
+        let mut calibration_indices: Vec<usize> = Vec::new();
+        let mut measure_calibration_indices: Vec<usize> = Vec::new();
+        let mut replacements = Vec::new();
+
+        for (index, instruction) in instructions.into_iter().enumerate() {
+            match instruction {
+                Instruction::CalibrationDefinition(calibration) => {
+                    let signature = calibration.signature();
+                    if let Some(position) = calibrations.calibrations.signature_position(&signature)
+                    {
+                        replacements.push(CalibrationReplacement {
+                            calibration: calibration.identifier.clone().into(),
+                            replaced_index: calibration_indices[position],
+                            replacement_index: index,
+                        });
+                        calibration_indices[position] = index;
+                    } else {
+                        calibration_indices.push(index);
+                    }
+                    calibrations.insert_calibration(calibration.clone());
+                }
+                Instruction::MeasureCalibrationDefinition(calibration) => {
+                    let signature = calibration.signature();
+                    if let Some(position) = calibrations
+                        .measure_calibrations
+                        .signature_position(&signature)
+                    {
+                        replacements.push(CalibrationReplacement {
+                            calibration: calibration.identifier.clone().into(),
+                            replaced_index: measure_calibration_indices[position],
+                            replacement_index: index,
+                        });
+                        measure_calibration_indices[position] = index;
+                    } else {
+                        measure_calibration_indices.push(index);
+                    }
+                    calibrations.insert_measurement_calibration(calibration.clone());
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            replacements,
+            ..calibrations.get_diagnostics()
+        }
+    }
+
+    /// Every pair of calibrations which overlap, ordered by the positions of the earlier and then
+    /// the later calibration of each pair; gate calibrations precede measurement calibrations.
+    pub fn overlaps(&self) -> &[CalibrationOverlap] {
+        &self.overlaps
+    }
+
+    /// The calibrations which are never used because every instruction they match is matched by
+    /// another calibration with higher precedence, in the order in which they are first reported
+    /// by [`CalibrationDiagnostics::overlaps`].
+    pub fn shadowed(&self) -> Vec<&CalibrationSource> {
+        let mut shadowed: Vec<&CalibrationSource> = Vec::new();
+        for overlap in self
+            .overlaps
+            .iter()
+            .filter(|overlap| overlap.loser_shadowed)
+        {
+            if !shadowed.contains(&&overlap.loser) {
+                shadowed.push(&overlap.loser);
+            }
+        }
+        shadowed
+    }
+
+    /// The calibrations which were replaced by later calibrations with the same signature.
+    pub fn replacements(&self) -> &[CalibrationReplacement] {
+        &self.replacements
+    }
+}
+
+impl Calibrations {
+    /// Report every pair of calibrations which may both match the same instruction, which of each
+    /// pair takes precedence, and which calibrations are fully shadowed by another and so are never
+    /// used.
+    ///
+    /// Replaced calibrations are not reported, since they are no longer part of this set; see
+    /// [`CalibrationDiagnostics::from_instructions`].
+    pub fn get_diagnostics(&self) -> CalibrationDiagnostics {
+        let mut overlaps = self.get_gate_calibration_overlaps();
+        overlaps.extend(self.get_measure_calibration_overlaps());
+
+        CalibrationDiagnostics {
+            overlaps,
+            replacements: Vec::new(),
+        }
+    }
+
+    fn get_gate_calibration_overlaps(&self) -> Vec<CalibrationOverlap> {
+        let patterns = self
+            .iter_calibrations()
+            .map(GateCalibrationPattern::new)
+            .collect::<Vec<_>>();
+
+        // Only calibrations with the same name, modifiers, and arity can overlap.
+        let mut groups: HashMap<_, Vec<usize>> = HashMap::new();
+        for (position, calibration) in self.iter_calibrations().enumerate() {
+            let identifier = &calibration.identifier;
+            groups
+                .entry((
+                    identifier.name.as_str(),
+                    &identifier.modifiers,
+                    identifier.parameters.len(),
+                    identifier.qubits.len(),
+                ))
+                .or_default()
+                .push(position);
+        }
+        let mut overlaps = Vec::new();
+        for group in groups.into_values() {
+            for (i, &earlier) in group.iter().enumerate() {
+                for &later in &group[i + 1..] {
+                    let (earlier_pattern, later_pattern) = (&patterns[earlier], &patterns[later]);
+                    let Some(overlap) =
+                        earlier_pattern.intersect(later_pattern, self.parameter_matching)
+                    else {
+                        continue;
+                    };
+                    // Per `get_match_for_gate`, the calibration with the most fixed qubits wins,
+                    // and the later calibration wins a tie.
+                    let (winner, loser) = if earlier_pattern.fixed_qubit_count()
+                        > later_pattern.fixed_qubit_count()
+                    {
+                        (earlier_pattern, later_pattern)
+                    } else {
+                        (later_pattern, earlier_pattern)
+                    };
+                    let overlap = CalibrationOverlap {
+                        winner: winner.calibration.identifier.clone().into(),
+                        loser: loser.calibration.identifier.clone().into(),
+                        overlap: overlap.into(),
+                        loser_shadowed: winner.contains(loser),
+                    };
+                    overlaps.push(((earlier, later), overlap));
+                }
+            }
+        }
+        overlaps.sort_by_key(|(positions, _)| *positions);
+
+        overlaps.into_iter().map(|(_, overlap)| overlap).collect()
+    }
+
+    fn get_measure_calibration_overlaps(&self) -> Vec<CalibrationOverlap> {
+        let calibrations = self.measure_calibrations();
+
+        let mut overlaps = Vec::new();
+        for (i, earlier) in calibrations.iter().enumerate() {
+            for later in &calibrations[i + 1..] {
+                let Some(overlap) = intersect_measure_qubits(
+                    earlier.identifier.qubit.as_ref(),
+                    later.identifier.qubit.as_ref(),
+                ) else {
+                    continue;
+                };
+                // Per `get_match_for_measurement`, a fixed qubit takes precedence over a variable
+                // qubit, which takes precedence over no qubit, and the later calibration wins a tie.
+                let (winner, loser) =
+                    if measure_calibration_rank(earlier) > measure_calibration_rank(later) {
+                        (earlier, later)
+                    } else {
+                        (later, earlier)
+                    };
+                overlaps.push(CalibrationOverlap {
+                    winner: winner.identifier.clone().into(),
+                    loser: loser.identifier.clone().into(),
+                    overlap: MeasureCalibrationIdentifier::new(
+                        overlap.cloned(),
+                        loser.identifier.parameter.clone(),
+                    )
+                    .into(),
+                    // A variable qubit matches the same measurements as no qubit at all.
+                    loser_shadowed: !matches!(winner.identifier.qubit, Some(Qubit::Fixed(_)))
+                        || winner.identifier.qubit == loser.identifier.qubit,
+                });
+            }
+        }
+
+        overlaps
+    }
+}
+
+/// The precedence of a measurement calibration, as determined by the kind of its qubit.
+fn measure_calibration_rank(calibration: &MeasureCalibrationDefinition) -> u8 {
+    match calibration.identifier.qubit {
+        Some(Qubit::Fixed(_)) => 2,
+        Some(Qubit::Variable(_)) => 1,
+        Some(Qubit::Placeholder(_)) | None => 0,
+    }
+}
+
+/// Return the qubit of a measurement calibration which matches exactly the measurements which are
+/// matched by both of the given qubits, or `None` if there are no such measurements.
+fn intersect_measure_qubits<'a>(
+    a: Option<&'a Qubit>,
+    b: Option<&'a Qubit>,
+) -> Option<Option<&'a Qubit>> {
+    match (a, b) {
+        // Placeholders never match
+        (Some(Qubit::Placeholder(_)), _) | (_, Some(Qubit::Placeholder(_))) => None,
+        (Some(Qubit::Fixed(this)), Some(Qubit::Fixed(that))) if this != that => None,
+        (Some(Qubit::Fixed(_)), _) => Some(a),
+        (_, Some(Qubit::Fixed(_))) => Some(b),
+        (Some(Qubit::Variable(_)), _) => Some(a),
+        _ => Some(b),
+    }
+}
+
+/// The set of gates matched by a gate calibration, per [`CalibrationIdentifier::matches`].
+struct GateCalibrationPattern<'a> {
+    calibration: &'a Calibration,
+
+    /// The simplified parameters of the calibration, such that a parameter which is a variable
+    /// matches any expression and any other parameter matches only itself.
+    parameters: Vec<Expression>,
+}
+
+impl<'a> GateCalibrationPattern<'a> {
+    fn new(calibration: &'a Calibration) -> Self {
+        Self {
+            calibration,
+            parameters: calibration
+                .identifier
+                .parameters
+                .iter()
+                .map(|parameter| parameter.clone().into_simplified())
+                .collect(),
+        }
+    }
+
+    fn qubits(&self) -> &[Qubit] {
+        &self.calibration.identifier.qubits
+    }
+
+    fn fixed_qubit_count(&self) -> usize {
+        self.qubits()
+            .iter()
+            .filter(|qubit| matches!(qubit, Qubit::Fixed(_)))
+            .count()
+    }
+
+    /// Return an identifier which matches exactly the gates matched by both `self` and `other`, or
+    /// `None` if there are no such gates. Both must have the same name, modifiers, and arity.
+    ///
+    /// Where fixed parameters match only by `parameter_matching`, the parameter of `self` is used.
+    fn intersect(
+        &self,
+        other: &Self,
+        parameter_matching: CalibrationParameterMatching,
+    ) -> Option<CalibrationIdentifier> {
+        let qubits = self
+            .qubits()
+            .iter()
+            .zip(other.qubits())
+            .map(|pair| match pair {
+                // Placeholders never match
+                (Qubit::Placeholder(_), _) | (_, Qubit::Placeholder(_)) => None,
+                (Qubit::Fixed(this), Qubit::Fixed(that)) if this != that => None,
+                (Qubit::Fixed(_), _) => Some(pair.0.clone()),
+                (_, that) => Some(that.clone()),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        // Report the parameters as written, rather than in their simplified forms.
+        let parameters = self
+            .parameters
+            .iter()
+            .zip(&other.parameters)
+            .enumerate()
+            .map(|(index, pair)| match pair {
+                (Expression::Variable(_), _) => {
+                    Some(&other.calibration.identifier.parameters[index])
+                }
+                (_, Expression::Variable(_)) => {
+                    Some(&self.calibration.identifier.parameters[index])
+                }
+                (this, that) if parameter_matching.matches(this, that) => {
+                    Some(&self.calibration.identifier.parameters[index])
+                }
+                _ => None,
+            })
+            .map(|parameter| parameter.cloned())
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(CalibrationIdentifier {
+            modifiers: self.calibration.identifier.modifiers.clone(),
+            name: self.calibration.identifier.name.clone(),
+            parameters,
+            qubits,
+        })
+    }
+
+    /// Whether every gate matched by `other` is also matched by `self`, given that they overlap.
+    fn contains(&self, other: &Self) -> bool {
+        let qubits_contained = self.qubits().iter().zip(other.qubits()).all(|pair| {
+            matches!(
+                pair,
+                (Qubit::Variable(_), _) | (Qubit::Fixed(_), Qubit::Fixed(_))
+            )
+        });
+        let parameters_contained = self.parameters.iter().zip(&other.parameters).all(|pair| {
+            matches!(pair, (Expression::Variable(_), _))
+                || !matches!(pair.1, Expression::Variable(_))
+        });
+
+        qubits_contained && parameters_contained
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{program::CalibrationSource, quil::Quil, Program};
+
+    use super::CalibrationDiagnostics;
+
+    fn describe(source: &CalibrationSource) -> String {
+        match source {
+            CalibrationSource::Calibration(identifier) => identifier.to_quil_or_debug(),
+            CalibrationSource::MeasureCalibration(identifier) => identifier.to_quil_or_debug(),
+        }
+    }
+
+    #[test]
+    fn gate_calibration_overlaps() {
+        let program = Program::from_str(
+            r#"DEFCAL RX(pi) 0:
+    NOP
+DEFCAL RX(%theta) 0:
+    NOP
+DEFCAL RX(%theta) q:
+    NOP
+DEFCAL RX(pi/2) 1:
+    NOP
+DEFCAL CZ 0 1:
+    NOP
+"#,
+        )
+        .unwrap();
+        let diagnostics = program.calibrations.get_diagnostics();
+
+        let overlaps = diagnostics
+            .overlaps()
+            .iter()
+            .map(|overlap| {
+                (
+                    describe(&overlap.winner),
+                    describe(&overlap.loser),
+                    describe(&overlap.overlap),
+                    overlap.loser_shadowed,
+                )
+            })
+            .collect::<Vec<_>>();
+        let expected = [
+            (
+                "DEFCAL RX(%theta) 0",
+                "DEFCAL RX(pi) 0",
+                "DEFCAL RX(pi) 0",
+                true,
+            ),
+            (
+                "DEFCAL RX(pi) 0",
+                "DEFCAL RX(%theta) q",
+                "DEFCAL RX(pi) 0",
+                false,
+            ),
+            (
+                "DEFCAL RX(%theta) 0",
+                "DEFCAL RX(%theta) q",
+                "DEFCAL RX(%theta) 0",
+                false,
+            ),
+            (
+                "DEFCAL RX(pi/2) 1",
+                "DEFCAL RX(%theta) q",
+                "DEFCAL RX(pi/2) 1",
+                false,
+            ),
+        ]
+        .map(|(winner, loser, overlap, shadowed)| {
+            (
+                winner.to_string(),
+                loser.to_string(),
+                overlap.to_string(),
+                shadowed,
+            )
+        });
+        assert_eq!(overlaps, expected);
+
+        let shadowed = diagnostics
+            .shadowed()
+            .into_iter()
+            .map(describe)
+            .collect::<Vec<_>>();
+        assert_eq!(shadowed, vec!["DEFCAL RX(pi) 0"]);
+    }
+
+    #[test]
+    fn measure_calibration_overlaps() {
+        let program = Program::from_str(
+            r#"DEFCAL MEASURE addr:
+    NOP
+DEFCAL MEASURE q addr:
+    NOP
+DEFCAL MEASURE 0 addr:
+    NOP
+DEFCAL MEASURE 1 addr:
+    NOP
+"#,
+        )
+        .unwrap();
+        let diagnostics = program.calibrations.get_diagnostics();
+
+        // Every pair overlaps except for the two fixed qubits
+        assert_eq!(diagnostics.overlaps().len(), 5);
+        let shadowed = diagnostics
+            .shadowed()
+            .into_iter()
+            .map(describe)
+            .collect::<Vec<_>>();
+        assert_eq!(shadowed, vec!["DEFCAL MEASURE addr"]);
+    }
+
+    #[test]
+    fn replaced_calibrations() {
+        let first =
+            Program::from_str("DEFCAL X 0:\n    NOP\nDEFCAL MEASURE 0 addr:\n    NOP\n").unwrap();
+        let second = Program::from_str("DEFCAL X 0:\n    PRAGMA REPLACEMENT\n").unwrap();
+        let instructions = first
+            .calibrations
+            .to_instructions()
+            .into_iter()
+            .chain(second.calibrations.to_instructions())
+            .collect::<Vec<_>>();
+
+        let diagnostics = CalibrationDiagnostics::from_instructions(&instructions);
+        let replacements = diagnostics
+            .replacements()
+            .iter()
+            .map(|replacement| {
+                (
+                    describe(&replacement.calibration),
+                    replacement.replaced_index,
+                    replacement.replacement_index,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(replacements, vec![("DEFCAL X 0".to_string(), 0, 2)]);
+        assert!(diagnostics.overlaps().is_empty());
+    }
+}
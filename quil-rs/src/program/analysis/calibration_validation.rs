@@ -0,0 +1,533 @@
+//! Validating the frames, waveforms, and memory referenced by calibrations
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::{
+    instruction::{
+        Capture, Declaration, Delay, ExternSignatureMap, FrameIdentifier, Instruction, Pulse,
+        Qubit, RawCapture, SetFrequency, SetPhase, SetScale, ShiftFrequency, ShiftPhase,
+        SwapPhases, WaveformInvocation,
+    },
+    program::CalibrationSource,
+    quil::Quil,
+    waveform::builtin::{BuiltinWaveform, TemplateParameter},
+    Program,
+};
+
+/// A problem with a single instruction within the body of a calibration.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+#[error("{}, instruction {instruction_index}: {kind}", calibration_signature(.calibration))]
+pub struct CalibrationValidationError {
+    /// The calibration whose body contains the instruction
+    pub calibration: CalibrationSource,
+
+    /// The index of the instruction within the body of the calibration
+    pub instruction_index: usize,
+
+    /// The problem with the instruction
+    pub kind: CalibrationValidationErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum CalibrationValidationErrorKind {
+    #[error("frame {} is not defined", .0.to_quil_or_debug())]
+    UndefinedFrame(FrameIdentifier),
+
+    #[error("frame {} is not defined for any qubits on which the calibration may be used", .0.to_quil_or_debug())]
+    UnresolvableFrame(FrameIdentifier),
+
+    #[error("qubit variable {0} is not a qubit of the calibration")]
+    UndefinedQubitVariable(String),
+
+    #[error("waveform {0} is not defined")]
+    UndefinedWaveform(String),
+
+    #[error("waveform {waveform} is missing parameters: {}", parameters.join(", "))]
+    MissingWaveformParameters {
+        waveform: String,
+        parameters: Vec<String>,
+    },
+
+    #[error("waveform {waveform} has unexpected parameters: {}", parameters.join(", "))]
+    UnexpectedWaveformParameters {
+        waveform: String,
+        parameters: Vec<String>,
+    },
+
+    #[error("memory region {0} is not declared")]
+    UndeclaredMemory(String),
+}
+
+/// Every problem found by [`Program::validate_calibrations`].
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+#[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+pub struct CalibrationValidationErrors(pub Vec<CalibrationValidationError>);
+
+pub(super) fn calibration_signature(calibration: &CalibrationSource) -> String {
+    match calibration {
+        CalibrationSource::Calibration(identifier) => identifier.to_quil_or_debug(),
+        CalibrationSource::MeasureCalibration(identifier) => identifier.to_quil_or_debug(),
+    }
+}
+
+/// Describe the location of an instruction within the body of a calibration or, given `None`, of
+/// the program.
+pub(super) fn instruction_location(
+    calibration: Option<&CalibrationSource>,
+    instruction_index: usize,
+) -> String {
+    match calibration {
+        Some(calibration) => format!(
+            "{}, instruction {instruction_index}",
+            calibration_signature(calibration)
+        ),
+        None => format!("instruction {instruction_index}"),
+    }
+}
+
+/// An assignment of fixed qubit indices to the qubit variables of a calibration.
+type QubitAssignment<'a> = BTreeMap<&'a str, u64>;
+
+impl Program {
+    /// Check that the body of every `DEFCAL` and `DEFCAL MEASURE` refers only to frames, waveforms,
+    /// and memory regions defined by this program, without expanding any instruction.
+    ///
+    /// A calibration with variable qubits is instantiated on each assignment of qubits to its
+    /// variables for which the frames it references are defined. A frame reference which cannot be
+    /// satisfied by any of these assignments is reported as unresolvable, without affecting the
+    /// assignments considered for later references. Invocations of waveforms defined by
+    /// `DEFWAVEFORM` must give exactly the waveform's parameters; invocations of built-in templates
+    /// must give each of the template's required parameters and no unknown ones.
+    ///
+    /// Memory regions may be declared either by the program or within the calibration, and the
+    /// memory reference parameter of a `DEFCAL MEASURE` is always considered declared.
+    pub fn validate_calibrations(&self) -> Result<(), CalibrationValidationErrors> {
+        let extern_signature_map = self
+            .try_extern_signature_map_from_pragma_map()
+            .unwrap_or_default();
+        let mut errors = Vec::new();
+
+        for calibration in self.calibrations.iter_calibrations() {
+            let validator = CalibrationValidator {
+                program: self,
+                extern_signature_map: &extern_signature_map,
+                calibration: CalibrationSource::Calibration(calibration.identifier.clone()),
+                qubits: &calibration.identifier.qubits,
+                memory_parameter: None,
+            };
+            validator.validate(&calibration.instructions, &mut errors);
+        }
+        for calibration in self.calibrations.iter_measure_calibrations() {
+            let validator = CalibrationValidator {
+                program: self,
+                extern_signature_map: &extern_signature_map,
+                calibration: CalibrationSource::MeasureCalibration(calibration.identifier.clone()),
+                qubits: calibration
+                    .identifier
+                    .qubit
+                    .as_ref()
+                    .map_or(&[][..], std::slice::from_ref),
+                memory_parameter: Some(&calibration.identifier.parameter),
+            };
+            validator.validate(&calibration.instructions, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(CalibrationValidationErrors(errors))
+        }
+    }
+}
+
+struct CalibrationValidator<'p> {
+    program: &'p Program,
+    extern_signature_map: &'p ExternSignatureMap,
+    calibration: CalibrationSource,
+    qubits: &'p [Qubit],
+    memory_parameter: Option<&'p String>,
+}
+
+impl<'p> CalibrationValidator<'p> {
+    fn validate(
+        &self,
+        instructions: &'p [Instruction],
+        errors: &mut Vec<CalibrationValidationError>,
+    ) {
+        let variables = self
+            .qubits
+            .iter()
+            .filter_map(|qubit| match qubit {
+                Qubit::Variable(name) => Some(name.as_str()),
+                Qubit::Fixed(_) | Qubit::Placeholder(_) => None,
+            })
+            .collect::<HashSet<_>>();
+        let calibration_fixed_qubits = self
+            .qubits
+            .iter()
+            .filter_map(|qubit| match qubit {
+                Qubit::Fixed(index) => Some(*index),
+                Qubit::Placeholder(_) | Qubit::Variable(_) => None,
+            })
+            .collect::<HashSet<_>>();
+        let mut declared = self
+            .program
+            .memory_regions
+            .keys()
+            .map(String::as_str)
+            .chain(self.memory_parameter.map(String::as_str))
+            .collect::<HashSet<_>>();
+        declared.extend(
+            instructions
+                .iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::Declaration(Declaration { name, .. }) => Some(name.as_str()),
+                    _ => None,
+                }),
+        );
+
+        let mut assignments = BTreeSet::from([QubitAssignment::new()]);
+        let mut push_error = |instruction_index, kind| {
+            errors.push(CalibrationValidationError {
+                calibration: self.calibration.clone(),
+                instruction_index,
+                kind,
+            })
+        };
+
+        for (instruction_index, instruction) in instructions.iter().enumerate() {
+            for frame in frame_references(instruction) {
+                let undefined_variable = frame.qubits.iter().find_map(|qubit| match qubit {
+                    Qubit::Variable(name) if !variables.contains(name.as_str()) => Some(name),
+                    _ => None,
+                });
+                if let Some(name) = undefined_variable {
+                    push_error(
+                        instruction_index,
+                        CalibrationValidationErrorKind::UndefinedQubitVariable(name.clone()),
+                    );
+                    continue;
+                }
+
+                if frame
+                    .qubits
+                    .iter()
+                    .any(|qubit| matches!(qubit, Qubit::Placeholder(_)))
+                {
+                    continue;
+                }
+
+                if frame
+                    .qubits
+                    .iter()
+                    .all(|qubit| matches!(qubit, Qubit::Fixed(_)))
+                {
+                    if self.program.frames.get(&frame).is_none() {
+                        push_error(
+                            instruction_index,
+                            CalibrationValidationErrorKind::UndefinedFrame(frame),
+                        );
+                    }
+                    continue;
+                }
+
+                let instantiated = instantiate(
+                    &frame,
+                    &assignments,
+                    &variables,
+                    &calibration_fixed_qubits,
+                    self.program,
+                );
+                if instantiated.is_empty() {
+                    push_error(
+                        instruction_index,
+                        CalibrationValidationErrorKind::UnresolvableFrame(frame),
+                    );
+                } else {
+                    assignments = instantiated;
+                }
+            }
+
+            if let Some(waveform) = waveform_reference(instruction) {
+                if let Some(kind) = self.validate_waveform(waveform) {
+                    push_error(instruction_index, kind);
+                }
+            }
+
+            // Failures to determine memory accesses, such as those of an invalid `CALL`, are the
+            // concern of type checking rather than of this validation.
+            if let Ok(accesses) = instruction.get_memory_accesses(self.extern_signature_map) {
+                let undeclared = accesses
+                    .reads
+                    .iter()
+                    .chain(&accesses.writes)
+                    .chain(&accesses.captures)
+                    .filter(|name| !declared.contains(name.as_str()))
+                    .collect::<BTreeSet<_>>();
+                for name in undeclared {
+                    push_error(
+                        instruction_index,
+                        CalibrationValidationErrorKind::UndeclaredMemory(name.clone()),
+                    );
+                }
+            }
+        }
+    }
+
+    fn validate_waveform(
+        &self,
+        waveform: &WaveformInvocation,
+    ) -> Option<CalibrationValidationErrorKind> {
+        let given = waveform
+            .parameters
+            .keys()
+            .map(String::as_str)
+            .collect::<BTreeSet<_>>();
+        let (required, optional) = match self.program.waveforms.get(&waveform.name) {
+            Some(definition) => (
+                definition
+                    .parameters
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<BTreeSet<_>>(),
+                BTreeSet::new(),
+            ),
+            None => {
+                let Some(template) = BuiltinWaveform::from_name(&waveform.name) else {
+                    return Some(CalibrationValidationErrorKind::UndefinedWaveform(
+                        waveform.name.clone(),
+                    ));
+                };
+                let (required, optional): (Vec<&TemplateParameter>, Vec<_>) = template
+                    .parameters()
+                    .partition(|parameter| parameter.required);
+                (
+                    required
+                        .into_iter()
+                        .map(|parameter| parameter.name)
+                        .collect(),
+                    optional
+                        .into_iter()
+                        .map(|parameter| parameter.name)
+                        .collect(),
+                )
+            }
+        };
+
+        let missing = required.difference(&given).collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Some(CalibrationValidationErrorKind::MissingWaveformParameters {
+                waveform: waveform.name.clone(),
+                parameters: missing.into_iter().map(ToString::to_string).collect(),
+            });
+        }
+        let unexpected = given
+            .iter()
+            .filter(|name| !required.contains(*name) && !optional.contains(*name))
+            .collect::<Vec<_>>();
+        if !unexpected.is_empty() {
+            return Some(
+                CalibrationValidationErrorKind::UnexpectedWaveformParameters {
+                    waveform: waveform.name.clone(),
+                    parameters: unexpected.into_iter().map(ToString::to_string).collect(),
+                },
+            );
+        }
+
+        None
+    }
+}
+
+/// Extend each of the given assignments with each way of binding the variable qubits of `frame` to
+/// those of a frame defined by `program`, such that every variable is bound to a distinct qubit
+/// which is not one of the fixed qubits of the calibration.
+fn instantiate<'a>(
+    frame: &FrameIdentifier,
+    assignments: &BTreeSet<QubitAssignment<'a>>,
+    variables: &HashSet<&'a str>,
+    calibration_fixed_qubits: &HashSet<u64>,
+    program: &Program,
+) -> BTreeSet<QubitAssignment<'a>> {
+    let candidates = program
+        .frames
+        .get_keys()
+        .into_iter()
+        .filter(|candidate| {
+            candidate.name == frame.name && candidate.qubits.len() == frame.qubits.len()
+        })
+        .filter_map(|candidate| {
+            candidate
+                .qubits
+                .iter()
+                .map(|qubit| match qubit {
+                    Qubit::Fixed(index) => Some(*index),
+                    Qubit::Placeholder(_) | Qubit::Variable(_) => None,
+                })
+                .collect::<Option<Vec<_>>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut instantiated = BTreeSet::new();
+    for assignment in assignments {
+        'candidates: for candidate in &candidates {
+            let mut assignment = assignment.clone();
+            for (qubit, index) in frame.qubits.iter().zip(candidate) {
+                match qubit {
+                    Qubit::Fixed(fixed) if fixed == index => {}
+                    Qubit::Variable(name) => match assignment.get(name.as_str()) {
+                        Some(bound) if bound == index => {}
+                        Some(_) => continue 'candidates,
+                        None => {
+                            let Some(variable) = variables.get(name.as_str()) else {
+                                continue 'candidates;
+                            };
+                            if calibration_fixed_qubits.contains(index)
+                                || assignment.values().any(|bound| bound == index)
+                            {
+                                continue 'candidates;
+                            }
+                            assignment.insert(*variable, *index);
+                        }
+                    },
+                    _ => continue 'candidates,
+                }
+            }
+            instantiated.insert(assignment);
+        }
+    }
+
+    instantiated
+}
+
+/// Return the frames to which an instruction refers explicitly.
+fn frame_references(instruction: &Instruction) -> Vec<FrameIdentifier> {
+    match instruction {
+        Instruction::Capture(Capture { frame, .. })
+        | Instruction::Pulse(Pulse { frame, .. })
+        | Instruction::RawCapture(RawCapture { frame, .. })
+        | Instruction::SetFrequency(SetFrequency { frame, .. })
+        | Instruction::SetPhase(SetPhase { frame, .. })
+        | Instruction::SetScale(SetScale { frame, .. })
+        | Instruction::ShiftFrequency(ShiftFrequency { frame, .. })
+        | Instruction::ShiftPhase(ShiftPhase { frame, .. }) => vec![frame.clone()],
+        Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => {
+            vec![frame_1.clone(), frame_2.clone()]
+        }
+        Instruction::Delay(Delay {
+            frame_names,
+            qubits,
+            ..
+        }) => frame_names
+            .iter()
+            .map(|name| FrameIdentifier::new(name.clone(), qubits.clone()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Return the waveform invoked by an instruction, if any.
+fn waveform_reference(instruction: &Instruction) -> Option<&WaveformInvocation> {
+    match instruction {
+        Instruction::Capture(Capture { waveform, .. })
+        | Instruction::Pulse(Pulse { waveform, .. }) => Some(waveform),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    const FRAMES: &str = r#"DECLARE ro BIT
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 0 1 "cz":
+    SAMPLE-RATE: 1e9
+DEFWAVEFORM custom(%a):
+    %a, %a
+"#;
+
+    #[test]
+    fn accepts_valid_calibrations() {
+        let program = Program::from_str(&format!(
+            r#"{FRAMES}
+DEFCAL X 0:
+    PULSE 0 "rf" custom(a: 0.5)
+DEFCAL RX(%theta) q:
+    PULSE q "rf" gaussian(duration: 1e-6, fwhm: 1e-7, t0: 5e-7, scale: %theta)
+DEFCAL CZ q0 q1:
+    PULSE q0 q1 "cz" flat(duration: 1e-6, iq: 1.0)
+    SHIFT-PHASE q0 "rf" 1.0
+    DELAY q1 "rf" 1e-6
+DEFCAL MEASURE 0 addr:
+    DECLARE scratch REAL
+    CAPTURE 0 "rf" boxcar_kernel(duration: 1e-6) addr
+    MOVE scratch ro
+"#
+        ))
+        .unwrap();
+
+        assert_eq!(program.validate_calibrations(), Ok(()));
+    }
+
+    #[test]
+    fn reports_errors_with_calibration_signature() {
+        let program = Program::from_str(&format!(
+            r#"{FRAMES}
+DEFCAL X 0:
+    PULSE 0 "missing" custom(a: 0.5)
+    PULSE 0 "rf" custom(b: 0.5)
+    PULSE 0 "rf" unknown
+DEFCAL CZ q0 q1:
+    PULSE q1 q0 "cz" flat(duration: 1e-6, iq: 1.0)
+    SHIFT-PHASE q0 "rf" 1.0
+    SHIFT-PHASE q2 "rf" 1.0
+DEFCAL CZ 0 q:
+    PULSE q "cz" flat(duration: 1e-6)
+DEFCAL MEASURE q addr:
+    CAPTURE q "rf" gaussian(duration: 1e-6, fwhm: 1e-7, t0: 0, iq: 1.0) addr
+    MOVE undeclared[0] 1
+"#
+        ))
+        .unwrap();
+
+        let errors = program
+            .validate_calibrations()
+            .unwrap_err()
+            .0
+            .into_iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                r#"DEFCAL X 0, instruction 0: frame 0 "missing" is not defined"#,
+                "DEFCAL X 0, instruction 1: waveform custom is missing parameters: a",
+                "DEFCAL X 0, instruction 2: waveform unknown is not defined",
+                r#"DEFCAL CZ q0 q1, instruction 2: qubit variable q2 is not a qubit of the calibration"#,
+                r#"DEFCAL CZ 0 q, instruction 0: frame q "cz" is not defined for any qubits on which the calibration may be used"#,
+                "DEFCAL CZ 0 q, instruction 0: waveform flat is missing parameters: iq",
+                "DEFCAL MEASURE q addr, instruction 0: waveform gaussian has unexpected parameters: iq",
+                "DEFCAL MEASURE q addr, instruction 1: memory region undeclared is not declared",
+            ]
+        );
+    }
+}
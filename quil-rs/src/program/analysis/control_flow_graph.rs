@@ -49,6 +49,11 @@ impl<'p> ControlFlowGraph<'p> {
             .any(|block| block.terminator().is_dynamic())
     }
 
+    /// Returns the basic blocks in the control flow graph.
+    pub fn blocks(&self) -> &[BasicBlock<'p>] {
+        &self.blocks
+    }
+
     /// Returns the basic blocks in the control flow graph.
     pub fn into_blocks(self) -> Vec<BasicBlock<'p>> {
         self.blocks
@@ -126,7 +131,7 @@ impl<'p> BasicBlock<'p> {
         &self,
         program: &Program,
     ) -> Result<Schedule<Seconds>, BasicBlockScheduleError> {
-        self.as_schedule(
+        self.try_as_schedule(
             program,
             ScheduledBasicBlock::get_instruction_duration_seconds,
         )
@@ -184,6 +189,27 @@ impl<'p> BasicBlock<'p> {
             + std::ops::Add<Time, Output = Time>
             + std::ops::Sub<Time, Output = Time>
             + Zero,
+    {
+        self.try_as_schedule(program, |program, instruction| {
+            Ok(get_duration(program, instruction))
+        })
+    }
+
+    /// Compute the schedule for this [`BasicBlock`] as in [`BasicBlock::as_schedule`], using a
+    /// function which may fail to calculate the duration of an instruction.
+    pub(crate) fn try_as_schedule<F, Time>(
+        &self,
+        program: &'p Program,
+        get_duration: F,
+    ) -> Result<Schedule<Time>, BasicBlockScheduleError>
+    where
+        F: Fn(&Program, &Instruction) -> Result<Option<Time>, ComputedScheduleError>,
+        Time: Clone
+            + Debug
+            + PartialOrd
+            + std::ops::Add<Time, Output = Time>
+            + std::ops::Sub<Time, Output = Time>
+            + Zero,
     {
         // 1: expand calibrations and track the source mapping
         let mut calibrated_to_uncalibrated_instruction_source_mapping = BTreeMap::new();
@@ -213,7 +239,7 @@ impl<'p> BasicBlock<'p> {
         let mut instruction_handler = InstructionHandler::default();
         let scheduled_self =
             ScheduledBasicBlock::build(calibrated_block, program, &mut instruction_handler)?;
-        let schedule = scheduled_self.as_schedule(program, get_duration)?;
+        let schedule = scheduled_self.try_as_schedule(program, get_duration)?;
 
         // 3: map that schedule back to the original instructions from this basic block using the source mapping
         let uncalibrated_schedule_items_by_instruction_index = schedule
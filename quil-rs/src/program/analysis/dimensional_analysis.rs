@@ -0,0 +1,625 @@
+//! Inferring the units of the operands of Quil-T instructions, and reporting their misuse
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, f64::consts::TAU, fmt};
+
+use crate::{
+    expression::{
+        BinaryExpressionFunction, BinaryFunctionCallExpression, Expression, ExpressionFunction,
+        FunctionCallExpression, InfixExpression, InfixOperator, PrefixExpression,
+    },
+    instruction::{
+        Capture, Delay, Instruction, Pulse, RawCapture, SetFrequency, SetPhase, SetScale,
+        ShiftFrequency, ShiftPhase, WaveformInvocation,
+    },
+    program::CalibrationSource,
+    units::{Cycles, Radians, Unit},
+    waveform::builtin::BuiltinWaveform,
+    Program,
+};
+
+use super::calibration_validation::instruction_location;
+
+/// Durations longer than this, in seconds, are more likely to have been given in another unit, such
+/// as nanoseconds.
+const MAXIMUM_PLAUSIBLE_SECONDS: f64 = 1.0;
+
+/// Non-zero frequencies lower than this, in Hz, are more likely to have been given in another unit,
+/// such as GHz.
+const MINIMUM_PLAUSIBLE_HERTZ: f64 = 1e3;
+
+/// A memory region or variable whose unit is inferred from its uses.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UnitSymbol {
+    MemoryRegion(String),
+    Variable(String),
+}
+
+impl fmt::Display for UnitSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MemoryRegion(name) => write!(f, "memory region {name}"),
+            Self::Variable(name) => write!(f, "variable %{name}"),
+        }
+    }
+}
+
+/// A likely misuse of units by a single instruction.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+#[error("{}: {kind}", instruction_location(.calibration.as_ref(), *.instruction_index))]
+pub struct UnitDiagnostic {
+    /// The calibration whose body contains the instruction, or `None` for the body of the program
+    pub calibration: Option<CalibrationSource>,
+
+    /// The index of the instruction within its body
+    pub instruction_index: usize,
+
+    /// The misuse of units
+    pub kind: UnitDiagnosticKind,
+}
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum UnitDiagnosticKind {
+    #[error("{operand} of {value} {unit} is implausible; it may have been given in another unit")]
+    SuspiciousMagnitude {
+        operand: String,
+        value: f64,
+        unit: Unit,
+    },
+
+    #[error("{symbol} is used in {found}, but was previously used in {expected}")]
+    UnitMismatch {
+        symbol: UnitSymbol,
+        expected: Unit,
+        found: Unit,
+    },
+}
+
+impl Program {
+    /// Infer the units of the memory regions and calibration variables used by the operands of
+    /// Quil-T instructions, and report each instruction which likely misuses them.
+    ///
+    /// Units are those of `DELAY` (seconds), `SET-FREQUENCY` and `SHIFT-FREQUENCY` (Hz),
+    /// `SET-PHASE` and `SHIFT-PHASE` (radians), `SET-SCALE` (dimensionless), and the parameters of
+    /// the built-in waveform templates. Each memory region or variable takes the unit of its first
+    /// use, and any later use in an incompatible unit is reported. Literal numbers take whichever
+    /// unit their use requires, and act as dimensionless factors within products.
+    ///
+    /// Constant operands are also reported if their magnitude is implausible for their unit: a
+    /// duration longer than a second, a non-zero frequency below a kHz, or an angle of more than a
+    /// full turn.
+    ///
+    /// Memory regions are shared by the whole program, in the order: the body of the program, then
+    /// each calibration, then each measurement calibration. Variables are local to their
+    /// calibration.
+    pub fn check_units(&self) -> Vec<UnitDiagnostic> {
+        let mut checker = UnitChecker {
+            program: self,
+            memory_regions: HashMap::new(),
+            variables: HashMap::new(),
+            calibration: None,
+            instruction_index: 0,
+            diagnostics: Vec::new(),
+        };
+
+        checker.check(None, self.body_instructions());
+        for calibration in self.calibrations.iter_calibrations() {
+            checker.check(
+                Some(CalibrationSource::Calibration(
+                    calibration.identifier.clone(),
+                )),
+                &calibration.instructions,
+            );
+        }
+        for calibration in self.calibrations.iter_measure_calibrations() {
+            checker.check(
+                Some(CalibrationSource::MeasureCalibration(
+                    calibration.identifier.clone(),
+                )),
+                &calibration.instructions,
+            );
+        }
+        checker.diagnostics
+    }
+}
+
+struct UnitChecker<'p> {
+    program: &'p Program,
+
+    /// The unit of each memory region, as of its first use
+    memory_regions: HashMap<String, Unit>,
+
+    /// The unit of each variable of the current calibration, as of its first use
+    variables: HashMap<String, Unit>,
+
+    /// The location of the instruction being checked
+    calibration: Option<CalibrationSource>,
+    instruction_index: usize,
+
+    diagnostics: Vec<UnitDiagnostic>,
+}
+
+impl UnitChecker<'_> {
+    fn check<'i>(
+        &mut self,
+        calibration: Option<CalibrationSource>,
+        instructions: impl IntoIterator<Item = &'i Instruction>,
+    ) {
+        self.calibration = calibration;
+        self.variables.clear();
+        for (instruction_index, instruction) in instructions.into_iter().enumerate() {
+            self.instruction_index = instruction_index;
+            self.check_instruction(instruction);
+        }
+    }
+
+    fn check_instruction(&mut self, instruction: &Instruction) {
+        match instruction {
+            Instruction::Delay(Delay { duration, .. })
+            | Instruction::RawCapture(RawCapture { duration, .. }) => {
+                self.check_operand("duration", duration, Unit::Seconds)
+            }
+            Instruction::SetFrequency(SetFrequency { frequency, .. })
+            | Instruction::ShiftFrequency(ShiftFrequency { frequency, .. }) => {
+                self.check_operand("frequency", frequency, Unit::Hertz)
+            }
+            Instruction::SetPhase(SetPhase { phase, .. })
+            | Instruction::ShiftPhase(ShiftPhase { phase, .. }) => {
+                self.check_operand("phase", phase, Unit::Radians)
+            }
+            Instruction::SetScale(SetScale { scale, .. }) => {
+                self.check_operand("scale", scale, Unit::Dimensionless)
+            }
+            Instruction::Capture(Capture { waveform, .. })
+            | Instruction::Pulse(Pulse { waveform, .. }) => self.check_waveform(waveform),
+            _ => {}
+        }
+    }
+
+    /// Check the parameters of an invocation of a built-in waveform template; those of waveforms
+    /// defined by the program have no known units.
+    fn check_waveform(&mut self, waveform: &WaveformInvocation) {
+        if self.program.waveforms.contains_key(&waveform.name) {
+            return;
+        }
+        let Some(template) = BuiltinWaveform::from_name(&waveform.name) else {
+            return;
+        };
+        for (parameter, value) in &waveform.parameters {
+            if let Some(template_parameter) = template.parameter(parameter) {
+                let operand = format!("{} parameter {parameter}", waveform.name);
+                self.check_operand(&operand, value, template_parameter.unit);
+            }
+        }
+    }
+
+    fn check_operand(&mut self, operand: &str, expression: &Expression, unit: Unit) {
+        if let Ok(value) = expression.clone().into_simplified().to_real() {
+            let implausible = match unit {
+                Unit::Seconds => value.abs() > MAXIMUM_PLAUSIBLE_SECONDS,
+                Unit::Hertz => value != 0.0 && value.abs() < MINIMUM_PLAUSIBLE_HERTZ,
+                Unit::Radians => value.abs() > TAU,
+                Unit::Cycles => Radians::from(Cycles(value)).0.abs() > TAU,
+                Unit::Dimensionless | Unit::SecondsPower(_) => false,
+            };
+            if implausible {
+                self.report(UnitDiagnosticKind::SuspiciousMagnitude {
+                    operand: operand.to_string(),
+                    value,
+                    unit,
+                });
+            }
+        }
+        self.constrain(expression, unit);
+    }
+
+    fn report(&mut self, kind: UnitDiagnosticKind) {
+        self.diagnostics.push(UnitDiagnostic {
+            calibration: self.calibration.clone(),
+            instruction_index: self.instruction_index,
+            kind,
+        });
+    }
+
+    /// Record that `expression` is used in `unit`, inferring the units of the symbols within it.
+    fn constrain(&mut self, expression: &Expression, unit: Unit) {
+        use Expression::*;
+
+        match expression {
+            Address(reference) => {
+                self.record(UnitSymbol::MemoryRegion(reference.name.clone()), unit)
+            }
+            Variable(name) => self.record(UnitSymbol::Variable(name.clone()), unit),
+            Number(_) | PiConstant => {}
+            Prefix(PrefixExpression { expression, .. }) => self.constrain(expression, unit),
+            Infix(InfixExpression {
+                left,
+                operator,
+                right,
+            }) => match operator {
+                InfixOperator::Plus | InfixOperator::Minus => {
+                    self.constrain(left, unit);
+                    self.constrain(right, unit);
+                }
+                // Each side of a product or quotient is constrained by the unit of the other, if
+                // that is known.
+                InfixOperator::Star => {
+                    let (left_unit, right_unit) = (self.infer(left), self.infer(right));
+                    if let Some(right_unit) = right_unit {
+                        self.constrain(left, divide(unit, right_unit));
+                    }
+                    if let Some(left_unit) = left_unit {
+                        self.constrain(right, divide(unit, left_unit));
+                    }
+                }
+                InfixOperator::Slash => {
+                    let (left_unit, right_unit) = (self.infer(left), self.infer(right));
+                    if let Some(right_unit) = right_unit {
+                        self.constrain(left, multiply(unit, right_unit));
+                    }
+                    if let Some(left_unit) = left_unit {
+                        self.constrain(right, divide(left_unit, unit));
+                    }
+                }
+                InfixOperator::Caret => {
+                    self.constrain(right, Unit::Dimensionless);
+                    if let Some(exponent) = integer_exponent(right) {
+                        if exponent != 0 && unit.time_exponent() % exponent == 0 {
+                            self.constrain(
+                                left,
+                                Unit::from_time_exponent(unit.time_exponent() / exponent),
+                            );
+                        }
+                    }
+                }
+            },
+            FunctionCall(FunctionCallExpression {
+                function,
+                expression,
+            }) => {
+                use ExpressionFunction::*;
+                match function {
+                    AbsoluteValue | Ceiling | Conjugate | Floor | ImaginaryPart | RealPart => {
+                        self.constrain(expression, unit)
+                    }
+                    SquareRoot => self.constrain(
+                        expression,
+                        Unit::from_time_exponent(2 * unit.time_exponent()),
+                    ),
+                    // The result of a transcendental function is a pure number, which may be used in
+                    // any unit like a literal, but its argument must be dimensionless.
+                    ArcCosine | ArcSine | ArcTangent | Cis | Cosine | Exponent | Logarithm
+                    | Sine | Tangent => self.constrain(expression, Unit::Dimensionless),
+                }
+            }
+            BinaryFunctionCall(BinaryFunctionCallExpression {
+                function,
+                left,
+                right,
+            }) => match function {
+                BinaryExpressionFunction::Maximum | BinaryExpressionFunction::Minimum => {
+                    self.constrain(left, unit);
+                    self.constrain(right, unit);
+                }
+                // The arguments of `atan2` may have any unit, so long as it is the same.
+                BinaryExpressionFunction::ArcTangent2 => {
+                    if let Some(left_unit) = self.infer(left) {
+                        self.constrain(right, left_unit);
+                    } else if let Some(right_unit) = self.infer(right) {
+                        self.constrain(left, right_unit);
+                    }
+                }
+            },
+        }
+    }
+
+    /// The unit of `expression`, if it can be inferred from the units of the symbols within it.
+    fn infer(&self, expression: &Expression) -> Option<Unit> {
+        use Expression::*;
+
+        match expression {
+            Address(reference) => self.memory_regions.get(&reference.name).copied(),
+            Variable(name) => self.variables.get(name).copied(),
+            Number(_) | PiConstant => Some(Unit::Dimensionless),
+            Prefix(PrefixExpression { expression, .. }) => self.infer(expression),
+            Infix(InfixExpression {
+                left,
+                operator,
+                right,
+            }) => match operator {
+                InfixOperator::Plus | InfixOperator::Minus => {
+                    self.infer(left).or_else(|| self.infer(right))
+                }
+                InfixOperator::Star => Some(multiply(self.infer(left)?, self.infer(right)?)),
+                InfixOperator::Slash => Some(divide(self.infer(left)?, self.infer(right)?)),
+                InfixOperator::Caret => {
+                    let base = self.infer(left)?;
+                    Some(Unit::from_time_exponent(
+                        base.time_exponent() * integer_exponent(right)?,
+                    ))
+                }
+            },
+            FunctionCall(FunctionCallExpression {
+                function,
+                expression,
+            }) => {
+                use ExpressionFunction::*;
+                match function {
+                    AbsoluteValue | Ceiling | Conjugate | Floor | ImaginaryPart | RealPart => {
+                        self.infer(expression)
+                    }
+                    SquareRoot => {
+                        let exponent = self.infer(expression)?.time_exponent();
+                        (exponent % 2 == 0).then(|| Unit::from_time_exponent(exponent / 2))
+                    }
+                    ArcCosine | ArcSine | ArcTangent | Cis | Cosine | Exponent | Logarithm
+                    | Sine | Tangent => Some(Unit::Dimensionless),
+                }
+            }
+            BinaryFunctionCall(BinaryFunctionCallExpression {
+                function,
+                left,
+                right,
+            }) => match function {
+                BinaryExpressionFunction::Maximum | BinaryExpressionFunction::Minimum => {
+                    self.infer(left).or_else(|| self.infer(right))
+                }
+                BinaryExpressionFunction::ArcTangent2 => Some(Unit::Dimensionless),
+            },
+        }
+    }
+
+    /// Record a use of `symbol` in `unit`, reporting it if incompatible with the unit of its first
+    /// use.
+    fn record(&mut self, symbol: UnitSymbol, unit: Unit) {
+        let units = match &symbol {
+            UnitSymbol::MemoryRegion(name) => self.memory_regions.entry(name.clone()),
+            UnitSymbol::Variable(name) => self.variables.entry(name.clone()),
+        };
+        let expected = *units.or_insert(unit);
+        if !expected.is_compatible_with(unit) {
+            self.report(UnitDiagnosticKind::UnitMismatch {
+                symbol,
+                expected,
+                found: unit,
+            });
+        } else if expected == Unit::Dimensionless {
+            // A dimensionless symbol may later be found to be an angle of a particular unit.
+            match symbol {
+                UnitSymbol::MemoryRegion(name) => self.memory_regions.insert(name, unit),
+                UnitSymbol::Variable(name) => self.variables.insert(name, unit),
+            };
+        }
+    }
+}
+
+/// The product of two units. Angles multiplied by anything are dimensionless, since their unit is
+/// then unknown.
+fn multiply(left: Unit, right: Unit) -> Unit {
+    Unit::from_time_exponent(left.time_exponent() + right.time_exponent())
+}
+
+/// The quotient of two units. As for [`multiply`], angles become dimensionless.
+fn divide(left: Unit, right: Unit) -> Unit {
+    Unit::from_time_exponent(left.time_exponent() - right.time_exponent())
+}
+
+/// The value of a constant integer exponent, if `expression` is one.
+fn integer_exponent(expression: &Expression) -> Option<i32> {
+    let value = expression.clone().into_simplified().to_real().ok()?;
+    (value.fract() == 0.0 && value.abs() <= i32::MAX as f64).then_some(value as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+
+    use crate::{
+        expression::Expression,
+        instruction::{CalibrationIdentifier, Qubit},
+        program::CalibrationSource,
+        units::Unit,
+        Program,
+    };
+
+    use super::{UnitDiagnostic, UnitDiagnosticKind, UnitSymbol};
+
+    const HEADER: &str = r#"DECLARE t REAL
+DECLARE f REAL
+DECLARE phi REAL
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+"#;
+
+    #[rstest]
+    #[case(r#"DELAY 0 "rf" 1e-6"#)]
+    #[case(
+        r#"DELAY 0 "rf" t[0]
+DELAY 0 "rf" 2 * t[0] + 1e-8"#
+    )]
+    #[case(
+        r#"SET-FREQUENCY 0 "rf" f[0]
+DELAY 0 "rf" t[0]
+SHIFT-PHASE 0 "rf" 2 * pi * f[0] * t[0]"#
+    )]
+    #[case(
+        r#"DELAY 0 "rf" t[0]
+SHIFT-FREQUENCY 0 "rf" 1 / t[0]"#
+    )]
+    #[case(
+        r#"SHIFT-PHASE 0 "rf" phi[0]
+PULSE 0 "rf" flat(duration: 1e-6, iq: 1, phase: phi[0] / (2 * pi))"#
+    )]
+    #[case(
+        r#"PULSE 0 "rf" gaussian(duration: t[0], fwhm: t[0] / 4, t0: t[0] / 2, detuning: f[0])
+SET-FREQUENCY 0 "rf" 5e9 + f[0]"#
+    )]
+    #[case(r#"SET-SCALE 0 "rf" cos(phi[0]) ^ 2"#)]
+    #[case(r#"DELAY 0 "rf" sqrt(t[0] * t[0])"#)]
+    #[case(
+        r#"DEFWAVEFORM custom(%duration):
+    %duration, %duration
+PULSE 0 "rf" custom(duration: 100)"#
+    )]
+    #[case(r#"PULSE 0 "rf" gaussian(duration: 1e-6, fwhm: 1e-7, t0: 5e-7, risetime: 100)"#)]
+    #[case(
+        r#"DEFCAL RX(%theta) 0:
+    SHIFT-PHASE 0 "rf" %theta
+DEFCAL RZ(%theta) 0:
+    DELAY 0 "rf" %theta"#
+    )]
+    fn accepts_consistent_units(#[case] instructions: &str) {
+        let program = Program::from_str(&format!("{HEADER}{instructions}")).unwrap();
+        assert_eq!(program.check_units(), vec![]);
+    }
+
+    #[rstest]
+    #[case(
+        r#"DELAY 0 "rf" 100"#,
+        UnitDiagnosticKind::SuspiciousMagnitude {
+            operand: "duration".to_string(),
+            value: 100.0,
+            unit: Unit::Seconds,
+        }
+    )]
+    #[case(
+        r#"SET-FREQUENCY 0 "rf" 5.2"#,
+        UnitDiagnosticKind::SuspiciousMagnitude {
+            operand: "frequency".to_string(),
+            value: 5.2,
+            unit: Unit::Hertz,
+        }
+    )]
+    #[case(
+        r#"SHIFT-PHASE 0 "rf" 90"#,
+        UnitDiagnosticKind::SuspiciousMagnitude {
+            operand: "phase".to_string(),
+            value: 90.0,
+            unit: Unit::Radians,
+        }
+    )]
+    #[case(
+        r#"PULSE 0 "rf" gaussian(duration: 1e-6, fwhm: 2e-7, t0: 5e-7, phase: pi)"#,
+        UnitDiagnosticKind::SuspiciousMagnitude {
+            operand: "gaussian parameter phase".to_string(),
+            value: std::f64::consts::PI,
+            unit: Unit::Cycles,
+        }
+    )]
+    fn reports_suspicious_magnitude(
+        #[case] instruction: &str,
+        #[case] expected: UnitDiagnosticKind,
+    ) {
+        let program = Program::from_str(&format!("{HEADER}{instruction}")).unwrap();
+        assert_eq!(
+            program.check_units(),
+            vec![UnitDiagnostic {
+                calibration: None,
+                instruction_index: 0,
+                kind: expected,
+            }]
+        );
+    }
+
+    #[rstest]
+    #[case(
+        r#"DELAY 0 "rf" t[0]
+SET-FREQUENCY 0 "rf" t[0]"#,
+        UnitSymbol::MemoryRegion("t".to_string()),
+        Unit::Seconds,
+        Unit::Hertz
+    )]
+    #[case(
+        r#"SET-FREQUENCY 0 "rf" f[0]
+DELAY 0 "rf" f[0] * 1e-9"#,
+        UnitSymbol::MemoryRegion("f".to_string()),
+        Unit::Hertz,
+        Unit::Seconds
+    )]
+    #[case(
+        r#"DELAY 0 "rf" t[0]
+SET-SCALE 0 "rf" sin(t[0])"#,
+        UnitSymbol::MemoryRegion("t".to_string()),
+        Unit::Seconds,
+        Unit::Dimensionless
+    )]
+    #[case(
+        r#"SHIFT-PHASE 0 "rf" phi[0]
+PULSE 0 "rf" flat(duration: 1e-6, iq: 1, phase: phi[0])"#,
+        UnitSymbol::MemoryRegion("phi".to_string()),
+        Unit::Radians,
+        Unit::Cycles
+    )]
+    fn reports_unit_mismatch(
+        #[case] instructions: &str,
+        #[case] symbol: UnitSymbol,
+        #[case] expected: Unit,
+        #[case] found: Unit,
+    ) {
+        let program = Program::from_str(&format!("{HEADER}{instructions}")).unwrap();
+        assert_eq!(
+            program.check_units(),
+            vec![UnitDiagnostic {
+                calibration: None,
+                instruction_index: 1,
+                kind: UnitDiagnosticKind::UnitMismatch {
+                    symbol,
+                    expected,
+                    found,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_calibration_variable_mismatch() {
+        let program = Program::from_str(&format!(
+            r#"{HEADER}DEFCAL RX(%theta) 0:
+    SHIFT-PHASE 0 "rf" %theta
+    DELAY 0 "rf" %theta
+"#
+        ))
+        .unwrap();
+
+        let diagnostics = program.check_units();
+        assert_eq!(
+            diagnostics,
+            vec![UnitDiagnostic {
+                calibration: Some(CalibrationSource::Calibration(
+                    CalibrationIdentifier::new(
+                        "RX".to_string(),
+                        vec![],
+                        vec![Expression::Variable("theta".to_string())],
+                        vec![Qubit::Fixed(0)],
+                    )
+                    .unwrap()
+                )),
+                instruction_index: 1,
+                kind: UnitDiagnosticKind::UnitMismatch {
+                    symbol: UnitSymbol::Variable("theta".to_string()),
+                    expected: Unit::Radians,
+                    found: Unit::Seconds,
+                },
+            }]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "DEFCAL RX(%theta) 0, instruction 1: variable %theta is used in s, but was previously used in rad"
+        );
+    }
+}
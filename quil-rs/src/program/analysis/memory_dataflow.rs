@@ -0,0 +1,912 @@
+//! Reaching definitions and liveness of classical memory, over the control flow graph of a program
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    expression::Expression,
+    instruction::{
+        Arithmetic, BinaryLogic, BinaryOperand, Capture, Comparison, ComparisonOperand, Convert,
+        Delay, Exchange, ExternError, ExternSignatureMap, Gate, Instruction, Load, Measurement,
+        MemoryReference, Move, Pragma, Pulse, RawCapture, SetFrequency, SetPhase, SetScale,
+        ShiftFrequency, ShiftPhase, Store, UnaryLogic,
+    },
+    program::{MemoryAccessesError, MemoryLayout},
+    quil::Quil,
+    Program,
+};
+
+use super::{BasicBlockTerminator, ControlFlowGraph};
+
+/// The memory regions through which a program communicates with its client, as assumed by
+/// [`Program::memory_dataflow`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryInterface {
+    /// The regions written by the client before the program runs, such as its parameters
+    pub inputs: HashSet<String>,
+
+    /// The regions read by the client after the program ends, such as its readout results
+    pub outputs: HashSet<String>,
+}
+
+/// An element of a memory region, or the whole region.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemoryLocation {
+    pub region: String,
+
+    /// The index of the element, or `None` for any element of the region, as when an instruction
+    /// accesses the whole region or an index which is not known until runtime
+    pub index: Option<u64>,
+}
+
+impl MemoryLocation {
+    pub fn new(region: String, index: Option<u64>) -> Self {
+        Self { region, index }
+    }
+}
+
+impl From<&MemoryReference> for MemoryLocation {
+    fn from(reference: &MemoryReference) -> Self {
+        Self::new(reference.name.clone(), Some(reference.index))
+    }
+}
+
+impl fmt::Display for MemoryLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}[{index}]", self.region),
+            None => write!(f, "{}", self.region),
+        }
+    }
+}
+
+/// How an instruction writes to memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryDefinitionKind {
+    /// A classical instruction, such as `MOVE` or `STORE`
+    Write,
+
+    /// A readout instruction: `MEASURE`, `CAPTURE`, or `RAW-CAPTURE`
+    Capture,
+}
+
+/// A write to memory by an instruction within a [`ControlFlowGraph`].
+///
+/// A definition of a single element replaces the value of that element. A definition of a whole
+/// region may replace the value of any of its elements, so it does not replace other definitions.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemoryDefinition {
+    /// The index of the basic block containing the instruction
+    pub block_index: usize,
+
+    /// The index of the instruction within its basic block, where the terminator of the block
+    /// follows its last instruction
+    pub instruction_index: usize,
+
+    pub location: MemoryLocation,
+    pub kind: MemoryDefinitionKind,
+}
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum MemoryDataflowError {
+    #[error("invalid extern signature {}: {source}", .pragma.to_quil_or_debug())]
+    ExternSignature { pragma: Pragma, source: ExternError },
+
+    #[error(transparent)]
+    MemoryAccesses(#[from] MemoryAccessesError),
+}
+
+/// A suspicious use of memory, found by [`MemoryDataflow::diagnostics`].
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("block {block_index}, instruction {instruction_index}: {kind}")]
+pub struct MemoryDataflowDiagnostic {
+    /// The index of the basic block containing the instruction
+    pub block_index: usize,
+
+    /// The index of the instruction within its basic block, where the terminator of the block
+    /// follows its last instruction
+    pub instruction_index: usize,
+
+    pub kind: MemoryDataflowDiagnosticKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum MemoryDataflowDiagnosticKind {
+    #[error("reads {0}, which is not written on any path to this instruction")]
+    UninitializedRead(MemoryLocation),
+
+    #[error("writes {0}, whose value is never read")]
+    DeadStore(MemoryLocation),
+
+    #[error("captures readout into {0}, whose value is never read")]
+    UnconsumedCapture(MemoryLocation),
+}
+
+/// The memory accessed by a single instruction.
+#[derive(Clone, Debug, Default)]
+struct InstructionAccesses {
+    reads: Vec<MemoryLocation>,
+
+    /// The indices of the definitions made by the instruction within [`MemoryDataflow::definitions`]
+    definitions: Vec<usize>,
+}
+
+/// The memory accesses of a basic block and its successors within the control flow graph.
+#[derive(Clone, Debug, Default)]
+struct BlockAccesses {
+    instructions: Vec<InstructionAccesses>,
+    successors: Vec<usize>,
+
+    /// Whether the program may end after this block
+    exits: bool,
+}
+
+/// The reaching definitions and liveness of classical memory throughout a program, computed over
+/// its [`ControlFlowGraph`].
+///
+/// Memory is tracked by element when an instruction references a constant index, and by region
+/// otherwise, as for the source of a `LOAD` or the destination of a `STORE`. Regions which share
+/// memory are assumed to alias one another where their layouts overlap, but a write to one is never
+/// assumed to replace the value of another.
+#[derive(Clone, Debug)]
+pub struct MemoryDataflow {
+    definitions: Vec<MemoryDefinition>,
+    blocks: Vec<BlockAccesses>,
+
+    /// The definitions which reach the start of each block
+    reaching_in: Vec<BTreeSet<usize>>,
+
+    /// The locations which are live at the end of each block
+    live_out: Vec<BTreeSet<MemoryLocation>>,
+
+    inputs: HashSet<String>,
+    layout: Option<MemoryLayout>,
+}
+
+impl Program {
+    /// Compute the reaching definitions and liveness of classical memory throughout the body of
+    /// the program. Blocks are indexed as within `ControlFlowGraph::from(&program).blocks()`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an extern signature is invalid or if a `CALL` cannot be resolved
+    /// against one.
+    pub fn memory_dataflow(
+        &self,
+        interface: &MemoryInterface,
+    ) -> Result<MemoryDataflow, MemoryDataflowError> {
+        let extern_signatures = self
+            .try_extern_signature_map_from_pragma_map()
+            .map_err(|(pragma, source)| MemoryDataflowError::ExternSignature { pragma, source })?;
+        MemoryDataflow::new(
+            &ControlFlowGraph::from(self),
+            MemoryLayout::new(&self.memory_regions).ok(),
+            &extern_signatures,
+            interface,
+        )
+    }
+}
+
+impl MemoryDataflow {
+    fn new(
+        graph: &ControlFlowGraph,
+        layout: Option<MemoryLayout>,
+        extern_signatures: &ExternSignatureMap,
+        interface: &MemoryInterface,
+    ) -> Result<Self, MemoryDataflowError> {
+        let labels: HashMap<_, _> = graph
+            .blocks()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, block)| Some((block.label()?, index)))
+            .collect();
+
+        let mut definitions = Vec::new();
+        let mut blocks = Vec::new();
+        for (block_index, block) in graph.blocks().iter().enumerate() {
+            let mut instructions = Vec::new();
+            let terminator_accesses = match block.terminator() {
+                BasicBlockTerminator::ConditionalJump { condition, .. } => Accesses {
+                    reads: vec![MemoryLocation::from(*condition)],
+                    ..Default::default()
+                },
+                _ => Accesses::default(),
+            };
+            let accesses = block
+                .instructions()
+                .iter()
+                .map(|instruction| Accesses::of(instruction, extern_signatures))
+                .chain(std::iter::once(Ok(terminator_accesses)));
+            for (instruction_index, accesses) in accesses.enumerate() {
+                let accesses = accesses?;
+                let mut instruction = InstructionAccesses {
+                    reads: accesses.reads,
+                    definitions: Vec::new(),
+                };
+                for (kind, location) in accesses.writes {
+                    instruction.definitions.push(definitions.len());
+                    definitions.push(MemoryDefinition {
+                        block_index,
+                        instruction_index,
+                        location,
+                        kind,
+                    });
+                }
+                instructions.push(instruction);
+            }
+
+            let next = (block_index + 1 < graph.blocks().len()).then_some(block_index + 1);
+            let jump = |target| labels.get(target).copied();
+            let successors: Vec<_> = match block.terminator() {
+                BasicBlockTerminator::Continue => next.into_iter().collect(),
+                BasicBlockTerminator::Jump { target } => jump(target).into_iter().collect(),
+                BasicBlockTerminator::ConditionalJump { target, .. } => {
+                    jump(target).into_iter().chain(next).collect()
+                }
+                BasicBlockTerminator::Halt => Vec::new(),
+            };
+            let exits = match block.terminator() {
+                BasicBlockTerminator::Continue => next.is_none(),
+                BasicBlockTerminator::Jump { target } => jump(target).is_none(),
+                BasicBlockTerminator::ConditionalJump { target, .. } => {
+                    next.is_none() || jump(target).is_none()
+                }
+                BasicBlockTerminator::Halt => true,
+            };
+            blocks.push(BlockAccesses {
+                instructions,
+                successors,
+                exits,
+            });
+        }
+
+        let mut dataflow = Self {
+            reaching_in: vec![BTreeSet::new(); blocks.len()],
+            live_out: vec![BTreeSet::new(); blocks.len()],
+            definitions,
+            blocks,
+            inputs: interface.inputs.clone(),
+            layout,
+        };
+        dataflow.solve_reaching_definitions();
+        dataflow.solve_liveness(&interface.outputs);
+        Ok(dataflow)
+    }
+
+    /// Every write to memory within the program.
+    pub fn definitions(&self) -> &[MemoryDefinition] {
+        &self.definitions
+    }
+
+    /// The definitions which may reach an instruction, before it is executed, or `None` if there
+    /// is no such instruction.
+    pub fn reaching_definitions(
+        &self,
+        block_index: usize,
+        instruction_index: usize,
+    ) -> Option<Vec<&MemoryDefinition>> {
+        let block = self.blocks.get(block_index)?;
+        block.instructions.get(instruction_index)?;
+        let mut reaching = self.reaching_in[block_index].clone();
+        for instruction in &block.instructions[..instruction_index] {
+            self.transfer_reaching(&mut reaching, instruction);
+        }
+        Some(
+            reaching
+                .into_iter()
+                .map(|definition| &self.definitions[definition])
+                .collect(),
+        )
+    }
+
+    /// The locations whose values may be read after an instruction is executed, before they are
+    /// replaced, or `None` if there is no such instruction.
+    pub fn live_locations(
+        &self,
+        block_index: usize,
+        instruction_index: usize,
+    ) -> Option<Vec<MemoryLocation>> {
+        let block = self.blocks.get(block_index)?;
+        block.instructions.get(instruction_index)?;
+        let mut live = self.live_out[block_index].clone();
+        for instruction in block.instructions[instruction_index + 1..].iter().rev() {
+            self.transfer_liveness(&mut live, instruction);
+        }
+        Some(live.into_iter().collect())
+    }
+
+    /// Report each read of memory which is not written on any path to it, unless it is one of
+    /// the [`MemoryInterface::inputs`], and each write whose value is never read, whether within
+    /// the program or, for the [`MemoryInterface::outputs`], by its client.
+    pub fn diagnostics(&self) -> Vec<MemoryDataflowDiagnostic> {
+        let mut diagnostics = Vec::new();
+        for (block_index, block) in self.blocks.iter().enumerate() {
+            let mut block_diagnostics = Vec::new();
+
+            let mut reaching = self.reaching_in[block_index].clone();
+            for (instruction_index, instruction) in block.instructions.iter().enumerate() {
+                for location in &instruction.reads {
+                    let initialized = self.inputs.contains(&location.region)
+                        || reaching.iter().any(|&definition| {
+                            self.overlaps(&self.definitions[definition].location, location)
+                        });
+                    if !initialized {
+                        block_diagnostics.push(MemoryDataflowDiagnostic {
+                            block_index,
+                            instruction_index,
+                            kind: MemoryDataflowDiagnosticKind::UninitializedRead(location.clone()),
+                        });
+                    }
+                }
+                self.transfer_reaching(&mut reaching, instruction);
+            }
+
+            let mut live = self.live_out[block_index].clone();
+            for (instruction_index, instruction) in block.instructions.iter().enumerate().rev() {
+                for &definition in &instruction.definitions {
+                    let definition = &self.definitions[definition];
+                    if live
+                        .iter()
+                        .any(|location| self.overlaps(&definition.location, location))
+                    {
+                        continue;
+                    }
+                    let location = definition.location.clone();
+                    block_diagnostics.push(MemoryDataflowDiagnostic {
+                        block_index,
+                        instruction_index,
+                        kind: match definition.kind {
+                            MemoryDefinitionKind::Write => {
+                                MemoryDataflowDiagnosticKind::DeadStore(location)
+                            }
+                            MemoryDefinitionKind::Capture => {
+                                MemoryDataflowDiagnosticKind::UnconsumedCapture(location)
+                            }
+                        },
+                    });
+                }
+                self.transfer_liveness(&mut live, instruction);
+            }
+
+            block_diagnostics.sort_by_key(|diagnostic| diagnostic.instruction_index);
+            diagnostics.extend(block_diagnostics);
+        }
+        diagnostics
+    }
+
+    /// Compute the definitions which reach the start of each block, iterating to a fixed point.
+    fn solve_reaching_definitions(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (block_index, block) in self.blocks.iter().enumerate() {
+                let mut reaching = self.reaching_in[block_index].clone();
+                for instruction in &block.instructions {
+                    self.transfer_reaching(&mut reaching, instruction);
+                }
+                for &successor in &block.successors {
+                    let before = self.reaching_in[successor].len();
+                    self.reaching_in[successor].extend(reaching.iter().copied());
+                    changed |= self.reaching_in[successor].len() != before;
+                }
+            }
+        }
+    }
+
+    /// Compute the locations which are live at the end of each block, iterating to a fixed point.
+    fn solve_liveness(&mut self, outputs: &HashSet<String>) {
+        let exit: BTreeSet<_> = outputs
+            .iter()
+            .map(|region| MemoryLocation::new(region.clone(), None))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block_index in (0..self.blocks.len()).rev() {
+                let block = &self.blocks[block_index];
+                let mut live_out = if block.exits {
+                    exit.clone()
+                } else {
+                    BTreeSet::new()
+                };
+                for &successor in &block.successors {
+                    let mut live_in = self.live_out[successor].clone();
+                    for instruction in self.blocks[successor].instructions.iter().rev() {
+                        self.transfer_liveness(&mut live_in, instruction);
+                    }
+                    live_out.extend(live_in);
+                }
+                if live_out != self.live_out[block_index] {
+                    self.live_out[block_index] = live_out;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// Update the definitions which reach an instruction to those which reach the next.
+    fn transfer_reaching(&self, reaching: &mut BTreeSet<usize>, instruction: &InstructionAccesses) {
+        for &definition in &instruction.definitions {
+            let location = &self.definitions[definition].location;
+            if location.index.is_some() {
+                reaching.retain(|&other| self.definitions[other].location != *location);
+            }
+        }
+        reaching.extend(instruction.definitions.iter().copied());
+    }
+
+    /// Update the locations which are live after an instruction to those which are live before it.
+    fn transfer_liveness(
+        &self,
+        live: &mut BTreeSet<MemoryLocation>,
+        instruction: &InstructionAccesses,
+    ) {
+        for &definition in &instruction.definitions {
+            let location = &self.definitions[definition].location;
+            if location.index.is_some() {
+                live.remove(location);
+            }
+        }
+        live.extend(instruction.reads.iter().cloned());
+    }
+
+    /// Whether two locations may occupy some of the same memory.
+    fn overlaps(&self, left: &MemoryLocation, right: &MemoryLocation) -> bool {
+        if left.region == right.region {
+            return left.index.is_none() || right.index.is_none() || left.index == right.index;
+        }
+        let Some(layout) = &self.layout else {
+            return false;
+        };
+        let bit_range = |location: &MemoryLocation| {
+            let region = layout.get(&location.region)?;
+            Some(match location.index {
+                Some(index) => {
+                    let width = region.data_type.bit_width();
+                    let start = region
+                        .bit_offset
+                        .saturating_add(index.saturating_mul(width));
+                    start..start.saturating_add(width)
+                }
+                None => region.bit_range(),
+            })
+        };
+        match (bit_range(left), bit_range(right)) {
+            (Some(left), Some(right)) => left.start < right.end && right.start < left.end,
+            _ => false,
+        }
+    }
+}
+
+/// The locations read and written by an instruction, without duplicates.
+#[derive(Clone, Debug, Default)]
+struct Accesses {
+    reads: Vec<MemoryLocation>,
+    writes: Vec<(MemoryDefinitionKind, MemoryLocation)>,
+}
+
+impl Accesses {
+    fn of(
+        instruction: &Instruction,
+        extern_signatures: &ExternSignatureMap,
+    ) -> Result<Self, MemoryDataflowError> {
+        let mut accesses = Self::default();
+        match instruction {
+            Instruction::Arithmetic(Arithmetic {
+                destination,
+                source,
+                ..
+            }) => {
+                accesses.read(destination);
+                if let Some(source) = source.get_memory_reference() {
+                    accesses.read(source);
+                }
+                accesses.write(MemoryDefinitionKind::Write, destination.into());
+            }
+            Instruction::BinaryLogic(BinaryLogic {
+                destination,
+                source,
+                ..
+            }) => {
+                accesses.read(destination);
+                if let BinaryOperand::MemoryReference(source) = source {
+                    accesses.read(source);
+                }
+                accesses.write(MemoryDefinitionKind::Write, destination.into());
+            }
+            Instruction::UnaryLogic(UnaryLogic { operand, .. }) => {
+                accesses.read(operand);
+                accesses.write(MemoryDefinitionKind::Write, operand.into());
+            }
+            Instruction::Comparison(Comparison {
+                destination,
+                lhs,
+                rhs,
+                ..
+            }) => {
+                accesses.read(lhs);
+                if let ComparisonOperand::MemoryReference(rhs) = rhs {
+                    accesses.read(rhs);
+                }
+                accesses.write(MemoryDefinitionKind::Write, destination.into());
+            }
+            Instruction::Convert(Convert {
+                destination,
+                source,
+            }) => {
+                accesses.read(source);
+                accesses.write(MemoryDefinitionKind::Write, destination.into());
+            }
+            Instruction::Move(Move {
+                destination,
+                source,
+            }) => {
+                if let Some(source) = source.get_memory_reference() {
+                    accesses.read(source);
+                }
+                accesses.write(MemoryDefinitionKind::Write, destination.into());
+            }
+            Instruction::Exchange(Exchange { left, right }) => {
+                accesses.read(left);
+                accesses.read(right);
+                accesses.write(MemoryDefinitionKind::Write, left.into());
+                accesses.write(MemoryDefinitionKind::Write, right.into());
+            }
+            Instruction::Load(Load {
+                destination,
+                source,
+                offset,
+            }) => {
+                accesses.read(offset);
+                accesses.read_region(source);
+                accesses.write(MemoryDefinitionKind::Write, destination.into());
+            }
+            Instruction::Store(Store {
+                destination,
+                offset,
+                source,
+            }) => {
+                accesses.read(offset);
+                if let Some(source) = source.get_memory_reference() {
+                    accesses.read(source);
+                }
+                accesses.write(
+                    MemoryDefinitionKind::Write,
+                    MemoryLocation::new(destination.clone(), None),
+                );
+            }
+            Instruction::Measurement(Measurement { target, .. }) => {
+                if let Some(target) = target {
+                    accesses.write(MemoryDefinitionKind::Capture, target.into());
+                }
+            }
+            Instruction::Capture(Capture {
+                memory_reference,
+                waveform,
+                ..
+            }) => {
+                for reference in waveform.get_memory_references() {
+                    accesses.read(reference);
+                }
+                accesses.write(MemoryDefinitionKind::Capture, memory_reference.into());
+            }
+            // A raw capture fills the region with samples, starting from the given index.
+            Instruction::RawCapture(RawCapture {
+                duration,
+                memory_reference,
+                ..
+            }) => {
+                accesses.read_expression(duration);
+                accesses.write(
+                    MemoryDefinitionKind::Capture,
+                    MemoryLocation::new(memory_reference.name.clone(), None),
+                );
+            }
+            Instruction::JumpWhen(jump_when) => accesses.read(&jump_when.condition),
+            Instruction::JumpUnless(jump_unless) => accesses.read(&jump_unless.condition),
+            Instruction::Gate(Gate { parameters, .. }) => {
+                for parameter in parameters {
+                    accesses.read_expression(parameter);
+                }
+            }
+            Instruction::Pulse(Pulse { waveform, .. }) => {
+                for reference in waveform.get_memory_references() {
+                    accesses.read(reference);
+                }
+            }
+            Instruction::Delay(Delay {
+                duration: expression,
+                ..
+            })
+            | Instruction::SetFrequency(SetFrequency {
+                frequency: expression,
+                ..
+            })
+            | Instruction::ShiftFrequency(ShiftFrequency {
+                frequency: expression,
+                ..
+            })
+            | Instruction::SetPhase(SetPhase {
+                phase: expression, ..
+            })
+            | Instruction::ShiftPhase(ShiftPhase {
+                phase: expression, ..
+            })
+            | Instruction::SetScale(SetScale {
+                scale: expression, ..
+            }) => accesses.read_expression(expression),
+            // Otherwise, only the regions accessed are known, as for the arguments of a `CALL`.
+            _ => {
+                let memory_accesses = instruction.get_memory_accesses(extern_signatures)?;
+                let sorted = |regions: HashSet<String>| {
+                    let mut regions: Vec<_> = regions.into_iter().collect();
+                    regions.sort();
+                    regions
+                };
+                for region in sorted(memory_accesses.reads) {
+                    accesses.read_region(&region);
+                }
+                for region in sorted(memory_accesses.writes) {
+                    accesses.write(
+                        MemoryDefinitionKind::Write,
+                        MemoryLocation::new(region, None),
+                    );
+                }
+                for region in sorted(memory_accesses.captures) {
+                    accesses.write(
+                        MemoryDefinitionKind::Capture,
+                        MemoryLocation::new(region, None),
+                    );
+                }
+            }
+        }
+        Ok(accesses)
+    }
+
+    fn read(&mut self, reference: &MemoryReference) {
+        self.read_location(reference.into());
+    }
+
+    fn read_region(&mut self, region: &str) {
+        self.read_location(MemoryLocation::new(region.to_string(), None));
+    }
+
+    fn read_expression(&mut self, expression: &Expression) {
+        for reference in expression.get_memory_references() {
+            self.read(reference);
+        }
+    }
+
+    fn read_location(&mut self, location: MemoryLocation) {
+        if !self.reads.contains(&location) {
+            self.reads.push(location);
+        }
+    }
+
+    fn write(&mut self, kind: MemoryDefinitionKind, location: MemoryLocation) {
+        if !self.writes.iter().any(|(_, other)| *other == location) {
+            self.writes.push((kind, location));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, str::FromStr};
+
+    use rstest::rstest;
+
+    use crate::Program;
+
+    use super::{
+        MemoryDataflowDiagnostic, MemoryDataflowDiagnosticKind, MemoryDefinition,
+        MemoryDefinitionKind, MemoryInterface, MemoryLocation,
+    };
+
+    fn location(region: &str, index: Option<u64>) -> MemoryLocation {
+        MemoryLocation::new(region.to_string(), index)
+    }
+
+    fn interface(inputs: &[&str], outputs: &[&str]) -> MemoryInterface {
+        let regions = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        MemoryInterface {
+            inputs: regions(inputs),
+            outputs: regions(outputs),
+        }
+    }
+
+    fn diagnostic(
+        block_index: usize,
+        instruction_index: usize,
+        kind: MemoryDataflowDiagnosticKind,
+    ) -> MemoryDataflowDiagnostic {
+        MemoryDataflowDiagnostic {
+            block_index,
+            instruction_index,
+            kind,
+        }
+    }
+
+    #[rstest]
+    #[case::straight_line(
+        "DECLARE a INTEGER[2]
+DECLARE b INTEGER
+MOVE a[0] 1
+MOVE a[1] 2
+ADD a[0] b
+MOVE a[1] 3",
+        interface(&[], &[]),
+        vec![
+            diagnostic(0, 1, MemoryDataflowDiagnosticKind::DeadStore(location("a", Some(1)))),
+            diagnostic(0, 2, MemoryDataflowDiagnosticKind::UninitializedRead(location("b", Some(0)))),
+            diagnostic(0, 2, MemoryDataflowDiagnosticKind::DeadStore(location("a", Some(0)))),
+            diagnostic(0, 3, MemoryDataflowDiagnosticKind::DeadStore(location("a", Some(1)))),
+        ]
+    )]
+    #[case::interface(
+        "DECLARE theta REAL
+DECLARE ro BIT
+RX(theta) 0
+MEASURE 0 ro",
+        interface(&["theta"], &["ro"]),
+        vec![]
+    )]
+    #[case::unconsumed_capture(
+        "DECLARE ro BIT[2]
+DECLARE out BIT
+MEASURE 0 ro[0]
+MEASURE 1 ro[1]
+MOVE out ro[0]",
+        interface(&[], &["out"]),
+        vec![diagnostic(0, 1, MemoryDataflowDiagnosticKind::UnconsumedCapture(location("ro", Some(1))))]
+    )]
+    #[case::loop_counter(
+        "DECLARE count INTEGER
+DECLARE done BIT
+MOVE count 0
+LABEL @loop
+ADD count 1
+GT done count 10
+JUMP-UNLESS @loop done
+HALT",
+        interface(&[], &[]),
+        vec![]
+    )]
+    #[case::branches(
+        "DECLARE ro BIT
+DECLARE x REAL
+DECLARE y REAL
+MEASURE 0 ro
+JUMP-WHEN @skip ro
+MOVE x 1.0
+LABEL @skip
+RX(x) 0
+RX(y) 0",
+        interface(&[], &[]),
+        vec![diagnostic(2, 1, MemoryDataflowDiagnosticKind::UninitializedRead(location("y", Some(0))))]
+    )]
+    #[case::dynamic_index(
+        "DECLARE table REAL[4]
+DECLARE i INTEGER
+DECLARE x REAL
+STORE table i 1.0
+LOAD x table i
+RX(x) 0",
+        interface(&["i"], &[]),
+        vec![]
+    )]
+    #[case::never_loaded(
+        "DECLARE table REAL[4]
+DECLARE i INTEGER
+STORE table i 1.0
+MOVE table[0] 2.0",
+        interface(&["i"], &[]),
+        vec![
+            diagnostic(0, 0, MemoryDataflowDiagnosticKind::DeadStore(location("table", None))),
+            diagnostic(0, 1, MemoryDataflowDiagnosticKind::DeadStore(location("table", Some(0)))),
+        ]
+    )]
+    #[case::sharing(
+        "DECLARE word INTEGER
+DECLARE bits BIT[64] SHARING word
+DECLARE other BIT[64]
+MOVE word 5
+JUMP-WHEN @end bits[2]
+JUMP-WHEN @end other[2]
+LABEL @end",
+        interface(&[], &[]),
+        vec![diagnostic(1, 0, MemoryDataflowDiagnosticKind::UninitializedRead(location("other", Some(2))))]
+    )]
+    fn reports_diagnostics(
+        #[case] program: &str,
+        #[case] interface: MemoryInterface,
+        #[case] expected: Vec<MemoryDataflowDiagnostic>,
+    ) {
+        let program = Program::from_str(program).unwrap();
+        let dataflow = program.memory_dataflow(&interface).unwrap();
+        assert_eq!(dataflow.diagnostics(), expected);
+    }
+
+    #[test]
+    fn reaching_definitions_and_liveness() {
+        let program = Program::from_str(
+            "DECLARE ro BIT
+DECLARE x REAL
+MOVE x 1.0
+MEASURE 0 ro
+JUMP-UNLESS @end ro
+MOVE x 2.0
+LABEL @end
+RX(x) 0",
+        )
+        .unwrap();
+        let dataflow = program
+            .memory_dataflow(&MemoryInterface::default())
+            .unwrap();
+
+        let definition = |block_index, instruction_index, region: &str, kind| MemoryDefinition {
+            block_index,
+            instruction_index,
+            location: location(region, Some(0)),
+            kind,
+        };
+        let first = definition(0, 0, "x", MemoryDefinitionKind::Write);
+        let capture = definition(0, 1, "ro", MemoryDefinitionKind::Capture);
+        let second = definition(1, 0, "x", MemoryDefinitionKind::Write);
+        assert_eq!(
+            dataflow.definitions(),
+            [first.clone(), capture.clone(), second.clone()]
+        );
+
+        assert_eq!(
+            dataflow
+                .reaching_definitions(2, 0)
+                .unwrap()
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            HashSet::from([&first, &capture, &second])
+        );
+        assert_eq!(
+            dataflow.reaching_definitions(1, 1).unwrap(),
+            vec![&capture, &second]
+        );
+        assert_eq!(dataflow.reaching_definitions(3, 0), None);
+
+        assert_eq!(
+            dataflow.live_locations(0, 1).unwrap(),
+            vec![location("ro", Some(0)), location("x", Some(0))]
+        );
+        assert_eq!(
+            dataflow.live_locations(1, 0).unwrap(),
+            vec![location("x", Some(0))]
+        );
+        assert_eq!(dataflow.live_locations(2, 0).unwrap(), vec![]);
+        assert_eq!(dataflow.diagnostics(), vec![]);
+
+        assert_eq!(
+            diagnostic(
+                1,
+                0,
+                MemoryDataflowDiagnosticKind::DeadStore(location("x", None))
+            )
+            .to_string(),
+            "block 1, instruction 0: writes x, whose value is never read"
+        );
+    }
+}
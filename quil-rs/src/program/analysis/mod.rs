@@ -14,13 +14,36 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod calibration_coverage;
+mod calibration_diagnostics;
+mod calibration_validation;
 mod control_flow_graph;
+mod dimensional_analysis;
+mod memory_dataflow;
+mod parameter_ranges;
+mod program_duration;
 mod qubit_graph;
 
+pub use calibration_coverage::{CalibrationCoverage, CalibrationCoverageItem};
+pub use calibration_diagnostics::{
+    CalibrationDiagnostics, CalibrationOverlap, CalibrationReplacement,
+};
+pub use calibration_validation::{
+    CalibrationValidationError, CalibrationValidationErrorKind, CalibrationValidationErrors,
+};
 pub use control_flow_graph::{
     BasicBlock, BasicBlockOwned, BasicBlockScheduleError, BasicBlockTerminator,
     BasicBlockTerminatorOwned, ControlFlowGraph, ControlFlowGraphOwned,
 };
+pub use dimensional_analysis::{UnitDiagnostic, UnitDiagnosticKind, UnitSymbol};
+pub use memory_dataflow::{
+    MemoryDataflow, MemoryDataflowDiagnostic, MemoryDataflowDiagnosticKind, MemoryDataflowError,
+    MemoryDefinition, MemoryDefinitionKind, MemoryInterface, MemoryLocation,
+};
+pub use parameter_ranges::{ParameterRangeViolation, ParameterRangeViolationKind, ParameterRanges};
+pub use program_duration::{
+    DurationBound, ProgramDurationBounds, ProgramDurationError, ProgramDurationResult,
+};
 pub use qubit_graph::{QubitGraph, QubitGraphError};
 
 #[cfg(test)]
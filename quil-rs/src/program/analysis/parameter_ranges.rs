@@ -0,0 +1,343 @@
+//! Proving, by interval analysis, that the operands of Quil-T instructions stay within valid ranges
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::{
+    expression::{Expression, Interval, IntervalError},
+    instruction::{Delay, FrameIdentifier, Instruction, ScalarType, SetFrequency, SetScale},
+    program::CalibrationSource,
+    quil::Quil,
+    Program,
+};
+
+use super::calibration_validation::instruction_location;
+
+/// The ranges of the values which are not known until runtime, as assumed by
+/// [`Program::check_parameter_ranges`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParameterRanges {
+    /// The range of each variable, such as the parameters of a calibration
+    pub variables: HashMap<String, Interval>,
+
+    /// The range of every element of each memory region. A `BIT` or `INTEGER` region without one
+    /// is assumed to take any value of its type.
+    pub memory_regions: HashMap<String, Interval>,
+
+    /// The frequencies, in Hz, to which each frame may be set
+    pub frame_bandwidths: HashMap<FrameIdentifier, Interval>,
+}
+
+/// An instruction operand which could not be proven to stay within its valid range.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+#[error("{}: {kind}", instruction_location(.calibration.as_ref(), *.instruction_index))]
+pub struct ParameterRangeViolation {
+    /// The calibration whose body contains the instruction, or `None` for the body of the program
+    pub calibration: Option<CalibrationSource>,
+
+    /// The index of the instruction within its body
+    pub instruction_index: usize,
+
+    /// The operand and the range it may take
+    pub kind: ParameterRangeViolationKind,
+}
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ParameterRangeViolationKind {
+    #[error("scale of frame {} may be within {range}, outside of [-1, 1]", .frame.to_quil_or_debug())]
+    ScaleOutOfRange {
+        frame: FrameIdentifier,
+        range: Interval,
+    },
+
+    #[error("duration may be within {0}, including negative values")]
+    NegativeDuration(Interval),
+
+    #[error(
+        "frequency of frame {} may be within {range}, outside of its bandwidth {bandwidth}",
+        .frame.to_quil_or_debug()
+    )]
+    FrequencyOutOfBandwidth {
+        frame: FrameIdentifier,
+        range: Interval,
+        bandwidth: Interval,
+    },
+
+    #[error("cannot bound {}: {source}", .expression.to_quil_or_debug())]
+    Unbounded {
+        expression: Expression,
+        source: IntervalError,
+    },
+}
+
+impl Program {
+    /// Attempt to prove, for every value within the given ranges, that each `SET-SCALE` within the
+    /// program and its calibrations stays within `[-1, 1]`, that each `DELAY` is non-negative, and
+    /// that each `SET-FREQUENCY` stays within the bandwidth of its frame, if one is given.
+    ///
+    /// Each operand which could not be proven to stay within its range is reported, whether it
+    /// certainly leaves that range or merely may. `SHIFT-FREQUENCY` is not checked, since its effect
+    /// depends on every preceding change to the frequency of its frame.
+    pub fn check_parameter_ranges(&self, ranges: &ParameterRanges) -> Vec<ParameterRangeViolation> {
+        let mut memory_regions = ranges.memory_regions.clone();
+        for (name, region) in &self.memory_regions {
+            let range = match region.size.data_type {
+                ScalarType::Bit => Interval::new(0.0, 1.0),
+                ScalarType::Integer => Interval::new(i64::MIN as f64, i64::MAX as f64),
+                ScalarType::Octet | ScalarType::Real => continue,
+            };
+            if let Ok(range) = range {
+                memory_regions.entry(name.clone()).or_insert(range);
+            }
+        }
+        let checker = ParameterRangeChecker {
+            variables: &ranges.variables,
+            memory_regions: &memory_regions,
+            frame_bandwidths: &ranges.frame_bandwidths,
+        };
+
+        let mut violations = Vec::new();
+        checker.check(None, self.body_instructions(), &mut violations);
+        for calibration in self.calibrations.iter_calibrations() {
+            checker.check(
+                Some(CalibrationSource::Calibration(
+                    calibration.identifier.clone(),
+                )),
+                &calibration.instructions,
+                &mut violations,
+            );
+        }
+        for calibration in self.calibrations.iter_measure_calibrations() {
+            checker.check(
+                Some(CalibrationSource::MeasureCalibration(
+                    calibration.identifier.clone(),
+                )),
+                &calibration.instructions,
+                &mut violations,
+            );
+        }
+        violations
+    }
+}
+
+struct ParameterRangeChecker<'a> {
+    variables: &'a HashMap<String, Interval>,
+    memory_regions: &'a HashMap<String, Interval>,
+    frame_bandwidths: &'a HashMap<FrameIdentifier, Interval>,
+}
+
+impl ParameterRangeChecker<'_> {
+    fn check<'i>(
+        &self,
+        calibration: Option<CalibrationSource>,
+        instructions: impl IntoIterator<Item = &'i Instruction>,
+        violations: &mut Vec<ParameterRangeViolation>,
+    ) {
+        for (instruction_index, instruction) in instructions.into_iter().enumerate() {
+            if let Some(kind) = self.check_instruction(instruction) {
+                violations.push(ParameterRangeViolation {
+                    calibration: calibration.clone(),
+                    instruction_index,
+                    kind,
+                });
+            }
+        }
+    }
+
+    fn check_instruction(&self, instruction: &Instruction) -> Option<ParameterRangeViolationKind> {
+        match instruction {
+            Instruction::SetScale(SetScale { frame, scale }) => {
+                let range = match self.range(scale) {
+                    Ok(range) => range,
+                    Err(violation) => return Some(violation),
+                };
+                (range.lower() < -1.0 || range.upper() > 1.0).then(|| {
+                    ParameterRangeViolationKind::ScaleOutOfRange {
+                        frame: frame.clone(),
+                        range,
+                    }
+                })
+            }
+            Instruction::Delay(Delay { duration, .. }) => {
+                let range = match self.range(duration) {
+                    Ok(range) => range,
+                    Err(violation) => return Some(violation),
+                };
+                (range.lower() < 0.0)
+                    .then_some(ParameterRangeViolationKind::NegativeDuration(range))
+            }
+            Instruction::SetFrequency(SetFrequency { frame, frequency }) => {
+                let bandwidth = self.frame_bandwidths.get(frame)?;
+                let range = match self.range(frequency) {
+                    Ok(range) => range,
+                    Err(violation) => return Some(violation),
+                };
+                (!range.is_subset_of(bandwidth)).then(|| {
+                    ParameterRangeViolationKind::FrequencyOutOfBandwidth {
+                        frame: frame.clone(),
+                        range,
+                        bandwidth: *bandwidth,
+                    }
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn range(&self, expression: &Expression) -> Result<Interval, ParameterRangeViolationKind> {
+        expression
+            .range(self.variables, self.memory_regions)
+            .map_err(|source| ParameterRangeViolationKind::Unbounded {
+                expression: expression.clone(),
+                source,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, f64::consts::PI, str::FromStr};
+
+    use rstest::rstest;
+
+    use crate::{
+        expression::{Expression, Interval, IntervalError},
+        instruction::{CalibrationIdentifier, FrameIdentifier, Qubit},
+        program::CalibrationSource,
+        Program,
+    };
+
+    use super::{ParameterRangeViolation, ParameterRangeViolationKind, ParameterRanges};
+
+    const FRAMES: &str = r#"DECLARE amplitude REAL
+DECLARE detuning REAL
+DECLARE ro BIT
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+"#;
+
+    fn interval(lower: f64, upper: f64) -> Interval {
+        Interval::new(lower, upper).unwrap()
+    }
+
+    fn frame() -> FrameIdentifier {
+        FrameIdentifier::new("rf".to_string(), vec![Qubit::Fixed(0)])
+    }
+
+    fn ranges() -> ParameterRanges {
+        ParameterRanges {
+            variables: HashMap::from([("theta".to_string(), interval(-PI, PI))]),
+            memory_regions: HashMap::from([
+                ("amplitude".to_string(), interval(0.0, 0.5)),
+                ("detuning".to_string(), interval(-1.0, 1.0)),
+            ]),
+            frame_bandwidths: HashMap::from([(frame(), interval(4.9e9, 5.1e9))]),
+        }
+    }
+
+    #[rstest]
+    #[case(r#"SET-SCALE 0 "rf" 2 * amplitude[0] - 0.5"#)]
+    #[case(r#"SET-SCALE 0 "rf" ro[0]"#)]
+    #[case(r#"DELAY 0 "rf" 1e-6 * (1 + cos(amplitude[0] * pi))"#)]
+    #[case(r#"SET-FREQUENCY 0 "rf" 5e9 + 1e7 * detuning[0]"#)]
+    #[case(r#"SET-FREQUENCY 1 "rf" 1e12"#)]
+    #[case(
+        r#"DEFCAL RX(%theta) 0:
+    SET-SCALE 0 "rf" %theta / pi"#
+    )]
+    fn accepts_operands_within_range(#[case] instructions: &str) {
+        let program = Program::from_str(&format!("{FRAMES}{instructions}")).unwrap();
+        assert_eq!(program.check_parameter_ranges(&ranges()), vec![]);
+    }
+
+    #[rstest]
+    #[case(
+        r#"SET-SCALE 0 "rf" 4 * amplitude[0]"#,
+        ParameterRangeViolationKind::ScaleOutOfRange { frame: frame(), range: interval(0.0, 2.0) }
+    )]
+    #[case(
+        r#"DELAY 0 "rf" 1e-6 * detuning[0]"#,
+        ParameterRangeViolationKind::NegativeDuration(interval(-1e-6, 1e-6))
+    )]
+    #[case(
+        r#"SET-FREQUENCY 0 "rf" 5e9 + 1e9 * detuning[0]"#,
+        ParameterRangeViolationKind::FrequencyOutOfBandwidth {
+            frame: frame(),
+            range: interval(4e9, 6e9),
+            bandwidth: interval(4.9e9, 5.1e9),
+        }
+    )]
+    #[case(
+        r#"SET-SCALE 0 "rf" sqrt(detuning[0])"#,
+        ParameterRangeViolationKind::Unbounded {
+            expression: Expression::from_str("sqrt(detuning[0])").unwrap(),
+            source: IntervalError::MayBeComplex(Expression::from_str("sqrt(detuning[0])").unwrap()),
+        }
+    )]
+    fn reports_operands_outside_of_range(
+        #[case] instruction: &str,
+        #[case] expected: ParameterRangeViolationKind,
+    ) {
+        let program = Program::from_str(&format!("{FRAMES}{instruction}")).unwrap();
+        assert_eq!(
+            program.check_parameter_ranges(&ranges()),
+            vec![ParameterRangeViolation {
+                calibration: None,
+                instruction_index: 0,
+                kind: expected,
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_calibration_and_index() {
+        let program = Program::from_str(&format!(
+            r#"{FRAMES}DEFCAL RX(%theta) 0:
+    DELAY 0 "rf" 1e-6
+    SET-SCALE 0 "rf" %theta
+"#
+        ))
+        .unwrap();
+
+        let violations = program.check_parameter_ranges(&ranges());
+        assert_eq!(
+            violations,
+            vec![ParameterRangeViolation {
+                calibration: Some(CalibrationSource::Calibration(
+                    CalibrationIdentifier::new(
+                        "RX".to_string(),
+                        vec![],
+                        vec![Expression::Variable("theta".to_string())],
+                        vec![Qubit::Fixed(0)],
+                    )
+                    .unwrap()
+                )),
+                instruction_index: 1,
+                kind: ParameterRangeViolationKind::ScaleOutOfRange {
+                    frame: frame(),
+                    range: interval(-PI, PI),
+                },
+            }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            format!(
+                r#"DEFCAL RX(%theta) 0, instruction 1: scale of frame 0 "rf" may be within [{}, {}], outside of [-1, 1]"#,
+                -PI, PI
+            )
+        );
+    }
+}
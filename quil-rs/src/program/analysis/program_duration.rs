@@ -0,0 +1,779 @@
+//! Estimation of the duration of a whole Quil program, across the blocks of its control flow graph.
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, HashMap};
+
+use itertools::Itertools;
+
+use crate::{
+    instruction::{
+        Arithmetic, ArithmeticOperand, ArithmeticOperator, ExternSignatureMap, Instruction,
+        InstructionRole, MemoryReference, Move, Target,
+    },
+    program::scheduling::{ScheduledBasicBlock, Seconds},
+    quil::Quil,
+    Program,
+};
+
+use super::{BasicBlock, BasicBlockScheduleError, BasicBlockTerminator, ControlFlowGraph};
+
+/// A bound on the duration of a program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DurationBound {
+    /// The bound is this many seconds
+    Bounded(Seconds),
+
+    /// No bound exists, or none could be determined statically
+    Unbounded,
+}
+
+impl DurationBound {
+    /// Interpret an infinite number of seconds as unbounded.
+    fn from_seconds(seconds: f64) -> Self {
+        if seconds.is_finite() {
+            Self::Bounded(Seconds(seconds))
+        } else {
+            Self::Unbounded
+        }
+    }
+}
+
+/// The shortest and longest durations of a program, over every path of execution through it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramDurationBounds {
+    /// The duration of the shortest path through the program. This is unbounded only if the program
+    /// cannot terminate.
+    pub minimum: DurationBound,
+
+    /// The duration of the longest path through the program. This is unbounded if the program
+    /// contains a loop whose iteration count cannot be determined statically.
+    pub maximum: DurationBound,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProgramDurationError {
+    #[error("failed to schedule block {index}: {source}")]
+    BlockSchedule {
+        index: usize,
+        source: Box<BasicBlockScheduleError>,
+    },
+
+    #[error("expected a duration for each of {expected} blocks, got {actual}")]
+    BlockDurationCount { expected: usize, actual: usize },
+
+    #[error("jump to undefined label {}", .0.to_quil_or_debug())]
+    UndefinedTarget(Target),
+}
+
+pub type ProgramDurationResult<T> = Result<T, ProgramDurationError>;
+
+/// A node within the reduced control flow graph used to compute program duration: either a single
+/// basic block or an entire loop, collapsed into one node once its duration is known.
+#[derive(Clone, Debug)]
+struct Region {
+    /// The indices of the basic blocks within this region
+    blocks: Vec<usize>,
+
+    /// The shortest time spent within this region on a single visit, in seconds
+    minimum: f64,
+
+    /// The longest time spent within this region on a single visit, in seconds; infinite if unbounded
+    maximum: f64,
+
+    /// The regions to which control may flow upon leaving this one
+    successors: Vec<usize>,
+}
+
+/// The result of a depth-first search of the regions of a [`DurationGraph`].
+struct DepthFirstSearch {
+    /// Edges from a region to one of its ancestors in the search, which close a cycle
+    back_edges: Vec<(usize, usize)>,
+
+    /// Every region visited, each following all of its descendants in the search
+    postorder: Vec<usize>,
+}
+
+/// A control flow graph of regions, reduced one loop at a time until it is acyclic.
+///
+/// Regions `0..blocks.len()` are the basic blocks of the program; region `blocks.len()` is the exit
+/// of the program; collapsed loops are appended thereafter.
+struct DurationGraph<'a, 'p> {
+    blocks: &'a [BasicBlock<'p>],
+    labels: HashMap<&'p Target, usize>,
+    regions: Vec<Region>,
+    entry: usize,
+    exit: usize,
+}
+
+impl<'a, 'p> DurationGraph<'a, 'p> {
+    fn new(blocks: &'a [BasicBlock<'p>], durations: &[Seconds]) -> ProgramDurationResult<Self> {
+        if blocks.len() != durations.len() {
+            return Err(ProgramDurationError::BlockDurationCount {
+                expected: blocks.len(),
+                actual: durations.len(),
+            });
+        }
+
+        let labels = blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, block)| block.label().map(|label| (label, index)))
+            .collect::<HashMap<_, _>>();
+        let get_target_index = |target: &Target| {
+            labels
+                .get(target)
+                .copied()
+                .ok_or_else(|| ProgramDurationError::UndefinedTarget(target.clone()))
+        };
+
+        let exit = blocks.len();
+        let mut regions = blocks
+            .iter()
+            .zip(durations)
+            .enumerate()
+            .map(|(index, (block, duration))| {
+                // The exit of the program directly follows its last block.
+                let next = index + 1;
+                let successors = match block.terminator() {
+                    BasicBlockTerminator::Continue => vec![next],
+                    BasicBlockTerminator::Jump { target } => vec![get_target_index(target)?],
+                    BasicBlockTerminator::ConditionalJump { target, .. } => {
+                        vec![get_target_index(target)?, next]
+                    }
+                    BasicBlockTerminator::Halt => vec![exit],
+                };
+
+                Ok(Region {
+                    blocks: vec![index],
+                    minimum: duration.0,
+                    maximum: duration.0,
+                    successors: successors.into_iter().unique().collect(),
+                })
+            })
+            .collect::<ProgramDurationResult<Vec<_>>>()?;
+        regions.push(Region {
+            blocks: Vec::new(),
+            minimum: 0.0,
+            maximum: 0.0,
+            successors: Vec::new(),
+        });
+
+        Ok(Self {
+            blocks,
+            labels,
+            regions,
+            entry: 0,
+            exit,
+        })
+    }
+
+    /// Search the regions reachable from `start`, following only those edges for which
+    /// `follow_edge(from, to)` returns `true`.
+    fn depth_first_search(
+        &self,
+        start: usize,
+        follow_edge: impl Fn(usize, usize) -> bool,
+    ) -> DepthFirstSearch {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            InProgress,
+            Done,
+        }
+
+        let mut states = vec![State::Unvisited; self.regions.len()];
+        let mut back_edges = Vec::new();
+        let mut postorder = Vec::new();
+        let mut stack = vec![(start, 0)];
+        states[start] = State::InProgress;
+
+        while let Some(frame) = stack.last_mut() {
+            let (region, next_successor_index) = *frame;
+            frame.1 += 1;
+
+            match self.regions[region].successors.get(next_successor_index) {
+                Some(&successor) if follow_edge(region, successor) => match states[successor] {
+                    State::Unvisited => {
+                        states[successor] = State::InProgress;
+                        stack.push((successor, 0));
+                    }
+                    State::InProgress => back_edges.push((region, successor)),
+                    State::Done => {}
+                },
+                Some(_) => {}
+                None => {
+                    states[region] = State::Done;
+                    postorder.push(region);
+                    stack.pop();
+                }
+            }
+        }
+
+        DepthFirstSearch {
+            back_edges,
+            postorder,
+        }
+    }
+
+    /// Return the predecessors of each region, considering only edges from the given regions.
+    fn get_predecessors(&self, regions: &[usize]) -> Vec<Vec<usize>> {
+        let mut predecessors = vec![Vec::new(); self.regions.len()];
+        for &region in regions {
+            for &successor in &self.regions[region].successors {
+                predecessors[successor].push(region);
+            }
+        }
+        predecessors
+    }
+
+    /// Return the natural loop with the given `head`: the head itself, and every region which can
+    /// reach one of the `tails` (the sources of back edges to the head) without passing through it.
+    fn get_loop_body(head: usize, tails: &[usize], predecessors: &[Vec<usize>]) -> BTreeSet<usize> {
+        let mut body = BTreeSet::from([head]);
+        let mut stack = tails.to_vec();
+        while let Some(region) = stack.pop() {
+            if body.insert(region) {
+                stack.extend(predecessors[region].iter().copied());
+            }
+        }
+        body
+    }
+
+    /// Reduce the graph, one innermost loop at a time, until it is acyclic.
+    fn collapse_loops(&mut self) {
+        loop {
+            let search = self.depth_first_search(self.entry, |_, _| true);
+            if search.back_edges.is_empty() {
+                return;
+            }
+
+            let predecessors = self.get_predecessors(&search.postorder);
+            let (head, tails, body) = search
+                .back_edges
+                .iter()
+                .map(|(_, head)| *head)
+                .unique()
+                .map(|head| {
+                    let tails = search
+                        .back_edges
+                        .iter()
+                        .filter(|(_, other_head)| *other_head == head)
+                        .map(|(tail, _)| *tail)
+                        .collect::<Vec<_>>();
+                    let body = Self::get_loop_body(head, &tails, &predecessors);
+                    (head, tails, body)
+                })
+                .min_by_key(|(_, _, body)| body.len())
+                .expect("there is at least one back edge");
+
+            self.collapse_loop(head, &tails, &body, &predecessors);
+        }
+    }
+
+    /// Replace the regions of a loop with a single region spanning the whole loop.
+    fn collapse_loop(
+        &mut self,
+        head: usize,
+        tails: &[usize],
+        body: &BTreeSet<usize>,
+        predecessors: &[Vec<usize>],
+    ) {
+        let (minimum, maximum) = self.get_loop_duration(head, tails, body, predecessors);
+        let blocks = body
+            .iter()
+            .flat_map(|region| self.regions[*region].blocks.iter().copied())
+            .sorted()
+            .collect();
+        let successors = body
+            .iter()
+            .flat_map(|region| self.regions[*region].successors.iter().copied())
+            .filter(|successor| !body.contains(successor))
+            .unique()
+            .collect();
+
+        let collapsed = self.regions.len();
+        self.regions.push(Region {
+            blocks,
+            minimum,
+            maximum,
+            successors,
+        });
+
+        for region in &mut self.regions {
+            region.successors = region
+                .successors
+                .iter()
+                .map(|successor| {
+                    if body.contains(successor) {
+                        collapsed
+                    } else {
+                        *successor
+                    }
+                })
+                .unique()
+                .collect();
+        }
+
+        if body.contains(&self.entry) {
+            self.entry = collapsed;
+        }
+    }
+
+    /// Return the shortest and longest time spent within a loop, from its entry until it exits.
+    ///
+    /// If the loop's iteration count can be determined (see [`DurationGraph::get_trip_count`]),
+    /// its longest duration is that of the longest path through the loop, repeated for every
+    /// iteration. Otherwise, its longest duration is unbounded, and its shortest duration is that of
+    /// the shortest path through a single iteration.
+    fn get_loop_duration(
+        &self,
+        head: usize,
+        tails: &[usize],
+        body: &BTreeSet<usize>,
+        predecessors: &[Vec<usize>],
+    ) -> (f64, f64) {
+        // The loop must be entered only through its head, and must contain no further loops.
+        let is_reducible = body
+            .iter()
+            .filter(|region| **region != head)
+            .all(|region| predecessors[*region].iter().all(|p| body.contains(p)));
+        let iteration = self.depth_first_search(head, |_, to| body.contains(&to) && to != head);
+        if !is_reducible || !iteration.back_edges.is_empty() {
+            return (0.0, f64::INFINITY);
+        }
+
+        // The shortest and longest paths from the start of an iteration through the end of each region
+        let mut shortest = vec![f64::INFINITY; self.regions.len()];
+        let mut longest = vec![f64::NEG_INFINITY; self.regions.len()];
+        shortest[head] = self.regions[head].minimum;
+        longest[head] = self.regions[head].maximum;
+        for &region in iteration.postorder.iter().rev() {
+            for &successor in &self.regions[region].successors {
+                if body.contains(&successor) && successor != head {
+                    shortest[successor] =
+                        shortest[successor].min(shortest[region] + self.regions[successor].minimum);
+                    longest[successor] =
+                        longest[successor].max(longest[region] + self.regions[successor].maximum);
+                }
+            }
+        }
+
+        let exiting_regions = body
+            .iter()
+            .copied()
+            .filter(|region| {
+                self.regions[*region]
+                    .successors
+                    .iter()
+                    .any(|successor| !body.contains(successor))
+            })
+            .collect::<Vec<_>>();
+        let shortest_through = |regions: &[usize]| {
+            regions
+                .iter()
+                .map(|region| shortest[*region])
+                .fold(f64::INFINITY, f64::min)
+        };
+        let longest_through = |regions: &[usize]| {
+            regions
+                .iter()
+                .map(|region| longest[*region])
+                .fold(f64::NEG_INFINITY, f64::max)
+        };
+
+        match self.get_trip_count(head, tails, body, &exiting_regions, predecessors) {
+            Some((test, trip_count)) => {
+                // The test is reached on every iteration, but the loop exits there only on the last.
+                let repetitions = (trip_count - 1) as f64;
+                let (shortest_repetitions, longest_repetitions) = if repetitions > 0.0 {
+                    (
+                        shortest_through(tails) * repetitions,
+                        longest_through(tails) * repetitions,
+                    )
+                } else {
+                    (0.0, 0.0)
+                };
+                let early_exits = exiting_regions
+                    .iter()
+                    .copied()
+                    .filter(|region| *region != test)
+                    .collect::<Vec<_>>();
+
+                let minimum =
+                    (shortest_repetitions + shortest[test]).min(shortest_through(&early_exits));
+                let maximum = longest_repetitions + longest_through(&exiting_regions);
+                (minimum, maximum)
+            }
+            None => (shortest_through(&exiting_regions), f64::INFINITY),
+        }
+    }
+
+    /// Return the exiting region which tests the loop counter, and the number of times that test is
+    /// executed, if both can be determined statically.
+    ///
+    /// This recognizes loops such as those produced by [`Program::wrap_in_loop`], in which:
+    ///
+    /// * A single block, executed on every iteration, decrements a counter by one and then tests it
+    ///   with `JUMP-WHEN` or `JUMP-UNLESS`, remaining in the loop until the counter reaches zero
+    /// * The counter is written nowhere else within the loop
+    /// * The loop is entered from a single block, which initializes the counter with `MOVE`
+    fn get_trip_count(
+        &self,
+        head: usize,
+        tails: &[usize],
+        body: &BTreeSet<usize>,
+        exiting_regions: &[usize],
+        predecessors: &[Vec<usize>],
+    ) -> Option<(usize, u64)> {
+        let body_blocks = body
+            .iter()
+            .flat_map(|region| self.regions[*region].blocks.iter().copied())
+            .collect::<BTreeSet<_>>();
+        let entries = predecessors[head]
+            .iter()
+            .filter(|region| !body.contains(region))
+            .collect::<Vec<_>>();
+        let [&preheader] = entries[..] else {
+            return None;
+        };
+
+        exiting_regions.iter().copied().find_map(|test| {
+            // Collapsed loops, as well as the exit, are not basic blocks.
+            let block = self.blocks.get(test)?;
+            let BasicBlockTerminator::ConditionalJump {
+                condition,
+                target,
+                jump_if_condition_zero,
+            } = block.terminator()
+            else {
+                return None;
+            };
+
+            let jump_target = *self.labels.get(target)?;
+            let (zero_successor, nonzero_successor) = if *jump_if_condition_zero {
+                (jump_target, test + 1)
+            } else {
+                (test + 1, jump_target)
+            };
+            if body_blocks.contains(&zero_successor) || !body_blocks.contains(&nonzero_successor) {
+                return None;
+            }
+
+            let last_write = get_last_write(block.instructions(), condition)?;
+            if !is_decrement(last_write, condition) {
+                return None;
+            }
+
+            let write_count = body_blocks
+                .iter()
+                .flat_map(|index| self.blocks[*index].instructions())
+                .filter(|instruction| may_write(instruction, condition))
+                .count();
+            if write_count != 1 {
+                return None;
+            }
+
+            // Every iteration must pass through the test.
+            if test != head {
+                let avoiding_test = self.depth_first_search(head, |from, to| {
+                    from != test && body.contains(&to) && to != head
+                });
+                if tails
+                    .iter()
+                    .any(|tail| *tail != test && avoiding_test.postorder.contains(tail))
+                {
+                    return None;
+                }
+            }
+
+            let preheader = self.blocks.get(preheader)?;
+            match get_last_write(preheader.instructions(), condition)? {
+                Instruction::Move(Move {
+                    destination,
+                    source: ArithmeticOperand::LiteralInteger(initial_value),
+                }) if destination == *condition && *initial_value > 0 => {
+                    Some((test, *initial_value as u64))
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Return the shortest and longest paths from the entry of the (acyclic) graph to its exit.
+    fn get_bounds(&self) -> ProgramDurationBounds {
+        let search = self.depth_first_search(self.entry, |_, _| true);
+
+        // The shortest and longest paths from the start of each region to the exit
+        let mut shortest = vec![f64::INFINITY; self.regions.len()];
+        let mut longest = vec![f64::INFINITY; self.regions.len()];
+        for &region in &search.postorder {
+            if region == self.exit {
+                shortest[region] = 0.0;
+                longest[region] = 0.0;
+                continue;
+            }
+
+            let Region {
+                minimum,
+                maximum,
+                successors,
+                ..
+            } = &self.regions[region];
+            // A region without successors is a loop which never exits.
+            if !successors.is_empty() {
+                shortest[region] = minimum
+                    + successors
+                        .iter()
+                        .map(|successor| shortest[*successor])
+                        .fold(f64::INFINITY, f64::min);
+                longest[region] = maximum
+                    + successors
+                        .iter()
+                        .map(|successor| longest[*successor])
+                        .fold(f64::NEG_INFINITY, f64::max);
+            }
+        }
+
+        ProgramDurationBounds {
+            minimum: DurationBound::from_seconds(shortest[self.entry]),
+            maximum: DurationBound::from_seconds(longest[self.entry]),
+        }
+    }
+}
+
+/// Return whether the instruction may write to the memory region of the given reference.
+fn may_write(instruction: &Instruction, reference: &MemoryReference) -> bool {
+    match instruction.get_memory_accesses(&ExternSignatureMap::default()) {
+        Ok(accesses) => {
+            accesses.writes.contains(&reference.name) || accesses.captures.contains(&reference.name)
+        }
+        // An instruction whose accesses cannot be resolved, such as a `CALL`, may write anywhere.
+        Err(_) => true,
+    }
+}
+
+/// Return the last of the given instructions which may write to the given memory reference.
+fn get_last_write<'i>(
+    instructions: &[&'i Instruction],
+    reference: &MemoryReference,
+) -> Option<&'i Instruction> {
+    instructions
+        .iter()
+        .rev()
+        .find(|instruction| may_write(instruction, reference))
+        .copied()
+}
+
+/// Return whether the instruction decrements the given memory reference by exactly one.
+fn is_decrement(instruction: &Instruction, reference: &MemoryReference) -> bool {
+    matches!(
+        instruction,
+        Instruction::Arithmetic(Arithmetic {
+            operator: ArithmeticOperator::Subtract,
+            destination,
+            source: ArithmeticOperand::LiteralInteger(1),
+        }) | Instruction::Arithmetic(Arithmetic {
+            operator: ArithmeticOperator::Add,
+            destination,
+            source: ArithmeticOperand::LiteralInteger(-1),
+        }) if destination == reference
+    )
+}
+
+impl<'p> ControlFlowGraph<'p> {
+    /// Return the shortest and longest durations of the program, composing the duration of each
+    /// block's schedule along every path through the graph.
+    ///
+    /// Each block is scheduled with [`BasicBlock::as_schedule_seconds`], except that classical
+    /// instructions are taken to execute instantaneously. Loops with a statically-known iteration
+    /// count, such as those produced by [`Program::wrap_in_loop`], contribute their duration once
+    /// per iteration; all other loops make the longest duration unbounded.
+    pub fn get_duration_bounds(
+        &self,
+        program: &'p Program,
+    ) -> ProgramDurationResult<ProgramDurationBounds> {
+        let durations = self
+            .blocks()
+            .iter()
+            .enumerate()
+            .map(|(index, block)| {
+                block
+                    .try_as_schedule(program, |program, instruction| {
+                        Ok(ScheduledBasicBlock::get_instruction_duration_seconds(
+                            program,
+                            instruction,
+                        )?
+                        .or_else(|| {
+                            matches!(
+                                InstructionRole::from(instruction),
+                                InstructionRole::ClassicalCompute
+                            )
+                            .then_some(Seconds(0.0))
+                        }))
+                    })
+                    .map(|schedule| schedule.duration().clone())
+                    .map_err(|source| ProgramDurationError::BlockSchedule {
+                        index,
+                        source: Box::new(source),
+                    })
+            })
+            .collect::<ProgramDurationResult<Vec<_>>>()?;
+
+        self.get_duration_bounds_for_block_durations(&durations)
+    }
+
+    /// Return the shortest and longest durations of the program as in
+    /// [`ControlFlowGraph::get_duration_bounds`], given the duration of each block, where
+    /// `block_durations[i]` is the duration of the `i`th block of this graph.
+    pub fn get_duration_bounds_for_block_durations(
+        &self,
+        block_durations: &[Seconds],
+    ) -> ProgramDurationResult<ProgramDurationBounds> {
+        let mut graph = DurationGraph::new(self.blocks(), block_durations)?;
+        graph.collapse_loops();
+        Ok(graph.get_bounds())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::{
+        instruction::{MemoryReference, Target},
+        program::{analysis::ControlFlowGraph, scheduling::Seconds},
+        Program,
+    };
+
+    use super::{DurationBound, ProgramDurationBounds};
+
+    const DEFINITIONS: &str = r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DECLARE ro BIT
+DECLARE inner INTEGER
+"#;
+
+    fn get_duration_bounds(program: &Program) -> ProgramDurationBounds {
+        ControlFlowGraph::from(program)
+            .get_duration_bounds(program)
+            .unwrap()
+    }
+
+    fn bounds(minimum: Option<f64>, maximum: Option<f64>) -> ProgramDurationBounds {
+        let bound = |seconds: Option<f64>| {
+            seconds.map_or(DurationBound::Unbounded, |s| {
+                DurationBound::Bounded(Seconds(s))
+            })
+        };
+        ProgramDurationBounds {
+            minimum: bound(minimum),
+            maximum: bound(maximum),
+        }
+    }
+
+    #[rstest]
+    #[case::linear(
+        r#"PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+PULSE 0 "rf" flat(duration: 2.0, iq: 1)
+"#,
+        bounds(Some(3.0), Some(3.0))
+    )]
+    #[case::branch(
+        r#"PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+JUMP-WHEN @skip ro[0]
+PULSE 0 "rf" flat(duration: 2.0, iq: 1)
+LABEL @skip
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+"#,
+        bounds(Some(2.0), Some(4.0))
+    )]
+    #[case::halt(
+        r#"PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+JUMP-WHEN @end ro[0]
+HALT
+LABEL @end
+PULSE 0 "rf" flat(duration: 2.0, iq: 1)
+"#,
+        bounds(Some(1.0), Some(3.0))
+    )]
+    #[case::counted_loop(
+        r#"MOVE inner 4
+LABEL @loop
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+SUB inner 1
+JUMP-WHEN @loop inner
+"#,
+        bounds(Some(4.0), Some(4.0))
+    )]
+    #[case::measured_loop(
+        r#"LABEL @loop
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+JUMP-WHEN @loop ro[0]
+PULSE 0 "rf" flat(duration: 2.0, iq: 1)
+"#,
+        bounds(Some(3.0), None)
+    )]
+    #[case::counter_modified_in_loop(
+        r#"MOVE inner 4
+LABEL @loop
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+ADD inner 1
+SUB inner 1
+JUMP-WHEN @loop inner
+"#,
+        bounds(Some(1.0), None)
+    )]
+    #[case::infinite_loop(
+        r#"LABEL @loop
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+JUMP @loop
+"#,
+        bounds(None, None)
+    )]
+    fn duration_bounds(#[case] body: &str, #[case] expected: ProgramDurationBounds) {
+        let program: Program = format!("{DEFINITIONS}{body}").parse().unwrap();
+        assert_eq!(get_duration_bounds(&program), expected);
+    }
+
+    #[rstest]
+    #[case::flat(r#"PULSE 0 "rf" flat(duration: 1.0, iq: 1)"#, 3.0)]
+    #[case::nested(
+        r#"MOVE inner 2
+LABEL @inner
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+SUB inner 1
+JUMP-WHEN @inner inner
+"#,
+        6.0
+    )]
+    fn wrapped_in_loop(#[case] body: &str, #[case] expected: f64) {
+        let program: Program = format!("{DEFINITIONS}{body}").parse().unwrap();
+        let program = program.wrap_in_loop(
+            MemoryReference {
+                name: "outer".to_string(),
+                index: 0,
+            },
+            Target::Fixed("start".to_string()),
+            Target::Fixed("end".to_string()),
+            3,
+        );
+
+        assert_eq!(
+            get_duration_bounds(&program),
+            bounds(Some(expected), Some(expected))
+        );
+    }
+}
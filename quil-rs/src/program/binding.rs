@@ -0,0 +1,628 @@
+//! Binding memory values into a program as constants
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    expression::{
+        BinaryFunctionCallExpression, Expression, FunctionCallExpression, InfixExpression,
+        PrefixExpression,
+    },
+    instruction::{
+        AttributeValue, Calibration, CircuitDefinition, Declaration, GateSpecification,
+        Instruction, MeasureCalibrationDefinition, MemoryReference, ScalarType,
+    },
+    program::FrameSet,
+    quil::Quil,
+    real, Program,
+};
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum BindParametersError {
+    #[error("memory region {0} is not declared")]
+    UndeclaredRegion(String),
+
+    #[error(
+        "memory region {region} has type {}, but only REAL and INTEGER regions may be bound",
+        .data_type.to_quil_or_debug()
+    )]
+    UnsupportedType {
+        region: String,
+        data_type: ScalarType,
+    },
+
+    #[error("memory region {region} has length {expected}, but {actual} values were given")]
+    LengthMismatch {
+        region: String,
+        expected: u64,
+        actual: usize,
+    },
+
+    #[error(
+        "memory region {region} has type INTEGER, but value {value} was given at index {index}"
+    )]
+    NonIntegerValue {
+        region: String,
+        index: usize,
+        value: f64,
+    },
+
+    #[error("memory reference {} is out of bounds", .0.to_quil_or_debug())]
+    OutOfBounds(MemoryReference),
+
+    #[error(
+        "memory region {region} is written by {}, and so may not be bound to constant values",
+        .instruction.to_quil_or_debug()
+    )]
+    WrittenRegion {
+        region: String,
+        instruction: Instruction,
+    },
+
+    #[error("memory region {region} shares memory with {other}, and so may not be bound to constant values")]
+    SharedRegion { region: String, other: String },
+
+    #[error(
+        "the memory accessed by {} cannot be determined, and so no region may be bound",
+        .0.to_quil_or_debug()
+    )]
+    UnknownMemoryAccesses(Instruction),
+}
+
+impl Program {
+    /// Return a copy of this program in which every reference to the given memory regions within
+    /// an expression is replaced by its value, such as within gate parameters, waveform
+    /// invocations, `DELAY` durations, and frame updates, including within calibrations. Each
+    /// expression in which a value is substituted is then simplified.
+    ///
+    /// `values` maps the name of each `REAL` or `INTEGER` region to be bound to one value for each
+    /// of its elements. A region may not be bound if the program may change its value, whether by
+    /// writing to it, as with `MOVE`, `STORE`, `MEASURE`, or `CAPTURE`, or by writing to a region
+    /// with which it shares memory through a `SHARING` clause.
+    ///
+    /// If `remove_unused_declarations` is set, the declaration of each bound region is removed when
+    /// nothing in the resulting program refers to it any longer, including classical instructions
+    /// and the `SHARING` clauses of other declarations.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::{Program, quil::Quil};
+    /// use std::collections::HashMap;
+    /// use std::str::FromStr;
+    ///
+    /// let program = Program::from_str("DECLARE theta REAL\nRX(theta * 2) 0").unwrap();
+    /// let values = HashMap::from([("theta".to_string(), vec![0.25])]);
+    /// let bound = program.bind_parameters(&values, true).unwrap();
+    ///
+    /// assert_eq!(bound.to_quil().unwrap(), "RX(0.5) 0\n");
+    /// ```
+    pub fn bind_parameters(
+        &self,
+        values: &HashMap<String, Vec<f64>>,
+        remove_unused_declarations: bool,
+    ) -> Result<Self, BindParametersError> {
+        for (region, region_values) in values {
+            let declared = self
+                .memory_regions
+                .get(region)
+                .ok_or_else(|| BindParametersError::UndeclaredRegion(region.clone()))?;
+            let data_type = declared.size.data_type;
+            if !matches!(data_type, ScalarType::Real | ScalarType::Integer) {
+                return Err(BindParametersError::UnsupportedType {
+                    region: region.clone(),
+                    data_type,
+                });
+            }
+            if region_values.len() as u64 != declared.size.length {
+                return Err(BindParametersError::LengthMismatch {
+                    region: region.clone(),
+                    expected: declared.size.length,
+                    actual: region_values.len(),
+                });
+            }
+            if data_type == ScalarType::Integer {
+                if let Some((index, value)) = region_values
+                    .iter()
+                    .enumerate()
+                    .find(|(_, value)| value.fract() != 0.0)
+                {
+                    return Err(BindParametersError::NonIntegerValue {
+                        region: region.clone(),
+                        index,
+                        value: *value,
+                    });
+                }
+            }
+        }
+
+        self.check_regions_are_constant(values)?;
+
+        // Bind within a copy of the program, rather than one rebuilt from its instructions, so that
+        // its other settings carry over, and definitions are copied only if a value is bound within
+        // them.
+        let mut program = self.clone();
+        for instruction in &mut program.instructions {
+            bind_instruction(instruction, values)?;
+        }
+        if let Some(calibrations) =
+            bind_each(self.calibrations.iter_calibrations(), |calibration| {
+                bind_expressions(&mut calibration.identifier.parameters, values)?;
+                bind_instructions(&mut calibration.instructions, values)
+            })?
+        {
+            program.calibrations.calibrations = calibrations.into();
+        }
+        if let Some(measure_calibrations) = bind_each(
+            self.calibrations.iter_measure_calibrations(),
+            |calibration| bind_instructions(&mut calibration.instructions, values),
+        )? {
+            program.calibrations.measure_calibrations = measure_calibrations.into();
+        }
+        if let Some(frames) = bind_each(
+            self.frames.iter().map(|(_, attributes)| attributes),
+            |attributes| {
+                bind_expressions(
+                    attributes.values_mut().filter_map(|value| match value {
+                        AttributeValue::Expression(expression) => Some(expression),
+                        AttributeValue::String(_) => None,
+                    }),
+                    values,
+                )
+            },
+        )? {
+            program.frames = FrameSet::new();
+            for (identifier, attributes) in self
+                .frames
+                .iter()
+                .map(|(identifier, _)| identifier)
+                .zip(frames)
+            {
+                program.frames.insert(identifier.clone(), attributes);
+            }
+        }
+        if let Some(waveforms) = bind_each(self.waveforms.values(), |waveform| {
+            bind_expressions(&mut waveform.matrix, values)
+        })? {
+            program.waveforms = Arc::new(self.waveforms.keys().cloned().zip(waveforms).collect());
+        }
+        if let Some(gate_definitions) =
+            bind_each(
+                self.gate_definitions.values(),
+                |definition| match &mut definition.specification {
+                    GateSpecification::Matrix(matrix) => {
+                        bind_expressions(matrix.iter_mut().flatten(), values)
+                    }
+                    _ => Ok(()),
+                },
+            )?
+        {
+            program.gate_definitions = Arc::new(
+                self.gate_definitions
+                    .keys()
+                    .cloned()
+                    .zip(gate_definitions)
+                    .collect(),
+            );
+        }
+
+        if remove_unused_declarations {
+            let used = used_regions(&program.to_instructions(), &program);
+            program
+                .memory_regions
+                .retain(|name, _| !values.contains_key(name) || used.contains(name));
+        }
+
+        Ok(program)
+    }
+
+    /// Return an error if any of the regions to be bound shares memory with another region, or
+    /// is written by any instruction, including within calibrations.
+    fn check_regions_are_constant(
+        &self,
+        values: &HashMap<String, Vec<f64>>,
+    ) -> Result<(), BindParametersError> {
+        for (name, region) in &self.memory_regions {
+            let Some(sharing) = &region.sharing else {
+                continue;
+            };
+            for (region, other) in [(name, &sharing.name), (&sharing.name, name)] {
+                if values.contains_key(region) {
+                    return Err(BindParametersError::SharedRegion {
+                        region: region.clone(),
+                        other: other.clone(),
+                    });
+                }
+            }
+        }
+
+        let extern_signature_map = self
+            .try_extern_signature_map_from_pragma_map()
+            .unwrap_or_default();
+        // A stack of the instructions yet to be checked, in reverse order
+        let mut instructions = self.to_instructions();
+        instructions.reverse();
+        while let Some(instruction) = instructions.pop() {
+            match instruction {
+                Instruction::CalibrationDefinition(Calibration {
+                    instructions: nested,
+                    ..
+                })
+                | Instruction::CircuitDefinition(CircuitDefinition {
+                    instructions: nested,
+                    ..
+                })
+                | Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+                    instructions: nested,
+                    ..
+                }) => instructions.extend(nested.into_iter().rev()),
+                _ => {
+                    let accesses = instruction
+                        .get_memory_accesses(&extern_signature_map)
+                        .map_err(|_| {
+                            BindParametersError::UnknownMemoryAccesses(instruction.clone())
+                        })?;
+                    let mut written = accesses
+                        .writes
+                        .iter()
+                        .chain(&accesses.captures)
+                        .filter(|region| values.contains_key(*region))
+                        .collect::<Vec<_>>();
+                    written.sort();
+                    if let Some(region) = written.first() {
+                        return Err(BindParametersError::WrittenRegion {
+                            region: (*region).clone(),
+                            instruction,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Substitute bound values within the expressions of an instruction and any instructions nested
+/// within it.
+fn bind_instruction(
+    instruction: &mut Instruction,
+    values: &HashMap<String, Vec<f64>>,
+) -> Result<(), BindParametersError> {
+    let mut result = Ok(());
+    instruction.apply_to_expressions(|expression| {
+        if result.is_ok() {
+            match bind_expression(expression, values) {
+                Ok(true) => expression.simplify(),
+                Ok(false) => {}
+                Err(error) => result = Err(error),
+            }
+        }
+    });
+    result?;
+
+    match instruction {
+        Instruction::CalibrationDefinition(Calibration { instructions, .. })
+        | Instruction::CircuitDefinition(CircuitDefinition { instructions, .. })
+        | Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+            instructions,
+            ..
+        }) => bind_instructions(instructions, values),
+        _ => Ok(()),
+    }
+}
+
+/// Substitute bound values within each of the given instructions.
+fn bind_instructions(
+    instructions: &mut [Instruction],
+    values: &HashMap<String, Vec<f64>>,
+) -> Result<(), BindParametersError> {
+    instructions
+        .iter_mut()
+        .try_for_each(|instruction| bind_instruction(instruction, values))
+}
+
+/// Substitute bound values within each of the given expressions, simplifying those in which a
+/// value is substituted.
+fn bind_expressions<'a>(
+    expressions: impl IntoIterator<Item = &'a mut Expression>,
+    values: &HashMap<String, Vec<f64>>,
+) -> Result<(), BindParametersError> {
+    for expression in expressions {
+        if bind_expression(expression, values)? {
+            expression.simplify();
+        }
+    }
+    Ok(())
+}
+
+/// Apply `bind` to a copy of each of the given definitions, returning the copies only if any of
+/// them changed, so that a program may otherwise keep sharing its definitions with its clones.
+fn bind_each<'a, T>(
+    definitions: impl IntoIterator<Item = &'a T>,
+    mut bind: impl FnMut(&mut T) -> Result<(), BindParametersError>,
+) -> Result<Option<Vec<T>>, BindParametersError>
+where
+    T: Clone + PartialEq + 'a,
+{
+    let mut changed = false;
+    let bound = definitions
+        .into_iter()
+        .map(|definition| {
+            let mut bound = definition.clone();
+            bind(&mut bound)?;
+            changed |= bound != *definition;
+            Ok(bound)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(changed.then_some(bound))
+}
+
+/// Replace each bound memory reference within the expression by its value, returning whether any
+/// were replaced.
+fn bind_expression(
+    expression: &mut Expression,
+    values: &HashMap<String, Vec<f64>>,
+) -> Result<bool, BindParametersError> {
+    match expression {
+        Expression::Address(reference) => match values.get(&reference.name) {
+            Some(region_values) => {
+                let value = region_values
+                    .get(reference.index as usize)
+                    .ok_or_else(|| BindParametersError::OutOfBounds(reference.clone()))?;
+                *expression = Expression::Number(real!(*value));
+                Ok(true)
+            }
+            None => Ok(false),
+        },
+        Expression::FunctionCall(FunctionCallExpression { expression, .. })
+        | Expression::Prefix(PrefixExpression { expression, .. }) => {
+            bind_expression(expression, values)
+        }
+        Expression::BinaryFunctionCall(BinaryFunctionCallExpression { left, right, .. })
+        | Expression::Infix(InfixExpression { left, right, .. }) => {
+            let left = bind_expression(left, values)?;
+            let right = bind_expression(right, values)?;
+            Ok(left || right)
+        }
+        Expression::Number(_) | Expression::PiConstant | Expression::Variable(_) => Ok(false),
+    }
+}
+
+/// The names of the memory regions referred to by the given instructions, either through memory
+/// accesses or by the `SHARING` clause of a declaration.
+///
+/// If the memory accesses of an instruction cannot be determined, as for an unresolvable `CALL`,
+/// every declared region is considered used.
+fn used_regions(instructions: &[Instruction], program: &Program) -> HashSet<String> {
+    let extern_signature_map = program
+        .try_extern_signature_map_from_pragma_map()
+        .unwrap_or_default();
+    let mut used = HashSet::new();
+    for instruction in instructions {
+        match instruction {
+            Instruction::Declaration(Declaration {
+                sharing: Some(sharing),
+                ..
+            }) => {
+                used.insert(sharing.name.clone());
+            }
+            Instruction::Declaration(_) => {}
+            _ => match instruction.get_memory_accesses(&extern_signature_map) {
+                Ok(accesses) => used.extend(
+                    accesses
+                        .reads
+                        .into_iter()
+                        .chain(accesses.writes)
+                        .chain(accesses.captures),
+                ),
+                Err(_) => used.extend(program.memory_regions.keys().cloned()),
+            },
+        }
+    }
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+    use rstest::rstest;
+
+    use crate::{
+        instruction::{CalibrationParameterMatching, Instruction, MemoryReference, ScalarType},
+        quil::Quil,
+        Program,
+    };
+
+    use super::BindParametersError;
+
+    const PROGRAM: &str = r#"DECLARE theta REAL[2]
+DECLARE count INTEGER
+DECLARE ro BIT
+DEFCAL RX(%angle) 0:
+    SHIFT-PHASE 0 "rf" theta[1] * %angle
+RX(theta[0] / 2) 0
+SET-SCALE 0 "rf" count * 0.5
+DELAY 0 "rf" theta[1]
+MEASURE 0 ro
+"#;
+
+    #[rstest]
+    #[case(false, &["theta", "count", "ro"])]
+    #[case(true, &["ro"])]
+    fn bind_parameters(
+        #[case] remove_unused_declarations: bool,
+        #[case] expected_regions: &[&str],
+    ) {
+        let program = Program::from_str(PROGRAM).unwrap();
+        let values = HashMap::from([
+            ("theta".to_string(), vec![1.0, 2.0]),
+            ("count".to_string(), vec![3.0]),
+        ]);
+        let bound = program
+            .bind_parameters(&values, remove_unused_declarations)
+            .unwrap();
+
+        let body = bound
+            .body_instructions()
+            .map(|instruction| instruction.to_quil().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            body,
+            [
+                "RX(0.5) 0",
+                r#"SET-SCALE 0 "rf" 1.5"#,
+                r#"DELAY 0 "rf" 2"#,
+                "MEASURE 0 ro[0]"
+            ]
+        );
+
+        let calibration = bound.calibrations.iter_calibrations().next().unwrap();
+        assert_eq!(
+            calibration.instructions[0].to_quil().unwrap(),
+            r#"SHIFT-PHASE 0 "rf" 2*%angle"#
+        );
+
+        let regions = bound.memory_regions.keys().collect::<Vec<_>>();
+        assert_eq!(regions, expected_regions);
+    }
+
+    #[test]
+    fn keeps_program_settings_and_shared_definitions() {
+        let mut program = Program::from_str(
+            r#"DECLARE theta REAL
+DEFWAVEFORM custom:
+    1, 1
+DEFCAL RX(-3*pi/2) 0:
+    PULSE 0 "rf" custom
+RX(theta * pi) 0
+"#,
+        )
+        .unwrap();
+        program.calibrations.parameter_matching =
+            CalibrationParameterMatching::AngleModulo2Pi { tolerance: 1e-9 };
+        let values = HashMap::from([("theta".to_string(), vec![0.5])]);
+        let bound = program.bind_parameters(&values, true).unwrap();
+
+        assert_eq!(
+            bound.calibrations.parameter_matching,
+            program.calibrations.parameter_matching
+        );
+        assert!(Arc::ptr_eq(&bound.waveforms, &program.waveforms));
+        assert!(bound
+            .calibrations
+            .calibrations
+            .shares_data_with(&program.calibrations.calibrations));
+
+        let expanded = bound.expand_calibrations().unwrap();
+        let body = expanded
+            .body_instructions()
+            .map(|instruction| instruction.to_quil().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(body, [r#"PULSE 0 "rf" custom"#]);
+    }
+
+    #[test]
+    fn keeps_declarations_still_in_use() {
+        let program = Program::from_str(
+            "DECLARE theta REAL\nDECLARE sum REAL\nDECLARE phi REAL\nRX(theta) 0\nRZ(phi) 0\nADD sum theta\n",
+        )
+        .unwrap();
+        let values = HashMap::from([
+            ("theta".to_string(), vec![1.0]),
+            ("phi".to_string(), vec![2.0]),
+        ]);
+        let bound = program.bind_parameters(&values, true).unwrap();
+        assert_eq!(
+            bound.to_quil().unwrap(),
+            "DECLARE theta REAL[1]\nDECLARE sum REAL[1]\nRX(1) 0\nRZ(2) 0\nADD sum[0] theta[0]\n"
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "DECLARE theta REAL\nRX(theta) 0\nMOVE theta 1.0\n",
+        BindParametersError::WrittenRegion {
+            region: "theta".to_string(),
+            instruction: Instruction::from_str("MOVE theta 1.0").unwrap(),
+        }
+    )]
+    #[case(
+        "DECLARE theta INTEGER\nMEASURE 0 theta\n",
+        BindParametersError::WrittenRegion {
+            region: "theta".to_string(),
+            instruction: Instruction::from_str("MEASURE 0 theta").unwrap(),
+        }
+    )]
+    #[case(
+        "DECLARE theta REAL\nDEFCAL RX(%angle) 0:\n    STORE theta theta[0] 1.0\nRX(theta) 0\n",
+        BindParametersError::WrittenRegion {
+            region: "theta".to_string(),
+            instruction: Instruction::from_str("STORE theta theta[0] 1.0").unwrap(),
+        }
+    )]
+    #[case(
+        "DECLARE theta REAL\nDECLARE alias REAL SHARING theta\nRX(theta) 0\n",
+        BindParametersError::SharedRegion { region: "theta".to_string(), other: "alias".to_string() }
+    )]
+    #[case(
+        "DECLARE base REAL\nDECLARE theta REAL SHARING base\nRX(theta) 0\n",
+        BindParametersError::SharedRegion { region: "theta".to_string(), other: "base".to_string() }
+    )]
+    #[case(
+        "DECLARE theta REAL\nCALL unknown theta\n",
+        BindParametersError::UnknownMemoryAccesses(Instruction::from_str("CALL unknown theta").unwrap())
+    )]
+    fn rejects_regions_which_may_change(
+        #[case] program: &str,
+        #[case] expected: BindParametersError,
+    ) {
+        let program = Program::from_str(program).unwrap();
+        let values = HashMap::from([("theta".to_string(), vec![1.0])]);
+        assert_eq!(program.bind_parameters(&values, false), Err(expected));
+    }
+
+    #[rstest]
+    #[case("missing", vec![1.0], BindParametersError::UndeclaredRegion("missing".to_string()))]
+    #[case("ro", vec![1.0], BindParametersError::UnsupportedType { region: "ro".to_string(), data_type: ScalarType::Bit })]
+    #[case("theta", vec![1.0], BindParametersError::LengthMismatch { region: "theta".to_string(), expected: 2, actual: 1 })]
+    #[case("count", vec![1.5], BindParametersError::NonIntegerValue { region: "count".to_string(), index: 0, value: 1.5 })]
+    fn invalid_values(
+        #[case] region: &str,
+        #[case] values: Vec<f64>,
+        #[case] expected: BindParametersError,
+    ) {
+        let program = Program::from_str(PROGRAM).unwrap();
+        let values = HashMap::from([(region.to_string(), values)]);
+        assert_eq!(program.bind_parameters(&values, false), Err(expected));
+    }
+
+    #[test]
+    fn out_of_bounds_reference() {
+        let program = Program::from_str("DECLARE theta REAL\nRX(theta[3]) 0\n").unwrap();
+        let values = HashMap::from([("theta".to_string(), vec![1.0])]);
+        assert_eq!(
+            program.bind_parameters(&values, false),
+            Err(BindParametersError::OutOfBounds(MemoryReference {
+                name: "theta".to_string(),
+                index: 3,
+            }))
+        );
+    }
+}
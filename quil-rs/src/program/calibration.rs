@@ -18,25 +18,33 @@ use std::ops::Range;
 use itertools::FoldWhile::{Continue, Done};
 use itertools::Itertools;
 
-use crate::instruction::{CalibrationIdentifier, MeasureCalibrationIdentifier};
+use crate::instruction::{CalibrationIdentifier, GateCalibrationKey, MeasureCalibrationIdentifier};
 use crate::quil::Quil;
 use crate::{
     expression::Expression,
     instruction::{
-        Calibration, Capture, Delay, Fence, FrameIdentifier, Gate, Instruction,
-        MeasureCalibrationDefinition, Measurement, Pulse, Qubit, RawCapture, SetFrequency,
-        SetPhase, SetScale, ShiftFrequency, ShiftPhase,
+        Calibration, CalibrationParameterMatching, Capture, Delay, Fence, FrameIdentifier, Gate,
+        Instruction, MeasureCalibrationDefinition, Measurement, Pulse, Qubit, RawCapture,
+        SetFrequency, SetPhase, SetScale, ShiftFrequency, ShiftPhase,
     },
 };
 
 use super::source_map::{SourceMap, SourceMapEntry, SourceMapIndexable};
 use super::{CalibrationSet, InstructionIndex, ProgramError};
 
+/// The greatest number of fixed qubits in a gate for which candidate calibrations are found using the
+/// index of a [`CalibrationSet`], since the number of keys to look up doubles with each fixed qubit.
+const MAX_INDEXED_FIXED_QUBIT_COUNT: usize = 8;
+
 /// A collection of Quil calibrations (`DEFCAL` instructions) with utility methods.
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Calibrations {
     pub calibrations: CalibrationSet<Calibration>,
     pub measure_calibrations: CalibrationSet<MeasureCalibrationDefinition>,
+
+    /// How the fixed parameters of a calibration are compared to those of a gate; by default,
+    /// only exactly equal parameters match, per the Quil-T specification.
+    pub parameter_matching: CalibrationParameterMatching,
 }
 
 struct MatchedCalibration<'a> {
@@ -191,6 +199,60 @@ impl From<MeasureCalibrationIdentifier> for CalibrationSource {
     }
 }
 
+type InstructionPredicate<'a> = Box<dyn Fn(&Instruction) -> bool + 'a>;
+
+/// Selects which instructions are expanded by [`Calibrations::expand_with_filter`] and
+/// [`Program::expand_calibrations_with_filter`](super::Program::expand_calibrations_with_filter),
+/// such that intermediate stages of expansion may be inspected.
+///
+/// An instruction is expanded only if it satisfies every predicate and lies at a depth less than
+/// the maximum depth, if any, where the instructions being expanded are at depth 0 and those
+/// produced by expanding an instruction at depth `n` are at depth `n + 1`. The default filter
+/// expands every instruction with a matching calibration, recursively.
+#[derive(Default)]
+pub struct CalibrationExpansionFilter<'a> {
+    predicates: Vec<InstructionPredicate<'a>>,
+    max_depth: Option<usize>,
+}
+
+impl<'a> CalibrationExpansionFilter<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expand only those instructions for which `predicate` returns `true`, in addition to any
+    /// previously given predicates.
+    pub fn with_predicate(mut self, predicate: impl Fn(&Instruction) -> bool + 'a) -> Self {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Expand only those instructions at a depth less than `max_depth`; a maximum depth of `1`
+    /// expands instructions once, without expanding the instructions which that produces.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Whether an instruction at the given depth should be expanded.
+    fn allows(&self, instruction: &Instruction, depth: usize) -> bool {
+        self.max_depth.map_or(true, |max_depth| depth < max_depth)
+            && self
+                .predicates
+                .iter()
+                .all(|predicate| predicate(instruction))
+    }
+}
+
+impl std::fmt::Debug for CalibrationExpansionFilter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CalibrationExpansionFilter")
+            .field("predicates", &self.predicates.len())
+            .field("max_depth", &self.max_depth)
+            .finish()
+    }
+}
+
 impl Calibrations {
     /// Return a vector containing a reference to all [`Calibration`]s in the set.
     pub fn calibrations(&self) -> Vec<&Calibration> {
@@ -224,8 +286,13 @@ impl Calibrations {
         instruction: &Instruction,
         previous_calibrations: &[Instruction],
     ) -> Result<Option<Vec<Instruction>>, ProgramError> {
-        self.expand_inner(instruction, previous_calibrations, false)
-            .map(|expansion| expansion.map(|expansion| expansion.new_instructions))
+        self.expand_inner(
+            instruction,
+            previous_calibrations,
+            false,
+            &CalibrationExpansionFilter::default(),
+        )
+        .map(|expansion| expansion.map(|expansion| expansion.new_instructions))
     }
 
     /// Given an instruction, return the instructions to which it is expanded if there is a match.
@@ -238,7 +305,26 @@ impl Calibrations {
         instruction: &Instruction,
         previous_calibrations: &[Instruction],
     ) -> Result<Option<CalibrationExpansionOutput>, ProgramError> {
-        self.expand_inner(instruction, previous_calibrations, true)
+        self.expand_inner(
+            instruction,
+            previous_calibrations,
+            true,
+            &CalibrationExpansionFilter::default(),
+        )
+    }
+
+    /// Like [`Self::expand_with_detail`], but expand only those instructions allowed by `filter`,
+    /// leaving the others unchanged. The depth of `instruction` is the length of
+    /// `previous_calibrations`.
+    ///
+    /// Return `None` if `instruction` itself is not expanded.
+    pub fn expand_with_filter(
+        &self,
+        instruction: &Instruction,
+        previous_calibrations: &[Instruction],
+        filter: &CalibrationExpansionFilter<'_>,
+    ) -> Result<Option<CalibrationExpansionOutput>, ProgramError> {
+        self.expand_inner(instruction, previous_calibrations, true, filter)
     }
 
     /// Expand an instruction, returning an error if a calibration directly or indirectly
@@ -249,12 +335,17 @@ impl Calibrations {
     /// * `instruction` - The instruction to expand.
     /// * `previous_calibrations` - The calibrations that were invoked to yield this current instruction.
     /// * `build_source_map` - Whether to build a source map of the expansion.
+    /// * `filter` - Which instructions to expand.
     fn expand_inner(
         &self,
         instruction: &Instruction,
         previous_calibrations: &[Instruction],
         build_source_map: bool,
+        filter: &CalibrationExpansionFilter<'_>,
     ) -> Result<Option<CalibrationExpansionOutput>, ProgramError> {
+        if !filter.allows(instruction, previous_calibrations.len()) {
+            return Ok(None);
+        }
         if previous_calibrations.contains(instruction) {
             return Err(ProgramError::RecursiveCalibration(instruction.clone()));
         }
@@ -401,7 +492,12 @@ impl Calibrations {
         calibration_path.push(instruction.clone());
         calibration_path.extend_from_slice(previous_calibrations);
 
-        self.recursively_expand_inner(expansion_result, &calibration_path, build_source_map)
+        self.recursively_expand_inner(
+            expansion_result,
+            &calibration_path,
+            build_source_map,
+            filter,
+        )
     }
 
     fn recursively_expand_inner(
@@ -409,6 +505,7 @@ impl Calibrations {
         expansion_result: Option<(Vec<Instruction>, CalibrationSource)>,
         calibration_path: &[Instruction],
         build_source_map: bool,
+        filter: &CalibrationExpansionFilter<'_>,
     ) -> Result<Option<CalibrationExpansionOutput>, ProgramError> {
         Ok(match expansion_result {
             Some((instructions, matched_calibration)) => {
@@ -422,8 +519,12 @@ impl Calibrations {
                 };
 
                 for (expanded_index, instruction) in instructions.into_iter().enumerate() {
-                    let expanded_instructions =
-                        self.expand_inner(&instruction, calibration_path, build_source_map)?;
+                    let expanded_instructions = self.expand_inner(
+                        &instruction,
+                        calibration_path,
+                        build_source_map,
+                        filter,
+                    )?;
                     match expanded_instructions {
                         Some(mut output) => {
                             if build_source_map {
@@ -525,28 +626,83 @@ impl Calibrations {
     /// 3. It has the same qubit count (any mix of fixed & variable)
     /// 4. It has the same parameter count (both specified and unspecified)
     /// 5. All fixed qubits in the calibration definition match those in the gate
-    /// 6. All specified parameters in the calibration definition match those in the gate
+    /// 6. All specified parameters in the calibration definition match those in the gate, as
+    ///    determined by [`Calibrations::parameter_matching`]
+    ///
+    /// If multiple calibrations match the gate, the one with the most fixed qubits wins; in the case
+    /// of multiple calibrations with equal precedence, the last one wins.
     pub fn get_match_for_gate(&self, gate: &Gate) -> Option<&Calibration> {
-        let mut matched_calibration: Option<MatchedCalibration> = None;
-
-        for calibration in self
-            .iter_calibrations()
-            .filter(|calibration| calibration.identifier.matches(gate))
+        let mut matched_calibration: Option<(usize, MatchedCalibration)> = None;
+
+        for (position, calibration) in
+            self.iter_candidate_calibrations_for_gate(gate)
+                .filter(|(_, calibration)| {
+                    calibration
+                        .identifier
+                        .matches_with(gate, self.parameter_matching)
+                })
         {
             matched_calibration = match matched_calibration {
-                None => Some(MatchedCalibration::new(calibration)),
-                Some(previous_match) => {
+                None => Some((position, MatchedCalibration::new(calibration))),
+                Some((previous_position, previous_match)) => {
                     let potential_match = MatchedCalibration::new(calibration);
-                    if potential_match.fixed_qubit_count >= previous_match.fixed_qubit_count {
-                        Some(potential_match)
+                    if (potential_match.fixed_qubit_count, position)
+                        > (previous_match.fixed_qubit_count, previous_position)
+                    {
+                        Some((position, potential_match))
                     } else {
-                        Some(previous_match)
+                        Some((previous_position, previous_match))
                     }
                 }
             }
         }
 
-        matched_calibration.map(|m| m.calibration)
+        matched_calibration.map(|(_, m)| m.calibration)
+    }
+
+    /// Return the calibrations which may match the given gate, along with their positions within
+    /// the set, using the index of [`GateCalibrationKey`]s rather than scanning every calibration.
+    ///
+    /// A calibration qubit matches a gate qubit if it is variable or fixed to the same index, so the
+    /// candidates are those indexed under any combination of the gate's fixed qubits being either
+    /// fixed or variable. If the gate has too many fixed qubits to enumerate those combinations,
+    /// every calibration is a candidate.
+    fn iter_candidate_calibrations_for_gate<'a>(
+        &'a self,
+        gate: &Gate,
+    ) -> Box<dyn Iterator<Item = (usize, &'a Calibration)> + 'a> {
+        let key = GateCalibrationKey::new(
+            &gate.name,
+            &gate.modifiers,
+            gate.parameters.len(),
+            &gate.qubits,
+        );
+        let fixed_qubit_positions = key
+            .fixed_qubits
+            .iter()
+            .positions(Option::is_some)
+            .collect::<Vec<_>>();
+        if fixed_qubit_positions.len() > MAX_INDEXED_FIXED_QUBIT_COUNT {
+            return Box::new(self.iter_calibrations().enumerate());
+        }
+
+        let candidate_keys = fixed_qubit_positions
+            .into_iter()
+            .powerset()
+            .map(move |variable_positions| {
+                let mut candidate_key = key.clone();
+                for position in variable_positions {
+                    candidate_key.fixed_qubits[position] = None;
+                }
+                candidate_key
+            })
+            .collect::<Vec<_>>();
+
+        Box::new(
+            candidate_keys
+                .into_iter()
+                .flat_map(move |key| self.calibrations.iter_key(&key).collect::<Vec<_>>()),
+        )
     }
 
     /// Return the count of contained calibrations.
@@ -619,6 +775,7 @@ impl Calibrations {
 mod tests {
     use std::str::FromStr;
 
+    use crate::instruction::{CalibrationParameterMatching, Instruction};
     use crate::program::calibration::{CalibrationSource, MeasureCalibrationIdentifier};
     use crate::program::source_map::{SourceMap, SourceMapEntry};
     use crate::program::{InstructionIndex, Program};
@@ -629,6 +786,9 @@ mod tests {
 
     use super::{CalibrationExpansion, CalibrationExpansionOutput, CalibrationIdentifier};
 
+    const ANGLE_MATCHING: CalibrationParameterMatching =
+        CalibrationParameterMatching::AngleModulo2Pi { tolerance: 1e-9 };
+
     #[rstest]
     #[case(
         "Calibration-Param-Precedence",
@@ -908,4 +1068,112 @@ X 1";
         let b = Program::from_str(input_b);
         assert_ne!(a, b);
     }
+
+    #[rstest]
+    #[case("CZ 0 1", Some("PRAGMA FIXED"))]
+    #[case("CZ 2 1", Some("PRAGMA SECOND_FIXED"))]
+    #[case("CZ 0 2", Some("PRAGMA FIRST_FIXED"))]
+    #[case("CZ 2 3", Some("PRAGMA VARIABLE"))]
+    #[case("CZ q 1", Some("PRAGMA SECOND_FIXED"))]
+    #[case("CZ q r", Some("PRAGMA VARIABLE"))]
+    #[case("CZ 0", None)]
+    #[case("CPHASE(pi) 0 1", None)]
+    fn test_get_match_for_gate_precedence(#[case] gate: &str, #[case] expected: Option<&str>) {
+        let program = Program::from_str(&format!(
+            "DEFCAL CZ a b:
+    PRAGMA VARIABLE
+DEFCAL CZ 0 1:
+    PRAGMA FIXED
+DEFCAL CZ a 1:
+    PRAGMA SECOND_FIXED
+DEFCAL CZ 0 b:
+    PRAGMA FIRST_FIXED
+DEFCAL CZ a b:
+    PRAGMA VARIABLE
+{gate}"
+        ))
+        .unwrap();
+        let Some(Instruction::Gate(gate)) = program.body_instructions().next() else {
+            panic!("expected a gate")
+        };
+
+        let matched = program
+            .calibrations
+            .get_match_for_gate(gate)
+            .map(|calibration| calibration.instructions[0].to_quil_or_debug());
+        assert_eq!(matched.as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("X 0", Some("PRAGMA BARE"))]
+    #[case("DAGGER X 0", Some("PRAGMA INVERSE"))]
+    #[case("CONTROLLED X 1 0", Some("PRAGMA CONTROL"))]
+    #[case("DAGGER DAGGER X 0", None)]
+    #[case("CONTROLLED X 0 1", None)]
+    fn test_get_match_for_gate_modifiers(#[case] gate: &str, #[case] expected: Option<&str>) {
+        let program = Program::from_str(&format!(
+            "DEFCAL X 0:
+    PRAGMA BARE
+DEFCAL DAGGER X 0:
+    PRAGMA INVERSE
+DEFCAL CONTROLLED X 1 0:
+    PRAGMA CONTROL
+{gate}"
+        ))
+        .unwrap();
+        assert_eq!(program.calibrations.len(), 3);
+        let Some(Instruction::Gate(gate)) = program.body_instructions().next() else {
+            panic!("expected a gate")
+        };
+
+        let matched = program
+            .calibrations
+            .get_match_for_gate(gate)
+            .map(|calibration| calibration.instructions[0].to_quil_or_debug());
+        assert_eq!(matched.as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("RX(-3*pi/2) 0", CalibrationParameterMatching::Exact, None)]
+    #[case(
+        "RX(1.5707963267948966) 0",
+        CalibrationParameterMatching::Exact,
+        Some("PRAGMA HALF_PI")
+    )]
+    #[case("RX(pi/2) 0", ANGLE_MATCHING, Some("PRAGMA HALF_PI"))]
+    #[case("RX(-3*pi/2) 0", ANGLE_MATCHING, Some("PRAGMA HALF_PI"))]
+    #[case("RX(5*pi/2) 0", ANGLE_MATCHING, Some("PRAGMA HALF_PI"))]
+    #[case("RX(pi/2 + 1e-12) 0", ANGLE_MATCHING, Some("PRAGMA HALF_PI"))]
+    #[case("RX(pi/2 + 1e-6) 0", ANGLE_MATCHING, None)]
+    #[case("RX(-pi) 0", ANGLE_MATCHING, Some("PRAGMA PI"))]
+    #[case(
+        "RX(2*pi/2) 0",
+        CalibrationParameterMatching::Canonical,
+        Some("PRAGMA PI")
+    )]
+    #[case("RX(-pi) 0", CalibrationParameterMatching::Canonical, None)]
+    fn test_get_match_for_gate_parameter_matching(
+        #[case] gate: &str,
+        #[case] parameter_matching: CalibrationParameterMatching,
+        #[case] expected: Option<&str>,
+    ) {
+        let mut program = Program::from_str(&format!(
+            "DEFCAL RX(1.5707963267948966) 0:
+    PRAGMA HALF_PI
+DEFCAL RX(pi) 0:
+    PRAGMA PI
+{gate}"
+        ))
+        .unwrap();
+        program.calibrations.parameter_matching = parameter_matching;
+        let Some(Instruction::Gate(gate)) = program.body_instructions().next() else {
+            panic!("expected a gate")
+        };
+
+        let matched = program
+            .calibrations
+            .get_match_for_gate(gate)
+            .map(|calibration| calibration.instructions[0].to_quil_or_debug());
+        assert_eq!(matched.as_deref(), expected);
+    }
 }
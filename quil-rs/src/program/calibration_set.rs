@@ -1,4 +1,6 @@
-use crate::instruction::CalibrationSignature;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::instruction::{CalibrationIndexKey, CalibrationSignature};
 
 /// A [`CalibrationSet`] is a collection of calibration instructions that respect how
 /// calibrations work in a Quil program.
@@ -9,59 +11,104 @@ use crate::instruction::CalibrationSignature;
 /// signature conflicts, [`CalibrationSet`] takes the liberty of only allowing one calibration
 /// per [`CalibrationSignature`].
 ///
-/// Calibrations maintain insertion order
-#[derive(Clone, Debug, PartialEq)]
-pub struct CalibrationSet<T> {
-    // The amount of calibrations in a program tends to be small enough that a Vec is more
-    // performant than a typical set.
-    // Sets also have trait bounds that `Instruction`s don't meet, which hampers utility.
-    data: Vec<T>,
+/// Calibrations maintain insertion order, and are indexed by their [`CalibrationIndexKey`] so
+/// that lookups need not scan the entire set.
+///
+/// Cloning a [`CalibrationSet`] is cheap: clones share their calibrations until one of them is
+/// modified, so that a large set of calibrations may be used by many programs at once.
+#[derive(Clone)]
+pub struct CalibrationSet<T>
+where
+    T: CalibrationIndexKey,
+{
+    // Sets have trait bounds that `Instruction`s don't meet, which hampers utility, so the
+    // calibrations themselves are kept in a Vec.
+    data: Arc<Vec<T>>,
+
+    // The positions within `data` of the calibrations with each key, in insertion order.
+    index: Arc<HashMap<T::Key, Vec<usize>>>,
+}
+
+// The index is derived entirely from the data, so it is omitted from comparison and debug output.
+impl<T> PartialEq for CalibrationSet<T>
+where
+    T: CalibrationIndexKey + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<T> std::fmt::Debug for CalibrationSet<T>
+where
+    T: CalibrationIndexKey + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CalibrationSet")
+            .field("data", &self.data)
+            .finish()
+    }
 }
 
 impl<T> Default for CalibrationSet<T>
 where
-    T: CalibrationSignature,
+    T: CalibrationIndexKey,
 {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T> IntoIterator for CalibrationSet<T> {
+// Since a set may share its calibrations with its clones, taking ownership of them requires
+// that they may be copied.
+impl<T> IntoIterator for CalibrationSet<T>
+where
+    T: CalibrationIndexKey + Clone,
+{
     type IntoIter = std::vec::IntoIter<Self::Item>;
     type Item = T;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.data.into_iter()
+        Arc::try_unwrap(self.data)
+            .unwrap_or_else(|data| (*data).clone())
+            .into_iter()
     }
 }
 
 impl<T> From<Vec<T>> for CalibrationSet<T>
 where
-    T: CalibrationSignature,
+    T: CalibrationIndexKey,
 {
-    fn from(data: Vec<T>) -> Self {
-        let mut set = Self::with_capacity(data.len());
-        for element in data {
-            set.replace(element);
+    fn from(elements: Vec<T>) -> Self {
+        let mut data = Vec::with_capacity(elements.len());
+        let mut index = HashMap::new();
+        for element in elements {
+            replace_within(&mut data, &mut index, element);
+        }
+        Self {
+            data: Arc::new(data),
+            index: Arc::new(index),
         }
-        set
     }
 }
 
 impl<T> CalibrationSet<T>
 where
-    T: CalibrationSignature,
+    T: CalibrationIndexKey,
 {
     /// Creates an empty [`ProgramCalibrationSet`].
     pub fn new() -> Self {
-        Self { data: Vec::new() }
+        Self {
+            data: Arc::new(Vec::new()),
+            index: Arc::new(HashMap::new()),
+        }
     }
 
     /// Creates a [`InnerCalibrationSet`] with the specified capacity.
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            data: Vec::with_capacity(capacity),
+            data: Arc::new(Vec::with_capacity(capacity)),
+            index: Arc::new(HashMap::new()),
         }
     }
 
@@ -85,6 +132,16 @@ where
         self.data.iter()
     }
 
+    /// Returns an iterator of the values indexed under the given key, in insertion order, along
+    /// with the position of each within the set.
+    pub fn iter_key(&self, key: &T::Key) -> impl Iterator<Item = (usize, &T)> + '_ {
+        self.index
+            .get(key)
+            .into_iter()
+            .flatten()
+            .map(|position| (*position, &self.data[*position]))
+    }
+
     /// Get a reference to a value that has a matching signature, if it exists.
     pub fn get(&self, signature: &<T as CalibrationSignature>::Signature<'_>) -> Option<&T> {
         if let Some(index) = self.signature_position(signature) {
@@ -94,50 +151,91 @@ where
         }
     }
 
+    /// Returns whether this set shares its calibrations with `other`, as a clone does until either
+    /// is modified.
+    pub fn shares_data_with(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+
+    /// Returns the index of an element whose [`CalibrationSignature`] matches the given value, if one exists.
+    pub(crate) fn signature_position(
+        &self,
+        signature: &<T as CalibrationSignature>::Signature<'_>,
+    ) -> Option<usize> {
+        self.iter_key(&T::key_for_signature(signature))
+            .find(|(_, element)| element.has_signature(signature))
+            .map(|(position, _)| position)
+    }
+}
+
+/// Modifying a set copies its calibrations if they are shared with a clone of the set, and so
+/// requires that they may be copied.
+impl<T> CalibrationSet<T>
+where
+    T: CalibrationIndexKey + Clone,
+{
     /// Adds a value to the set, replacing and returning an existing value with a matching
     /// [`CalibrationSignature`], if it exists.
     pub fn replace(&mut self, value: T) -> Option<T> {
-        let position = self.signature_position(&value.signature());
-        if let Some(index) = position {
-            let replaced = std::mem::replace(&mut self.data[index], value);
-            Some(replaced)
-        } else {
-            self.data.push(value);
-            None
-        }
+        replace_within(
+            Arc::make_mut(&mut self.data),
+            Arc::make_mut(&mut self.index),
+            value,
+        )
     }
 
     /// Removes a value from the set. Returns whether the value was present in the set.
     pub fn remove(&mut self, signature: &<T as CalibrationSignature>::Signature<'_>) -> bool {
         if let Some(index) = self.signature_position(signature) {
-            self.data.remove(index);
+            Arc::make_mut(&mut self.data).remove(index);
+            self.rebuild_index();
             true
         } else {
             false
         }
     }
 
-    /// Returns the index of an element whose [`CalibrationSignature`] matches the given value, if one exists.
-    fn signature_position(
-        &self,
-        signature: &<T as CalibrationSignature>::Signature<'_>,
-    ) -> Option<usize> {
-        for (i, element) in self.data.iter().enumerate() {
-            if element.has_signature(signature) {
-                return Some(i);
-            }
+    /// Rebuild the index from scratch, as is necessary when the positions of elements change.
+    fn rebuild_index(&mut self) {
+        let mut index = HashMap::<_, Vec<_>>::new();
+        for (position, element) in self.data.iter().enumerate() {
+            index.entry(element.key()).or_default().push(position);
         }
+        self.index = Arc::new(index);
+    }
+}
 
+/// Add `value` to the calibrations `data`, indexed by `index`, replacing and returning an existing
+/// value with a matching [`CalibrationSignature`], if it exists.
+fn replace_within<T>(
+    data: &mut Vec<T>,
+    index: &mut HashMap<T::Key, Vec<usize>>,
+    value: T,
+) -> Option<T>
+where
+    T: CalibrationIndexKey,
+{
+    let position = {
+        let signature = value.signature();
+        index
+            .get(&T::key_for_signature(&signature))
+            .into_iter()
+            .flatten()
+            .copied()
+            .find(|position| data[*position].has_signature(&signature))
+    };
+    if let Some(position) = position {
+        Some(std::mem::replace(&mut data[position], value))
+    } else {
+        index.entry(value.key()).or_default().push(data.len());
+        data.push(value);
         None
-        //self.data
-        //    .iter()
-        //    .position(move |element| { signature == element.signature() })
     }
 }
 
 impl<T> Extend<T> for CalibrationSet<T>
 where
-    T: CalibrationSignature,
+    T: CalibrationIndexKey + Clone,
 {
     fn extend<I>(&mut self, iter: I)
     where
@@ -152,7 +250,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::instruction::CalibrationSignature;
+
+    use crate::instruction::{CalibrationIndexKey, CalibrationSignature};
 
     #[derive(Clone, Debug, PartialEq)]
     struct TestCalibration {
@@ -183,6 +282,15 @@ mod tests {
         }
     }
 
+    /// Index by the first character of the signature, so that some signatures share a key.
+    impl CalibrationIndexKey for TestCalibration {
+        type Key = Option<char>;
+
+        fn key_for_signature(signature: &Self::Signature<'_>) -> Self::Key {
+            signature.chars().next()
+        }
+    }
+
     #[test]
     fn test_replace() {
         let mut set = CalibrationSet::new();
@@ -238,4 +346,28 @@ mod tests {
         let set: CalibrationSet<TestCalibration> = CalibrationSet::with_capacity(capacity);
         assert_eq!(set.capacity(), capacity);
     }
+
+    #[test]
+    fn test_index_after_remove() {
+        let mut set = CalibrationSet::from(vec![
+            TestCalibration::new("a1", None),
+            TestCalibration::new("b1", None),
+            TestCalibration::new("a2", None),
+        ]);
+        assert!(set.remove(&"a1"));
+
+        let keyed: Vec<(usize, &str)> = set
+            .iter_key(&Some('a'))
+            .map(|(position, calib)| (position, calib.signature()))
+            .collect();
+        assert_eq!(keyed, vec![(1, "a2")]);
+
+        let calib = TestCalibration::new("a2", Some(42));
+        assert_eq!(
+            set.replace(calib.clone()),
+            Some(TestCalibration::new("a2", None))
+        );
+        assert_eq!(set.get(&"a2"), Some(&calib));
+        assert_eq!(set.len(), 2);
+    }
 }
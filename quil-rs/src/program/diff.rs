@@ -0,0 +1,547 @@
+//! Semantic comparison of the definitions within two programs
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::hash::Hash;
+
+use indexmap::IndexMap;
+
+use crate::{
+    expression::Expression,
+    instruction::{
+        AttributeValue, CalibrationIndexKey, FrameIdentifier, GateSpecification, Instruction,
+    },
+    program::{CalibrationSet, CalibrationSource},
+    Program,
+};
+
+/// The difference between the old and new versions of an entry identified by `K`, such as a
+/// `DEFFRAME` identified by its [`FrameIdentifier`], where `C` describes the changes made to an
+/// entry present in both.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryDiff<K, C> {
+    /// The entry is present only in the new program
+    Added(K),
+
+    /// The entry is present only in the old program
+    Removed(K),
+
+    /// The entry is present in both programs, and was changed
+    Modified { key: K, changes: C },
+}
+
+impl<K, C> EntryDiff<K, C> {
+    /// The identifier of the entry which differs.
+    pub fn key(&self) -> &K {
+        match self {
+            Self::Added(key) | Self::Removed(key) | Self::Modified { key, .. } => key,
+        }
+    }
+}
+
+/// A change to a single value within an entry, such as an attribute of a frame.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueChange<K, V> {
+    /// The value is present only in the new entry
+    Added { key: K, value: V },
+
+    /// The value is present only in the old entry
+    Removed { key: K, value: V },
+
+    /// The value is present in both entries, and differs
+    Changed { key: K, old: V, new: V },
+}
+
+/// One step of the shortest sequence of insertions and removals which transforms the old
+/// instructions of a calibration into its new instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstructionChange {
+    /// The instruction at `index` within the new instructions was inserted
+    Inserted {
+        index: usize,
+        instruction: Instruction,
+    },
+
+    /// The instruction at `index` within the old instructions was removed
+    Removed {
+        index: usize,
+        instruction: Instruction,
+    },
+}
+
+/// The changes made to a `DEFWAVEFORM`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WaveformChanges {
+    /// The old and new parameter names, if they differ
+    pub parameters: Option<(Vec<String>, Vec<String>)>,
+
+    /// The changed samples, by index
+    pub samples: Vec<ValueChange<usize, Expression>>,
+}
+
+/// The changes made to a `DEFGATE`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GateDefinitionChanges {
+    /// The old and new parameter names, if they differ
+    pub parameters: Option<(Vec<String>, Vec<String>)>,
+
+    /// The old and new specifications, if they differ
+    pub specification: Option<(GateSpecification, GateSpecification)>,
+}
+
+/// The differences between the calibrations, frames, waveforms, and gate definitions of two
+/// programs, as returned by [`Program::diff`].
+///
+/// Entries are compared by their identifiers rather than by their text or position, so reordering
+/// definitions produces no differences. Within each kind of entry, removed and modified entries are
+/// listed in the order in which they appear in the old program, followed by added entries in the
+/// order in which they appear in the new program.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProgramDiff {
+    /// `DEFCAL` and `DEFCAL MEASURE` differences, identified by their signatures
+    pub calibrations: Vec<EntryDiff<CalibrationSource, Vec<InstructionChange>>>,
+
+    /// `DEFFRAME` differences, along with the changes to the attributes of modified frames
+    pub frames: Vec<EntryDiff<FrameIdentifier, Vec<ValueChange<String, AttributeValue>>>>,
+
+    /// `DEFWAVEFORM` differences, identified by name
+    pub waveforms: Vec<EntryDiff<String, WaveformChanges>>,
+
+    /// `DEFGATE` differences, identified by name
+    pub gate_definitions: Vec<EntryDiff<String, GateDefinitionChanges>>,
+}
+
+impl ProgramDiff {
+    /// Whether the programs have identical definitions.
+    pub fn is_empty(&self) -> bool {
+        self.calibrations.is_empty()
+            && self.frames.is_empty()
+            && self.waveforms.is_empty()
+            && self.gate_definitions.is_empty()
+    }
+}
+
+impl Program {
+    /// Compare the calibrations, frames, waveforms, and gate definitions of this program to those
+    /// of `new`, treating this program as the old version.
+    ///
+    /// Body instructions and memory declarations are not compared.
+    pub fn diff(&self, new: &Program) -> ProgramDiff {
+        let mut calibrations = diff_calibration_sets(
+            &self.calibrations.calibrations,
+            &new.calibrations.calibrations,
+            |calibration| (&calibration.identifier, &calibration.instructions),
+        );
+        calibrations.extend(diff_calibration_sets(
+            &self.calibrations.measure_calibrations,
+            &new.calibrations.measure_calibrations,
+            |calibration| (&calibration.identifier, &calibration.instructions),
+        ));
+
+        let mut old_frames = self.frames.get_keys();
+        old_frames.sort_by(|a, b| (&a.qubits, &a.name).cmp(&(&b.qubits, &b.name)));
+        let mut new_frames = new.frames.get_keys();
+        new_frames.sort_by(|a, b| (&a.qubits, &a.name).cmp(&(&b.qubits, &b.name)));
+        let frames = diff_entries(
+            old_frames.into_iter().map(|identifier| {
+                (
+                    identifier,
+                    self.frames.get(identifier).expect("frame key exists"),
+                )
+            }),
+            new_frames.into_iter().map(|identifier| {
+                (
+                    identifier,
+                    new.frames.get(identifier).expect("frame key exists"),
+                )
+            }),
+            |old, new| Some(diff_maps(old, new)).filter(|changes| !changes.is_empty()),
+        );
+
+        let waveforms = diff_entries(&*self.waveforms, &*new.waveforms, |old, new| {
+            let changes = WaveformChanges {
+                parameters: diff_values(&old.parameters, &new.parameters),
+                samples: diff_sequences_by_index(&old.matrix, &new.matrix),
+            };
+            Some(changes).filter(|changes| changes != &WaveformChanges::default())
+        });
+
+        let gate_definitions = diff_entries(
+            &*self.gate_definitions,
+            &*new.gate_definitions,
+            |old, new| {
+                let changes = GateDefinitionChanges {
+                    parameters: diff_values(&old.parameters, &new.parameters),
+                    specification: diff_values(&old.specification, &new.specification),
+                };
+                Some(changes).filter(|changes| changes != &GateDefinitionChanges::default())
+            },
+        );
+
+        ProgramDiff {
+            calibrations,
+            frames,
+            waveforms,
+            gate_definitions,
+        }
+    }
+}
+
+/// Compare two sequences of keyed entries, where `diff` returns the changes between two versions of
+/// an entry, or `None` if they are the same.
+fn diff_entries<'a, K, V, C>(
+    old: impl IntoIterator<Item = (&'a K, &'a V)>,
+    new: impl IntoIterator<Item = (&'a K, &'a V)>,
+    diff: impl Fn(&V, &V) -> Option<C>,
+) -> Vec<EntryDiff<K, C>>
+where
+    K: Clone + Eq + Hash + 'a,
+    V: 'a,
+{
+    let old = old.into_iter().collect::<IndexMap<_, _>>();
+    let new = new.into_iter().collect::<IndexMap<_, _>>();
+
+    let mut diffs = Vec::new();
+    for (key, old_value) in &old {
+        match new.get(key) {
+            None => diffs.push(EntryDiff::Removed((*key).clone())),
+            Some(new_value) => {
+                if let Some(changes) = diff(old_value, new_value) {
+                    diffs.push(EntryDiff::Modified {
+                        key: (*key).clone(),
+                        changes,
+                    });
+                }
+            }
+        }
+    }
+    for key in new.keys() {
+        if !old.contains_key(key) {
+            diffs.push(EntryDiff::Added((*key).clone()));
+        }
+    }
+
+    diffs
+}
+
+/// Compare two sets of calibrations by signature. The signatures of gate and measurement
+/// calibrations comprise their entire identifiers, including gate modifiers; should a calibration
+/// share a signature with one which has a different identifier, they are reported as removed and
+/// added.
+fn diff_calibration_sets<'a, T, I>(
+    old: &'a CalibrationSet<T>,
+    new: &'a CalibrationSet<T>,
+    parts: impl Fn(&'a T) -> (&'a I, &'a Vec<Instruction>),
+) -> Vec<EntryDiff<CalibrationSource, Vec<InstructionChange>>>
+where
+    T: CalibrationIndexKey,
+    I: Clone + PartialEq + Into<CalibrationSource> + 'a,
+{
+    let mut diffs = Vec::new();
+    for old_calibration in old.iter() {
+        let (old_identifier, old_instructions) = parts(old_calibration);
+        match new.get(&old_calibration.signature()).map(&parts) {
+            Some((new_identifier, new_instructions)) if new_identifier == old_identifier => {
+                let changes = diff_instructions(old_instructions, new_instructions);
+                if !changes.is_empty() {
+                    diffs.push(EntryDiff::Modified {
+                        key: old_identifier.clone().into(),
+                        changes,
+                    });
+                }
+            }
+            _ => diffs.push(EntryDiff::Removed(old_identifier.clone().into())),
+        }
+    }
+    for new_calibration in new.iter() {
+        let (new_identifier, _) = parts(new_calibration);
+        let is_added = old
+            .get(&new_calibration.signature())
+            .map_or(true, |old_calibration| {
+                parts(old_calibration).0 != new_identifier
+            });
+        if is_added {
+            diffs.push(EntryDiff::Added(new_identifier.clone().into()));
+        }
+    }
+
+    diffs
+}
+
+/// Return the old and new values if they differ.
+fn diff_values<V: Clone + PartialEq>(old: &V, new: &V) -> Option<(V, V)> {
+    (old != new).then(|| (old.clone(), new.clone()))
+}
+
+/// Compare two maps, reporting changed values in the order of the old map followed by added values
+/// in the order of the new map.
+fn diff_maps<K, V>(old: &IndexMap<K, V>, new: &IndexMap<K, V>) -> Vec<ValueChange<K, V>>
+where
+    K: Clone + Eq + Hash,
+    V: Clone + PartialEq,
+{
+    let mut changes = Vec::new();
+    for (key, old_value) in old {
+        match new.get(key) {
+            None => changes.push(ValueChange::Removed {
+                key: key.clone(),
+                value: old_value.clone(),
+            }),
+            Some(new_value) if new_value != old_value => changes.push(ValueChange::Changed {
+                key: key.clone(),
+                old: old_value.clone(),
+                new: new_value.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            changes.push(ValueChange::Added {
+                key: key.clone(),
+                value: new_value.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+/// Compare two sequences element by element, such as the samples of a waveform.
+fn diff_sequences_by_index<V: Clone + PartialEq>(
+    old: &[V],
+    new: &[V],
+) -> Vec<ValueChange<usize, V>> {
+    (0..old.len().max(new.len()))
+        .filter_map(|index| match (old.get(index), new.get(index)) {
+            (Some(old), Some(new)) if old != new => Some(ValueChange::Changed {
+                key: index,
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            (Some(value), None) => Some(ValueChange::Removed {
+                key: index,
+                value: value.clone(),
+            }),
+            (None, Some(value)) => Some(ValueChange::Added {
+                key: index,
+                value: value.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Return the shortest sequence of insertions and removals which transforms `old` into `new`,
+/// using their longest common subsequence.
+fn diff_instructions(old: &[Instruction], new: &[Instruction]) -> Vec<InstructionChange> {
+    // `common[i][j]` is the length of the longest common subsequence of `old[i..]` and `new[j..]`.
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            changes.push(InstructionChange::Removed {
+                index: i,
+                instruction: old[i].clone(),
+            });
+            i += 1;
+        } else {
+            changes.push(InstructionChange::Inserted {
+                index: j,
+                instruction: new[j].clone(),
+            });
+            j += 1;
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::{
+        expression::Expression,
+        instruction::{AttributeValue, CalibrationIdentifier, FrameIdentifier, Qubit},
+        program::CalibrationSource,
+        quil::Quil,
+        real, Program,
+    };
+
+    use super::{EntryDiff, InstructionChange, ValueChange};
+
+    #[test]
+    fn reordered_definitions_are_equal() {
+        let old = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1e9
+DEFWAVEFORM a:
+    1, 2
+DEFCAL X 0:
+    NOP
+DEFCAL X 1:
+    NOP
+"#,
+        )
+        .unwrap();
+        let new = Program::from_str(
+            r#"DEFCAL X 1:
+    NOP
+DEFCAL X 0:
+    NOP
+DEFWAVEFORM a:
+    1, 2
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+"#,
+        )
+        .unwrap();
+
+        assert!(old.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn reports_changed_definitions() {
+        let old = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+    INITIAL-FREQUENCY: 5e9
+DEFWAVEFORM a:
+    1, 2
+DEFWAVEFORM b:
+    1
+DEFCAL X 0:
+    PRAGMA A
+    PRAGMA B
+    PRAGMA C
+DEFCAL X 1:
+    NOP
+"#,
+        )
+        .unwrap();
+        let new = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 2e9
+    CENTER-FREQUENCY: 5e9
+DEFWAVEFORM a:
+    1, 3, 4
+DEFCAL X 0:
+    PRAGMA A
+    PRAGMA D
+    PRAGMA C
+DEFCAL X 2:
+    NOP
+"#,
+        )
+        .unwrap();
+        let diff = old.diff(&new);
+
+        let x = |qubit| {
+            CalibrationSource::Calibration(CalibrationIdentifier {
+                name: "X".to_string(),
+                qubits: vec![Qubit::Fixed(qubit)],
+                ..Default::default()
+            })
+        };
+        let calibrations = diff
+            .calibrations
+            .iter()
+            .map(|entry| match entry {
+                EntryDiff::Modified { key, changes } => (
+                    format!("~{key:?}"),
+                    changes
+                        .iter()
+                        .map(|change| match change {
+                            InstructionChange::Inserted { index, instruction } => {
+                                format!("+{index} {}", instruction.to_quil_or_debug())
+                            }
+                            InstructionChange::Removed { index, instruction } => {
+                                format!("-{index} {}", instruction.to_quil_or_debug())
+                            }
+                        })
+                        .collect::<Vec<_>>(),
+                ),
+                EntryDiff::Added(key) => (format!("+{key:?}"), vec![]),
+                EntryDiff::Removed(key) => (format!("-{key:?}"), vec![]),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calibrations,
+            vec![
+                (
+                    format!("~{:?}", x(0)),
+                    vec!["-1 PRAGMA B".to_string(), "+1 PRAGMA D".to_string()]
+                ),
+                (format!("-{:?}", x(1)), vec![]),
+                (format!("+{:?}", x(2)), vec![]),
+            ]
+        );
+
+        assert_eq!(
+            diff.frames,
+            vec![EntryDiff::Modified {
+                key: FrameIdentifier::new("rf".to_string(), vec![Qubit::Fixed(0)]),
+                changes: vec![
+                    ValueChange::Changed {
+                        key: "SAMPLE-RATE".to_string(),
+                        old: AttributeValue::Expression(Expression::Number(real!(1e9))),
+                        new: AttributeValue::Expression(Expression::Number(real!(2e9))),
+                    },
+                    ValueChange::Removed {
+                        key: "INITIAL-FREQUENCY".to_string(),
+                        value: AttributeValue::Expression(Expression::Number(real!(5e9))),
+                    },
+                    ValueChange::Added {
+                        key: "CENTER-FREQUENCY".to_string(),
+                        value: AttributeValue::Expression(Expression::Number(real!(5e9))),
+                    },
+                ],
+            }]
+        );
+
+        let waveforms = diff
+            .waveforms
+            .iter()
+            .map(|entry| match entry {
+                EntryDiff::Modified { key, changes } => {
+                    format!("~{key} {}", changes.samples.len())
+                }
+                EntryDiff::Added(key) => format!("+{key}"),
+                EntryDiff::Removed(key) => format!("-{key}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(waveforms, vec!["~a 2", "-b"]);
+        assert!(diff.gate_definitions.is_empty());
+    }
+}
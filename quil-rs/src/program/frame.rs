@@ -12,20 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use crate::instruction::{FrameAttributes, FrameDefinition, FrameIdentifier, Instruction, Qubit};
+use crate::instruction::{
+    FrameAttributeError, FrameAttributes, FrameDefinition, FrameIdentifier, Instruction, Qubit,
+    TypedFrameAttributes,
+};
 
 /// A collection of Quil frames (`DEFFRAME` instructions) with utility methods.
+///
+/// As with [`CalibrationSet`](super::CalibrationSet), clones share their frames until one of them
+/// is modified.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct FrameSet {
-    frames: HashMap<FrameIdentifier, FrameAttributes>,
+    frames: Arc<HashMap<FrameIdentifier, FrameAttributes>>,
 }
 
 impl FrameSet {
     pub fn new() -> Self {
         FrameSet {
-            frames: HashMap::new(),
+            frames: Arc::new(HashMap::new()),
         }
     }
 
@@ -34,6 +43,17 @@ impl FrameSet {
         self.frames.get(identifier)
     }
 
+    /// Retrieve and validate the attributes of a frame known to Quil-T by its identifier.
+    ///
+    /// See [`TypedFrameAttributes::try_from_attributes`].
+    pub fn get_typed(
+        &self,
+        identifier: &FrameIdentifier,
+    ) -> Option<Result<TypedFrameAttributes, FrameAttributeError>> {
+        self.get(identifier)
+            .map(TypedFrameAttributes::try_from_attributes)
+    }
+
     /// Return a list of all frame IDs described by this FrameSet.
     pub fn get_keys(&self) -> Vec<&FrameIdentifier> {
         self.frames.keys().collect()
@@ -103,19 +123,27 @@ impl FrameSet {
 
     /// Insert a new frame by ID, overwriting any existing one.
     pub fn insert(&mut self, identifier: FrameIdentifier, attributes: FrameAttributes) {
-        self.frames.insert(identifier, attributes);
+        Arc::make_mut(&mut self.frames).insert(identifier, attributes);
     }
 
     /// Merge another [FrameSet] with this one, overwriting any existing keys
     pub fn merge(&mut self, other: FrameSet) {
-        self.frames.extend(other.frames);
+        if other.is_empty() {
+            return;
+        }
+        if self.frames.is_empty() {
+            self.frames = other.frames;
+        } else {
+            Arc::make_mut(&mut self.frames)
+                .extend(Arc::try_unwrap(other.frames).unwrap_or_else(|frames| (*frames).clone()));
+        }
     }
 
     /// Return a new [FrameSet] which describes only the given [FrameIdentifier]s.
     pub fn intersection(&self, identifiers: &HashSet<&FrameIdentifier>) -> Self {
         let mut new_frameset = Self::new();
 
-        for (identifier, definition) in &self.frames {
+        for (identifier, definition) in self.frames.iter() {
             if identifiers.contains(&identifier) {
                 new_frameset.insert(identifier.clone(), definition.clone())
             }
@@ -134,6 +162,12 @@ impl FrameSet {
         self.frames.len()
     }
 
+    /// Return true if this shares its frames with `other`, as a clone does until either is
+    /// modified.
+    pub fn shares_data_with(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.frames, &other.frames)
+    }
+
     /// Return true if this describes no frames.
     pub fn is_empty(&self) -> bool {
         self.frames.is_empty()
@@ -141,7 +175,8 @@ impl FrameSet {
 
     /// Return the Quil instructions which describe the contained frames, consuming the [`FrameSet`].
     pub fn into_instructions(self) -> Vec<Instruction> {
-        self.frames
+        Arc::try_unwrap(self.frames)
+            .unwrap_or_else(|frames| (*frames).clone())
             .into_iter()
             .map(|(identifier, attributes)| {
                 Instruction::FrameDefinition(FrameDefinition {
@@ -233,3 +268,81 @@ impl<'a> MatchedFrames<'a> {
         &self.used
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+
+    use crate::{
+        instruction::{
+            FrameAttributeError, FrameAttributeWarning, FrameDirection, FrameIdentifier, Qubit,
+            TypedFrameAttributes,
+        },
+        Program,
+    };
+
+    #[test]
+    fn typed_attributes() {
+        let program = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 2 * 1e9
+    INITIAL-FREQUENCY: 5e9
+    CENTER-FREQUENCY: 5.1e9
+    HARDWARE-OBJECT: "q0_rf"
+    DIRECTION: "tx"
+    CUSTOM: 1
+"#,
+        )
+        .unwrap();
+        let typed = program
+            .frames
+            .get_typed(&FrameIdentifier::new(
+                "rf".to_string(),
+                vec![Qubit::Fixed(0)],
+            ))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            typed,
+            TypedFrameAttributes {
+                sample_rate: Some(2e9),
+                initial_frequency: Some(5e9),
+                center_frequency: Some(5.1e9),
+                hardware_object: Some("q0_rf".to_string()),
+                direction: Some(FrameDirection::Tx),
+                warnings: vec![FrameAttributeWarning::UnknownAttribute(
+                    "CUSTOM".to_string()
+                )],
+            }
+        );
+    }
+
+    #[rstest]
+    #[case("SAMPLE-RATE: 0", FrameAttributeError::InvalidSampleRate(0.0))]
+    #[case(
+        "INITIAL-FREQUENCY: -1",
+        FrameAttributeError::InvalidFrequency {
+            attribute: "INITIAL-FREQUENCY".to_string(),
+            value: -1.0,
+        }
+    )]
+    #[case(
+        "DIRECTION: \"sideways\"",
+        FrameAttributeError::UnknownDirection("sideways".to_string())
+    )]
+    fn invalid_attributes(#[case] attribute: &str, #[case] expected: FrameAttributeError) {
+        let program = Program::from_str(&format!("DEFFRAME 0 \"rf\":\n    {attribute}\n")).unwrap();
+        let typed = program
+            .frames
+            .get_typed(&FrameIdentifier::new(
+                "rf".to_string(),
+                vec![Qubit::Fixed(0)],
+            ))
+            .unwrap();
+
+        assert_eq!(typed, Err(expected));
+    }
+}
@@ -0,0 +1,465 @@
+//! Calibration libraries: definitions which are parsed once and linked into many programs
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashSet, str::FromStr, sync::Arc};
+
+use indexmap::IndexMap;
+
+use crate::{
+    instruction::{
+        CalibrationIndexKey, CalibrationSignature, Declaration, FrameIdentifier, GateDefinition,
+        Instruction, Qubit, Waveform, WaveformDefinition,
+    },
+    quil::Quil,
+};
+
+use super::{CalibrationSet, CalibrationSource, Calibrations, FrameSet, Program, ProgramError};
+
+/// Errors which may occur when building a [`CalibrationLibrary`] or linking one into a program.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum CalibrationLibraryError {
+    #[error("{0}")]
+    Program(#[from] ProgramError),
+
+    #[error("a calibration library may only contain definitions, but found: {}", .0.to_quil_or_debug())]
+    UnexpectedInstruction(Instruction),
+
+    #[error("the program and the calibration library have conflicting definitions of {0}")]
+    Conflict(LinkConflict),
+}
+
+/// A definition which differs between a program and the [`CalibrationLibrary`] linked into it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkConflict {
+    Calibration(CalibrationSource),
+    Frame(FrameIdentifier),
+    Waveform(String),
+    GateDefinition(String),
+}
+
+impl std::fmt::Display for LinkConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Calibration(CalibrationSource::Calibration(identifier)) => {
+                write!(f, "{}", identifier.to_quil_or_debug())
+            }
+            Self::Calibration(CalibrationSource::MeasureCalibration(identifier)) => {
+                write!(f, "{}", identifier.to_quil_or_debug())
+            }
+            Self::Frame(identifier) => write!(f, "DEFFRAME {}", identifier.to_quil_or_debug()),
+            Self::Waveform(name) => write!(f, "DEFWAVEFORM {name}"),
+            Self::GateDefinition(name) => write!(f, "DEFGATE {name}"),
+        }
+    }
+}
+
+/// How to resolve definitions which differ between a program and the [`CalibrationLibrary`]
+/// linked into it. Definitions which are identical in both are never in conflict.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LinkConflictPolicy {
+    /// Fail to link, leaving the program unchanged
+    #[default]
+    Error,
+
+    /// Keep the library's definition, discarding the program's
+    PreferLibrary,
+
+    /// Keep the program's definition, discarding the library's
+    PreferProgram,
+}
+
+/// A set of frame, waveform, calibration, and gate definitions, without any body instructions,
+/// which may be linked into any number of programs using [`Program::link_library`].
+///
+/// This allows device calibrations to be kept and parsed separately from the programs which use
+/// them. Calibrations, frames, waveforms, and gate definitions are shared between the library and
+/// each program it is linked into, rather than copied, until they are modified; see
+/// [`CalibrationSet`] and [`FrameSet`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CalibrationLibrary {
+    calibrations: Calibrations,
+    frames: FrameSet,
+    waveforms: Arc<IndexMap<String, Waveform>>,
+    gate_definitions: Arc<IndexMap<String, GateDefinition>>,
+    // The qubits used by the definitions, which become used by each program they're linked into
+    used_qubits: HashSet<Qubit>,
+}
+
+impl CalibrationLibrary {
+    /// The calibrations defined by this library.
+    pub fn calibrations(&self) -> &Calibrations {
+        &self.calibrations
+    }
+
+    /// The frames defined by this library.
+    pub fn frames(&self) -> &FrameSet {
+        &self.frames
+    }
+
+    /// The waveforms defined by this library.
+    pub fn waveforms(&self) -> &IndexMap<String, Waveform> {
+        &self.waveforms
+    }
+
+    /// The gates defined by this library.
+    pub fn gate_definitions(&self) -> &IndexMap<String, GateDefinition> {
+        &self.gate_definitions
+    }
+
+    /// Return a copy of the instructions which constitute this library.
+    pub fn to_instructions(&self) -> Vec<Instruction> {
+        let mut instructions = self.frames.to_instructions();
+        instructions.extend(self.waveforms.iter().map(|(name, definition)| {
+            Instruction::WaveformDefinition(WaveformDefinition {
+                name: name.clone(),
+                definition: definition.clone(),
+            })
+        }));
+        instructions.extend(self.calibrations.to_instructions());
+        instructions.extend(
+            self.gate_definitions
+                .values()
+                .cloned()
+                .map(Instruction::GateDefinition),
+        );
+        instructions
+    }
+}
+
+impl TryFrom<Program> for CalibrationLibrary {
+    type Error = CalibrationLibraryError;
+
+    /// Build a library from the definitions of a program, which must not contain any body
+    /// instructions, memory declarations, or `PRAGMA EXTERN` instructions.
+    fn try_from(program: Program) -> Result<Self, Self::Error> {
+        let unexpected = program
+            .extern_pragma_map
+            .to_instructions()
+            .into_iter()
+            .chain(program.memory_regions.iter().map(|(name, region)| {
+                Instruction::Declaration(Declaration {
+                    name: name.clone(),
+                    size: region.size.clone(),
+                    sharing: region.sharing.clone(),
+                })
+            }))
+            .chain(program.instructions.iter().cloned())
+            .next();
+        if let Some(instruction) = unexpected {
+            return Err(CalibrationLibraryError::UnexpectedInstruction(instruction));
+        }
+
+        Ok(Self {
+            calibrations: program.calibrations,
+            frames: program.frames,
+            waveforms: program.waveforms,
+            gate_definitions: program.gate_definitions,
+            used_qubits: program.used_qubits,
+        })
+    }
+}
+
+impl FromStr for CalibrationLibrary {
+    type Err = CalibrationLibraryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Program::from_str(s)?.try_into()
+    }
+}
+
+impl Program {
+    /// Link the definitions of a [`CalibrationLibrary`] into this program.
+    ///
+    /// The result is as if the library's instructions were placed before those of this program,
+    /// except that definitions which differ between the two are resolved according to `policy`.
+    /// Since the relative order of calibrations determines which of several matching calibrations
+    /// is used, the library's calibrations precede those of the program.
+    ///
+    /// If this program defines no calibrations, frames, waveforms, or gates of its own, or only
+    /// the same ones as the library, those of the library are shared rather than copied.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CalibrationLibraryError::Conflict`] for the first conflicting definition if
+    /// `policy` is [`LinkConflictPolicy::Error`], in which case this program is left unchanged.
+    pub fn link_library(
+        &mut self,
+        library: &CalibrationLibrary,
+        policy: LinkConflictPolicy,
+    ) -> Result<(), CalibrationLibraryError> {
+        if policy == LinkConflictPolicy::Error {
+            if let Some(conflict) = self.find_link_conflict(library) {
+                return Err(CalibrationLibraryError::Conflict(conflict));
+            }
+        }
+        let prefer_library = policy == LinkConflictPolicy::PreferLibrary;
+
+        link_calibration_sets(
+            &mut self.calibrations.calibrations,
+            &library.calibrations.calibrations,
+            prefer_library,
+        );
+        link_calibration_sets(
+            &mut self.calibrations.measure_calibrations,
+            &library.calibrations.measure_calibrations,
+            prefer_library,
+        );
+
+        let program_frames = std::mem::replace(&mut self.frames, library.frames.clone());
+        if prefer_library {
+            for (identifier, attributes) in program_frames.iter() {
+                if self.frames.get(identifier).is_none() {
+                    self.frames.insert(identifier.clone(), attributes.clone());
+                }
+            }
+        } else {
+            self.frames.merge(program_frames);
+        }
+
+        link_maps(&mut self.waveforms, &library.waveforms, prefer_library);
+        link_maps(
+            &mut self.gate_definitions,
+            &library.gate_definitions,
+            prefer_library,
+        );
+
+        self.used_qubits.extend(library.used_qubits.iter().cloned());
+
+        Ok(())
+    }
+
+    /// Return the first definition which differs between this program and the library.
+    fn find_link_conflict(&self, library: &CalibrationLibrary) -> Option<LinkConflict> {
+        let calibrations = self
+            .calibrations
+            .iter_calibrations()
+            .find_map(|calibration| {
+                library
+                    .calibrations
+                    .calibrations
+                    .get(&calibration.signature())
+                    .filter(|other| *other != calibration)
+                    .map(|_| {
+                        LinkConflict::Calibration(CalibrationSource::Calibration(
+                            calibration.identifier.clone(),
+                        ))
+                    })
+            });
+        let measure_calibrations = || {
+            self.calibrations
+                .iter_measure_calibrations()
+                .find_map(|calibration| {
+                    library
+                        .calibrations
+                        .measure_calibrations
+                        .get(&calibration.signature())
+                        .filter(|other| *other != calibration)
+                        .map(|_| {
+                            LinkConflict::Calibration(CalibrationSource::MeasureCalibration(
+                                calibration.identifier.clone(),
+                            ))
+                        })
+                })
+        };
+        let frames = || {
+            let mut identifiers = self.frames.get_keys();
+            identifiers.sort_by(|a, b| (&a.qubits, &a.name).cmp(&(&b.qubits, &b.name)));
+            identifiers.into_iter().find_map(|identifier| {
+                library
+                    .frames
+                    .get(identifier)
+                    .filter(|other| Some(*other) != self.frames.get(identifier))
+                    .map(|_| LinkConflict::Frame(identifier.clone()))
+            })
+        };
+        let waveforms =
+            || find_map_conflict(&self.waveforms, &library.waveforms).map(LinkConflict::Waveform);
+        let gate_definitions = || {
+            find_map_conflict(&self.gate_definitions, &library.gate_definitions)
+                .map(LinkConflict::GateDefinition)
+        };
+
+        calibrations
+            .or_else(measure_calibrations)
+            .or_else(frames)
+            .or_else(waveforms)
+            .or_else(gate_definitions)
+    }
+}
+
+/// Replace `program` with the calibrations of `library` followed by those of `program`, keeping
+/// the library's version of each conflicting calibration if `prefer_library` is set.
+fn link_calibration_sets<T>(
+    program: &mut CalibrationSet<T>,
+    library: &CalibrationSet<T>,
+    prefer_library: bool,
+) where
+    T: CalibrationIndexKey + Clone,
+{
+    let program_calibrations = std::mem::replace(program, library.clone());
+    for calibration in program_calibrations {
+        if !(prefer_library && library.get(&calibration.signature()).is_some()) {
+            program.replace(calibration);
+        }
+    }
+}
+
+/// Replace `program` with the entries of `library` followed by those of `program`, keeping the
+/// library's version of each conflicting entry if `prefer_library` is set.
+///
+/// The library's map is shared, rather than copied, unless `program` adds entries to it.
+fn link_maps<V: Clone + PartialEq>(
+    program: &mut Arc<IndexMap<String, V>>,
+    library: &Arc<IndexMap<String, V>>,
+    prefer_library: bool,
+) {
+    let program_entries = std::mem::replace(program, library.clone());
+    for (name, value) in program_entries.iter() {
+        let is_kept = match library.get(name) {
+            Some(library_value) => !prefer_library && library_value != value,
+            None => true,
+        };
+        if is_kept {
+            Arc::make_mut(program).insert(name.clone(), value.clone());
+        }
+    }
+}
+
+/// Return the name of the first entry which differs between `program` and `library`.
+fn find_map_conflict<V: PartialEq>(
+    program: &IndexMap<String, V>,
+    library: &IndexMap<String, V>,
+) -> Option<String> {
+    program
+        .iter()
+        .find(|(name, value)| library.get(*name).is_some_and(|other| other != *value))
+        .map(|(name, _)| name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use rstest::rstest;
+
+    use crate::{instruction::Instruction, quil::Quil, Program};
+
+    use super::{CalibrationLibrary, CalibrationLibraryError, LinkConflict, LinkConflictPolicy};
+
+    const LIBRARY: &str = r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFWAVEFORM w:
+    1, 1
+DEFCAL X 0:
+    PULSE 0 "rf" w
+DEFCAL X q:
+    NOP
+"#;
+
+    #[test]
+    fn shares_definitions_with_body_only_programs() {
+        let library = CalibrationLibrary::from_str(LIBRARY).unwrap();
+        let mut program = Program::from_str("X 0\nX 1\n").unwrap();
+        program
+            .link_library(&library, LinkConflictPolicy::Error)
+            .unwrap();
+
+        assert!(program
+            .calibrations
+            .calibrations
+            .shares_data_with(&library.calibrations().calibrations));
+        assert!(program.frames.shares_data_with(library.frames()));
+        assert!(Arc::ptr_eq(&program.waveforms, &library.waveforms));
+        assert!(Arc::ptr_eq(
+            &program.gate_definitions,
+            &library.gate_definitions
+        ));
+
+        let expected = Program::from_str(&format!("{LIBRARY}X 0\nX 1\n")).unwrap();
+        assert_eq!(program, expected);
+    }
+
+    #[rstest]
+    #[case(LinkConflictPolicy::Error)]
+    #[case(LinkConflictPolicy::PreferProgram)]
+    fn shares_definitions_identical_to_the_library(#[case] policy: LinkConflictPolicy) {
+        let library = CalibrationLibrary::from_str(LIBRARY).unwrap();
+        let mut program = Program::from_str("DEFWAVEFORM w:\n    1, 1\nX 0\n").unwrap();
+        program.link_library(&library, policy).unwrap();
+
+        assert!(Arc::ptr_eq(&program.waveforms, &library.waveforms));
+    }
+
+    #[test]
+    fn rejects_body_instructions() {
+        let result = CalibrationLibrary::from_str("DECLARE ro BIT\nX 0\n");
+        assert!(matches!(
+            result,
+            Err(CalibrationLibraryError::UnexpectedInstruction(
+                Instruction::Declaration(_)
+            ))
+        ));
+    }
+
+    #[rstest]
+    #[case(LinkConflictPolicy::Error, None)]
+    #[case(LinkConflictPolicy::PreferLibrary, Some("PULSE 0 \"rf\" w"))]
+    #[case(LinkConflictPolicy::PreferProgram, Some("RESET"))]
+    fn conflict_policy(#[case] policy: LinkConflictPolicy, #[case] expected: Option<&str>) {
+        let library = CalibrationLibrary::from_str(LIBRARY).unwrap();
+        let original = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFCAL X 0:
+    RESET
+X 0
+"#,
+        )
+        .unwrap();
+        let mut program = original.clone();
+        let result = program.link_library(&library, policy);
+
+        match expected {
+            None => {
+                assert_eq!(
+                    result,
+                    Err(CalibrationLibraryError::Conflict(
+                        LinkConflict::Calibration(
+                            original.calibrations.calibrations()[0]
+                                .identifier
+                                .clone()
+                                .into()
+                        )
+                    ))
+                );
+                assert_eq!(program, original);
+            }
+            Some(expected) => {
+                result.unwrap();
+                let expanded = program.expand_calibrations().unwrap();
+                let body = expanded
+                    .body_instructions()
+                    .map(|instruction| instruction.to_quil_or_debug())
+                    .collect::<Vec<_>>();
+                assert_eq!(body, vec![expected.to_string()]);
+                // The identical frame definition is not a conflict, and the generic `X q`
+                // calibration is retained in either case.
+                assert_eq!(program.frames.len(), 1);
+                assert_eq!(program.calibrations.len(), 2);
+            }
+        }
+    }
+}
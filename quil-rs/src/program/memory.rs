@@ -14,7 +14,10 @@
 
 use std::collections::HashSet;
 
-use crate::expression::{Expression, FunctionCallExpression, InfixExpression, PrefixExpression};
+use crate::expression::{
+    BinaryFunctionCallExpression, Expression, FunctionCallExpression, InfixExpression,
+    PrefixExpression,
+};
 use crate::instruction::{
     Arithmetic, ArithmeticOperand, BinaryLogic, BinaryOperand, CallResolutionError, Capture,
     CircuitDefinition, Comparison, ComparisonOperand, Convert, Delay, Exchange, ExternSignatureMap,
@@ -342,6 +345,13 @@ impl Expression {
     pub fn get_memory_references(&self) -> Vec<&MemoryReference> {
         match self {
             Expression::Address(reference) => vec![reference],
+            Expression::BinaryFunctionCall(BinaryFunctionCallExpression {
+                left, right, ..
+            }) => {
+                let mut result = left.get_memory_references();
+                result.extend(right.get_memory_references());
+                result
+            }
             Expression::FunctionCall(FunctionCallExpression { expression, .. }) => {
                 expression.get_memory_references()
             }
@@ -0,0 +1,374 @@
+//! Computing the position of each declared memory region within a single buffer
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, ops::Range};
+
+use indexmap::IndexMap;
+
+use crate::instruction::ScalarType;
+
+use super::MemoryRegion;
+
+/// The alignment, in bits, of each region which does not share another.
+const ROOT_ALIGNMENT: u64 = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum MemoryLayoutError {
+    #[error("memory region {region} shares memory region {parent}, which is not declared")]
+    UndeclaredParent { region: String, parent: String },
+
+    #[error("memory region {0} shares memory with itself")]
+    CyclicSharing(String),
+
+    #[error(
+        "memory region {region} extends beyond the end of memory region {parent}, which it shares"
+    )]
+    ExceedsParent { region: String, parent: String },
+
+    #[error("memory region {0} is too large to allocate")]
+    TooLarge(String),
+}
+
+/// The position of a memory region within the buffer described by a [`MemoryLayout`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RegionLayout {
+    pub data_type: ScalarType,
+    pub length: u64,
+
+    /// The position of the first bit of the region within the buffer
+    pub bit_offset: u64,
+}
+
+impl RegionLayout {
+    pub fn new(data_type: ScalarType, length: u64, bit_offset: u64) -> Self {
+        Self {
+            data_type,
+            length,
+            bit_offset,
+        }
+    }
+
+    /// The number of bits occupied by the region.
+    pub fn bit_size(&self) -> u64 {
+        self.length.saturating_mul(self.data_type.bit_width())
+    }
+
+    /// The bits of the buffer occupied by the region.
+    pub fn bit_range(&self) -> Range<u64> {
+        self.bit_offset..self.bit_offset.saturating_add(self.bit_size())
+    }
+
+    /// The bytes of the buffer which hold at least one bit of the region.
+    pub fn byte_range(&self) -> Range<u64> {
+        let bits = self.bit_range();
+        bits.start / 8..bits.end / 8 + u64::from(bits.end % 8 != 0)
+    }
+
+    /// Whether the region starts at a multiple of the width of its type, so that each of its
+    /// values may be accessed without straddling a boundary of that width.
+    pub fn is_aligned(&self) -> bool {
+        self.bit_offset % self.data_type.bit_width() == 0
+    }
+}
+
+/// The layout of every memory region declared by a program within a single buffer, with the widths
+/// of each type given by the Quil specification.
+///
+/// Each region which does not share another is allocated its own memory, in the order of
+/// declaration and aligned to 64 bits. Each region declared with `SHARING` starts at the sum of its
+/// `OFFSET`s from the start of the region it shares, and must fit within that region.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryLayout {
+    regions: IndexMap<String, RegionLayout>,
+
+    /// The region shared by each region declared with `SHARING`
+    parents: HashMap<String, String>,
+
+    bit_size: u64,
+}
+
+impl MemoryLayout {
+    /// Compute the layout of the memory declared by a program, such as
+    /// [`Program::memory_regions`](super::Program::memory_regions).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a region shares one which is not declared, if regions share one another
+    /// cyclically, if a region extends beyond the end of the one it shares, or if the memory is too
+    /// large to be addressed.
+    pub fn new(memory_regions: &IndexMap<String, MemoryRegion>) -> Result<Self, MemoryLayoutError> {
+        let mut layouts = HashMap::new();
+        let mut bit_size = 0u64;
+        for (name, region) in memory_regions {
+            if region.sharing.is_none() {
+                let layout = RegionLayout::new(region.size.data_type, region.size.length, bit_size);
+                bit_size = region
+                    .size
+                    .length
+                    .checked_mul(region.size.data_type.bit_width())
+                    .and_then(|size| size.checked_add(ROOT_ALIGNMENT - 1))
+                    .and_then(|size| bit_size.checked_add(size / ROOT_ALIGNMENT * ROOT_ALIGNMENT))
+                    .filter(|size| usize::try_from(size / 8).is_ok())
+                    .ok_or_else(|| MemoryLayoutError::TooLarge(name.clone()))?;
+                layouts.insert(name.as_str(), layout);
+            }
+        }
+        for name in memory_regions.keys() {
+            resolve_shared_layout(name, memory_regions, &mut layouts, &mut Vec::new())?;
+        }
+
+        let regions = memory_regions
+            .keys()
+            .filter_map(|name| Some((name.clone(), layouts.remove(name.as_str())?)))
+            .collect();
+        let parents = memory_regions
+            .iter()
+            .filter_map(|(name, region)| {
+                Some((name.clone(), region.sharing.as_ref()?.name.clone()))
+            })
+            .collect();
+        Ok(Self {
+            regions,
+            parents,
+            bit_size,
+        })
+    }
+
+    /// The number of bits in the buffer, holding every region.
+    pub fn bit_size(&self) -> u64 {
+        self.bit_size
+    }
+
+    /// The number of bytes in the buffer, holding every region.
+    pub fn byte_size(&self) -> usize {
+        // Checked to fit within a `usize` when the layout was computed
+        (self.bit_size / 8) as usize
+    }
+
+    /// The layout of a memory region, if it is declared.
+    pub fn get(&self, name: &str) -> Option<&RegionLayout> {
+        self.regions.get(name)
+    }
+
+    /// The layout of every memory region, in the order of declaration.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &RegionLayout)> {
+        self.regions.iter()
+    }
+
+    /// The name of the region shared by a memory region, if it is declared with `SHARING`.
+    pub fn parent(&self, name: &str) -> Option<&str> {
+        self.parents.get(name).map(String::as_str)
+    }
+
+    /// The names of the regions which do not start at a multiple of the width of their type, in
+    /// the order of declaration. Only a region declared with `SHARING` may be misaligned.
+    pub fn misaligned_regions(&self) -> Vec<&str> {
+        self.regions
+            .iter()
+            .filter(|(_, layout)| !layout.is_aligned())
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Each pair of regions which occupy some of the same memory, though neither shares the other,
+    /// directly or through other regions. Each pair is given in the order of declaration.
+    pub fn overlapping_regions(&self) -> Vec<(&str, &str)> {
+        let mut overlaps = Vec::new();
+        for (index, (first, first_layout)) in self.regions.iter().enumerate() {
+            for (second, second_layout) in self.regions.iter().skip(index + 1) {
+                let (first_bits, second_bits) =
+                    (first_layout.bit_range(), second_layout.bit_range());
+                if first_bits.start < second_bits.end
+                    && second_bits.start < first_bits.end
+                    && !self.is_shared_by(first, second)
+                    && !self.is_shared_by(second, first)
+                {
+                    overlaps.push((first.as_str(), second.as_str()));
+                }
+            }
+        }
+        overlaps
+    }
+
+    /// Whether `region` shares `ancestor`, directly or through other regions.
+    fn is_shared_by(&self, ancestor: &str, region: &str) -> bool {
+        std::iter::successors(self.parent(region), |name| self.parent(name))
+            .any(|name| name == ancestor)
+    }
+}
+
+/// Compute the layout of a region which shares another, after that of the region it shares.
+fn resolve_shared_layout<'a>(
+    name: &'a str,
+    memory_regions: &'a IndexMap<String, MemoryRegion>,
+    layouts: &mut HashMap<&'a str, RegionLayout>,
+    sharing_chain: &mut Vec<&'a str>,
+) -> Result<RegionLayout, MemoryLayoutError> {
+    if let Some(layout) = layouts.get(name) {
+        return Ok(layout.clone());
+    }
+    if sharing_chain.contains(&name) {
+        return Err(MemoryLayoutError::CyclicSharing(name.to_string()));
+    }
+    let region = &memory_regions[name];
+    let Some(sharing) = &region.sharing else {
+        unreachable!("every region which does not share another has already been laid out");
+    };
+    if !memory_regions.contains_key(&sharing.name) {
+        return Err(MemoryLayoutError::UndeclaredParent {
+            region: name.to_string(),
+            parent: sharing.name.clone(),
+        });
+    }
+
+    sharing_chain.push(name);
+    let parent = resolve_shared_layout(&sharing.name, memory_regions, layouts, sharing_chain)?;
+    sharing_chain.pop();
+
+    let too_large = || MemoryLayoutError::TooLarge(name.to_string());
+    let offset = sharing.offsets.iter().try_fold(0u64, |total, offset| {
+        offset
+            .offset
+            .checked_mul(offset.data_type.bit_width())
+            .and_then(|size| total.checked_add(size))
+            .ok_or_else(too_large)
+    })?;
+    let end = region
+        .size
+        .length
+        .checked_mul(region.size.data_type.bit_width())
+        .and_then(|size| size.checked_add(offset))
+        .ok_or_else(too_large)?;
+    // The parent fits within the buffer, so this offset cannot overflow if the region fits within it.
+    if end > parent.bit_size() {
+        return Err(MemoryLayoutError::ExceedsParent {
+            region: name.to_string(),
+            parent: sharing.name.clone(),
+        });
+    }
+
+    let layout = RegionLayout::new(
+        region.size.data_type,
+        region.size.length,
+        parent.bit_offset + offset,
+    );
+    layouts.insert(name, layout.clone());
+    Ok(layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+
+    use crate::{instruction::ScalarType, Program};
+
+    use super::{MemoryLayout, MemoryLayoutError, RegionLayout};
+
+    fn layout(declarations: &str) -> Result<MemoryLayout, MemoryLayoutError> {
+        MemoryLayout::new(&Program::from_str(declarations).unwrap().memory_regions)
+    }
+
+    #[test]
+    fn lays_out_regions() {
+        let layout = layout(
+            "DECLARE ro BIT[3]
+DECLARE theta REAL[2]
+DECLARE bytes OCTET[9]
+DECLARE high OCTET[2] SHARING theta OFFSET 1 REAL 6 OCTET
+DECLARE flags BIT[4] SHARING high OFFSET 1 OCTET 2 BIT",
+        )
+        .unwrap();
+
+        let expected = [
+            ("ro", RegionLayout::new(ScalarType::Bit, 3, 0)),
+            ("theta", RegionLayout::new(ScalarType::Real, 2, 64)),
+            ("bytes", RegionLayout::new(ScalarType::Octet, 9, 192)),
+            ("high", RegionLayout::new(ScalarType::Octet, 2, 176)),
+            ("flags", RegionLayout::new(ScalarType::Bit, 4, 186)),
+        ];
+        assert_eq!(
+            layout
+                .iter()
+                .map(|(name, region)| (name.as_str(), region.clone()))
+                .collect::<Vec<_>>(),
+            expected
+        );
+        // Each region which does not share another is aligned to 64 bits.
+        assert_eq!(layout.bit_size(), 64 + 128 + 128);
+        assert_eq!(layout.byte_size(), 40);
+
+        let ro = layout.get("ro").unwrap();
+        assert_eq!(ro.bit_range(), 0..3);
+        assert_eq!(ro.byte_range(), 0..1);
+        let flags = layout.get("flags").unwrap();
+        assert_eq!(flags.bit_range(), 186..190);
+        assert_eq!(flags.byte_range(), 23..24);
+
+        assert_eq!(layout.parent("flags"), Some("high"));
+        assert_eq!(layout.parent("theta"), None);
+        assert_eq!(layout.misaligned_regions(), Vec::<&str>::new());
+        assert_eq!(layout.overlapping_regions(), vec![]);
+    }
+
+    #[test]
+    fn reports_misaligned_and_overlapping_regions() {
+        let layout = layout(
+            "DECLARE words INTEGER[2]
+DECLARE low REAL SHARING words
+DECLARE shifted INTEGER SHARING words OFFSET 4 OCTET
+DECLARE bits BIT[8] SHARING shifted OFFSET 4 BIT
+DECLARE other OCTET[16]",
+        )
+        .unwrap();
+
+        assert_eq!(layout.misaligned_regions(), vec!["shifted"]);
+        assert_eq!(
+            layout.overlapping_regions(),
+            vec![("low", "shifted"), ("low", "bits")]
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "DECLARE a BIT SHARING b",
+        MemoryLayoutError::UndeclaredParent { region: "a".to_string(), parent: "b".to_string() }
+    )]
+    #[case(
+        "DECLARE a BIT SHARING b\nDECLARE b BIT SHARING a",
+        MemoryLayoutError::CyclicSharing("a".to_string())
+    )]
+    #[case(
+        "DECLARE a BIT SHARING a",
+        MemoryLayoutError::CyclicSharing("a".to_string())
+    )]
+    #[case(
+        "DECLARE a REAL[2]\nDECLARE b INTEGER SHARING a OFFSET 1 REAL 1 BIT",
+        MemoryLayoutError::ExceedsParent { region: "b".to_string(), parent: "a".to_string() }
+    )]
+    #[case(
+        "DECLARE a BIT[8]\nDECLARE b OCTET[2] SHARING a",
+        MemoryLayoutError::ExceedsParent { region: "b".to_string(), parent: "a".to_string() }
+    )]
+    #[case(
+        "DECLARE a REAL[18446744073709551615]",
+        MemoryLayoutError::TooLarge("a".to_string())
+    )]
+    fn invalid_layout(#[case] declarations: &str, #[case] expected: MemoryLayoutError) {
+        assert_eq!(layout(declarations), Err(expected));
+    }
+}
@@ -0,0 +1,410 @@
+//! A typed model of the classical memory of a Quil program at runtime
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use indexmap::IndexMap;
+
+use crate::{
+    instruction::{MemoryReference, ScalarType},
+    quil::Quil,
+};
+
+use super::{MemoryLayout, MemoryLayoutError, MemoryRegion};
+
+/// A single value stored in classical memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemoryValue {
+    Bit(bool),
+    Octet(u8),
+    Integer(i64),
+    Real(f64),
+}
+
+impl MemoryValue {
+    /// The type of memory region in which this value may be stored.
+    pub fn data_type(&self) -> ScalarType {
+        match self {
+            Self::Bit(_) => ScalarType::Bit,
+            Self::Octet(_) => ScalarType::Octet,
+            Self::Integer(_) => ScalarType::Integer,
+            Self::Real(_) => ScalarType::Real,
+        }
+    }
+
+    /// The value as a real number, as when it is used within an expression.
+    pub fn to_real(&self) -> f64 {
+        match self {
+            Self::Bit(bit) => f64::from(u8::from(*bit)),
+            Self::Octet(octet) => f64::from(*octet),
+            Self::Integer(integer) => *integer as f64,
+            Self::Real(real) => *real,
+        }
+    }
+
+    /// Decode a value of the given type from its bits, stored in the low bits of a `u64`.
+    fn from_bits(data_type: ScalarType, bits: u64) -> Self {
+        match data_type {
+            ScalarType::Bit => Self::Bit(bits != 0),
+            ScalarType::Octet => Self::Octet(bits as u8),
+            ScalarType::Integer => Self::Integer(bits as i64),
+            ScalarType::Real => Self::Real(f64::from_bits(bits)),
+        }
+    }
+
+    /// Encode the value as bits, stored in the low bits of a `u64`.
+    fn to_bits(self) -> u64 {
+        match self {
+            Self::Bit(bit) => u64::from(bit),
+            Self::Octet(octet) => u64::from(octet),
+            Self::Integer(integer) => integer as u64,
+            Self::Real(real) => real.to_bits(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum MemoryStateError {
+    #[error(transparent)]
+    Layout(#[from] MemoryLayoutError),
+
+    #[error("memory region {0} is not declared")]
+    UndeclaredRegion(String),
+
+    #[error("{} is out of bounds for a memory region of length {length}", .reference.to_quil_or_debug())]
+    IndexOutOfBounds {
+        reference: MemoryReference,
+        length: u64,
+    },
+
+    #[error(
+        "cannot write a value of type {} to {}, of type {}",
+        .found.to_quil_or_debug(),
+        .reference.to_quil_or_debug(),
+        .expected.to_quil_or_debug()
+    )]
+    TypeMismatch {
+        reference: MemoryReference,
+        expected: ScalarType,
+        found: ScalarType,
+    },
+}
+
+/// The classical memory of a Quil program: a single buffer of bytes, holding every declared memory
+/// region with the width of its type.
+///
+/// Memory is laid out as by [`MemoryLayout`] and initialized to zero. Regions declared with
+/// `SHARING` alias the memory of the region they share; so, a write to either is visible when
+/// reading the other.
+///
+/// Values are stored as little-endian, with `BIT`s packed from the least significant bit of each
+/// byte.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryState {
+    buffer: Vec<u8>,
+    layout: MemoryLayout,
+}
+
+impl MemoryState {
+    /// Allocate the memory declared by a program, such as [`Program::memory_regions`](super::Program::memory_regions).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a region shares one which is not declared, if regions share one another
+    /// cyclically, or if a region extends beyond the end of the one it shares.
+    pub fn new(memory_regions: &IndexMap<String, MemoryRegion>) -> Result<Self, MemoryStateError> {
+        let layout = MemoryLayout::new(memory_regions)?;
+        Ok(Self {
+            buffer: vec![0; layout.byte_size()],
+            layout,
+        })
+    }
+
+    /// The bytes of memory, holding every region.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// The position of each memory region within [`Self::as_bytes`].
+    pub fn layout(&self) -> &MemoryLayout {
+        &self.layout
+    }
+
+    /// The type and length of a memory region, if it is declared.
+    pub fn region_size(&self, name: &str) -> Option<(ScalarType, u64)> {
+        self.layout
+            .get(name)
+            .map(|layout| (layout.data_type, layout.length))
+    }
+
+    /// Read a single value from memory.
+    pub fn read(&self, reference: &MemoryReference) -> Result<MemoryValue, MemoryStateError> {
+        let (data_type, start) = self.locate(reference)?;
+        Ok(MemoryValue::from_bits(
+            data_type,
+            read_bits(&self.buffer, start, data_type.bit_width()),
+        ))
+    }
+
+    /// Read every value of a memory region.
+    pub fn read_region(&self, name: &str) -> Result<Vec<MemoryValue>, MemoryStateError> {
+        let length = self
+            .layout
+            .get(name)
+            .ok_or_else(|| MemoryStateError::UndeclaredRegion(name.to_string()))?
+            .length;
+        (0..length)
+            .map(|index| self.read(&MemoryReference::new(name.to_string(), index)))
+            .collect()
+    }
+
+    /// Write a single value to memory, which must have the type of its region.
+    pub fn write(
+        &mut self,
+        reference: &MemoryReference,
+        value: MemoryValue,
+    ) -> Result<(), MemoryStateError> {
+        let (data_type, start) = self.locate(reference)?;
+        if value.data_type() != data_type {
+            return Err(MemoryStateError::TypeMismatch {
+                reference: reference.clone(),
+                expected: data_type,
+                found: value.data_type(),
+            });
+        }
+        write_bits(
+            &mut self.buffer,
+            start,
+            data_type.bit_width(),
+            value.to_bits(),
+        );
+        Ok(())
+    }
+
+    /// The type of the region of a reference, and the position of its first bit within the buffer.
+    fn locate(&self, reference: &MemoryReference) -> Result<(ScalarType, u64), MemoryStateError> {
+        let layout = self
+            .layout
+            .get(&reference.name)
+            .ok_or_else(|| MemoryStateError::UndeclaredRegion(reference.name.clone()))?;
+        if reference.index >= layout.length {
+            return Err(MemoryStateError::IndexOutOfBounds {
+                reference: reference.clone(),
+                length: layout.length,
+            });
+        }
+        Ok((
+            layout.data_type,
+            layout.bit_offset + reference.index * layout.data_type.bit_width(),
+        ))
+    }
+}
+
+/// Read `width` bits, at most 64, starting from bit `start` of `buffer`.
+fn read_bits(buffer: &[u8], start: u64, width: u64) -> u64 {
+    if start % 8 == 0 && width % 8 == 0 {
+        let start = (start / 8) as usize;
+        return buffer[start..start + (width / 8) as usize]
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| (value << 8) | u64::from(byte));
+    }
+    (0..width).fold(0, |value, index| {
+        let bit = start + index;
+        let byte = buffer[(bit / 8) as usize];
+        value | (u64::from((byte >> (bit % 8)) & 1) << index)
+    })
+}
+
+/// Write the low `width` bits, at most 64, of `value` starting from bit `start` of `buffer`.
+fn write_bits(buffer: &mut [u8], start: u64, width: u64, value: u64) {
+    if start % 8 == 0 && width % 8 == 0 {
+        let start = (start / 8) as usize;
+        let bytes = value.to_le_bytes();
+        let width = (width / 8) as usize;
+        buffer[start..start + width].copy_from_slice(&bytes[..width]);
+        return;
+    }
+    for index in 0..width {
+        let bit = start + index;
+        let byte = &mut buffer[(bit / 8) as usize];
+        let mask = 1 << (bit % 8);
+        if (value >> index) & 1 == 1 {
+            *byte |= mask;
+        } else {
+            *byte &= !mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::rstest;
+
+    use crate::{instruction::MemoryReference, Program};
+
+    use super::{MemoryLayoutError, MemoryState, MemoryStateError, MemoryValue};
+
+    fn memory(declarations: &str) -> Result<MemoryState, MemoryStateError> {
+        MemoryState::new(&Program::from_str(declarations).unwrap().memory_regions)
+    }
+
+    fn memory_reference(name: &str, index: u64) -> MemoryReference {
+        MemoryReference::new(name.to_string(), index)
+    }
+
+    #[test]
+    fn reads_and_writes_each_type() {
+        let mut memory = memory(
+            "DECLARE ro BIT[3]
+DECLARE count INTEGER
+DECLARE bytes OCTET[2]
+DECLARE theta REAL[2]",
+        )
+        .unwrap();
+
+        let writes = [
+            (memory_reference("ro", 1), MemoryValue::Bit(true)),
+            (memory_reference("count", 0), MemoryValue::Integer(-42)),
+            (memory_reference("bytes", 1), MemoryValue::Octet(200)),
+            (memory_reference("theta", 1), MemoryValue::Real(1.5)),
+        ];
+        for (reference, value) in &writes {
+            memory.write(reference, *value).unwrap();
+        }
+        for (reference, value) in &writes {
+            assert_eq!(memory.read(reference).unwrap(), *value);
+        }
+
+        assert_eq!(
+            memory.read_region("ro").unwrap(),
+            vec![
+                MemoryValue::Bit(false),
+                MemoryValue::Bit(true),
+                MemoryValue::Bit(false)
+            ]
+        );
+        assert_eq!(
+            memory.read(&memory_reference("theta", 0)).unwrap(),
+            MemoryValue::Real(0.0)
+        );
+        // Each region is aligned to 64 bits.
+        assert_eq!(memory.as_bytes().len(), 8 + 8 + 8 + 16);
+    }
+
+    #[test]
+    fn sharing_aliases_memory() {
+        let mut memory = memory(
+            "DECLARE words INTEGER[2]
+DECLARE bits BIT[128] SHARING words
+DECLARE high OCTET SHARING words OFFSET 1 INTEGER 7 OCTET
+DECLARE second INTEGER SHARING bits OFFSET 64 BIT",
+        )
+        .unwrap();
+
+        memory
+            .write(&memory_reference("words", 1), MemoryValue::Integer(-1))
+            .unwrap();
+        assert_eq!(
+            memory.read(&memory_reference("high", 0)).unwrap(),
+            MemoryValue::Octet(0xFF)
+        );
+        assert_eq!(
+            memory.read(&memory_reference("bits", 64)).unwrap(),
+            MemoryValue::Bit(true)
+        );
+
+        memory
+            .write(&memory_reference("bits", 127), MemoryValue::Bit(false))
+            .unwrap();
+        assert_eq!(
+            memory.read(&memory_reference("second", 0)).unwrap(),
+            MemoryValue::Integer(i64::MAX)
+        );
+        assert_eq!(
+            memory.read(&memory_reference("words", 0)).unwrap(),
+            MemoryValue::Integer(0)
+        );
+    }
+
+    #[test]
+    fn unaligned_values() {
+        let mut memory = memory(
+            "DECLARE bits BIT[80]
+DECLARE real REAL SHARING bits OFFSET 3 BIT",
+        )
+        .unwrap();
+
+        memory
+            .write(&memory_reference("real", 0), MemoryValue::Real(-2.5))
+            .unwrap();
+        assert_eq!(
+            memory.read(&memory_reference("real", 0)).unwrap(),
+            MemoryValue::Real(-2.5)
+        );
+        assert_eq!(
+            memory.read(&memory_reference("bits", 2)).unwrap(),
+            MemoryValue::Bit(false)
+        );
+        // The sign bit of the real
+        assert_eq!(
+            memory.read(&memory_reference("bits", 66)).unwrap(),
+            MemoryValue::Bit(true)
+        );
+    }
+
+    #[test]
+    fn invalid_layout() {
+        assert_eq!(
+            memory("DECLARE a BIT SHARING b"),
+            Err(MemoryStateError::Layout(
+                MemoryLayoutError::UndeclaredParent {
+                    region: "a".to_string(),
+                    parent: "b".to_string()
+                }
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case(
+        memory_reference("theta", 2),
+        MemoryValue::Real(0.0),
+        MemoryStateError::IndexOutOfBounds { reference: memory_reference("theta", 2), length: 2 }
+    )]
+    #[case(
+        memory_reference("phi", 0),
+        MemoryValue::Real(0.0),
+        MemoryStateError::UndeclaredRegion("phi".to_string())
+    )]
+    #[case(
+        memory_reference("theta", 0),
+        MemoryValue::Integer(1),
+        MemoryStateError::TypeMismatch {
+            reference: memory_reference("theta", 0),
+            expected: crate::instruction::ScalarType::Real,
+            found: crate::instruction::ScalarType::Integer,
+        }
+    )]
+    fn invalid_write(
+        #[case] reference: MemoryReference,
+        #[case] value: MemoryValue,
+        #[case] expected: MemoryStateError,
+    ) {
+        let mut memory = memory("DECLARE theta REAL[2]").unwrap();
+        assert_eq!(memory.write(&reference, value), Err(expected));
+    }
+}
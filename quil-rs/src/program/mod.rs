@@ -15,11 +15,13 @@
 use std::collections::{HashMap, HashSet};
 use std::ops::{self};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use indexmap::{IndexMap, IndexSet};
 use ndarray::Array2;
 use nom_locate::LocatedSpan;
 
+use crate::expression::CompiledExpressionSet;
 use crate::instruction::{
     Arithmetic, ArithmeticOperand, ArithmeticOperator, Call, Declaration, ExternError,
     ExternPragmaMap, ExternSignatureMap, FrameDefinition, FrameIdentifier, GateDefinition,
@@ -28,11 +30,13 @@ use crate::instruction::{
     WaveformDefinition, RESERVED_PRAGMA_EXTERN,
 };
 use crate::parser::{lex, parse_instructions, ParseError};
-use crate::quil::Quil;
+use crate::quil::{Quil, WriteOptions};
 
+pub use self::binding::BindParametersError;
 pub use self::calibration::Calibrations;
 pub use self::calibration::{
-    CalibrationExpansion, CalibrationExpansionOutput, CalibrationSource, MaybeCalibrationExpansion,
+    CalibrationExpansion, CalibrationExpansionFilter, CalibrationExpansionOutput,
+    CalibrationSource, MaybeCalibrationExpansion,
 };
 pub use self::calibration_set::CalibrationSet;
 pub use self::error::{
@@ -40,17 +44,29 @@ pub use self::error::{
 };
 pub use self::frame::FrameSet;
 pub use self::frame::MatchedFrames;
+pub use self::library::{
+    CalibrationLibrary, CalibrationLibraryError, LinkConflict, LinkConflictPolicy,
+};
 pub use self::memory::{
     MemoryAccess, MemoryAccesses, MemoryAccessesError, MemoryAccessesResult, MemoryRegion,
 };
+pub use self::memory_layout::{MemoryLayout, MemoryLayoutError, RegionLayout};
+pub use self::memory_state::{MemoryState, MemoryStateError, MemoryValue};
+pub use self::remap::{ProgramRemapping, RemapError};
 pub use self::source_map::{SourceMap, SourceMapEntry};
 
 pub mod analysis;
+mod binding;
 mod calibration;
 mod calibration_set;
+pub mod diff;
 mod error;
 pub(crate) mod frame;
+mod library;
 mod memory;
+mod memory_layout;
+mod memory_state;
+mod remap;
 pub mod scheduling;
 mod source_map;
 pub mod type_check;
@@ -86,8 +102,12 @@ pub struct Program {
     extern_pragma_map: ExternPragmaMap,
     pub frames: FrameSet,
     pub memory_regions: IndexMap<String, MemoryRegion>,
-    pub waveforms: IndexMap<String, Waveform>,
-    pub gate_definitions: IndexMap<String, GateDefinition>,
+    /// Waveform definitions, which are shared between clones of this program, and with any
+    /// [`CalibrationLibrary`] linked into it, until modified.
+    pub waveforms: Arc<IndexMap<String, Waveform>>,
+    /// Gate definitions, which are shared between clones of this program, and with any
+    /// [`CalibrationLibrary`] linked into it, until modified.
+    pub gate_definitions: Arc<IndexMap<String, GateDefinition>>,
     instructions: Vec<Instruction>,
     // private field used for caching operations
     used_qubits: HashSet<Qubit>,
@@ -100,8 +120,8 @@ impl Program {
             extern_pragma_map: ExternPragmaMap::default(),
             frames: FrameSet::new(),
             memory_regions: IndexMap::new(),
-            waveforms: IndexMap::new(),
-            gate_definitions: IndexMap::new(),
+            waveforms: Arc::new(IndexMap::new()),
+            gate_definitions: Arc::new(IndexMap::new()),
             instructions: vec![],
             used_qubits: HashSet::new(),
         }
@@ -116,6 +136,21 @@ impl Program {
         self.instructions.into_iter()
     }
 
+    /// Compile every expression within the body of the program for fast repeated evaluation, such
+    /// as over a parameter sweep.
+    ///
+    /// Expressions appear in the returned set in the order in which they are visited by
+    /// [`Instruction::apply_to_expressions`] over [`Program::body_instructions`].
+    pub fn compile_expressions(&self) -> CompiledExpressionSet {
+        let mut compiled = CompiledExpressionSet::new();
+        for instruction in &self.instructions {
+            instruction.for_each_expression(|expression| {
+                compiled.insert(expression);
+            });
+        }
+        compiled
+    }
+
     /// Returns an iterator over mutable references to the instructions that make up the body of the program.
     #[cfg(test)]
     pub(crate) fn for_each_body_instruction<F>(&mut self, closure: F)
@@ -173,7 +208,7 @@ impl Program {
                     .insert(name, MemoryRegion { size, sharing });
             }
             Instruction::GateDefinition(gate_definition) => {
-                self.gate_definitions
+                Arc::make_mut(&mut self.gate_definitions)
                     .insert(gate_definition.name.clone(), gate_definition);
             }
             Instruction::MeasureCalibrationDefinition(calibration) => {
@@ -181,7 +216,7 @@ impl Program {
                     .insert_measurement_calibration(calibration);
             }
             Instruction::WaveformDefinition(WaveformDefinition { name, definition }) => {
-                self.waveforms.insert(name, definition);
+                Arc::make_mut(&mut self.waveforms).insert(name, definition);
             }
             Instruction::Gate(gate) => {
                 self.instructions.push(Instruction::Gate(gate));
@@ -262,14 +297,27 @@ impl Program {
     ///
     /// See [`Program::expand_calibrations_with_source_map`] for a version that returns a source mapping.
     pub fn expand_calibrations(&self) -> Result<Self> {
-        self.expand_calibrations_inner(None)
+        self.expand_calibrations_inner(None, &CalibrationExpansionFilter::default())
     }
 
     /// Expand any instructions in the program which have a matching calibration, leaving the others
     /// unchanged. Return the expanded copy of the program and a source mapping of the expansions made.
     pub fn expand_calibrations_with_source_map(&self) -> Result<ProgramCalibrationExpansion> {
+        self.expand_calibrations_with_filter(&CalibrationExpansionFilter::default())
+    }
+
+    /// Expand only those instructions in the program which have a matching calibration and are
+    /// allowed by `filter`, leaving the others unchanged. Return the partially-expanded copy of the
+    /// program and a source mapping of the expansions made.
+    ///
+    /// For example, a filter with a maximum depth of `1` expands each instruction in the program
+    /// using its calibration, but does not expand the instructions within that calibration.
+    pub fn expand_calibrations_with_filter(
+        &self,
+        filter: &CalibrationExpansionFilter<'_>,
+    ) -> Result<ProgramCalibrationExpansion> {
         let mut source_mapping = ProgramCalibrationExpansionSourceMap::default();
-        let new_program = self.expand_calibrations_inner(Some(&mut source_mapping))?;
+        let new_program = self.expand_calibrations_inner(Some(&mut source_mapping), filter)?;
 
         Ok(ProgramCalibrationExpansion {
             program: new_program,
@@ -285,6 +333,7 @@ impl Program {
     fn expand_calibrations_inner(
         &self,
         mut source_mapping: Option<&mut ProgramCalibrationExpansionSourceMap>,
+        filter: &CalibrationExpansionFilter<'_>,
     ) -> Result<Self> {
         let mut new_program = Self {
             calibrations: self.calibrations.clone(),
@@ -300,7 +349,10 @@ impl Program {
         for (index, instruction) in self.instructions.iter().enumerate() {
             let index = InstructionIndex(index);
 
-            match self.calibrations.expand_with_detail(instruction, &[])? {
+            match self
+                .calibrations
+                .expand_with_filter(instruction, &[], filter)?
+            {
                 Some(expanded) => {
                     new_program.append_calibration_expansion_output_inner(
                         expanded,
@@ -440,12 +492,14 @@ impl Program {
             })
         }));
         instructions.extend(self.frames.into_instructions());
-        instructions.extend(self.waveforms.into_iter().map(|(name, definition)| {
-            Instruction::WaveformDefinition(WaveformDefinition { name, definition })
-        }));
+        instructions.extend(unwrap_or_clone(self.waveforms).into_iter().map(
+            |(name, definition)| {
+                Instruction::WaveformDefinition(WaveformDefinition { name, definition })
+            },
+        ));
         instructions.extend(self.calibrations.to_instructions());
         instructions.extend(
-            self.gate_definitions
+            unwrap_or_clone(self.gate_definitions)
                 .into_values()
                 .map(Instruction::GateDefinition),
         );
@@ -485,9 +539,14 @@ impl Program {
         }
 
         expanded_program.frames = self.frames.intersection(&frames_used);
-        expanded_program
+        if !expanded_program
             .waveforms
-            .retain(|name, _definition| waveforms_used.contains(name));
+            .keys()
+            .all(|name| waveforms_used.contains(name))
+        {
+            Arc::make_mut(&mut expanded_program.waveforms)
+                .retain(|name, _definition| waveforms_used.contains(name));
+        }
         expanded_program
             .extern_pragma_map
             .retain(|name, _signature| {
@@ -774,10 +833,10 @@ impl Quil for Program {
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> std::result::Result<(), crate::quil::ToQuilError> {
         for instruction in self.to_instructions() {
-            instruction.write(writer, fall_back_to_debug)?;
+            instruction.write(writer, options)?;
             writeln!(writer)?;
         }
         Ok(())
@@ -819,8 +878,8 @@ impl ops::Add<Program> for Program {
         new_program.calibrations.extend(rhs.calibrations);
         new_program.memory_regions.extend(rhs.memory_regions);
         new_program.frames.merge(rhs.frames);
-        new_program.waveforms.extend(rhs.waveforms);
-        new_program.gate_definitions.extend(rhs.gate_definitions);
+        extend_shared(&mut new_program.waveforms, rhs.waveforms);
+        extend_shared(&mut new_program.gate_definitions, rhs.gate_definitions);
         new_program.instructions.extend(rhs.instructions);
         new_program.used_qubits.extend(rhs.used_qubits);
         new_program
@@ -832,13 +891,28 @@ impl ops::AddAssign<Program> for Program {
         self.calibrations.extend(rhs.calibrations);
         self.memory_regions.extend(rhs.memory_regions);
         self.frames.merge(rhs.frames);
-        self.waveforms.extend(rhs.waveforms);
-        self.gate_definitions.extend(rhs.gate_definitions);
+        extend_shared(&mut self.waveforms, rhs.waveforms);
+        extend_shared(&mut self.gate_definitions, rhs.gate_definitions);
         self.instructions.extend(rhs.instructions);
         self.used_qubits.extend(rhs.used_qubits);
     }
 }
 
+/// Take the value out of an [`Arc`], cloning it only if it is shared.
+fn unwrap_or_clone<T: Clone>(shared: Arc<T>) -> T {
+    Arc::try_unwrap(shared).unwrap_or_else(|shared| (*shared).clone())
+}
+
+/// Extend a shared map with the entries of another, sharing the other's entries rather than
+/// copying them if this map is empty.
+fn extend_shared<V: Clone>(map: &mut Arc<IndexMap<String, V>>, other: Arc<IndexMap<String, V>>) {
+    if map.is_empty() {
+        *map = other;
+    } else if !other.is_empty() {
+        Arc::make_mut(map).extend(unwrap_or_clone(other));
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct InstructionIndex(pub usize);
 
@@ -879,7 +953,10 @@ mod tests {
             RESERVED_PRAGMA_EXTERN,
         },
         program::{
-            calibration::{CalibrationExpansion, CalibrationSource, MaybeCalibrationExpansion},
+            calibration::{
+                CalibrationExpansion, CalibrationExpansionFilter, CalibrationSource,
+                MaybeCalibrationExpansion,
+            },
             source_map::{SourceMap, SourceMapEntry},
             InstructionIndex, MemoryAccesses,
         },
@@ -1121,6 +1198,94 @@ NOP
         pretty_assertions::assert_eq!(expanded_program.source_map, expected_source_map);
     }
 
+    #[rstest]
+    #[case::all(CalibrationExpansionFilter::new(), &["NOP", "HALT", "HALT"], 2)]
+    #[case::depth_1(
+        CalibrationExpansionFilter::new().with_max_depth(1),
+        &["Y 0", "MEASURE 0 ro[0]", "HALT"],
+        2
+    )]
+    #[case::depth_0(
+        CalibrationExpansionFilter::new().with_max_depth(0),
+        &["X 0", "MEASURE 0 ro[0]"],
+        0
+    )]
+    #[case::measurements(
+        CalibrationExpansionFilter::new()
+            .with_predicate(|instruction| matches!(instruction, Instruction::Measurement(_))),
+        &["X 0", "HALT"],
+        1
+    )]
+    #[case::gates_on_qubit_0(
+        CalibrationExpansionFilter::new().with_predicate(|instruction| match instruction {
+            Instruction::Gate(gate) => gate.qubits == [Qubit::Fixed(0)],
+            _ => false,
+        }),
+        &["NOP", "MEASURE 0 ro[0]", "MEASURE 0 ro[0]"],
+        1
+    )]
+    fn expand_calibrations_with_filter(
+        #[case] filter: CalibrationExpansionFilter<'static>,
+        #[case] expected_body: &[&str],
+        #[case] expected_expansions: usize,
+    ) {
+        let program = Program::from_str(
+            r#"DECLARE ro BIT
+DEFCAL X 0:
+    Y 0
+    MEASURE 0 ro
+DEFCAL Y 0:
+    NOP
+DEFCAL MEASURE 0 addr:
+    HALT
+X 0
+MEASURE 0 ro
+"#,
+        )
+        .unwrap();
+        let expansion = program.expand_calibrations_with_filter(&filter).unwrap();
+
+        let body = expansion
+            .program()
+            .body_instructions()
+            .map(|instruction| instruction.to_quil().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(body, expected_body);
+
+        let expansions = expansion
+            .source_map()
+            .entries()
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    entry.target_location(),
+                    MaybeCalibrationExpansion::Expanded(_)
+                )
+            })
+            .count();
+        assert_eq!(expansions, expected_expansions);
+    }
+
+    #[test]
+    fn compile_expressions() {
+        let program = Program::from_str(
+            r#"DECLARE theta REAL[2]
+RX(theta[0] * 2) 0
+SHIFT-PHASE 0 "rf" %phi + theta[1]
+DELAY 0 1e-6
+"#,
+        )
+        .unwrap();
+        let compiled = program.compile_expressions();
+
+        assert_eq!(compiled.expressions().len(), 3);
+        assert_eq!(compiled.slots().variables().collect::<Vec<_>>(), ["phi"]);
+        assert_eq!(
+            compiled.evaluate(&[real!(0.5)], &[1.0, 2.0]),
+            Ok(vec![real!(2.0), real!(2.5), real!(1e-6)])
+        );
+    }
+
     #[test]
     fn frame_blocking() {
         let input = "DEFFRAME 0 \"a\":
@@ -1806,6 +1971,19 @@ DEFFRAME 0 \"xy\":
         }
     }
 
+    #[rstest]
+    #[case("DEFCAL RX(%alpha) 0:\n\tRZ(atan2(%alpha, 1)) 0\n")]
+    #[case("DECLARE theta REAL[2]\nRX(max(theta[0], theta[1])) 0\n")]
+    #[case("DEFCIRCUIT C:\n\tRZ(tan(pi/4)) 0\n")]
+    #[case("PULSE 0 \"rf\" flat(duration: 1e-6, iq: abs(-1))\n")]
+    fn test_non_standard_function_serialization(#[case] input: &str) {
+        let program = Program::from_str(input).unwrap();
+        assert!(program.to_quil().is_err());
+
+        let quil = program.to_quil_with_non_standard_functions().unwrap();
+        assert_eq!(Program::from_str(&quil).unwrap(), program);
+    }
+
     /// Test that a program with a `CALL` instruction can be parsed and properly resolved to
     /// the corresponding `EXTERN` instruction. Additionally, test that the memory accesses are
     /// correctly calculated with the resolved `CALL` instruction.
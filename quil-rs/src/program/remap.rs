@@ -0,0 +1,521 @@
+//! Relabelling the qubits and frames of a program
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::{
+    instruction::{
+        Calibration, Capture, CircuitDefinition, Delay, Fence, FrameDefinition, FrameIdentifier,
+        Gate, Instruction, MeasureCalibrationDefinition, Measurement, Pulse, Qubit, RawCapture,
+        Reset, SetFrequency, SetPhase, SetScale, ShiftFrequency, ShiftPhase, SwapPhases,
+    },
+    program::FrameSet,
+    quil::Quil,
+    Program,
+};
+
+/// A relabelling of the fixed qubits and frame names of a program, for use with
+/// [`Program::remap`].
+///
+/// Qubits and frame names which are not given a new label are left unchanged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProgramRemapping {
+    qubits: HashMap<u64, u64>,
+    frame_names: HashMap<String, String>,
+}
+
+impl ProgramRemapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Relabel fixed qubit `from` as `to`.
+    pub fn with_qubit(mut self, from: u64, to: u64) -> Self {
+        self.qubits.insert(from, to);
+        self
+    }
+
+    /// Relabel each fixed qubit `from` as `to`.
+    pub fn with_qubits(mut self, qubits: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        self.qubits.extend(qubits);
+        self
+    }
+
+    /// Rename every frame named `from` to `to`, regardless of its qubits.
+    pub fn with_frame_name(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.frame_names.insert(from.into(), to.into());
+        self
+    }
+
+    fn remap_qubit(&self, qubit: &mut Qubit) {
+        if let Qubit::Fixed(index) = qubit {
+            if let Some(target) = self.qubits.get(index) {
+                *index = *target;
+            }
+        }
+    }
+
+    fn remap_frame_name(&self, name: &mut String) {
+        if let Some(target) = self.frame_names.get(name) {
+            name.clone_from(target);
+        }
+    }
+
+    fn remap_frame(&self, frame: &FrameIdentifier) -> FrameIdentifier {
+        let mut frame = frame.clone();
+        frame
+            .qubits
+            .iter_mut()
+            .for_each(|qubit| self.remap_qubit(qubit));
+        self.remap_frame_name(&mut frame.name);
+        frame
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum RemapError {
+    #[error("qubits {first} and {second} would both be relabelled as qubit {target}")]
+    QubitCollision {
+        first: u64,
+        second: u64,
+        target: u64,
+    },
+
+    #[error(
+        "frames {} and {} would both be relabelled as frame {}",
+        .first.to_quil_or_debug(),
+        .second.to_quil_or_debug(),
+        .target.to_quil_or_debug()
+    )]
+    FrameCollision {
+        first: FrameIdentifier,
+        second: FrameIdentifier,
+        target: FrameIdentifier,
+    },
+}
+
+impl Program {
+    /// Return a copy of this program in which qubits and frames are relabelled according to
+    /// `remapping`.
+    ///
+    /// Fixed qubits are relabelled wherever they appear: in the body, in the signatures and bodies
+    /// of calibrations, in frame definitions and frame references, and in `DELAY` and `FENCE`
+    /// qubit lists. Frames are renamed wherever they appear, including the frame names of `DELAY`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if two distinct qubits used by the program would share a label, or if two
+    /// distinct frames defined or used by the program would share an identifier, since either would
+    /// change the meaning of the program.
+    pub fn remap(&self, remapping: &ProgramRemapping) -> Result<Self, RemapError> {
+        // Remap a copy of the program, rather than one rebuilt from its instructions, so that its
+        // other settings and its shared waveform and gate definitions carry over. Only the
+        // definitions which refer to qubits or frames are rebuilt.
+        let mut program = self.clone();
+        let mut definitions = self.calibrations.to_instructions();
+        definitions.extend(self.frames.to_instructions());
+
+        let mut used_qubits = BTreeSet::new();
+        for instruction in definitions.iter_mut().chain(&mut program.instructions) {
+            visit_instruction(instruction, &mut |qubit| {
+                if let Qubit::Fixed(index) = qubit {
+                    used_qubits.insert(*index);
+                }
+            });
+        }
+        let mut remapped_qubits = BTreeMap::new();
+        for qubit in used_qubits {
+            let target = remapping.qubits.get(&qubit).copied().unwrap_or(qubit);
+            if let Some(first) = remapped_qubits.insert(target, qubit) {
+                return Err(RemapError::QubitCollision {
+                    first,
+                    second: qubit,
+                    target,
+                });
+            }
+        }
+
+        let mut used_frames = self
+            .frames
+            .get_keys()
+            .into_iter()
+            .cloned()
+            .collect::<HashSet<_>>();
+        for instruction in definitions.iter().chain(&program.instructions) {
+            visit_frames(instruction, &mut |frame| {
+                used_frames.insert(frame);
+            });
+        }
+        let mut frames = used_frames.iter().collect::<Vec<_>>();
+        frames.sort_by(|a, b| (&a.qubits, &a.name).cmp(&(&b.qubits, &b.name)));
+        let mut remapped_frames = HashMap::new();
+        for frame in frames {
+            let target = remapping.remap_frame(frame);
+            if let Some(first) = remapped_frames.insert(target.clone(), frame) {
+                return Err(RemapError::FrameCollision {
+                    first: first.clone(),
+                    second: frame.clone(),
+                    target,
+                });
+            }
+        }
+
+        for instruction in definitions.iter_mut().chain(&mut program.instructions) {
+            visit_instruction(instruction, &mut |qubit| remapping.remap_qubit(qubit));
+            visit_frame_names(instruction, &mut |name| remapping.remap_frame_name(name));
+        }
+
+        program.used_qubits = definitions
+            .iter()
+            .chain(&program.instructions)
+            .flat_map(|instruction| instruction.get_qubits().into_iter().cloned())
+            .collect();
+
+        let mut calibrations = Vec::new();
+        let mut measure_calibrations = Vec::new();
+        program.frames = FrameSet::new();
+        for instruction in definitions {
+            match instruction {
+                Instruction::CalibrationDefinition(calibration) => calibrations.push(calibration),
+                Instruction::MeasureCalibrationDefinition(calibration) => {
+                    measure_calibrations.push(calibration)
+                }
+                Instruction::FrameDefinition(FrameDefinition {
+                    identifier,
+                    attributes,
+                }) => program.frames.insert(identifier, attributes),
+                _ => {}
+            }
+        }
+        program.calibrations.calibrations = calibrations.into();
+        program.calibrations.measure_calibrations = measure_calibrations.into();
+
+        Ok(program)
+    }
+}
+
+/// Call `visit` on every qubit within an instruction, including those of frames and those within
+/// the bodies of calibrations and circuits.
+fn visit_instruction(instruction: &mut Instruction, visit: &mut impl FnMut(&mut Qubit)) {
+    match instruction {
+        Instruction::Gate(Gate { qubits, .. })
+        | Instruction::Delay(Delay { qubits, .. })
+        | Instruction::Fence(Fence { qubits })
+        | Instruction::Capture(Capture {
+            frame: FrameIdentifier { qubits, .. },
+            ..
+        })
+        | Instruction::FrameDefinition(FrameDefinition {
+            identifier: FrameIdentifier { qubits, .. },
+            ..
+        })
+        | Instruction::Pulse(Pulse {
+            frame: FrameIdentifier { qubits, .. },
+            ..
+        })
+        | Instruction::RawCapture(RawCapture {
+            frame: FrameIdentifier { qubits, .. },
+            ..
+        })
+        | Instruction::SetFrequency(SetFrequency {
+            frame: FrameIdentifier { qubits, .. },
+            ..
+        })
+        | Instruction::SetPhase(SetPhase {
+            frame: FrameIdentifier { qubits, .. },
+            ..
+        })
+        | Instruction::SetScale(SetScale {
+            frame: FrameIdentifier { qubits, .. },
+            ..
+        })
+        | Instruction::ShiftFrequency(ShiftFrequency {
+            frame: FrameIdentifier { qubits, .. },
+            ..
+        })
+        | Instruction::ShiftPhase(ShiftPhase {
+            frame: FrameIdentifier { qubits, .. },
+            ..
+        }) => qubits.iter_mut().for_each(visit),
+        Instruction::Measurement(Measurement { qubit, .. }) => visit(qubit),
+        Instruction::Reset(Reset { qubit }) => qubit.iter_mut().for_each(visit),
+        Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => frame_1
+            .qubits
+            .iter_mut()
+            .chain(frame_2.qubits.iter_mut())
+            .for_each(visit),
+        Instruction::CalibrationDefinition(Calibration {
+            identifier,
+            instructions,
+        }) => {
+            identifier.qubits.iter_mut().for_each(&mut *visit);
+            for instruction in instructions {
+                visit_instruction(instruction, visit);
+            }
+        }
+        Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+            identifier,
+            instructions,
+        }) => {
+            identifier.qubit.iter_mut().for_each(&mut *visit);
+            for instruction in instructions {
+                visit_instruction(instruction, visit);
+            }
+        }
+        Instruction::CircuitDefinition(CircuitDefinition { instructions, .. }) => {
+            for instruction in instructions {
+                visit_instruction(instruction, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Call `visit` on every frame used within an instruction, including those within the bodies of
+/// calibrations and circuits. A `DELAY` uses one frame for each of its frame names.
+fn visit_frames(instruction: &Instruction, visit: &mut impl FnMut(FrameIdentifier)) {
+    match instruction {
+        Instruction::Capture(Capture { frame, .. })
+        | Instruction::Pulse(Pulse { frame, .. })
+        | Instruction::RawCapture(RawCapture { frame, .. })
+        | Instruction::SetFrequency(SetFrequency { frame, .. })
+        | Instruction::SetPhase(SetPhase { frame, .. })
+        | Instruction::SetScale(SetScale { frame, .. })
+        | Instruction::ShiftFrequency(ShiftFrequency { frame, .. })
+        | Instruction::ShiftPhase(ShiftPhase { frame, .. }) => visit(frame.clone()),
+        Instruction::Delay(Delay {
+            frame_names,
+            qubits,
+            ..
+        }) => {
+            for name in frame_names {
+                visit(FrameIdentifier::new(name.clone(), qubits.clone()));
+            }
+        }
+        Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => {
+            visit(frame_1.clone());
+            visit(frame_2.clone());
+        }
+        Instruction::CalibrationDefinition(Calibration { instructions, .. })
+        | Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+            instructions,
+            ..
+        })
+        | Instruction::CircuitDefinition(CircuitDefinition { instructions, .. }) => {
+            for instruction in instructions {
+                visit_frames(instruction, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Call `visit` on every frame name within an instruction, including those within the bodies of
+/// calibrations and circuits.
+fn visit_frame_names(instruction: &mut Instruction, visit: &mut impl FnMut(&mut String)) {
+    match instruction {
+        Instruction::Capture(Capture {
+            frame: FrameIdentifier { name, .. },
+            ..
+        })
+        | Instruction::FrameDefinition(FrameDefinition {
+            identifier: FrameIdentifier { name, .. },
+            ..
+        })
+        | Instruction::Pulse(Pulse {
+            frame: FrameIdentifier { name, .. },
+            ..
+        })
+        | Instruction::RawCapture(RawCapture {
+            frame: FrameIdentifier { name, .. },
+            ..
+        })
+        | Instruction::SetFrequency(SetFrequency {
+            frame: FrameIdentifier { name, .. },
+            ..
+        })
+        | Instruction::SetPhase(SetPhase {
+            frame: FrameIdentifier { name, .. },
+            ..
+        })
+        | Instruction::SetScale(SetScale {
+            frame: FrameIdentifier { name, .. },
+            ..
+        })
+        | Instruction::ShiftFrequency(ShiftFrequency {
+            frame: FrameIdentifier { name, .. },
+            ..
+        })
+        | Instruction::ShiftPhase(ShiftPhase {
+            frame: FrameIdentifier { name, .. },
+            ..
+        }) => visit(name),
+        Instruction::Delay(Delay { frame_names, .. }) => frame_names.iter_mut().for_each(visit),
+        Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => {
+            visit(&mut frame_1.name);
+            visit(&mut frame_2.name);
+        }
+        Instruction::CalibrationDefinition(Calibration { instructions, .. })
+        | Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+            instructions,
+            ..
+        })
+        | Instruction::CircuitDefinition(CircuitDefinition { instructions, .. }) => {
+            for instruction in instructions {
+                visit_frame_names(instruction, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use crate::{
+        instruction::{CalibrationParameterMatching, FrameIdentifier, Qubit},
+        Program,
+    };
+
+    use super::{ProgramRemapping, RemapError};
+
+    const ANGLE_MATCHING: CalibrationParameterMatching =
+        CalibrationParameterMatching::AngleModulo2Pi { tolerance: 1e-9 };
+
+    const PROGRAM: &str = r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 0 1 "cz":
+    SAMPLE-RATE: 1e9
+DEFCAL X 0:
+    PULSE 0 "rf" flat(duration: 1e-6, iq: 1)
+DEFCAL CZ 0 1:
+    PULSE 0 1 "cz" flat(duration: 1e-6, iq: 1)
+    SHIFT-PHASE 1 "rf" 1.0
+DEFCAL CZ q0 q1:
+    FENCE q0 q1
+X 0
+CZ 0 1
+DELAY 0 1 "rf" 1e-6
+FENCE 0 1
+"#;
+
+    #[test]
+    fn remaps_qubits_and_frames() {
+        let mut program = Program::from_str(PROGRAM).unwrap();
+        program.calibrations.parameter_matching = ANGLE_MATCHING;
+        let remapping = ProgramRemapping::new()
+            .with_qubits([(0, 10), (1, 11)])
+            .with_frame_name("rf", "drive");
+        let mut remapped = program.remap(&remapping).unwrap();
+
+        let expected = Program::from_str(
+            r#"DEFFRAME 10 "drive":
+    SAMPLE-RATE: 1e9
+DEFFRAME 11 "drive":
+    SAMPLE-RATE: 1e9
+DEFFRAME 10 11 "cz":
+    SAMPLE-RATE: 1e9
+DEFCAL X 10:
+    PULSE 10 "drive" flat(duration: 1e-6, iq: 1)
+DEFCAL CZ 10 11:
+    PULSE 10 11 "cz" flat(duration: 1e-6, iq: 1)
+    SHIFT-PHASE 11 "drive" 1.0
+DEFCAL CZ q0 q1:
+    FENCE q0 q1
+X 10
+CZ 10 11
+DELAY 10 11 "drive" 1e-6
+FENCE 10 11
+"#,
+        )
+        .unwrap();
+        assert_eq!(remapped.calibrations.parameter_matching, ANGLE_MATCHING);
+        assert!(Arc::ptr_eq(&remapped.waveforms, &program.waveforms));
+        assert!(Arc::ptr_eq(
+            &remapped.gate_definitions,
+            &program.gate_definitions
+        ));
+        assert_eq!(remapped.get_used_qubits(), expected.get_used_qubits());
+        remapped.calibrations.parameter_matching = expected.calibrations.parameter_matching;
+        assert_eq!(remapped, expected);
+    }
+
+    #[test]
+    fn rejects_qubit_collisions() {
+        let program = Program::from_str(PROGRAM).unwrap();
+        let result = program.remap(&ProgramRemapping::new().with_qubit(0, 1));
+        assert_eq!(
+            result,
+            Err(RemapError::QubitCollision {
+                first: 0,
+                second: 1,
+                target: 1,
+            })
+        );
+
+        // Swapping qubits is not a collision.
+        let swapped = program
+            .remap(&ProgramRemapping::new().with_qubits([(0, 1), (1, 0)]))
+            .unwrap();
+        assert_eq!(
+            swapped.remap(&ProgramRemapping::new().with_qubits([(0, 1), (1, 0)])),
+            Ok(program)
+        );
+    }
+
+    #[test]
+    fn rejects_frame_collisions() {
+        let program = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 0 "xy":
+    SAMPLE-RATE: 1e9
+"#,
+        )
+        .unwrap();
+        let result = program.remap(&ProgramRemapping::new().with_frame_name("xy", "rf"));
+        assert_eq!(
+            result,
+            Err(RemapError::FrameCollision {
+                first: FrameIdentifier::new("rf".to_string(), vec![Qubit::Fixed(0)]),
+                second: FrameIdentifier::new("xy".to_string(), vec![Qubit::Fixed(0)]),
+                target: FrameIdentifier::new("rf".to_string(), vec![Qubit::Fixed(0)]),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_collisions_of_undefined_frames() {
+        let program = Program::from_str(
+            r#"PULSE 0 "xy" flat(duration: 1e-6, iq: 1)
+DELAY 0 "rf" 1e-6
+"#,
+        )
+        .unwrap();
+        let result = program.remap(&ProgramRemapping::new().with_frame_name("xy", "rf"));
+        assert_eq!(
+            result,
+            Err(RemapError::FrameCollision {
+                first: FrameIdentifier::new("rf".to_string(), vec![Qubit::Fixed(0)]),
+                second: FrameIdentifier::new("xy".to_string(), vec![Qubit::Fixed(0)]),
+                target: FrameIdentifier::new("rf".to_string(), vec![Qubit::Fixed(0)]),
+            })
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Utilities for exporting computed schedules in the Chrome Trace Event format
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, HashMap};
+
+use itertools::Itertools;
+use serde_json::{json, Value};
+
+use crate::{
+    instruction::{FrameIdentifier, Instruction, Qubit},
+    quil::Quil,
+    Program,
+};
+
+use super::{
+    ComputedScheduleError, ComputedScheduleResult, ScheduleSeconds, ScheduledBasicBlock,
+    ScheduledProgram,
+};
+
+/// Trace timestamps are expressed in microseconds.
+const MICROSECONDS_PER_SECOND: f64 = 1e6;
+
+/// A single row ("thread", in trace terminology) within the trace of a block.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum TraceTrack<'p> {
+    Frame(&'p FrameIdentifier),
+    Qubit(&'p Qubit),
+    Unassigned,
+}
+
+impl TraceTrack<'_> {
+    fn rank(&self) -> u8 {
+        match self {
+            TraceTrack::Frame(_) => 0,
+            TraceTrack::Qubit(_) => 1,
+            TraceTrack::Unassigned => 2,
+        }
+    }
+}
+
+/// Tracks are ordered so that trace output is deterministic: frames first (by qubits, then name),
+/// followed by qubits.
+impl Ord for TraceTrack<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (TraceTrack::Frame(a), TraceTrack::Frame(b)) => {
+                (&a.qubits, &a.name).cmp(&(&b.qubits, &b.name))
+            }
+            (TraceTrack::Qubit(a), TraceTrack::Qubit(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for TraceTrack<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for TraceTrack<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceTrack::Frame(frame) => write!(f, "{}", frame.to_quil_or_debug()),
+            TraceTrack::Qubit(qubit) => write!(f, "qubit {}", qubit.to_quil_or_debug()),
+            TraceTrack::Unassigned => write!(f, "unassigned"),
+        }
+    }
+}
+
+/// Return the trace event category for an instruction, used for filtering within trace viewers.
+fn get_category(instruction: &Instruction) -> &'static str {
+    match instruction {
+        Instruction::Pulse(_) => "pulse",
+        Instruction::Capture(_) | Instruction::RawCapture(_) => "capture",
+        Instruction::Delay(_) => "delay",
+        Instruction::Fence(_) => "fence",
+        Instruction::SetFrequency(_)
+        | Instruction::SetPhase(_)
+        | Instruction::SetScale(_)
+        | Instruction::ShiftFrequency(_)
+        | Instruction::ShiftPhase(_)
+        | Instruction::SwapPhases(_) => "frame",
+        Instruction::Gate(_) => "gate",
+        _ => "other",
+    }
+}
+
+impl<'p> ScheduledBasicBlock<'p> {
+    /// Return the tracks on which the given instruction should be displayed, and whether it should
+    /// be displayed as an instant event rather than as a span of time.
+    ///
+    /// Instructions are shown on the frames they use. Instructions which only block frames (such as
+    /// `FENCE`) are shown as instant markers on each blocked frame. Instructions which do not operate
+    /// on frames at all (such as gates) are shown on the qubits they act upon.
+    fn get_trace_tracks(
+        program: &'p Program,
+        instruction: &'p Instruction,
+    ) -> (Vec<TraceTrack<'p>>, bool) {
+        if let Some(matched_frames) = program.get_frames_for_instruction(instruction) {
+            if !matched_frames.used().is_empty() {
+                let tracks = matched_frames.used.into_iter().map(TraceTrack::Frame);
+                return (tracks.sorted().collect(), false);
+            }
+
+            if !matched_frames.blocked().is_empty() {
+                let tracks = matched_frames.blocked.into_iter().map(TraceTrack::Frame);
+                return (tracks.sorted().collect(), true);
+            }
+        }
+
+        let qubits = instruction.get_qubits();
+        if qubits.is_empty() {
+            (vec![TraceTrack::Unassigned], false)
+        } else {
+            (qubits.into_iter().map(TraceTrack::Qubit).collect(), false)
+        }
+    }
+
+    /// Append the trace events describing this block's `schedule` to `events`, using `process_id`
+    /// and `process_name` to distinguish this block from others within the same trace.
+    fn write_chrome_trace_events(
+        &self,
+        program: &'p Program,
+        schedule: &ScheduleSeconds,
+        process_id: usize,
+        process_name: &str,
+        events: &mut Vec<Value>,
+    ) {
+        events.push(json!({
+            "name": "process_name",
+            "ph": "M",
+            "pid": process_id,
+            "args": { "name": process_name },
+        }));
+        events.push(json!({
+            "name": "process_sort_index",
+            "ph": "M",
+            "pid": process_id,
+            "args": { "sort_index": process_id },
+        }));
+
+        let mut items = schedule
+            .items()
+            .iter()
+            .filter_map(|item| {
+                let instruction = *self
+                    .basic_block()
+                    .instructions()
+                    .get(item.instruction_index)?;
+                let (tracks, is_instant) = Self::get_trace_tracks(program, instruction);
+                Some((item, instruction, tracks, is_instant))
+            })
+            .collect::<Vec<_>>();
+        items.sort_by_key(|(item, ..)| item.instruction_index);
+
+        let tracks = items
+            .iter()
+            .flat_map(|(_, _, tracks, _)| tracks.iter().cloned())
+            .collect::<BTreeSet<_>>();
+        let thread_ids = tracks
+            .iter()
+            .enumerate()
+            .map(|(thread_id, track)| (track.clone(), thread_id))
+            .collect::<HashMap<_, _>>();
+
+        for (thread_id, track) in tracks.iter().enumerate() {
+            events.push(json!({
+                "name": "thread_name",
+                "ph": "M",
+                "pid": process_id,
+                "tid": thread_id,
+                "args": { "name": track.to_string() },
+            }));
+            events.push(json!({
+                "name": "thread_sort_index",
+                "ph": "M",
+                "pid": process_id,
+                "tid": thread_id,
+                "args": { "sort_index": thread_id },
+            }));
+        }
+
+        for (item, instruction, instruction_tracks, is_instant) in items {
+            let name = instruction.to_quil_or_debug();
+            let category = get_category(instruction);
+            let start_time = item.time_span.start_time.0 * MICROSECONDS_PER_SECOND;
+            let duration = item.time_span.duration.0 * MICROSECONDS_PER_SECOND;
+            let args = json!({
+                "instruction_index": item.instruction_index,
+                "start_time_seconds": item.time_span.start_time.0,
+                "duration_seconds": item.time_span.duration.0,
+            });
+
+            for track in instruction_tracks {
+                let thread_id = thread_ids[&track];
+                let event = if is_instant {
+                    json!({
+                        "name": name,
+                        "cat": category,
+                        "ph": "i",
+                        "s": "t",
+                        "ts": start_time,
+                        "pid": process_id,
+                        "tid": thread_id,
+                        "args": args,
+                    })
+                } else {
+                    json!({
+                        "name": name,
+                        "cat": category,
+                        "ph": "X",
+                        "ts": start_time,
+                        "dur": duration,
+                        "pid": process_id,
+                        "tid": thread_id,
+                        "args": args,
+                    })
+                };
+                events.push(event);
+            }
+        }
+    }
+}
+
+impl ScheduledProgram<'_> {
+    /// Return a Chrome Trace Event format JSON document (as bytes) describing the schedule of each
+    /// block, for use with [Perfetto](https://ui.perfetto.dev) or `chrome://tracing`.
+    ///
+    /// Schedules are computed using [`ScheduledBasicBlock::as_schedule_seconds`]; return an error if
+    /// the schedule of any block cannot be computed. See [`ScheduledProgram::get_chrome_trace_for_schedules`]
+    /// to export schedules computed with custom instruction durations.
+    pub fn get_chrome_trace(&self, program: &Program) -> ComputedScheduleResult<Vec<u8>> {
+        let schedules = self
+            .basic_blocks()
+            .iter()
+            .map(|block| block.as_schedule_seconds(program))
+            .collect::<ComputedScheduleResult<Vec<_>>>()?;
+
+        self.get_chrome_trace_for_schedules(program, &schedules)
+    }
+
+    /// Return a Chrome Trace Event format JSON document (as bytes) describing the given schedules,
+    /// where `schedules[i]` is the schedule of the `i`th block of this program.
+    ///
+    /// Each block is written as its own process, named for the block's label, with all of its
+    /// instructions timed relative to the start of that block. Within each block:
+    ///
+    /// * Each frame used by an instruction is written as its own track
+    /// * Instructions which do not use frames, such as gates, are written on a track per qubit
+    /// * Each instruction is written as a span of time named by its Quil text, such that `DELAY`s
+    ///   appear as spans on each frame they delay
+    /// * `FENCE`s are written as instant markers on each frame they block
+    ///
+    /// Return an error if there is not exactly one schedule per block.
+    pub fn get_chrome_trace_for_schedules(
+        &self,
+        program: &Program,
+        schedules: &[ScheduleSeconds],
+    ) -> ComputedScheduleResult<Vec<u8>> {
+        if schedules.len() != self.basic_blocks().len() {
+            return Err(ComputedScheduleError::ScheduleCount {
+                expected: self.basic_blocks().len(),
+                actual: schedules.len(),
+            });
+        }
+
+        let mut events = Vec::new();
+
+        for (index, (block, schedule)) in self.basic_blocks().iter().zip(schedules).enumerate() {
+            let process_name = block
+                .label()
+                .map(|label| label.to_quil_or_debug())
+                .unwrap_or_else(|| format!("block_{index}"));
+            block.write_chrome_trace_events(program, schedule, index, &process_name, &mut events);
+        }
+
+        Ok(json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ns",
+        })
+        .to_string()
+        .into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::{
+        instruction::InstructionHandler,
+        program::scheduling::{ComputedScheduleError, ScheduledProgram, Seconds},
+        Program,
+    };
+
+    /// Build a test case which schedules the input program, exports its Chrome trace, and then
+    /// compares the pretty-printed trace to a snapshot.
+    macro_rules! build_chrome_trace_snapshot_test_case {
+        ($name: ident, $input: expr) => {
+            #[test]
+            fn $name() {
+                let program: Program = $input.parse().unwrap();
+                let scheduled_program =
+                    ScheduledProgram::from_program(&program, &mut InstructionHandler::default())
+                        .unwrap();
+                let trace = scheduled_program.get_chrome_trace(&program).unwrap();
+                let trace: Value = serde_json::from_slice(&trace).unwrap();
+
+                insta::assert_snapshot!(serde_json::to_string_pretty(&trace).unwrap());
+            }
+        };
+    }
+
+    build_chrome_trace_snapshot_test_case!(
+        pulses_and_fence,
+        r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1e9
+PULSE 0 "rf" flat(duration: 1e-6, iq: 1)
+NONBLOCKING PULSE 1 "rf" flat(duration: 2e-6, iq: 1)
+FENCE
+DELAY 0 "rf" 1e-6
+PULSE 1 "rf" flat(duration: 1e-6, iq: 1)
+"#
+    );
+
+    build_chrome_trace_snapshot_test_case!(
+        multiple_blocks,
+        r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DECLARE ro BIT
+LABEL @start
+PULSE 0 "rf" flat(duration: 1e-6, iq: 1)
+JUMP-WHEN @start ro[0]
+SHIFT-PHASE 0 "rf" 1.0
+PULSE 0 "rf" flat(duration: 1e-6, iq: 1)
+"#
+    );
+
+    /// Gates have no built-in duration, so they may only be exported with custom schedules,
+    /// in which case they are written on a track per qubit.
+    #[test]
+    fn gates_with_custom_schedule() {
+        let program: Program = "X 0\nCZ 0 1\nX 1".parse().unwrap();
+        let mut handler = InstructionHandler::default().set_role_for_instruction(|instruction| {
+            matches!(instruction, crate::instruction::Instruction::Gate(_))
+                .then_some(crate::instruction::InstructionRole::RFControl)
+        });
+        let scheduled_program = ScheduledProgram::from_program(&program, &mut handler).unwrap();
+        let schedules = scheduled_program
+            .basic_blocks()
+            .iter()
+            .map(|block| {
+                block
+                    .as_schedule(&program, |_, _| Some(Seconds(1e-6)))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let trace = scheduled_program
+            .get_chrome_trace_for_schedules(&program, &schedules)
+            .unwrap();
+        let trace: Value = serde_json::from_slice(&trace).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+
+        let thread_names = events
+            .iter()
+            .filter(|event| event["name"] == "thread_name")
+            .map(|event| event["args"]["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(thread_names, vec!["qubit 0", "qubit 1"]);
+
+        let gate_events = events
+            .iter()
+            .filter(|event| event["cat"] == "gate")
+            .map(|event| {
+                (
+                    event["name"].as_str().unwrap(),
+                    event["tid"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            gate_events,
+            vec![("X 0", 0), ("CZ 0 1", 0), ("CZ 0 1", 1), ("X 1", 1)]
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_schedule_count() {
+        let program: Program = "DECLARE ro BIT\nJUMP-WHEN @end ro[0]\nFENCE 0\nLABEL @end"
+            .parse()
+            .unwrap();
+        let scheduled_program =
+            ScheduledProgram::from_program(&program, &mut InstructionHandler::default()).unwrap();
+
+        assert!(matches!(
+            scheduled_program.get_chrome_trace_for_schedules(&program, &[]),
+            Err(ComputedScheduleError::ScheduleCount { expected, actual: 0 }) if expected > 0
+        ));
+    }
+}
@@ -0,0 +1,312 @@
+//! Critical path and slack analysis of computed schedules
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, HashMap};
+
+use petgraph::{
+    visit::{EdgeFiltered, Topo, Walker},
+    Direction,
+};
+
+use crate::Program;
+
+use super::{
+    graph::{ExecutionDependency, ScheduledGraphNode},
+    schedule::Zero,
+    ComputedScheduleResult, Schedule, ScheduledBasicBlock, Seconds,
+};
+
+/// The flexibility in the start time of a single scheduled instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComputedSlackItem<TimeUnit> {
+    pub instruction_index: usize,
+
+    /// The time at which the instruction is scheduled to start, which is the earliest it may start
+    pub earliest_start_time: TimeUnit,
+
+    /// The latest time at which the instruction may start without delaying the end of its block
+    pub latest_start_time: TimeUnit,
+}
+
+impl<TimeUnit: Clone + std::ops::Sub<TimeUnit, Output = TimeUnit>> ComputedSlackItem<TimeUnit> {
+    /// The amount by which the instruction may be delayed without delaying the end of its block.
+    pub fn slack(&self) -> TimeUnit {
+        self.latest_start_time.clone() - self.earliest_start_time.clone()
+    }
+}
+
+/// The critical path through the schedule of a block, along with the slack of each instruction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CriticalPath<TimeUnit> {
+    path: Vec<usize>,
+    critical_instructions: BTreeSet<usize>,
+    items: Vec<ComputedSlackItem<TimeUnit>>,
+}
+
+impl<TimeUnit> CriticalPath<TimeUnit> {
+    /// The indices of the instructions along a single critical path, in order of execution.
+    ///
+    /// When several paths are equally long, the path through the lowest-indexed instructions is
+    /// chosen; see [`CriticalPath::critical_instructions`] for the instructions on any of them.
+    pub fn path(&self) -> &[usize] {
+        &self.path
+    }
+
+    /// The indices of all instructions which lie on any critical path, and so have no slack.
+    pub fn critical_instructions(&self) -> &BTreeSet<usize> {
+        &self.critical_instructions
+    }
+
+    /// The slack of each scheduled instruction, in order of instruction index.
+    pub fn items(&self) -> &[ComputedSlackItem<TimeUnit>] {
+        &self.items
+    }
+}
+
+impl ScheduledBasicBlock<'_> {
+    /// Compute the critical path through this block's schedule in terms of seconds, using a default
+    /// built-in calculation for the duration of scheduled instructions.
+    ///
+    /// See [`ScheduledBasicBlock::get_critical_path`].
+    pub fn get_critical_path_seconds(
+        &self,
+        program: &Program,
+    ) -> ComputedScheduleResult<CriticalPath<Seconds>> {
+        let schedule = self.as_schedule_seconds(program)?;
+        Ok(self.get_critical_path(&schedule))
+    }
+
+    /// Compute the critical path through the given schedule of this block, following the
+    /// [`ExecutionDependency::Scheduled`] edges of its dependency graph.
+    ///
+    /// The critical path is a chain of instructions, each starting exactly when its predecessor
+    /// ends, which spans the whole block: delaying any of them delays the end of the block. Every
+    /// other instruction has slack, the difference between the latest time at which it could start
+    /// without delaying the end of the block and the time at which it is scheduled to start.
+    pub fn get_critical_path<TimeUnit>(
+        &self,
+        schedule: &Schedule<TimeUnit>,
+    ) -> CriticalPath<TimeUnit>
+    where
+        TimeUnit: Clone
+            + PartialOrd
+            + std::ops::Add<TimeUnit, Output = TimeUnit>
+            + std::ops::Sub<TimeUnit, Output = TimeUnit>
+            + Zero,
+    {
+        let items_by_index = schedule
+            .items()
+            .iter()
+            .map(|item| (item.instruction_index, item))
+            .collect::<HashMap<_, _>>();
+        let start_time = |node: ScheduledGraphNode| match node {
+            ScheduledGraphNode::BlockStart => Some(TimeUnit::zero()),
+            ScheduledGraphNode::InstructionIndex(index) => items_by_index
+                .get(&index)
+                .map(|item| item.time_span.start_time.clone()),
+            ScheduledGraphNode::BlockEnd => Some(schedule.duration().clone()),
+        };
+        let end_time = |node: ScheduledGraphNode| match node {
+            ScheduledGraphNode::InstructionIndex(index) => {
+                items_by_index.get(&index).map(|item| item.time_span.end())
+            }
+            _ => start_time(node),
+        };
+        let scheduled_edges = |node: ScheduledGraphNode, direction: Direction| {
+            self.graph
+                .edges_directed(node, direction)
+                .filter(|(_, _, dependencies)| {
+                    dependencies.contains(&ExecutionDependency::Scheduled)
+                })
+                .map(move |(source, target, _)| match direction {
+                    Direction::Incoming => source,
+                    Direction::Outgoing => target,
+                })
+        };
+        // A predecessor is "tight" if it ends exactly when the given node starts.
+        let tight_predecessors = |node: ScheduledGraphNode| {
+            let node_start_time = start_time(node);
+            scheduled_edges(node, Direction::Incoming).filter_map(move |predecessor| {
+                match predecessor {
+                    ScheduledGraphNode::InstructionIndex(index)
+                        if node_start_time.is_some()
+                            && end_time(predecessor) == node_start_time =>
+                    {
+                        Some(index)
+                    }
+                    _ => None,
+                }
+            })
+        };
+
+        // Every instruction from which a chain of tight dependencies reaches the end of the block
+        let mut critical_instructions = BTreeSet::new();
+        let mut stack = vec![ScheduledGraphNode::BlockEnd];
+        while let Some(node) = stack.pop() {
+            for index in tight_predecessors(node) {
+                if critical_instructions.insert(index) {
+                    stack.push(ScheduledGraphNode::InstructionIndex(index));
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut node = ScheduledGraphNode::BlockEnd;
+        while let Some(index) = tight_predecessors(node).min() {
+            path.push(index);
+            node = ScheduledGraphNode::InstructionIndex(index);
+        }
+        path.reverse();
+
+        // Latest start times are computed in reverse topological order, such that each node's
+        // successors are visited before it.
+        let graph_filtered = EdgeFiltered::from_fn(&self.graph, |(_, _, dependencies)| {
+            dependencies.contains(&ExecutionDependency::Scheduled)
+        });
+        let mut nodes = Topo::new(&graph_filtered)
+            .iter(&graph_filtered)
+            .collect::<Vec<_>>();
+        nodes.reverse();
+
+        let mut latest_start_times = HashMap::new();
+        latest_start_times.insert(ScheduledGraphNode::BlockEnd, schedule.duration().clone());
+        let mut items = Vec::new();
+        for node in nodes {
+            let ScheduledGraphNode::InstructionIndex(index) = node else {
+                continue;
+            };
+            let Some(item) = items_by_index.get(&index) else {
+                continue;
+            };
+
+            let latest_start_time = if critical_instructions.contains(&index) {
+                item.time_span.start_time.clone()
+            } else {
+                let latest_end_time = scheduled_edges(node, Direction::Outgoing)
+                    .filter_map(|successor| latest_start_times.get(&successor).cloned())
+                    // As in `as_schedule`, this allows us to require PartialOrd instead of Ord.
+                    .fold(
+                        schedule.duration().clone(),
+                        |acc, el| {
+                            if el < acc {
+                                el
+                            } else {
+                                acc
+                            }
+                        },
+                    );
+                latest_end_time - item.time_span.duration.clone()
+            };
+
+            latest_start_times.insert(node, latest_start_time.clone());
+            items.push(ComputedSlackItem {
+                instruction_index: index,
+                earliest_start_time: item.time_span.start_time.clone(),
+                latest_start_time,
+            });
+        }
+        items.sort_by_key(|item| item.instruction_index);
+
+        CriticalPath {
+            path,
+            critical_instructions,
+            items,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        instruction::InstructionHandler,
+        program::scheduling::{ScheduledProgram, Seconds},
+        Program,
+    };
+
+    #[test]
+    fn critical_path_and_slack() {
+        let program: Program = r#"DEFFRAME 0 "a":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "a":
+    SAMPLE-RATE: 1e9
+PULSE 0 "a" flat(duration: 1.0)
+PULSE 1 "a" flat(duration: 3.0)
+PULSE 0 "a" flat(duration: 1.0)
+FENCE
+PULSE 0 "a" flat(duration: 1.0)
+"#
+        .parse()
+        .unwrap();
+        let scheduled_program =
+            ScheduledProgram::from_program(&program, &mut InstructionHandler::default()).unwrap();
+        let critical_path = scheduled_program.basic_blocks()[0]
+            .get_critical_path_seconds(&program)
+            .unwrap();
+
+        assert_eq!(critical_path.path(), &[1, 3, 4]);
+        assert_eq!(
+            critical_path
+                .critical_instructions()
+                .iter()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![1, 3, 4]
+        );
+
+        let slack = critical_path
+            .items()
+            .iter()
+            .map(|item| (item.instruction_index, item.slack()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            slack,
+            vec![
+                (0, Seconds(1.0)),
+                (1, Seconds(0.0)),
+                (2, Seconds(1.0)),
+                (3, Seconds(0.0)),
+                (4, Seconds(0.0)),
+            ]
+        );
+    }
+
+    /// When independent frames finish at the same time, each of them is critical.
+    #[test]
+    fn parallel_critical_paths() {
+        let program: Program = r#"DEFFRAME 0 "a":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "a":
+    SAMPLE-RATE: 1e9
+PULSE 0 "a" flat(duration: 2.0)
+PULSE 1 "a" flat(duration: 1.0)
+PULSE 1 "a" flat(duration: 1.0)
+"#
+        .parse()
+        .unwrap();
+        let scheduled_program =
+            ScheduledProgram::from_program(&program, &mut InstructionHandler::default()).unwrap();
+        let critical_path = scheduled_program.basic_blocks()[0]
+            .get_critical_path_seconds(&program)
+            .unwrap();
+
+        assert_eq!(critical_path.path(), &[0]);
+        assert_eq!(critical_path.critical_instructions().len(), 3);
+        assert!(critical_path
+            .items()
+            .iter()
+            .all(|item| item.slack() == Seconds(0.0)));
+    }
+}
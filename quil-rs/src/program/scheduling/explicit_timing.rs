@@ -0,0 +1,359 @@
+//! Utilities for lowering computed schedules into Quil-T with explicit timing
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::{
+    expression::Expression,
+    instruction::{Delay, FrameIdentifier, Instruction, Label},
+    real, Program,
+};
+
+use super::{
+    ComputedScheduleError, ComputedScheduleResult, ScheduleSeconds, ScheduledBasicBlock,
+    ScheduledProgram,
+};
+
+/// Idle time shorter than this, in seconds, is attributed to floating-point error rather than
+/// filled with a `DELAY`.
+const IDLE_TIME_TOLERANCE_SECONDS: f64 = 1e-15;
+
+/// Sort frames by qubits and then by name, so that generated instructions are deterministic.
+fn sort_frames(frames: &mut [&FrameIdentifier]) {
+    frames.sort_by(|a, b| (&a.qubits, &a.name).cmp(&(&b.qubits, &b.name)));
+}
+
+/// Return a `DELAY` of the given duration on a single frame.
+fn delay_frame(frame: &FrameIdentifier, seconds: f64) -> Instruction {
+    Instruction::Delay(Delay::new(
+        Expression::Number(real!(seconds)),
+        vec![frame.name.clone()],
+        frame.qubits.clone(),
+    ))
+}
+
+/// Return a `NONBLOCKING` copy of the given instruction, if it may be marked as such.
+///
+/// Once every frame's timeline is explicit, an instruction no longer needs to block the other
+/// frames on its qubits: any later instruction on those frames is preceded by a `DELAY` instead.
+fn into_nonblocking(instruction: &Instruction) -> Instruction {
+    let mut instruction = instruction.clone();
+    match &mut instruction {
+        Instruction::Pulse(pulse) => pulse.blocking = false,
+        Instruction::Capture(capture) => capture.blocking = false,
+        Instruction::RawCapture(raw_capture) => raw_capture.blocking = false,
+        _ => {}
+    }
+    instruction
+}
+
+impl ScheduledBasicBlock<'_> {
+    /// Return the instructions of this block, excluding its label and terminator, rewritten such
+    /// that the given schedule is expressed by explicit timing alone:
+    ///
+    /// * Every idle span on every frame used within the block, from the start of the block to its
+    ///   end, is filled by a single-frame `DELAY`
+    /// * `FENCE` and `DELAY` instructions are removed, since their effect is expressed by the above;
+    ///   those absent from the schedule, and `DELAY`s on no defined frame, are retained
+    /// * `PULSE`, `CAPTURE`, and `RAW-CAPTURE` are made `NONBLOCKING`, since frames no longer need
+    ///   to block one another
+    ///
+    /// All other instructions are retained in their original order.
+    pub fn to_explicitly_timed_instructions(
+        &self,
+        program: &Program,
+        schedule: &ScheduleSeconds,
+    ) -> Vec<Instruction> {
+        let schedule_items = schedule
+            .items()
+            .iter()
+            .map(|item| (item.instruction_index, item))
+            .collect::<HashMap<_, _>>();
+
+        let mut frame_end_times: HashMap<&FrameIdentifier, f64> = HashMap::new();
+        let mut instructions = Vec::new();
+
+        for (index, instruction) in self.basic_block().instructions().iter().enumerate() {
+            let mut used_frames = program
+                .get_frames_for_instruction(instruction)
+                .map(|frames| frames.used.into_iter().collect::<Vec<_>>())
+                .unwrap_or_default();
+            sort_frames(&mut used_frames);
+
+            let item = schedule_items.get(&index);
+
+            // The effect of these instructions is captured by the schedule, and so by the `DELAY`s
+            // which fill idle time on each frame, so long as the schedule covers the instruction and,
+            // for a `DELAY`, there is some frame on which to express it.
+            let is_implicit_timing = item.is_some()
+                && match instruction {
+                    Instruction::Delay(_) => !used_frames.is_empty(),
+                    Instruction::Fence(_) => true,
+                    _ => false,
+                };
+
+            if let Some(item) = item {
+                for frame in used_frames {
+                    let frame_end_time = frame_end_times.entry(frame).or_insert(0.0);
+                    if is_implicit_timing {
+                        continue;
+                    }
+
+                    let idle_time = item.time_span.start_time.0 - *frame_end_time;
+                    if idle_time > IDLE_TIME_TOLERANCE_SECONDS {
+                        instructions.push(delay_frame(frame, idle_time));
+                    }
+                    *frame_end_time = item.time_span.end().0;
+                }
+            }
+
+            if !is_implicit_timing {
+                instructions.push(into_nonblocking(instruction));
+            }
+        }
+
+        // Pad every frame to the end of the block, since each block begins with all frames in sync.
+        let block_end_time = schedule.duration().0;
+        let mut frames = frame_end_times.keys().copied().collect::<Vec<_>>();
+        sort_frames(&mut frames);
+        for frame in frames {
+            let idle_time = block_end_time - frame_end_times[frame];
+            if idle_time > IDLE_TIME_TOLERANCE_SECONDS {
+                instructions.push(delay_frame(frame, idle_time));
+            }
+        }
+
+        instructions
+    }
+}
+
+impl ScheduledProgram<'_> {
+    /// Return a copy of `program` in which each block is rewritten with explicit timing, as described
+    /// in [`ScheduledBasicBlock::to_explicitly_timed_instructions`].
+    ///
+    /// Schedules are computed using [`ScheduledBasicBlock::as_schedule_seconds`]; return an error if
+    /// the schedule of any block cannot be computed. See
+    /// [`ScheduledProgram::to_explicitly_timed_program_for_schedules`] to lower schedules computed
+    /// with custom instruction durations.
+    pub fn to_explicitly_timed_program(
+        &self,
+        program: &Program,
+    ) -> ComputedScheduleResult<Program> {
+        let schedules = self
+            .basic_blocks()
+            .iter()
+            .map(|block| block.as_schedule_seconds(program))
+            .collect::<ComputedScheduleResult<Vec<_>>>()?;
+
+        self.to_explicitly_timed_program_for_schedules(program, &schedules)
+    }
+
+    /// Return a copy of `program` in which each block is rewritten with explicit timing according
+    /// to the given schedules, where `schedules[i]` is the schedule of the `i`th block of this program.
+    ///
+    /// Block labels and terminators are retained, as are all non-body instructions of `program`.
+    /// Return an error if there is not exactly one schedule per block.
+    pub fn to_explicitly_timed_program_for_schedules(
+        &self,
+        program: &Program,
+        schedules: &[ScheduleSeconds],
+    ) -> ComputedScheduleResult<Program> {
+        if schedules.len() != self.basic_blocks().len() {
+            return Err(ComputedScheduleError::ScheduleCount {
+                expected: self.basic_blocks().len(),
+                actual: schedules.len(),
+            });
+        }
+
+        let mut explicitly_timed_program = program.clone_without_body_instructions();
+
+        for (block, schedule) in self.basic_blocks().iter().zip(schedules) {
+            if let Some(label) = block.label() {
+                explicitly_timed_program.add_instruction(Instruction::Label(Label {
+                    target: label.clone(),
+                }));
+            }
+            explicitly_timed_program
+                .add_instructions(block.to_explicitly_timed_instructions(program, schedule));
+            if let Some(terminator) = block.terminator().clone().into_instruction() {
+                explicitly_timed_program.add_instruction(terminator);
+            }
+        }
+
+        Ok(explicitly_timed_program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        instruction::InstructionHandler,
+        program::scheduling::{ComputedScheduleError, ScheduledProgram},
+        Program,
+    };
+
+    /// Assert that the explicitly-timed form of the `input` program has the same body as `expected`.
+    fn assert_explicitly_timed(input: &str, expected: &str) {
+        let program: Program = input.parse().unwrap();
+        let scheduled_program =
+            ScheduledProgram::from_program(&program, &mut InstructionHandler::default()).unwrap();
+        let explicitly_timed_program = scheduled_program
+            .to_explicitly_timed_program(&program)
+            .unwrap();
+        let expected: Program = expected.parse().unwrap();
+
+        pretty_assertions::assert_eq!(
+            explicitly_timed_program
+                .body_instructions()
+                .cloned()
+                .collect::<Vec<_>>(),
+            expected.body_instructions().cloned().collect::<Vec<_>>(),
+        );
+    }
+
+    const FRAMES: &str = r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 0 "ro":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1e9
+"#;
+
+    #[test]
+    fn fills_idle_time_and_removes_fences() {
+        assert_explicitly_timed(
+            &format!(
+                r#"{FRAMES}
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+NONBLOCKING PULSE 1 "rf" flat(duration: 2.0, iq: 1)
+FENCE
+SHIFT-PHASE 0 "rf" 1.0
+PULSE 0 "ro" flat(duration: 1.0, iq: 1)
+"#
+            ),
+            r#"NONBLOCKING PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+NONBLOCKING PULSE 1 "rf" flat(duration: 2.0, iq: 1)
+DELAY 0 "rf" 1.0
+SHIFT-PHASE 0 "rf" 1.0
+DELAY 0 "ro" 2.0
+NONBLOCKING PULSE 0 "ro" flat(duration: 1.0, iq: 1)
+DELAY 0 "rf" 1.0
+DELAY 1 "rf" 1.0
+"#,
+        );
+    }
+
+    #[test]
+    fn merges_existing_delays() {
+        assert_explicitly_timed(
+            &format!(
+                r#"{FRAMES}
+DELAY 0 "rf" 1.0
+DELAY 0 "rf" 2.0
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+"#
+            ),
+            r#"DELAY 0 "rf" 3.0
+NONBLOCKING PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+"#,
+        );
+    }
+
+    #[test]
+    fn retains_labels_and_terminators() {
+        assert_explicitly_timed(
+            &format!(
+                r#"{FRAMES}
+DECLARE ro BIT
+LABEL @start
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+JUMP-WHEN @start ro[0]
+DELAY 0 "rf" 2.0
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+"#
+            ),
+            r#"LABEL @start
+NONBLOCKING PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+JUMP-WHEN @start ro[0]
+DELAY 0 "rf" 2.0
+NONBLOCKING PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+"#,
+        );
+    }
+
+    #[test]
+    fn pads_frames_used_only_by_delays() {
+        assert_explicitly_timed(
+            &format!(
+                r#"{FRAMES}
+DELAY 0 "ro" 3.0
+NONBLOCKING PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+"#
+            ),
+            r#"NONBLOCKING PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+DELAY 0 "rf" 2.0
+DELAY 0 "ro" 3.0
+"#,
+        );
+    }
+
+    #[test]
+    fn retains_delays_on_undefined_frames() {
+        assert_explicitly_timed(
+            &format!(
+                r#"{FRAMES}
+DELAY 2 "rf" 2.0
+DELAY 2 1.0
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+"#
+            ),
+            r#"DELAY 2 "rf" 2.0
+DELAY 2 1.0
+NONBLOCKING PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+DELAY 0 "rf" 1.0
+"#,
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_schedule_count() {
+        let program: Program = format!(
+            r#"{FRAMES}
+DECLARE ro BIT
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+JUMP-WHEN @end ro[0]
+PULSE 0 "rf" flat(duration: 1.0, iq: 1)
+LABEL @end
+"#
+        )
+        .parse()
+        .unwrap();
+        let scheduled_program =
+            ScheduledProgram::from_program(&program, &mut InstructionHandler::default()).unwrap();
+        let schedules = scheduled_program
+            .basic_blocks()
+            .iter()
+            .map(|block| block.as_schedule_seconds(&program).unwrap())
+            .skip(1)
+            .collect::<Vec<_>>();
+
+        assert!(matches!(
+            scheduled_program.to_explicitly_timed_program_for_schedules(&program, &schedules),
+            Err(ComputedScheduleError::ScheduleCount { actual, expected })
+                if actual + 1 == expected
+        ));
+    }
+}
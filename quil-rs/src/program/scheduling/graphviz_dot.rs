@@ -14,7 +14,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
+
 use dot_writer::{Attributes, DotWriter, Shape, Style};
+use itertools::Itertools;
 
 use crate::{
     instruction::Target,
@@ -25,14 +28,44 @@ use crate::{
         },
     },
     quil::Quil,
+    Program,
 };
 
-use super::graph::ScheduledProgram;
+use super::{graph::ScheduledProgram, ComputedScheduleResult, CriticalPath, Seconds};
+
+/// The color of nodes and edges along the critical path of a block
+const CRITICAL_PATH_COLOR: &str = "red";
+
+/// The pen width of nodes and edges along the critical path of a block
+const CRITICAL_PATH_PEN_WIDTH: &str = "2";
 
 impl ScheduledBasicBlock<'_> {
     /// Given a [`dot_writer::Scope`] representing a subgraph/cluster, write the timing graph for this block into it.
     /// Uses the `node_prefix` argument for namespacing so that node IDs remain unique within the overall graph.
-    fn write_dot_format(&self, cluster: &mut dot_writer::Scope, node_prefix: &str) {
+    ///
+    /// If a `critical_path` is given, the nodes and edges along it are highlighted and each instruction is
+    /// labeled with its slack.
+    fn write_dot_format(
+        &self,
+        cluster: &mut dot_writer::Scope,
+        node_prefix: &str,
+        critical_path: Option<&CriticalPath<Seconds>>,
+    ) {
+        let critical_path_edges = critical_path
+            .map(|critical_path| {
+                std::iter::once(ScheduledGraphNode::BlockStart)
+                    .chain(
+                        critical_path
+                            .path()
+                            .iter()
+                            .map(|index| ScheduledGraphNode::InstructionIndex(*index)),
+                    )
+                    .chain(std::iter::once(ScheduledGraphNode::BlockEnd))
+                    .tuple_windows()
+                    .collect::<HashSet<(ScheduledGraphNode, ScheduledGraphNode)>>()
+            })
+            .unwrap_or_default();
+
         self.graph.nodes().for_each(|node| {
             let node_id = get_node_id(&node, node_prefix);
             match &node {
@@ -49,14 +82,36 @@ impl ScheduledBasicBlock<'_> {
                         .set_label("start");
                 }
                 ScheduledGraphNode::InstructionIndex(index) => {
-                    cluster
-                        .node_named(node_id)
-                        .set_shape(Shape::Rectangle)
-                        .set_label(&escape_label(&format!(
-                            "[{}] {}",
-                            index,
-                            self.instructions().get(*index).unwrap().to_quil_or_debug()
-                        )));
+                    let mut label = format!(
+                        "[{}] {}",
+                        index,
+                        self.instructions().get(*index).unwrap().to_quil_or_debug()
+                    );
+                    let slack = critical_path.and_then(|critical_path| {
+                        critical_path
+                            .items()
+                            .iter()
+                            .find(|item| item.instruction_index == *index)
+                            .map(|item| item.slack())
+                    });
+                    if let Some(slack) = slack {
+                        label.push_str(&format!("\nslack: {}", slack.0));
+                    }
+                    if critical_path.is_some_and(|critical_path| {
+                        critical_path.critical_instructions().contains(index)
+                    }) {
+                        cluster
+                            .node_named(node_id)
+                            .set_shape(Shape::Rectangle)
+                            .set_label(&escape_label(&label))
+                            .set("color", CRITICAL_PATH_COLOR, false)
+                            .set("penwidth", CRITICAL_PATH_PEN_WIDTH, false);
+                    } else {
+                        cluster
+                            .node_named(node_id)
+                            .set_shape(Shape::Rectangle)
+                            .set_label(&escape_label(&label));
+                    }
                 }
             };
             self.graph.edges(node).for_each(|(src, dest, edge)| {
@@ -79,10 +134,19 @@ impl ScheduledBasicBlock<'_> {
                 // without sorting would cause flaky tests.
                 labels.sort_unstable();
                 let label = labels.join("\n");
-                cluster
-                    .edge(source, target)
-                    .attributes()
-                    .set_label(label.as_str());
+                if critical_path_edges.contains(&(src, dest)) {
+                    cluster
+                        .edge(source, target)
+                        .attributes()
+                        .set_label(label.as_str())
+                        .set("color", CRITICAL_PATH_COLOR, false)
+                        .set("penwidth", CRITICAL_PATH_PEN_WIDTH, false);
+                } else {
+                    cluster
+                        .edge(source, target)
+                        .attributes()
+                        .set_label(label.as_str());
+                }
             })
         });
     }
@@ -133,6 +197,29 @@ impl ScheduledProgram<'_> {
     /// Lines on the graph indicate scheduling dependencies within blocks and control flow among blocks.
     /// Each node representing an instruction is labeled with the contents of that instruction.
     pub fn get_dot_format(&self) -> Vec<u8> {
+        self.write_dot_format(None)
+    }
+
+    /// Return a DOT format string (as bytes) for use with Graphviz, as with [`ScheduledProgram::get_dot_format`],
+    /// in which the critical path of each block is highlighted and each instruction is labeled with its slack.
+    ///
+    /// Critical paths are computed using [`ScheduledBasicBlock::get_critical_path_seconds`]; return an error if
+    /// the schedule of any block cannot be computed.
+    pub fn get_dot_format_with_critical_paths(
+        &self,
+        program: &Program,
+    ) -> ComputedScheduleResult<Vec<u8>> {
+        let critical_paths = self
+            .basic_blocks()
+            .iter()
+            .map(|block| block.get_critical_path_seconds(program))
+            .collect::<ComputedScheduleResult<Vec<_>>>()?;
+
+        Ok(self.write_dot_format(Some(&critical_paths)))
+    }
+
+    /// Write the DOT format for this program, highlighting `critical_paths[i]` within the `i`th block if given.
+    fn write_dot_format(&self, critical_paths: Option<&[CriticalPath<Seconds>]>) -> Vec<u8> {
         let mut output_bytes = Vec::new();
 
         {
@@ -171,7 +258,11 @@ impl ScheduledProgram<'_> {
                     cluster.set_label(&string_label);
                     cluster.node_attributes().set_style(Style::Filled);
 
-                    scheduled_basic_block.write_dot_format(&mut cluster, &string_label);
+                    scheduled_basic_block.write_dot_format(
+                        &mut cluster,
+                        &string_label,
+                        critical_paths.and_then(|critical_paths| critical_paths.get(index)),
+                    );
                 }
 
                 let next_index_and_block = iter.peek();
@@ -555,4 +646,40 @@ NONBLOCKING PULSE 1 \"rf\" test(a: ro[0])"
             "DECLARE ro INTEGER[2]\nMOVE ro[0] 1\nMOVE ro[1] 0\nADD ro[0] 5\nSUB ro[1] ro[0]"
         );
     }
+
+    mod critical_path {
+        use crate::instruction::InstructionHandler;
+        use crate::program::scheduling::graph::ScheduledProgram;
+        use crate::program::Program;
+
+        #[test]
+        fn highlights_critical_path_and_slack() {
+            let program: Program = r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1e9
+PULSE 0 "rf" flat(duration: 1.0)
+PULSE 1 "rf" flat(duration: 2.0)
+"#
+            .parse()
+            .unwrap();
+            let scheduled_program =
+                ScheduledProgram::from_program(&program, &mut InstructionHandler::default())
+                    .unwrap();
+
+            let dot_format = String::from_utf8(
+                scheduled_program
+                    .get_dot_format_with_critical_paths(&program)
+                    .unwrap(),
+            )
+            .unwrap();
+            assert!(dot_format.contains("slack: 1"));
+            assert!(dot_format.contains("slack: 0"));
+            assert!(dot_format.contains("red"));
+
+            let plain_dot_format = String::from_utf8(scheduled_program.get_dot_format()).unwrap();
+            assert!(!plain_dot_format.contains("slack"));
+            assert!(!plain_dot_format.contains("red"));
+        }
+    }
 }
@@ -1,9 +1,18 @@
+#[cfg(feature = "chrome-trace")]
+pub(crate) mod chrome_trace;
+pub(crate) mod critical_path;
+pub(crate) mod explicit_timing;
 pub(crate) mod graph;
 pub(crate) mod schedule;
 
 #[cfg(feature = "graphviz-dot")]
 pub(crate) mod graphviz_dot;
 
+#[cfg(feature = "svg-timeline")]
+pub(crate) mod svg_timeline;
+
+pub use critical_path::{ComputedSlackItem, CriticalPath};
+
 pub use graph::{
     DependencyGraph, ExecutionDependency, MemoryAccessType, ScheduleError, ScheduleErrorVariant,
     ScheduleResult, ScheduledBasicBlock, ScheduledBasicBlockOwned, ScheduledGraphNode,
@@ -11,5 +20,9 @@ pub use graph::{
 };
 
 pub use schedule::{
-    ComputedScheduleError, ComputedScheduleItem, Schedule, ScheduleSeconds, Seconds, TimeSpan,
+    ComputedScheduleError, ComputedScheduleItem, ComputedScheduleResult, Schedule, ScheduleSeconds,
+    Seconds, TimeSpan,
 };
+
+#[cfg(feature = "svg-timeline")]
+pub use svg_timeline::SvgTimelineOptions;
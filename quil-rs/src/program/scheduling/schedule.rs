@@ -11,7 +11,8 @@ use petgraph::{
 
 use crate::{
     instruction::{
-        AttributeValue, Capture, Delay, Instruction, Pulse, RawCapture, WaveformInvocation,
+        Capture, Delay, FrameAttributeError, Instruction, Pulse, RawCapture, TypedFrameAttributes,
+        WaveformInvocation,
     },
     quil::Quil,
     Program,
@@ -107,8 +108,17 @@ pub enum ComputedScheduleError {
     #[error("unknown duration for instruction {}", instruction.to_quil_or_debug())]
     UnknownDuration { instruction: Instruction },
 
+    #[error("invalid frame attributes for instruction {}: {source}", instruction.to_quil_or_debug())]
+    InvalidFrameAttributes {
+        instruction: Instruction,
+        source: FrameAttributeError,
+    },
+
     #[error("internal error: invalid dependency graph")]
     InvalidDependencyGraph,
+
+    #[error("expected a schedule for each of {expected} blocks, got {actual}")]
+    ScheduleCount { expected: usize, actual: usize },
 }
 
 pub type ComputedScheduleResult<T> = Result<T, ComputedScheduleError>;
@@ -175,11 +185,12 @@ impl<'p> ScheduledBasicBlock<'p> {
     /// * For DELAY and RAW-CAPTURE, it's the named duration
     /// * For supporting instructions like SET-*, SHIFT-*, and FENCE, it's 0
     ///
-    /// Return `None` for other instructions.
+    /// Return `None` for other instructions, and an error if the sample rate of a frame used by
+    /// the instruction is invalid.
     pub(crate) fn get_instruction_duration_seconds(
         program: &Program,
         instruction: &Instruction,
-    ) -> Option<Seconds> {
+    ) -> ComputedScheduleResult<Option<Seconds>> {
         match instruction {
             Instruction::Capture(Capture { waveform, .. })
             | Instruction::Pulse(Pulse { waveform, .. }) => {
@@ -187,7 +198,7 @@ impl<'p> ScheduledBasicBlock<'p> {
             }
             Instruction::Delay(Delay { duration, .. })
             | Instruction::RawCapture(RawCapture { duration, .. }) => {
-                duration.to_real().ok().map(Seconds)
+                Ok(duration.to_real().ok().map(Seconds))
             }
             Instruction::Fence(_)
             | Instruction::SetFrequency(_)
@@ -195,8 +206,8 @@ impl<'p> ScheduledBasicBlock<'p> {
             | Instruction::SetScale(_)
             | Instruction::ShiftFrequency(_)
             | Instruction::ShiftPhase(_)
-            | Instruction::SwapPhases(_) => Some(Seconds(0.0)),
-            _ => None,
+            | Instruction::SwapPhases(_) => Ok(Some(Seconds(0.0))),
+            _ => Ok(None),
         }
     }
 
@@ -211,37 +222,29 @@ impl<'p> ScheduledBasicBlock<'p> {
         program: &Program,
         instruction: &Instruction,
         WaveformInvocation { name, parameters }: &WaveformInvocation,
-    ) -> Option<Seconds> {
+    ) -> ComputedScheduleResult<Option<Seconds>> {
         if let Some(definition) = program.waveforms.get(name) {
             let sample_count = definition.matrix.len();
-            let common_sample_rate =
-                program
-                    .get_frames_for_instruction(instruction)
-                    .and_then(|frames| {
-                        frames
-                            .used
-                            .into_iter()
-                            .filter_map(|frame| {
-                                program
-                                    .frames
-                                    .get(frame)
-                                    .and_then(|frame_definition| {
-                                        frame_definition.get("SAMPLE-RATE")
-                                    })
-                                    .and_then(|sample_rate_expression| match sample_rate_expression
-                                    {
-                                        AttributeValue::String(_) => None,
-                                        AttributeValue::Expression(expression) => Some(expression),
-                                    })
-                                    .and_then(|expression| expression.to_real().ok())
-                            })
-                            .all_equal_value()
-                            .ok()
-                    });
-
-            common_sample_rate
-                .map(|sample_rate| sample_count as f64 / sample_rate)
-                .map(Seconds)
+            let Some(frames) = program.get_frames_for_instruction(instruction) else {
+                return Ok(None);
+            };
+            let sample_rates = frames
+                .used
+                .into_iter()
+                .filter_map(|frame| program.frames.get(frame))
+                .map(TypedFrameAttributes::sample_rate_of)
+                .filter_map(Result::transpose)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|source| ComputedScheduleError::InvalidFrameAttributes {
+                    instruction: instruction.clone(),
+                    source,
+                })?;
+
+            Ok(sample_rates
+                .into_iter()
+                .all_equal_value()
+                .ok()
+                .map(|sample_rate| Seconds(sample_count as f64 / sample_rate)))
         } else {
             // Per the Quil spec, all waveform templates have a "duration"
             // parameter, and "erf_square" also has "pad_left" and "pad_right".
@@ -254,11 +257,11 @@ impl<'p> ScheduledBasicBlock<'p> {
                     .and_then(|v| v.to_real().ok())
                     .map(Seconds)
             };
-            Some(
-                parameter("duration")?
+            Ok(parameter("duration").map(|duration| {
+                duration
                     + parameter("pad_left").unwrap_or(Seconds::zero())
-                    + parameter("pad_right").unwrap_or(Seconds::zero()),
-            )
+                    + parameter("pad_right").unwrap_or(Seconds::zero())
+            }))
         }
     }
 
@@ -268,7 +271,7 @@ impl<'p> ScheduledBasicBlock<'p> {
         &self,
         program: &Program,
     ) -> ComputedScheduleResult<ScheduleSeconds> {
-        self.as_schedule(program, Self::get_instruction_duration_seconds)
+        self.try_as_schedule(program, Self::get_instruction_duration_seconds)
     }
 
     /// Compute the flattened schedule for this [`ScheduledBasicBlock`] using a user-provided
@@ -285,6 +288,24 @@ impl<'p> ScheduledBasicBlock<'p> {
     ) -> ComputedScheduleResult<Schedule<TimeUnit>>
     where
         F: Fn(&'p Program, &'p Instruction) -> Option<TimeUnit>,
+    {
+        self.try_as_schedule(program, |program, instruction| {
+            Ok(get_duration(program, instruction))
+        })
+    }
+
+    /// Compute the flattened schedule for this [`ScheduledBasicBlock`] using a closure which may
+    /// fail to compute the duration of an instruction.
+    pub(crate) fn try_as_schedule<
+        F,
+        TimeUnit: Clone + PartialOrd + std::ops::Add<TimeUnit, Output = TimeUnit> + Zero,
+    >(
+        &self,
+        program: &'p Program,
+        get_duration: F,
+    ) -> ComputedScheduleResult<Schedule<TimeUnit>>
+    where
+        F: Fn(&'p Program, &'p Instruction) -> ComputedScheduleResult<Option<TimeUnit>>,
     {
         let mut schedule = Schedule::default();
         let mut end_time_by_instruction_index = HashMap::<usize, TimeUnit>::new();
@@ -301,7 +322,7 @@ impl<'p> ScheduledBasicBlock<'p> {
                     .instructions()
                     .get(index)
                     .ok_or_else(|| ComputedScheduleError::InvalidDependencyGraph)?;
-                let duration = get_duration(program, instruction).ok_or(
+                let duration = get_duration(program, instruction)?.ok_or(
                     ComputedScheduleError::UnknownDuration {
                         instruction: instruction.clone(),
                     },
@@ -388,7 +409,8 @@ mod tests {
             crate::program::scheduling::ScheduledBasicBlock::get_instruction_duration_seconds(
                 &empty_program,
                 &instruction,
-            );
+            )
+            .unwrap();
         assert_eq!(
             expected_duration.map(crate::program::scheduling::Seconds),
             duration
@@ -512,4 +534,32 @@ PULSE 0 "a" flat(duration: 1.0)
         };
         assert_eq!(expected, a.union(b));
     }
+
+    #[rstest::rstest]
+    #[case("-1e9")]
+    #[case("\"fast\"")]
+    fn invalid_sample_rate(#[case] sample_rate: &str) {
+        let program = Program::from_str(&format!(
+            r#"DEFFRAME 0 "a":
+    SAMPLE-RATE: {sample_rate}
+DEFWAVEFORM wf:
+    1.0, 1.0
+PULSE 0 "a" wf
+"#
+        ))
+        .unwrap();
+        let pulse = program.body_instructions().last().unwrap();
+        let error =
+            crate::program::scheduling::ScheduledBasicBlock::get_instruction_duration_seconds(
+                &program, pulse,
+            )
+            .unwrap_err();
+        assert!(
+            matches!(
+                error,
+                crate::program::scheduling::ComputedScheduleError::InvalidFrameAttributes { .. }
+            ),
+            "unexpected error: {error}"
+        );
+    }
 }
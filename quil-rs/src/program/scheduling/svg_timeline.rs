@@ -0,0 +1,568 @@
+//! Utilities for rendering computed schedules as SVG timelines
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    instruction::{Capture, FrameIdentifier, Instruction, Pulse, WaveformInvocation},
+    quil::Quil,
+    waveform::{
+        builtin::BuiltinWaveform, DragGaussian, ErfSquare, Gaussian, HermiteGaussian,
+        WaveformTemplate,
+    },
+    Program,
+};
+
+use super::{ComputedScheduleResult, ScheduleSeconds, ScheduledBasicBlock};
+
+/// Width, in pixels, of the column of frame labels to the left of the timeline.
+const LABEL_WIDTH: f64 = 160.0;
+
+/// Height, in pixels, of the time axis above the timeline.
+const AXIS_HEIGHT: f64 = 30.0;
+
+/// Padding, in pixels, around the timeline and within each row.
+const PADDING: f64 = 4.0;
+
+/// The number of labeled ticks drawn on the time axis, including the start and end of the block.
+const AXIS_TICK_COUNT: usize = 6;
+
+const STYLE: &str = "text{font-family:monospace;font-size:12px}\
+.axis{stroke:#000}\
+.grid{stroke:#ddd}\
+.pulse{fill:#9ecae1;stroke:#3182bd}\
+.capture{fill:#fdd0a2;stroke:#e6550d}\
+.delay{fill:#f0f0f0;stroke:#969696;stroke-dasharray:2 2}\
+.envelope{fill:none;stroke:#08306b}\
+.frame-update{stroke:#31a354;stroke-width:2}\
+.fence{stroke:#de2d26;stroke-dasharray:4 2}";
+
+/// Options controlling the layout and content of an SVG schedule timeline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SvgTimelineOptions {
+    /// Width, in pixels, of the area in which the schedule is drawn, excluding frame labels
+    pub width: f64,
+
+    /// Height, in pixels, of the row drawn for each frame
+    pub row_height: f64,
+
+    /// Whether to draw the envelope of each `PULSE` and `CAPTURE` waveform within its box
+    pub include_waveform_envelopes: bool,
+
+    /// The maximum number of points used to draw a single waveform envelope
+    pub max_envelope_points: usize,
+}
+
+impl Default for SvgTimelineOptions {
+    fn default() -> Self {
+        Self {
+            width: 1000.0,
+            row_height: 40.0,
+            include_waveform_envelopes: true,
+            max_envelope_points: 200,
+        }
+    }
+}
+
+/// The way in which a single scheduled instruction is drawn on the rows of its frames.
+#[derive(Clone, Copy)]
+enum TimelineMark {
+    /// A box spanning the scheduled time of the instruction, with the given CSS class
+    Box(&'static str),
+
+    /// A vertical tick at the start time of a zero-duration frame update
+    FrameUpdate,
+
+    /// A vertical dashed line at the time at which frames are synchronized
+    Fence,
+}
+
+impl TimelineMark {
+    /// Return the mark for the given instruction, or `None` if it isn't drawn on the timeline.
+    fn for_instruction(instruction: &Instruction) -> Option<Self> {
+        match instruction {
+            Instruction::Pulse(_) => Some(Self::Box("pulse")),
+            Instruction::Capture(_) | Instruction::RawCapture(_) => Some(Self::Box("capture")),
+            Instruction::Delay(_) => Some(Self::Box("delay")),
+            Instruction::Fence(_) => Some(Self::Fence),
+            Instruction::SetFrequency(_)
+            | Instruction::SetPhase(_)
+            | Instruction::SetScale(_)
+            | Instruction::ShiftFrequency(_)
+            | Instruction::ShiftPhase(_)
+            | Instruction::SwapPhases(_) => Some(Self::FrameUpdate),
+            _ => None,
+        }
+    }
+}
+
+/// Escape text for inclusion within SVG content or attribute values.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Return the unit (and its size in seconds) used to label a time axis spanning `duration` seconds.
+fn get_time_unit(duration: f64) -> (f64, &'static str) {
+    if duration < 1e-6 {
+        (1e-9, "ns")
+    } else if duration < 1e-3 {
+        (1e-6, "µs")
+    } else if duration < 1.0 {
+        (1e-3, "ms")
+    } else {
+        (1.0, "s")
+    }
+}
+
+/// Return the magnitude of the IQ values of the given waveform over its duration, if they can be
+/// determined from the program and the waveform's parameters.
+///
+/// Waveforms defined with `DEFWAVEFORM` are evaluated sample-by-sample. Template waveforms are
+/// generated with `point_count` samples across their duration, since their envelope does not
+/// depend upon the sample rate of the frame on which they are played.
+fn get_waveform_envelope(
+    program: &Program,
+    WaveformInvocation { name, parameters }: &WaveformInvocation,
+    point_count: usize,
+) -> Option<Vec<f64>> {
+    let no_memory = HashMap::new();
+
+    if let Some(definition) = program.waveforms.get(name) {
+        let variables = parameters
+            .iter()
+            .filter_map(|(parameter, value)| {
+                value
+                    .evaluate(&HashMap::new(), &no_memory)
+                    .ok()
+                    .map(|value| (parameter.clone(), value))
+            })
+            .collect();
+
+        return definition
+            .matrix
+            .iter()
+            .map(|sample| {
+                sample
+                    .evaluate(&variables, &no_memory)
+                    .ok()
+                    .map(|value| value.norm())
+            })
+            .collect();
+    }
+
+    let template = BuiltinWaveform::from_name(name)?;
+    let parameter = |parameter_name: &str| {
+        parameters
+            .get(parameter_name)
+            .and_then(|value| value.to_real().ok())
+    };
+
+    let duration = parameter("duration")?;
+    let pad_left = parameter("pad_left").unwrap_or(0.0);
+    let pad_right = parameter("pad_right").unwrap_or(0.0);
+    let total_duration = duration + pad_left + pad_right;
+    if total_duration <= 0.0 || point_count == 0 {
+        return None;
+    }
+
+    let sample_rate = point_count as f64 / total_duration;
+    let scale = parameter("scale").unwrap_or(1.0);
+
+    // Phase and detuning do not affect the magnitude of the waveform, so they are ignored.
+    let iq_values = match template {
+        BuiltinWaveform::Flat | BuiltinWaveform::BoxcarKernel => {
+            let iq = parameters.get("iq").map_or(Some(1.0), |iq| {
+                iq.evaluate(&HashMap::new(), &no_memory)
+                    .ok()
+                    .map(|value| value.norm())
+            })?;
+            return Some(vec![scale * iq; point_count]);
+        }
+        BuiltinWaveform::Gaussian => Gaussian {
+            duration,
+            fwhm: parameter("fwhm")?,
+            t0: parameter("t0")?,
+            sample_rate,
+            scale,
+            phase: 0.0,
+            detuning: 0.0,
+        }
+        .into_iq_values(),
+        BuiltinWaveform::DragGaussian => DragGaussian {
+            duration,
+            fwhm: parameter("fwhm")?,
+            t0: parameter("t0")?,
+            anh: parameter("anh")?,
+            alpha: parameter("alpha")?,
+            sample_rate,
+            scale,
+            phase: 0.0,
+            detuning: 0.0,
+        }
+        .into_iq_values(),
+        BuiltinWaveform::HermiteGaussian => HermiteGaussian {
+            duration,
+            fwhm: parameter("fwhm")?,
+            t0: parameter("t0")?,
+            anh: parameter("anh")?,
+            alpha: parameter("alpha")?,
+            sample_rate,
+            second_order_hrm_coeff: parameter("second_order_hrm_coeff")?,
+            scale,
+            phase: 0.0,
+            detuning: 0.0,
+        }
+        .into_iq_values(),
+        BuiltinWaveform::ErfSquare => ErfSquare {
+            duration,
+            risetime: parameter("risetime")?,
+            sample_rate,
+            pad_left,
+            pad_right,
+            positive_polarity: parameter("positive_polarity") != Some(0.0),
+            scale,
+            phase: 0.0,
+            detuning: 0.0,
+        }
+        .into_iq_values(),
+    };
+
+    Some(iq_values.into_iter().map(|value| value.norm()).collect())
+}
+
+/// Reduce `values` to at most `max_count` evenly-spaced values.
+fn downsample(values: Vec<f64>, max_count: usize) -> Vec<f64> {
+    if values.len() <= max_count {
+        return values;
+    }
+
+    (0..max_count)
+        .map(|index| values[index * values.len() / max_count])
+        .collect()
+}
+
+impl<'p> ScheduledBasicBlock<'p> {
+    /// Return an SVG document (as bytes) depicting this block's schedule as a timeline, with one row
+    /// per frame.
+    ///
+    /// The schedule is computed using [`ScheduledBasicBlock::as_schedule_seconds`]; return an error if
+    /// it cannot be computed. See [`ScheduledBasicBlock::get_svg_timeline_for_schedule`] to render a
+    /// schedule computed with custom instruction durations.
+    pub fn get_svg_timeline(
+        &self,
+        program: &'p Program,
+        options: &SvgTimelineOptions,
+    ) -> ComputedScheduleResult<Vec<u8>> {
+        let schedule = self.as_schedule_seconds(program)?;
+        Ok(self.get_svg_timeline_for_schedule(program, &schedule, options))
+    }
+
+    /// Return an SVG document (as bytes) depicting the given schedule of this block as a timeline.
+    ///
+    /// Each frame used within the block is drawn as its own row, on which:
+    ///
+    /// * `PULSE`, `CAPTURE`, `RAW-CAPTURE`, and `DELAY` are drawn as boxes spanning their scheduled
+    ///   time, optionally overlaid with the envelope of their waveform
+    /// * Frame updates such as `SET-PHASE` and `SHIFT-FREQUENCY` are drawn as ticks
+    /// * `FENCE` is drawn as a dashed line on each frame it blocks
+    ///
+    /// Every mark is titled with the Quil text of its instruction, which most viewers display on hover.
+    pub fn get_svg_timeline_for_schedule(
+        &self,
+        program: &'p Program,
+        schedule: &ScheduleSeconds,
+        options: &SvgTimelineOptions,
+    ) -> Vec<u8> {
+        let mut output = String::new();
+        self.write_svg_timeline(program, schedule, options, &mut output)
+            .expect("writing to a String should not fail");
+        output.into_bytes()
+    }
+
+    fn write_svg_timeline(
+        &self,
+        program: &'p Program,
+        schedule: &ScheduleSeconds,
+        options: &SvgTimelineOptions,
+        writer: &mut impl Write,
+    ) -> std::fmt::Result {
+        let mut items = schedule
+            .items()
+            .iter()
+            .filter_map(|item| {
+                let instruction = *self
+                    .basic_block()
+                    .instructions()
+                    .get(item.instruction_index)?;
+                let mark = TimelineMark::for_instruction(instruction)?;
+                let frames = program.get_frames_for_instruction(instruction)?;
+                Some((item, instruction, mark, frames))
+            })
+            .collect::<Vec<_>>();
+        items.sort_by_key(|(item, ..)| item.instruction_index);
+
+        // Rows are only drawn for frames which are used within the block, since a FENCE without
+        // qubits blocks every frame in the program.
+        let mut frames: Vec<&FrameIdentifier> = items
+            .iter()
+            .flat_map(|(_, _, _, frames)| frames.used().iter().copied())
+            .collect();
+        frames.sort_by(|a, b| (&a.qubits, &a.name).cmp(&(&b.qubits, &b.name)));
+        frames.dedup();
+        let rows = frames
+            .iter()
+            .enumerate()
+            .map(|(row, frame)| (*frame, row))
+            .collect::<HashMap<_, _>>();
+
+        let duration = schedule.duration().0;
+        let seconds_to_x = |seconds: f64| {
+            let fraction = if duration > 0.0 {
+                seconds / duration
+            } else {
+                0.0
+            };
+            LABEL_WIDTH + fraction * options.width
+        };
+        let row_top = |row: usize| AXIS_HEIGHT + row as f64 * options.row_height;
+        let total_width = LABEL_WIDTH + options.width + 2.0 * PADDING;
+        let total_height = row_top(frames.len()) + PADDING;
+
+        writeln!(
+            writer,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{total_width:.2}" height="{total_height:.2}" viewBox="0 0 {total_width:.2} {total_height:.2}">"#
+        )?;
+        writeln!(writer, "<style>{STYLE}</style>")?;
+
+        let (unit_seconds, unit_name) = get_time_unit(duration);
+        for tick in 0..AXIS_TICK_COUNT {
+            let seconds = duration * tick as f64 / (AXIS_TICK_COUNT - 1) as f64;
+            let x = seconds_to_x(seconds);
+            writeln!(
+                writer,
+                r#"<line class="grid" x1="{x:.2}" y1="{:.2}" x2="{x:.2}" y2="{:.2}"/>"#,
+                AXIS_HEIGHT - PADDING,
+                total_height - PADDING,
+            )?;
+            writeln!(
+                writer,
+                r#"<text x="{x:.2}" y="{:.2}" text-anchor="middle">{:.1} {unit_name}</text>"#,
+                AXIS_HEIGHT - 2.0 * PADDING,
+                seconds / unit_seconds,
+            )?;
+        }
+        writeln!(
+            writer,
+            r#"<line class="axis" x1="{LABEL_WIDTH:.2}" y1="{AXIS_HEIGHT:.2}" x2="{:.2}" y2="{AXIS_HEIGHT:.2}"/>"#,
+            LABEL_WIDTH + options.width,
+        )?;
+
+        for (row, frame) in frames.iter().enumerate() {
+            writeln!(
+                writer,
+                r#"<text x="{PADDING:.2}" y="{:.2}" dominant-baseline="middle">{}</text>"#,
+                row_top(row) + options.row_height / 2.0,
+                escape_xml(&frame.to_quil_or_debug()),
+            )?;
+        }
+
+        for (item, instruction, mark, matched_frames) in items {
+            let title = escape_xml(&instruction.to_quil_or_debug());
+            let start_x = seconds_to_x(item.time_span.start_time.0);
+            let end_x = seconds_to_x(item.time_span.end().0);
+
+            let frame_rows = match mark {
+                TimelineMark::Fence => matched_frames.blocked(),
+                TimelineMark::Box(_) | TimelineMark::FrameUpdate => matched_frames.used(),
+            };
+            let mut frame_rows = frame_rows
+                .iter()
+                .filter_map(|frame| rows.get(frame).copied())
+                .collect::<Vec<_>>();
+            frame_rows.sort_unstable();
+
+            let envelope = match (mark, instruction) {
+                (
+                    TimelineMark::Box(_),
+                    Instruction::Pulse(Pulse { waveform, .. })
+                    | Instruction::Capture(Capture { waveform, .. }),
+                ) if options.include_waveform_envelopes => {
+                    get_waveform_envelope(program, waveform, options.max_envelope_points)
+                        .filter(|envelope| !envelope.is_empty())
+                        .map(|envelope| downsample(envelope, options.max_envelope_points))
+                }
+                _ => None,
+            };
+
+            for row in frame_rows {
+                let top = row_top(row) + PADDING;
+                let bottom = row_top(row + 1) - PADDING;
+
+                match mark {
+                    TimelineMark::Box(class) => {
+                        writeln!(
+                            writer,
+                            r#"<rect class="{class}" x="{start_x:.2}" y="{top:.2}" width="{:.2}" height="{:.2}"><title>{title}</title></rect>"#,
+                            end_x - start_x,
+                            bottom - top,
+                        )?;
+
+                        if let Some(envelope) = &envelope {
+                            // Envelopes are drawn to scale, unless their magnitude exceeds the
+                            // height of the box.
+                            let peak = envelope.iter().copied().fold(1.0, f64::max);
+                            let step = (end_x - start_x) / envelope.len() as f64;
+                            let points = envelope
+                                .iter()
+                                .enumerate()
+                                .map(|(index, magnitude)| {
+                                    format!(
+                                        "{:.2},{:.2}",
+                                        start_x + (index as f64 + 0.5) * step,
+                                        bottom - magnitude / peak * (bottom - top),
+                                    )
+                                })
+                                .collect::<Vec<_>>()
+                                .join(" ");
+                            writeln!(writer, r#"<polyline class="envelope" points="{points}"/>"#)?;
+                        }
+                    }
+                    TimelineMark::FrameUpdate | TimelineMark::Fence => {
+                        let class = match mark {
+                            TimelineMark::Fence => "fence",
+                            _ => "frame-update",
+                        };
+                        writeln!(
+                            writer,
+                            r#"<line class="{class}" x1="{start_x:.2}" y1="{top:.2}" x2="{start_x:.2}" y2="{bottom:.2}"><title>{title}</title></line>"#,
+                        )?;
+                    }
+                }
+            }
+        }
+
+        writeln!(writer, "</svg>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        instruction::InstructionHandler,
+        program::scheduling::{ScheduledProgram, SvgTimelineOptions},
+        Program,
+    };
+
+    /// Schedule the first block of the given program and render it as an SVG timeline.
+    fn get_svg_timeline(input: &str, options: &SvgTimelineOptions) -> String {
+        let program: Program = input.parse().unwrap();
+        let scheduled_program =
+            ScheduledProgram::from_program(&program, &mut InstructionHandler::default()).unwrap();
+        let svg = scheduled_program.basic_blocks()[0]
+            .get_svg_timeline(&program, options)
+            .unwrap();
+        String::from_utf8(svg).unwrap()
+    }
+
+    const PROGRAM: &str = r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFFRAME 0 "ro_rx":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1e9
+DEFWAVEFORM custom:
+    0.0, 0.5, 1.0, 0.5
+DECLARE ro BIT
+PULSE 0 "rf" gaussian(duration: 1e-6, fwhm: 2e-7, t0: 5e-7)
+SHIFT-PHASE 1 "rf" 1.0
+PULSE 1 "rf" custom
+FENCE
+DELAY 1 "rf" 1e-6
+CAPTURE 0 "ro_rx" flat(duration: 2e-6, iq: 0.5) ro[0]
+"#;
+
+    #[test]
+    fn rows_and_marks() {
+        let svg = get_svg_timeline(PROGRAM, &SvgTimelineOptions::default());
+
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.trim_end().ends_with("</svg>"));
+
+        let labels = [
+            r#"0 &quot;rf&quot;"#,
+            r#"0 &quot;ro_rx&quot;"#,
+            r#"1 &quot;rf&quot;"#,
+        ];
+        let positions = labels
+            .iter()
+            .map(|label| svg.find(&format!(">{label}</text>")).unwrap())
+            .collect::<Vec<_>>();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert_eq!(svg.matches(r#"<rect class="pulse""#).count(), 2);
+        assert_eq!(svg.matches(r#"<rect class="capture""#).count(), 1);
+        assert_eq!(svg.matches(r#"<rect class="delay""#).count(), 1);
+        assert_eq!(svg.matches(r#"<line class="frame-update""#).count(), 1);
+        assert_eq!(svg.matches(r#"<line class="fence""#).count(), 3);
+        assert_eq!(svg.matches(r#"<polyline class="envelope""#).count(), 3);
+        assert!(svg.contains("<title>SHIFT-PHASE 1 &quot;rf&quot; 1</title>"));
+    }
+
+    #[test]
+    fn without_envelopes() {
+        let options = SvgTimelineOptions {
+            include_waveform_envelopes: false,
+            ..Default::default()
+        };
+        let svg = get_svg_timeline(PROGRAM, &options);
+
+        assert_eq!(svg.matches("<rect ").count(), 4);
+        assert!(!svg.contains("<polyline"));
+    }
+
+    #[test]
+    fn envelope_point_limit() {
+        let options = SvgTimelineOptions {
+            max_envelope_points: 10,
+            ..Default::default()
+        };
+        let svg = get_svg_timeline(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+PULSE 0 "rf" erf_square(duration: 1e-6, risetime: 1e-7, pad_left: 1e-7, pad_right: 1e-7)
+"#,
+            &options,
+        );
+
+        let points = svg
+            .split(r#"points=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap();
+        assert_eq!(points.split(' ').count(), 10);
+    }
+}
@@ -7,7 +7,10 @@ use indexmap::IndexMap;
 use thiserror::Error;
 
 use crate::{
-    expression::{Expression, FunctionCallExpression, InfixExpression, PrefixExpression},
+    expression::{
+        BinaryFunctionCallExpression, Expression, FunctionCallExpression, InfixExpression,
+        PrefixExpression,
+    },
     instruction::{
         Arithmetic, ArithmeticOperand, ArithmeticOperator, BinaryLogic, BinaryOperand,
         BinaryOperator, Comparison, ComparisonOperand, ComparisonOperator, Exchange, Instruction,
@@ -233,7 +236,8 @@ fn should_be_real(
         Expression::FunctionCall(FunctionCallExpression { expression, .. }) => {
             should_be_real(instruction, expression, memory_regions)
         }
-        Expression::Infix(InfixExpression { left, right, .. }) => should_be_real(
+        Expression::BinaryFunctionCall(BinaryFunctionCallExpression { left, right, .. })
+        | Expression::Infix(InfixExpression { left, right, .. }) => should_be_real(
             instruction,
             left,
             memory_regions,
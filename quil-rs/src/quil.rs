@@ -2,12 +2,13 @@
 ///
 /// If you want to serialize an object to string and fail if it can't be represented as valid Quil, then use
 /// `to_quil()`. If you want to serialize an object to string infallibly, and can tolerate invalid Quil, then
-/// use `to_quil_or_debug()`.
+/// use `to_quil_or_debug()`. If you want to serialize an object which uses functions this library supports
+/// beyond the Quil specification, such as `atan2`, then use `to_quil_with_non_standard_functions()`.
 pub trait Quil: std::fmt::Debug {
     /// Return a string in valid Quil syntax or an error if the item cannot be represented with valid Quil.
     fn to_quil(&self) -> Result<String, ToQuilError> {
         let mut buffer = String::new();
-        self.write(&mut buffer, false)?;
+        self.write(&mut buffer, WriteOptions::default())?;
         Ok(buffer)
     }
 
@@ -16,16 +17,39 @@ pub trait Quil: std::fmt::Debug {
     /// component.
     fn to_quil_or_debug(&self) -> String {
         let mut buffer = String::new();
-        let _ = self.write(&mut buffer, true);
+        let _ = self.write(
+            &mut buffer,
+            WriteOptions {
+                fall_back_to_debug: true,
+                ..WriteOptions::default()
+            },
+        );
         buffer
     }
 
-    /// Write the Quil representation of the item to the given writer. If `fall_back_to_debug`
-    /// is `true`, then it must not return an error.
+    /// Return a string in Quil syntax, in which functions which are not defined by the Quil
+    /// specification are written as calls like any other, or an error if the item cannot otherwise
+    /// be represented with valid Quil.
+    ///
+    /// The result may be parsed by this library, but not necessarily by other Quil implementations.
+    fn to_quil_with_non_standard_functions(&self) -> Result<String, ToQuilError> {
+        let mut buffer = String::new();
+        self.write(
+            &mut buffer,
+            WriteOptions {
+                non_standard_functions: true,
+                ..WriteOptions::default()
+            },
+        )?;
+        Ok(buffer)
+    }
+
+    /// Write the Quil representation of the item to the given writer, as controlled by `options`.
+    /// If `options.fall_back_to_debug` is `true`, then it must not return an error.
     fn write(
         &self,
         writer: &mut impl std::fmt::Write,
-        fall_back_to_debug: bool,
+        options: WriteOptions,
     ) -> Result<(), ToQuilError>;
 }
 
@@ -33,6 +57,18 @@ pub trait Quil: std::fmt::Debug {
 /// See [Quil 3-2](https://quil-lang.github.io/#3-2Syntactic-Rudiments)
 pub(crate) const INDENT: &str = "    ";
 
+/// Options which control how a [`Quil`] item is written, passed unchanged to the items it contains.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Write any component which cannot be represented in Quil as its `Debug` representation,
+    /// rather than returning an error, as in [`Quil::to_quil_or_debug`].
+    pub fall_back_to_debug: bool,
+
+    /// Write functions which are not defined by the Quil specification as calls like any other,
+    /// rather than returning an error, as in [`Quil::to_quil_with_non_standard_functions`].
+    pub non_standard_functions: bool,
+}
+
 pub type ToQuilResult<T> = Result<T, ToQuilError>;
 
 /// Errors which can occur when converting a Quil item to a string.
@@ -45,12 +81,14 @@ pub enum ToQuilError {
     UnresolvedLabelPlaceholder,
     #[error("Qubit has not yet been resolved")]
     UnresolvedQubitPlaceholder,
+    #[error("Function {0} is not defined by the Quil specification")]
+    NonStandardFunction(String),
 }
 
 /// Write an iterator of Quil items to the given writer, joined with the provided `joiner`.
 pub(crate) fn write_join_quil<'i, I, T>(
     writer: &mut impl std::fmt::Write,
-    fall_back_to_debug: bool,
+    options: WriteOptions,
     values: I,
     joiner: &str,
     prefix: &str,
@@ -62,11 +100,11 @@ where
     let mut iter = values.into_iter();
     if let Some(first) = iter.next() {
         write!(writer, "{prefix}")?;
-        first.write(writer, fall_back_to_debug)?;
+        first.write(writer, options)?;
 
         for value in iter {
             write!(writer, "{joiner}{prefix}")?;
-            value.write(writer, fall_back_to_debug)?;
+            value.write(writer, options)?;
         }
     }
     Ok(())
@@ -15,3 +15,86 @@ impl From<Radians<f64>> for Cycles<f64> {
         Cycles(radians.0 / (2.0 * std::f64::consts::PI))
     }
 }
+
+/// The unit of a Quil-T quantity, as inferred by dimensional analysis.
+///
+/// Angles are dimensionless, but [`Unit::Radians`] and [`Unit::Cycles`] are distinguished wherever
+/// a quantity is used directly as an angle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unit {
+    /// A pure number, such as a scale
+    Dimensionless,
+
+    /// An angle in radians, as of `SHIFT-PHASE`
+    Radians,
+
+    /// An angle in cycles, as of the `phase` of a waveform template
+    Cycles,
+
+    /// A time, as of `DELAY`
+    Seconds,
+
+    /// A frequency, as of `SET-FREQUENCY`
+    Hertz,
+
+    /// Seconds raised to a power other than -1, 0, or 1; see [`Unit::from_time_exponent`]
+    SecondsPower(TimeExponent),
+}
+
+/// A power of seconds other than -1, 0, or 1, which have their own [`Unit`]s.
+///
+/// This can only be constructed by [`Unit::from_time_exponent`], so that each unit has a single
+/// representation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimeExponent(i32);
+
+impl TimeExponent {
+    /// The power of seconds.
+    pub fn get(self) -> i32 {
+        self.0
+    }
+}
+
+impl Unit {
+    /// The unit of a quantity with the given power of seconds.
+    pub fn from_time_exponent(exponent: i32) -> Self {
+        match exponent {
+            -1 => Self::Hertz,
+            0 => Self::Dimensionless,
+            1 => Self::Seconds,
+            _ => Self::SecondsPower(TimeExponent(exponent)),
+        }
+    }
+
+    /// The power of seconds in this unit.
+    pub fn time_exponent(self) -> i32 {
+        match self {
+            Self::Dimensionless | Self::Radians | Self::Cycles => 0,
+            Self::Seconds => 1,
+            Self::Hertz => -1,
+            Self::SecondsPower(exponent) => exponent.get(),
+        }
+    }
+
+    /// Whether a single quantity may be used with both units: they have the same dimension, and are
+    /// not different units of angle.
+    pub fn is_compatible_with(self, other: Self) -> bool {
+        match (self, other) {
+            (Self::Radians, Self::Cycles) | (Self::Cycles, Self::Radians) => false,
+            _ => self.time_exponent() == other.time_exponent(),
+        }
+    }
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dimensionless => write!(f, "dimensionless"),
+            Self::Radians => write!(f, "rad"),
+            Self::Cycles => write!(f, "cycles"),
+            Self::Seconds => write!(f, "s"),
+            Self::Hertz => write!(f, "Hz"),
+            Self::SecondsPower(exponent) => write!(f, "s^{}", exponent.get()),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! The names, parameters, and parameter units of the built-in waveform templates
+
+// Copyright 2024 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::units::Unit;
+
+/// A parameter of a built-in waveform template.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct TemplateParameter {
+    pub(crate) name: &'static str,
+    pub(crate) unit: Unit,
+    pub(crate) required: bool,
+}
+
+const fn required(name: &'static str, unit: Unit) -> TemplateParameter {
+    TemplateParameter {
+        name,
+        unit,
+        required: true,
+    }
+}
+
+const fn optional(name: &'static str, unit: Unit) -> TemplateParameter {
+    TemplateParameter {
+        name,
+        unit,
+        required: false,
+    }
+}
+
+/// The optional parameters accepted by every built-in waveform template.
+///
+/// As in scheduling, any template may be given `pad_left` and `pad_right` parameters.
+const COMMON_PARAMETERS: &[TemplateParameter] = &[
+    optional("scale", Unit::Dimensionless),
+    optional("phase", Unit::Cycles),
+    optional("detuning", Unit::Hertz),
+    optional("pad_left", Unit::Seconds),
+    optional("pad_right", Unit::Seconds),
+];
+
+/// A waveform template which may be invoked without being defined by the program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum BuiltinWaveform {
+    Flat,
+    Gaussian,
+    DragGaussian,
+    HermiteGaussian,
+    ErfSquare,
+    BoxcarKernel,
+}
+
+impl BuiltinWaveform {
+    const ALL: [Self; 6] = [
+        Self::Flat,
+        Self::Gaussian,
+        Self::DragGaussian,
+        Self::HermiteGaussian,
+        Self::ErfSquare,
+        Self::BoxcarKernel,
+    ];
+
+    /// The built-in template invoked by the given name, if any.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|template| template.name() == name)
+    }
+
+    /// The name by which this template is invoked.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Flat => "flat",
+            Self::Gaussian => "gaussian",
+            Self::DragGaussian => "drag_gaussian",
+            Self::HermiteGaussian => "hermite_gaussian",
+            Self::ErfSquare => "erf_square",
+            Self::BoxcarKernel => "boxcar_kernel",
+        }
+    }
+
+    /// The parameters specific to this template, excluding [`COMMON_PARAMETERS`].
+    fn specific_parameters(self) -> &'static [TemplateParameter] {
+        const DURATION: TemplateParameter = required("duration", Unit::Seconds);
+        const FWHM: TemplateParameter = required("fwhm", Unit::Seconds);
+        const T0: TemplateParameter = required("t0", Unit::Seconds);
+        const ANH: TemplateParameter = required("anh", Unit::Hertz);
+        const ALPHA: TemplateParameter = required("alpha", Unit::Dimensionless);
+        const IQ: TemplateParameter = required("iq", Unit::Dimensionless);
+        const SECOND_ORDER_HRM_COEFF: TemplateParameter =
+            required("second_order_hrm_coeff", Unit::Dimensionless);
+        const RISETIME: TemplateParameter = required("risetime", Unit::Seconds);
+        const POSITIVE_POLARITY: TemplateParameter =
+            optional("positive_polarity", Unit::Dimensionless);
+        const OPTIONAL_IQ: TemplateParameter = optional("iq", Unit::Dimensionless);
+
+        match self {
+            Self::Flat => &[DURATION, IQ],
+            Self::Gaussian => &[DURATION, FWHM, T0],
+            Self::DragGaussian => &[DURATION, FWHM, T0, ANH, ALPHA],
+            Self::HermiteGaussian => &[DURATION, FWHM, T0, ANH, ALPHA, SECOND_ORDER_HRM_COEFF],
+            Self::ErfSquare => &[DURATION, RISETIME, POSITIVE_POLARITY],
+            Self::BoxcarKernel => &[DURATION, OPTIONAL_IQ],
+        }
+    }
+
+    /// Every parameter accepted by this template.
+    pub(crate) fn parameters(self) -> impl Iterator<Item = &'static TemplateParameter> {
+        self.specific_parameters().iter().chain(COMMON_PARAMETERS)
+    }
+
+    /// The parameter of this template with the given name, if it accepts one.
+    pub(crate) fn parameter(self, name: &str) -> Option<&'static TemplateParameter> {
+        self.parameters().find(|parameter| parameter.name == name)
+    }
+}
@@ -1,3 +1,4 @@
+pub(crate) mod builtin;
 pub(crate) mod templates;
 
 pub use templates::*;